@@ -0,0 +1,15 @@
+use thiserror::Error;
+
+use super::ChunkerConfig;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Chunking error: {0}")]
+    Chunking(#[from] fastcdc::v2020::Error),
+    #[error("Chunk sizes {0} are out of the chunker's bounds")]
+    InvalidConfig(ChunkerConfig),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
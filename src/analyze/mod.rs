@@ -0,0 +1,334 @@
+mod error;
+
+use std::{
+    collections::HashSet,
+    fmt,
+    fs::{self, File},
+    hash::BuildHasher,
+    io::{self, Read, Seek},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use fastcdc::v2020::{
+    StreamCDC, AVERAGE_MAX, AVERAGE_MIN, MAXIMUM_MAX, MAXIMUM_MIN, MINIMUM_MAX, MINIMUM_MIN,
+};
+use serde::Serialize;
+
+use crate::system::{hash_chunk, AVG_SIZE, MAX_SIZE, MIN_SIZE};
+
+pub use error::{Error, Result};
+
+/// Bytes a file's meta spends on each of its chunks: the hash and the
+/// recorded size.
+const META_BYTES_PER_CHUNK: u64 = 8 + 4;
+
+/// Chunk sizes FastCDC cuts a file with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct ChunkerConfig {
+    pub min_size: u32,
+    pub avg_size: u32,
+    pub max_size: u32,
+}
+
+impl ChunkerConfig {
+    pub fn new(min_size: u32, avg_size: u32, max_size: u32) -> Self {
+        Self {
+            min_size,
+            avg_size,
+            max_size,
+        }
+    }
+
+    /// Sizes around `avg_size` in the proportions [`System`](crate::System)
+    /// uses, a quarter of it at least and four times at most.
+    pub fn with_avg_size(avg_size: u32) -> Self {
+        Self::new(avg_size / 4, avg_size, avg_size.saturating_mul(4))
+    }
+
+    fn validate(self) -> Result<Self> {
+        let valid = (MINIMUM_MIN..=MINIMUM_MAX).contains(&self.min_size)
+            && (AVERAGE_MIN..=AVERAGE_MAX).contains(&self.avg_size)
+            && (MAXIMUM_MIN..=MAXIMUM_MAX).contains(&self.max_size)
+            && self.min_size <= self.avg_size
+            && self.avg_size <= self.max_size;
+        valid.then_some(self).ok_or(Error::InvalidConfig(self))
+    }
+}
+
+/// The sizes [`System`](crate::System) writes files with.
+impl Default for ChunkerConfig {
+    fn default() -> Self {
+        Self::new(MIN_SIZE, AVG_SIZE, MAX_SIZE)
+    }
+}
+
+impl fmt::Display for ChunkerConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}/{}", self.min_size, self.avg_size, self.max_size)
+    }
+}
+
+/// Estimates how well a corpus would deduplicate under several chunker
+/// configurations and hashers, without storing anything.
+///
+/// Files are chunked exactly as [`System::write`](crate::System::write) and
+/// [`System::write_stream`](crate::System::write_stream) would, and only the
+/// distinct chunk hashes are kept. Each file is streamed once per
+/// configuration, so no more than the largest chunk is held in memory.
+///
+/// ```no_run
+/// use cdcfs::{analyze::{Analyzer, ChunkerConfig}, BuildHighwayHasher, BuildWyHasher};
+///
+/// let analysis = Analyzer::new()
+///     .with_config(ChunkerConfig::with_avg_size(8192))
+///     .with_config(ChunkerConfig::default())
+///     .with_hasher("wyhash", BuildWyHasher::default())
+///     .with_hasher("highway", BuildHighwayHasher::default())
+///     .analyze_dir("corpus")?;
+/// println!("{analysis}");
+/// # Ok::<_, cdcfs::analyze::Error>(())
+/// ```
+pub struct Analyzer {
+    configs: Vec<ChunkerConfig>,
+    hashers: Vec<(String, ChunkHasher)>,
+}
+
+/// Hashes a chunk with a hasher given to [`Analyzer::with_hasher`].
+type ChunkHasher = Box<dyn Fn(&[u8]) -> u64 + Send + Sync>;
+
+impl Analyzer {
+    /// Analyzer without configurations or hashers. Unless some are added,
+    /// the default configuration and wyhash are compared on their own.
+    pub fn new() -> Self {
+        Self {
+            configs: vec![],
+            hashers: vec![],
+        }
+    }
+
+    pub fn with_config(mut self, config: ChunkerConfig) -> Self {
+        self.configs.push(config);
+        self
+    }
+
+    pub fn with_hasher<H>(mut self, name: impl Into<String>, hasher: H) -> Self
+    where
+        H: BuildHasher + Send + Sync + 'static,
+    {
+        self.hashers.push((
+            name.into(),
+            Box::new(move |bytes| hash_chunk(&hasher, bytes)),
+        ));
+        self
+    }
+
+    /// Analyzes every regular file below `root`, in the order of their paths.
+    pub fn analyze_dir(self, root: impl AsRef<Path>) -> Result<Analysis> {
+        let mut paths = vec![];
+        collect_files(root.as_ref(), &mut paths)?;
+        paths.sort();
+
+        self.run(paths.into_iter().map(File::open))
+    }
+
+    /// Analyzes the contents of every reader as a file of its own. Readers
+    /// are rewound for every configuration.
+    pub fn analyze<R: Read + Seek>(self, corpus: impl IntoIterator<Item = R>) -> Result<Analysis> {
+        self.run(corpus.into_iter().map(Ok))
+    }
+
+    fn run<R: Read + Seek>(
+        mut self,
+        corpus: impl Iterator<Item = io::Result<R>>,
+    ) -> Result<Analysis> {
+        if self.configs.is_empty() {
+            self.configs.push(ChunkerConfig::default());
+        }
+        if self.hashers.is_empty() {
+            self = self.with_hasher("wyhash", crate::BuildWyHasher::default());
+        }
+        for config in &self.configs {
+            config.validate()?;
+        }
+
+        let mut tallies: Vec<Tally> = self
+            .configs
+            .iter()
+            .flat_map(|config| {
+                self.hashers.iter().map(|(name, _)| Tally {
+                    config: *config,
+                    hasher: name.clone(),
+                    chunks: 0,
+                    unique: HashSet::new(),
+                    physical_bytes: 0,
+                    elapsed: Duration::ZERO,
+                })
+            })
+            .collect();
+
+        let mut files = 0;
+        let mut logical_bytes = 0;
+        for reader in corpus {
+            let mut reader = reader?;
+            files += 1;
+
+            let rows = tallies.chunks_mut(self.hashers.len());
+            for (idx, (config, tallies)) in self.configs.iter().zip(rows).enumerate() {
+                reader.rewind()?;
+                let mut chunker = StreamCDC::new(
+                    &mut reader,
+                    config.min_size,
+                    config.avg_size,
+                    config.max_size,
+                );
+                loop {
+                    let started = Instant::now();
+                    let Some(chunk) = chunker.next().transpose()? else {
+                        break;
+                    };
+                    let chunking = started.elapsed();
+                    // Every configuration sees the same bytes.
+                    if idx == 0 {
+                        logical_bytes += chunk.length as u64;
+                    }
+
+                    for ((_, hash), tally) in self.hashers.iter().zip(tallies.iter_mut()) {
+                        let started = Instant::now();
+                        if tally.unique.insert(hash(&chunk.data)) {
+                            tally.physical_bytes += chunk.length as u64;
+                        }
+                        tally.chunks += 1;
+                        tally.elapsed += chunking + started.elapsed();
+                    }
+                }
+            }
+        }
+
+        Ok(Analysis {
+            files,
+            logical_bytes,
+            outcomes: tallies
+                .into_iter()
+                .map(|tally| Outcome {
+                    config: tally.config,
+                    hasher: tally.hasher,
+                    chunks: tally.chunks,
+                    unique_chunks: tally.unique.len(),
+                    physical_bytes: tally.physical_bytes,
+                    metadata_bytes: tally.chunks as u64 * META_BYTES_PER_CHUNK,
+                    elapsed: tally.elapsed,
+                    logical_bytes,
+                })
+                .collect(),
+        })
+    }
+}
+
+impl Default for Analyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Analyzer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hashers: Vec<_> = self.hashers.iter().map(|(name, _)| name).collect();
+        f.debug_struct("Analyzer")
+            .field("configs", &self.configs)
+            .field("hashers", &hashers)
+            .finish()
+    }
+}
+
+/// Counts of one configuration and hasher while the corpus is analyzed.
+struct Tally {
+    config: ChunkerConfig,
+    hasher: String,
+    chunks: usize,
+    unique: HashSet<u64>,
+    physical_bytes: u64,
+    elapsed: Duration,
+}
+
+/// Outcome of [`Analyzer::analyze`]. Displays as a comparison table.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Analysis {
+    pub files: usize,
+    /// Combined size of the files.
+    pub logical_bytes: u64,
+    /// One entry per configuration and hasher, grouped by configuration.
+    pub outcomes: Vec<Outcome>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Outcome {
+    pub config: ChunkerConfig,
+    pub hasher: String,
+    /// Chunks the files were cut into.
+    pub chunks: usize,
+    /// Chunks that would be stored.
+    pub unique_chunks: usize,
+    /// Combined length of the chunks that would be stored.
+    pub physical_bytes: u64,
+    /// Space the metas would spend on chunk hashes and sizes.
+    pub metadata_bytes: u64,
+    /// Time spent reading the files, cutting them and hashing the chunks.
+    pub elapsed: Duration,
+    /// Combined size of the files, as in [`Analysis`].
+    pub logical_bytes: u64,
+}
+
+impl Outcome {
+    /// Logical bytes per physical byte, 1 for an empty corpus.
+    pub fn dedup_ratio(&self) -> f64 {
+        if self.physical_bytes == 0 {
+            return 1.0;
+        }
+        self.logical_bytes as f64 / self.physical_bytes as f64
+    }
+
+    /// Logical bytes read, cut and hashed per second.
+    pub fn throughput(&self) -> f64 {
+        self.logical_bytes as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+impl fmt::Display for Analysis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} files, {} bytes", self.files, self.logical_bytes)?;
+        writeln!(
+            f,
+            "{:<24} {:<10} {:>10} {:>10} {:>8} {:>14} {:>12} {:>10}",
+            "min/avg/max", "hasher", "chunks", "unique", "dedup", "stored", "metadata", "MiB/s"
+        )?;
+        for outcome in &self.outcomes {
+            writeln!(
+                f,
+                "{:<24} {:<10} {:>10} {:>10} {:>8.3} {:>14} {:>12} {:>10.1}",
+                outcome.config.to_string(),
+                outcome.hasher,
+                outcome.chunks,
+                outcome.unique_chunks,
+                outcome.dedup_ratio(),
+                outcome.physical_bytes,
+                outcome.metadata_bytes,
+                outcome.throughput() / (1024.0 * 1024.0)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn collect_files(dir: &Path, paths: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_files(&entry.path(), paths)?;
+        } else if file_type.is_file() {
+            paths.push(entry.path());
+        }
+    }
+    Ok(())
+}
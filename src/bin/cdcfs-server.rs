@@ -0,0 +1,103 @@
+use std::{hash::BuildHasher, net::TcpListener, process::ExitCode, sync::Arc};
+
+use cdcfs::{
+    chunks::ChunkStore,
+    http::{self, Config, MetaBackend, SharedSystem},
+    meta::MetaStore,
+    s3, FileMetaStore, MemoryMetaStore, PostgresMetaStore, System,
+};
+use tokio::sync::RwLock;
+use tracing_subscriber::EnvFilter;
+
+const USAGE: &str = "Usage: cdcfs-server <config.toml>, or set CDCFS_SERVER_CONFIG. \
+                     RUST_LOG filters the log, info by default";
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    // Causes of internal errors are only logged, not returned to clients.
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+        )
+        .with_writer(std::io::stderr)
+        .init();
+
+    let Some(path) = std::env::args()
+        .nth(1)
+        .or_else(|| std::env::var("CDCFS_SERVER_CONFIG").ok())
+    else {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    };
+
+    match run(&path).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("cdcfs-server: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(path: &str) -> http::Result<()> {
+    let config = Config::load(path)?;
+    if config.s3.is_some() && matches!(config.meta, MetaBackend::Postgres { .. }) {
+        return Err(http::Error::S3NeedsStringKeys);
+    }
+    let chunk_store = config.chunks.open()?;
+    let hasher = config.hasher()?;
+    let listener = TcpListener::bind(config.listen)?;
+    tracing::info!("Listening on {}", listener.local_addr()?);
+
+    match &config.meta {
+        MetaBackend::Memory => {
+            let meta_store = MemoryMetaStore::<String>::new();
+            let system = shared(System::new_keyed(chunk_store, meta_store, hasher)?);
+            serve_with_s3(&config, listener, system).await
+        }
+        MetaBackend::File { path } => {
+            let meta_store = FileMetaStore::<String>::new(path)?;
+            let system = shared(System::new_keyed(chunk_store, meta_store, hasher)?);
+            serve_with_s3(&config, listener, system).await
+        }
+        MetaBackend::Postgres { url } => {
+            let meta_store = PostgresMetaStore::new(url).await?;
+            let system = shared(System::new_keyed(chunk_store, meta_store, hasher)?);
+            http::serve(listener, system).await
+        }
+    }
+}
+
+/// Serves the HTTP API, and the S3 API as well if it is configured.
+async fn serve_with_s3<C, M, H>(
+    config: &Config,
+    listener: TcpListener,
+    system: SharedSystem<C, M, H>,
+) -> http::Result<()>
+where
+    C: ChunkStore + Send + Sync + 'static,
+    M: MetaStore<Key = String> + Send + Sync + 'static,
+    H: BuildHasher + Send + Sync + 'static,
+{
+    let Some(s3_config) = &config.s3 else {
+        return http::serve(listener, system).await;
+    };
+
+    let s3_listener = TcpListener::bind(s3_config.listen)?;
+    tracing::info!("Serving S3 on {}", s3_listener.local_addr()?);
+    let buckets = s3_config.buckets.clone();
+    tokio::try_join!(
+        http::serve(listener, Arc::clone(&system)),
+        s3::serve(s3_listener, system, buckets),
+    )?;
+    Ok(())
+}
+
+fn shared<C, M, H>(system: System<C, M, H>) -> SharedSystem<C, M, H>
+where
+    C: ChunkStore,
+    M: MetaStore,
+    H: BuildHasher,
+{
+    Arc::new(RwLock::new(system))
+}
@@ -0,0 +1,622 @@
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    fs::File,
+    hash::{BuildHasher, Hash},
+    io::{self, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    str::FromStr,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context};
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+
+use cdcfs::{
+    analyze::{Analyzer, ChunkerConfig},
+    chunks::{ChunkStore, ShardedChunkStore},
+    http::{self, ChunkBackend, Config, MetaBackend},
+    meta::MetaStore,
+    system::{FileStatus, GcOptions, Histogram},
+    BuildHighwayHasher, BuildKeyedBlake3Hasher, BuildWyHasher, BuildXxh3Hasher, FileMetaStore,
+    MemoryMetaStore, PostgresMetaStore, System,
+};
+
+/// Inspects and edits the files of a cdcfs deployment.
+///
+/// Backends are taken from a config file in the format of `cdcfs-server`,
+/// from `--chunks` and `--meta`, or both, with the options taking precedence.
+#[derive(Debug, Parser)]
+#[command(name = "cdcfs", version)]
+struct Cli {
+    /// Config file to read the backends and hash key from.
+    #[arg(long, env = "CDCFS_CONFIG", global = true)]
+    config: Option<PathBuf>,
+
+    /// Chunk store: `memory`, a `redis://` URL or a directory.
+    #[arg(long, env = "CDCFS_CHUNKS", global = true)]
+    chunks: Option<ChunkBackend>,
+
+    /// Meta store: `memory`, a `postgres://` URL or a directory.
+    #[arg(long, env = "CDCFS_META", global = true)]
+    meta: Option<MetaBackend>,
+
+    /// Hex encoded key of the hasher chunk ids are derived with.
+    #[arg(long, env = "CDCFS_HASH_KEY", global = true)]
+    hash_key: Option<String>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Stores a file read from PATH, or from standard input if it is `-` or
+    /// left out.
+    Put { key: String, path: Option<PathBuf> },
+    /// Writes a file to PATH, or to standard output if it is `-`.
+    Get { key: String, path: PathBuf },
+    /// Writes a file to standard output.
+    Cat { key: String },
+    /// Deletes files. Their chunks are left for `gc`.
+    Rm {
+        #[arg(required = true)]
+        keys: Vec<String>,
+    },
+    /// Copies a file without copying its chunks.
+    Cp { from: String, to: String },
+    /// Lists the size and key of every file, sorted by key.
+    Ls { prefix: Option<String> },
+    /// Shows the meta of a file.
+    Stat { key: String },
+    /// Shows which byte ranges of two files differ, without reading them.
+    Diff {
+        a: String,
+        b: String,
+        /// Prints the shared and differing ranges as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Lists the files sharing most of their chunks with a file.
+    Similar {
+        key: String,
+        /// Fraction of distinct chunks a file has to share, from 0 to 1.
+        #[arg(long, default_value_t = 0.5)]
+        min: f64,
+        /// Prints the files as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Removes the chunks no file references.
+    Gc {
+        /// Only counts the chunks that would be removed.
+        #[arg(long)]
+        dry_run: bool,
+        /// Seconds to wait before removing chunks, sparing those of files
+        /// other processes finish writing meanwhile.
+        #[arg(long, value_name = "SECONDS", default_value_t = 60)]
+        grace: u64,
+        /// Removes chunks right away. Only safe while nothing else writes to
+        /// the stores.
+        #[arg(long, conflicts_with = "grace")]
+        force: bool,
+        /// Reads the chunks before removing them to report the bytes freed.
+        #[arg(long)]
+        count_bytes: bool,
+    },
+    /// Checks that every file can be read back intact. Exits with status 1 if
+    /// any cannot.
+    Fsck {
+        /// Prints the full report as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Compares how a directory of files would deduplicate with other chunk
+    /// sizes and hashers, without storing anything. Needs no backends.
+    Analyze {
+        corpus: PathBuf,
+        /// Average chunk size to try, in bytes. May be repeated, defaults to
+        /// the size files are written with.
+        #[arg(long = "avg-size", value_name = "BYTES")]
+        avg_sizes: Vec<u32>,
+        /// Hasher to try. May be repeated, defaults to wyhash.
+        #[arg(long = "hasher", value_enum)]
+        hashers: Vec<HasherName>,
+        /// Prints the comparison as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Moves the chunks of a sharded chunk store to the shards they belong
+    /// to after shards were added or removed. Needs no other backends.
+    Rebalance {
+        /// Shard on the ring. May be repeated.
+        #[arg(long = "shard", value_name = "NAME=CHUNKS", required = true)]
+        shards: Vec<ShardArg>,
+        /// Shard taken off the ring, whose chunks are moved to the others.
+        /// May be repeated.
+        #[arg(long = "drain", value_name = "NAME=CHUNKS")]
+        drained: Vec<ShardArg>,
+        /// Ring points per shard, if the store was not built with the
+        /// default.
+        #[arg(long)]
+        virtual_nodes: Option<usize>,
+        /// Number of chunks moved between progress reports.
+        #[arg(long, default_value_t = 1000)]
+        batch: usize,
+    },
+    /// Shows how much space deduplication saves.
+    Stats {
+        /// Prints the numbers as JSON, including every file.
+        #[arg(long)]
+        json: bool,
+        /// Also shows how much of each file is shared with others.
+        #[arg(long)]
+        files: bool,
+    },
+}
+
+/// Shard of a sharded chunk store, given as its name and chunk store.
+#[derive(Clone, Debug)]
+struct ShardArg {
+    name: String,
+    chunks: ChunkBackend,
+}
+
+impl FromStr for ShardArg {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (name, chunks) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Expected NAME=CHUNKS"))?;
+        Ok(Self {
+            name: name.to_owned(),
+            chunks: chunks.parse()?,
+        })
+    }
+}
+
+impl Command {
+    /// Whether the command changes the stores.
+    fn writes(&self) -> bool {
+        matches!(
+            self,
+            Self::Put { .. } | Self::Rm { .. } | Self::Cp { .. } | Self::Gc { dry_run: false, .. }
+        )
+    }
+
+    /// Whether the command reports on every file, which an empty meta store
+    /// would misrepresent.
+    fn surveys(&self) -> bool {
+        matches!(
+            self,
+            Self::Gc { .. } | Self::Fsck { .. } | Self::Stats { .. }
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum HasherName {
+    Wyhash,
+    Xxh3,
+    Highway,
+    /// BLAKE3 with the all zero key.
+    Blake3,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli).await {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("cdcfs: {}", describe(&err));
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Joins the error's chain of causes, skipping those its message already
+/// includes.
+fn describe(err: &anyhow::Error) -> String {
+    let mut message = err.to_string();
+    for cause in err.chain().skip(1) {
+        let cause = cause.to_string();
+        if !message.ends_with(&cause) {
+            message = format!("{message}: {cause}");
+        }
+    }
+    message
+}
+
+async fn run(cli: Cli) -> anyhow::Result<ExitCode> {
+    if let Command::Analyze {
+        corpus,
+        avg_sizes,
+        hashers,
+        json,
+    } = cli.command
+    {
+        return analyze(&corpus, &avg_sizes, &hashers, json);
+    }
+    if let Command::Rebalance {
+        shards,
+        drained,
+        virtual_nodes,
+        batch,
+    } = cli.command
+    {
+        return rebalance(&shards, &drained, virtual_nodes, batch);
+    }
+
+    let config = config(&cli)?;
+    // Memory stores are gone once the command exits, leaving metas without
+    // chunks or chunks without metas behind.
+    let memory = config.chunks == ChunkBackend::Memory || config.meta == MetaBackend::Memory;
+    if memory && cli.command.writes() {
+        return Err(anyhow!(
+            "The command changes the stores, which needs persistent chunk and meta stores"
+        ));
+    }
+    if config.meta == MetaBackend::Memory && cli.command.surveys() {
+        return Err(anyhow!("The command needs a persistent meta store"));
+    }
+    let chunk_store = config.chunks.open()?;
+    let hasher = config.hasher().map_err(|err| match err {
+        http::Error::MissingHashKey => {
+            anyhow!("No hash key configured, pass --hash-key or set hash_key in the config")
+        }
+        err => err.into(),
+    })?;
+
+    match &config.meta {
+        MetaBackend::Memory => {
+            let meta_store = MemoryMetaStore::<String>::new();
+            let system = System::new_keyed(chunk_store, meta_store, hasher)?;
+            execute(system, cli.command).await
+        }
+        MetaBackend::File { path } => {
+            let meta_store = FileMetaStore::<String>::new(path)?;
+            let system = System::new_keyed(chunk_store, meta_store, hasher)?;
+            execute(system, cli.command).await
+        }
+        MetaBackend::Postgres { url } => {
+            let meta_store = PostgresMetaStore::new(url).await?;
+            let system = System::new_keyed(chunk_store, meta_store, hasher)?;
+            execute(system, cli.command).await
+        }
+    }
+}
+
+/// Merges the config file with the backends given as options.
+fn config(cli: &Cli) -> anyhow::Result<Config> {
+    let mut config = match &cli.config {
+        Some(path) => {
+            let mut config =
+                Config::load(path).with_context(|| format!("Cannot load {}", path.display()))?;
+            if let Some(chunks) = &cli.chunks {
+                config.chunks = chunks.clone();
+            }
+            if let Some(meta) = &cli.meta {
+                config.meta = meta.clone();
+            }
+            config
+        }
+        None => {
+            let (Some(chunks), Some(meta)) = (&cli.chunks, &cli.meta) else {
+                return Err(anyhow!(
+                    "No backends configured, pass --config or both --chunks and --meta"
+                ));
+            };
+            Config::new(chunks.clone(), meta.clone())
+        }
+    };
+    if let Some(hash_key) = &cli.hash_key {
+        config = config.with_hash_key(hash_key);
+    }
+    Ok(config)
+}
+
+fn analyze(
+    corpus: &Path,
+    avg_sizes: &[u32],
+    hashers: &[HasherName],
+    json: bool,
+) -> anyhow::Result<ExitCode> {
+    let mut analyzer = Analyzer::new();
+    for avg_size in avg_sizes {
+        analyzer = analyzer.with_config(ChunkerConfig::with_avg_size(*avg_size));
+    }
+    for hasher in hashers {
+        analyzer = match hasher {
+            HasherName::Wyhash => analyzer.with_hasher("wyhash", BuildWyHasher::default()),
+            HasherName::Xxh3 => analyzer.with_hasher("xxh3", BuildXxh3Hasher::default()),
+            HasherName::Highway => analyzer.with_hasher("highway", BuildHighwayHasher::default()),
+            HasherName::Blake3 => {
+                analyzer.with_hasher("blake3", BuildKeyedBlake3Hasher::new(&[0; 32]))
+            }
+        };
+    }
+
+    let analysis = analyzer
+        .analyze_dir(corpus)
+        .with_context(|| format!("Cannot analyze {}", corpus.display()))?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&analysis)?);
+    } else {
+        print!("{analysis}");
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn rebalance(
+    shards: &[ShardArg],
+    drained: &[ShardArg],
+    virtual_nodes: Option<usize>,
+    batch: usize,
+) -> anyhow::Result<ExitCode> {
+    let mut store = ShardedChunkStore::new();
+    if let Some(virtual_nodes) = virtual_nodes {
+        store = store.with_virtual_nodes(virtual_nodes);
+    }
+    for shard in shards.iter().chain(drained) {
+        let chunks = shard
+            .chunks
+            .open()
+            .with_context(|| format!("Cannot open shard {}", shard.name))?;
+        store.add_shard(&shard.name, chunks)?;
+    }
+    for shard in drained {
+        store.remove_shard(&shard.name)?;
+    }
+
+    let mut moved = 0;
+    loop {
+        match store.rebalance(batch.max(1))? {
+            0 => break,
+            batch => moved += batch,
+        }
+        eprintln!("Moved {moved} chunks so far");
+    }
+    println!("Moved {moved} chunks");
+    for (name, _) in store.take_drained_shards()? {
+        println!("Shard {name} is drained");
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+async fn execute<K, C, M, H>(
+    mut system: System<C, M, H>,
+    command: Command,
+) -> anyhow::Result<ExitCode>
+where
+    K: FromStr + Display + Ord + Hash + Clone + Serialize,
+    K::Err: std::error::Error + Send + Sync + 'static,
+    C: ChunkStore,
+    M: MetaStore<Key = K>,
+    H: BuildHasher,
+{
+    match command {
+        Command::Put { key, path } => {
+            let source: Box<dyn Read> = match path {
+                Some(path) if path.as_os_str() != "-" => Box::new(
+                    File::open(&path).with_context(|| format!("Cannot open {}", path.display()))?,
+                ),
+                _ => Box::new(io::stdin().lock()),
+            };
+            system.write_stream(&parse_key(&key)?, source).await?;
+        }
+        Command::Get { key, path } => {
+            let key = parse_key(&key)?;
+            if path.as_os_str() == "-" {
+                cat(&system, &key).await?;
+                return Ok(ExitCode::SUCCESS);
+            }
+            // Fails before creating the file if the key does not exist.
+            system.meta_store().get(&key).await?;
+            let file =
+                File::create(&path).with_context(|| format!("Cannot create {}", path.display()))?;
+            let mut writer = BufWriter::new(file);
+            system.read_into(&key, &mut writer).await?;
+            writer.flush()?;
+        }
+        Command::Cat { key } => cat(&system, &parse_key(&key)?).await?,
+        Command::Rm { keys } => {
+            for key in keys {
+                system
+                    .delete(&parse_key(&key)?)
+                    .await
+                    .with_context(|| format!("Cannot delete {key}"))?;
+            }
+        }
+        Command::Cp { from, to } => {
+            system.copy(&parse_key(&from)?, &parse_key(&to)?).await?;
+        }
+        Command::Ls { prefix } => {
+            let mut files = system.meta_store().list().await?;
+            files.sort_by(|(a, _), (b, _)| a.cmp(b));
+            let mut stdout = BufWriter::new(io::stdout().lock());
+            for (key, meta) in files {
+                let key = key.to_string();
+                if prefix.as_ref().is_none_or(|prefix| key.starts_with(prefix)) {
+                    writeln!(stdout, "{}\t{key}", meta.size)?;
+                }
+            }
+            stdout.flush()?;
+        }
+        Command::Stat { key } => {
+            let meta = system.meta_store().get(&parse_key(&key)?).await?;
+            let unique: HashSet<_> = meta.hashes.iter().collect();
+            println!("key:           {key}");
+            println!("size:          {}", meta.size);
+            println!("chunks:        {}", meta.hashes.len());
+            println!("unique chunks: {}", unique.len());
+            println!(
+                "encrypted:     {}",
+                if meta.sealed_keys.is_some() {
+                    "yes"
+                } else {
+                    "no"
+                }
+            );
+            if let Some(sizes) = meta.chunk_sizes.filter(|sizes| !sizes.is_empty()) {
+                let min = sizes.iter().min().expect("Sizes are not empty");
+                let max = sizes.iter().max().expect("Sizes are not empty");
+                println!("chunk sizes:   {min} to {max}");
+            }
+        }
+        Command::Diff { a, b, json } => {
+            let diff = system.diff(&parse_key(&a)?, &parse_key(&b)?).await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&diff)?);
+            } else {
+                println!("similarity: {:.1}%", diff.similarity() * 100.0);
+                for (key, side) in [(a, &diff.a), (b, &diff.b)] {
+                    println!(
+                        "{key}: {} bytes, {} shared, {} differing",
+                        side.size,
+                        side.shared_bytes(),
+                        side.differing_bytes()
+                    );
+                    for range in &side.differing {
+                        println!("  {}..{}", range.start, range.end);
+                    }
+                }
+            }
+        }
+        Command::Similar { key, min, json } => {
+            let index = system.similarity_index().await?;
+            let similar = system.similar(&index, &parse_key(&key)?, min).await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&similar)?);
+            } else {
+                for file in similar {
+                    println!("{:>6.1}% {}", file.similarity * 100.0, file.key);
+                }
+            }
+        }
+        Command::Gc { dry_run: true, .. } => {
+            let orphans = system.orphan_chunks().await?;
+            println!("Would remove {} unreferenced chunks", orphans.len());
+        }
+        Command::Gc {
+            dry_run: false,
+            grace,
+            force,
+            count_bytes,
+        } => {
+            let grace = if force { 0 } else { grace };
+            let options = GcOptions::new()
+                .with_grace(Duration::from_secs(grace))
+                .with_count_bytes(count_bytes);
+            let report = system.gc_with(&options).await?;
+            match report.bytes_freed {
+                Some(bytes) => println!(
+                    "Removed {} unreferenced chunks, freeing {bytes} bytes",
+                    report.removed.len()
+                ),
+                None => println!("Removed {} unreferenced chunks", report.removed.len()),
+            }
+        }
+        Command::Fsck { json } => {
+            let report = system.fsck().await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                for file in report.damaged() {
+                    match file.status {
+                        FileStatus::KeysUnavailable => {
+                            println!("{}: chunk keys unavailable", file.key);
+                        }
+                        _ => println!(
+                            "{}: damaged, {} missing and {} corrupt chunks, {} of {} bytes readable",
+                            file.key,
+                            file.missing_chunks.len(),
+                            file.corrupt_chunks.len(),
+                            file.actual_size,
+                            file.expected_size
+                        ),
+                    }
+                }
+                println!(
+                    "{} files checked, {} damaged, {} orphan chunks",
+                    report.files.len(),
+                    report.damaged().count(),
+                    report.orphan_chunks.len()
+                );
+            }
+            if !report.is_clean() {
+                return Ok(ExitCode::from(1));
+            }
+        }
+        Command::Analyze { .. } => unreachable!("Analyses need no backends"),
+        Command::Rebalance { .. } => unreachable!("Rebalancing needs no meta store"),
+        Command::Stats { json, files } => {
+            let mut stats = system.stats().await?;
+            stats.per_file.sort_by(|a, b| a.key.cmp(&b.key));
+            if json {
+                println!("{}", serde_json::to_string_pretty(&stats)?);
+                return Ok(ExitCode::SUCCESS);
+            }
+
+            println!("files:          {}", stats.files);
+            println!("logical bytes:  {}", stats.logical_bytes);
+            println!("physical bytes: {}", stats.physical_bytes);
+            println!("bytes saved:    {}", stats.bytes_saved());
+            println!("unique chunks:  {}", stats.unique_chunks);
+            println!("dedup ratio:    {:.2}", stats.dedup_ratio);
+            println!();
+            print_histogram("chunk size", &stats.chunk_sizes);
+            println!();
+            print_histogram("references", &stats.ref_counts);
+            if files {
+                println!();
+                println!("{:>12}  {:>12}  {:>6}  key", "size", "shared", "%");
+                for file in &stats.per_file {
+                    println!(
+                        "{:>12}  {:>12}  {:>6.1}  {}",
+                        file.size,
+                        file.shared_bytes,
+                        file.shared_ratio() * 100.0,
+                        file.key
+                    );
+                }
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn print_histogram(label: &str, histogram: &Histogram) {
+    println!("{label:>23}  chunks");
+    for bucket in &histogram.buckets {
+        let range = if bucket.min == bucket.max {
+            bucket.min.to_string()
+        } else {
+            format!("{} - {}", bucket.min, bucket.max)
+        };
+        println!("{range:>23}  {}", bucket.count);
+    }
+}
+
+async fn cat<K, C, M, H>(system: &System<C, M, H>, key: &K) -> anyhow::Result<()>
+where
+    C: ChunkStore,
+    M: MetaStore<Key = K>,
+    H: BuildHasher,
+{
+    let mut stdout = BufWriter::new(io::stdout().lock());
+    system.read_into(key, &mut stdout).await?;
+    stdout.flush()?;
+    Ok(())
+}
+
+fn parse_key<K>(key: &str) -> anyhow::Result<K>
+where
+    K: FromStr,
+    K::Err: std::error::Error + Send + Sync + 'static,
+{
+    key.parse().with_context(|| format!("Invalid key {key:?}"))
+}
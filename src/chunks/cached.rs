@@ -0,0 +1,201 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, PoisonError,
+    },
+};
+
+use super::{error::Result, traits::ChunkStore};
+
+/// Counters of a [`CachedChunkStore`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Chunks currently cached.
+    pub chunks: usize,
+    /// Bytes of chunks currently cached.
+    pub bytes: usize,
+}
+
+/// Chunk store wrapper keeping recently read chunks in memory.
+///
+/// Chunks are cached on read and evicted least recently used first once they
+/// take up more than the byte budget. A chunk never changes under its hash,
+/// so cached chunks only go stale when they are removed. Writes drop the
+/// cached copy as well, which keeps the cache correct should a hash ever be
+/// rewritten with different contents.
+pub struct CachedChunkStore<C: ChunkStore> {
+    inner: C,
+    cache: Mutex<Lru>,
+    budget: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Default)]
+struct Lru {
+    chunks: HashMap<u64, (Vec<u8>, u64)>,
+    /// Cached hashes by the tick they were last used at.
+    recency: BTreeMap<u64, u64>,
+    bytes: usize,
+    clock: u64,
+}
+
+impl<C: ChunkStore> CachedChunkStore<C> {
+    /// Caches up to `budget` bytes of chunks read from `inner`.
+    pub fn new(inner: C, budget: usize) -> Self {
+        Self {
+            inner,
+            cache: Mutex::default(),
+            budget,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let cache = self.lock();
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            chunks: cache.chunks.len(),
+            bytes: cache.bytes,
+        }
+    }
+
+    /// Drops every cached chunk. The counters are kept.
+    pub fn clear(&self) {
+        *self.lock() = Lru::default();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Lru> {
+        self.cache.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Lru {
+    fn get(&mut self, hash: &u64) -> Option<Vec<u8>> {
+        self.clock += 1;
+        let (chunk, last_used) = self.chunks.get_mut(hash)?;
+        self.recency.remove(last_used);
+        *last_used = self.clock;
+        self.recency.insert(self.clock, *hash);
+        Some(chunk.clone())
+    }
+
+    fn insert(&mut self, hash: u64, chunk: Vec<u8>, budget: usize) {
+        if chunk.len() > budget {
+            return;
+        }
+        self.remove(&hash);
+
+        self.clock += 1;
+        self.bytes += chunk.len();
+        self.recency.insert(self.clock, hash);
+        self.chunks.insert(hash, (chunk, self.clock));
+
+        while self.bytes > budget {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            if let Some((chunk, _)) = self.chunks.remove(&oldest) {
+                self.bytes -= chunk.len();
+            }
+        }
+    }
+
+    fn remove(&mut self, hash: &u64) {
+        if let Some((chunk, last_used)) = self.chunks.remove(hash) {
+            self.recency.remove(&last_used);
+            self.bytes -= chunk.len();
+        }
+    }
+}
+
+impl<C: ChunkStore> Debug for CachedChunkStore<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CachedChunkStore")
+            .field("inner", &self.inner)
+            .field("budget", &self.budget)
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+impl<C: ChunkStore> ChunkStore for CachedChunkStore<C> {
+    fn get(&self, hash: &u64) -> Result<Vec<u8>> {
+        if let Some(chunk) = self.lock().get(hash) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(chunk);
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let chunk = self.inner.get(hash)?;
+        self.lock().insert(*hash, chunk.clone(), self.budget);
+        Ok(chunk)
+    }
+
+    fn upsert(&mut self, hash: u64, chunk: Vec<u8>) -> Result<()> {
+        self.cache
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&hash);
+        self.inner.upsert(hash, chunk)
+    }
+
+    fn writes_shared(&self) -> bool {
+        self.inner.writes_shared()
+    }
+
+    fn upsert_shared(&self, hash: u64, chunk: Vec<u8>) -> Result<()> {
+        self.lock().remove(&hash);
+        self.inner.upsert_shared(hash, chunk)
+    }
+
+    fn remove(&mut self, hash: &u64) -> Result<()> {
+        self.cache
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(hash);
+        self.inner.remove(hash)
+    }
+
+    fn stored_size(&self, hash: &u64) -> Result<usize> {
+        self.inner.stored_size(hash)
+    }
+
+    fn contains_many(&self, hashes: &[u64]) -> Result<Vec<bool>> {
+        self.inner.contains_many(hashes)
+    }
+
+    fn hashes(&self) -> Result<Vec<u64>> {
+        self.inner.hashes()
+    }
+
+    fn is_empty(&self) -> Result<bool> {
+        self.inner.is_empty()
+    }
+
+    fn hasher_fingerprint(&self) -> Result<Option<[u8; 32]>> {
+        self.inner.hasher_fingerprint()
+    }
+
+    fn set_hasher_fingerprint(&mut self, fingerprint: [u8; 32]) -> Result<()> {
+        self.inner.set_hasher_fingerprint(fingerprint)
+    }
+
+    fn init_hasher_fingerprint(&mut self, fingerprint: [u8; 32]) -> Result<Option<[u8; 32]>> {
+        self.inner.init_hasher_fingerprint(fingerprint)
+    }
+}
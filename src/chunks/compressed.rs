@@ -0,0 +1,150 @@
+use anyhow::Context;
+use fastcdc::v2020::MAXIMUM_MAX;
+
+use super::{
+    error::{Error, Result},
+    traits::ChunkStore,
+};
+
+/// Codec used by [`CompressedChunkStore`] for newly written chunks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    Zstd { level: i32 },
+    Lz4,
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Self::Zstd { level: 3 }
+    }
+}
+
+const RAW: u8 = 0;
+const ZSTD: u8 = 1;
+const LZ4: u8 = 2;
+
+/// Largest chunk decompressed, the largest FastCDC cuts. Stored values claiming
+/// more are corrupt, and are rejected before they can exhaust memory.
+const MAX_CHUNK_SIZE: usize = MAXIMUM_MAX as usize;
+
+/// Chunk store wrapper compressing chunks before they reach the inner store.
+///
+/// Every stored value starts with a one byte header naming the codec it was
+/// written with, so chunks written with different codecs can be read back side
+/// by side. Chunks that do not shrink are stored raw behind the same header.
+#[derive(Debug)]
+pub struct CompressedChunkStore<C: ChunkStore> {
+    inner: C,
+    compression: Compression,
+}
+
+impl<C: ChunkStore> CompressedChunkStore<C> {
+    pub fn new(inner: C, compression: Compression) -> Self {
+        Self { inner, compression }
+    }
+
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+
+    fn compress(&self, chunk: Vec<u8>) -> Result<Vec<u8>> {
+        let (codec, compressed) = match self.compression {
+            Compression::Zstd { level } => (
+                ZSTD,
+                zstd::bulk::compress(&chunk, level).context("Compression error")?,
+            ),
+            Compression::Lz4 => (LZ4, lz4_flex::compress_prepend_size(&chunk)),
+        };
+
+        let (codec, body) = if compressed.len() < chunk.len() {
+            (codec, compressed)
+        } else {
+            (RAW, chunk)
+        };
+
+        let mut value = Vec::with_capacity(body.len() + 1);
+        value.push(codec);
+        value.extend_from_slice(&body);
+        Ok(value)
+    }
+
+    /// Values that cannot be decompressed fail with [`Error::Integrity`], like
+    /// any other corrupt chunk.
+    fn decompress(value: Vec<u8>) -> Result<Vec<u8>> {
+        let Some((&codec, body)) = value.split_first() else {
+            return Err(Error::Integrity);
+        };
+
+        match codec {
+            RAW => Ok(body.to_vec()),
+            ZSTD => zstd::bulk::decompress(body, MAX_CHUNK_SIZE).map_err(|_| Error::Integrity),
+            LZ4 => {
+                let Some((size, block)) = body.split_first_chunk::<4>() else {
+                    return Err(Error::Integrity);
+                };
+                let size = u32::from_le_bytes(*size) as usize;
+                if size > MAX_CHUNK_SIZE {
+                    return Err(Error::Integrity);
+                }
+                lz4_flex::decompress(block, size).map_err(|_| Error::Integrity)
+            }
+            _ => Err(Error::Integrity),
+        }
+    }
+}
+
+impl<C: ChunkStore> ChunkStore for CompressedChunkStore<C> {
+    fn get(&self, hash: &u64) -> Result<Vec<u8>> {
+        Self::decompress(self.inner.get(hash)?)
+    }
+
+    fn upsert(&mut self, hash: u64, chunk: Vec<u8>) -> Result<()> {
+        let value = self.compress(chunk)?;
+        self.inner.upsert(hash, value)
+    }
+
+    fn writes_shared(&self) -> bool {
+        self.inner.writes_shared()
+    }
+
+    fn upsert_shared(&self, hash: u64, chunk: Vec<u8>) -> Result<()> {
+        let value = self.compress(chunk)?;
+        self.inner.upsert_shared(hash, value)
+    }
+
+    fn remove(&mut self, hash: &u64) -> Result<()> {
+        self.inner.remove(hash)
+    }
+
+    fn stored_size(&self, hash: &u64) -> Result<usize> {
+        self.inner.stored_size(hash)
+    }
+
+    fn contains_many(&self, hashes: &[u64]) -> Result<Vec<bool>> {
+        self.inner.contains_many(hashes)
+    }
+
+    fn hashes(&self) -> Result<Vec<u64>> {
+        self.inner.hashes()
+    }
+
+    fn is_empty(&self) -> Result<bool> {
+        self.inner.is_empty()
+    }
+
+    fn hasher_fingerprint(&self) -> Result<Option<[u8; 32]>> {
+        self.inner.hasher_fingerprint()
+    }
+
+    fn set_hasher_fingerprint(&mut self, fingerprint: [u8; 32]) -> Result<()> {
+        self.inner.set_hasher_fingerprint(fingerprint)
+    }
+
+    fn init_hasher_fingerprint(&mut self, fingerprint: [u8; 32]) -> Result<Option<[u8; 32]>> {
+        self.inner.init_hasher_fingerprint(fingerprint)
+    }
+}
@@ -0,0 +1,137 @@
+use std::fmt::Debug;
+
+use anyhow::anyhow;
+use chacha20poly1305::{
+    aead::{Aead, OsRng, Payload},
+    AeadCore, KeyInit, XChaCha20Poly1305, XNonce,
+};
+
+use super::{
+    error::{Error, Result},
+    traits::ChunkStore,
+};
+
+const NONCE_SIZE: usize = 24;
+
+/// Chunk store wrapper encrypting chunks with XChaCha20-Poly1305 before they
+/// reach the inner store.
+///
+/// Chunks stay addressed by their plaintext hash, which is also bound to the
+/// ciphertext as associated data, so a chunk copied or moved to another hash
+/// fails authentication just like a modified one. Each stored value is a
+/// random nonce followed by the ciphertext and tag.
+///
+/// Encryption makes chunks incompressible, so a [`CompressedChunkStore`] has to
+/// wrap this store rather than the other way around.
+///
+/// [`CompressedChunkStore`]: super::CompressedChunkStore
+pub struct EncryptedChunkStore<C: ChunkStore> {
+    inner: C,
+    cipher: XChaCha20Poly1305,
+}
+
+impl<C: ChunkStore> EncryptedChunkStore<C> {
+    pub fn new(inner: C, key: &[u8; 32]) -> Self {
+        Self {
+            inner,
+            cipher: XChaCha20Poly1305::new(key.into()),
+        }
+    }
+
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+
+    fn encrypt(&self, hash: u64, chunk: &[u8]) -> Result<Vec<u8>> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let payload = Payload {
+            msg: chunk,
+            aad: &hash.to_le_bytes(),
+        };
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, payload)
+            .map_err(|_| anyhow!("Encryption error"))?;
+
+        let mut value = Vec::with_capacity(NONCE_SIZE + ciphertext.len());
+        value.extend_from_slice(&nonce);
+        value.extend_from_slice(&ciphertext);
+        Ok(value)
+    }
+}
+
+impl<C: ChunkStore> Debug for EncryptedChunkStore<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EncryptedChunkStore")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C: ChunkStore> ChunkStore for EncryptedChunkStore<C> {
+    fn get(&self, hash: &u64) -> Result<Vec<u8>> {
+        let value = self.inner.get(hash)?;
+        if value.len() < NONCE_SIZE {
+            return Err(Error::Integrity);
+        }
+
+        let (nonce, ciphertext) = value.split_at(NONCE_SIZE);
+        let payload = Payload {
+            msg: ciphertext,
+            aad: &hash.to_le_bytes(),
+        };
+        self.cipher
+            .decrypt(XNonce::from_slice(nonce), payload)
+            .map_err(|_| Error::Integrity)
+    }
+
+    fn upsert(&mut self, hash: u64, chunk: Vec<u8>) -> Result<()> {
+        let value = self.encrypt(hash, &chunk)?;
+        self.inner.upsert(hash, value)
+    }
+
+    fn writes_shared(&self) -> bool {
+        self.inner.writes_shared()
+    }
+
+    fn upsert_shared(&self, hash: u64, chunk: Vec<u8>) -> Result<()> {
+        let value = self.encrypt(hash, &chunk)?;
+        self.inner.upsert_shared(hash, value)
+    }
+
+    fn remove(&mut self, hash: &u64) -> Result<()> {
+        self.inner.remove(hash)
+    }
+
+    fn stored_size(&self, hash: &u64) -> Result<usize> {
+        self.inner.stored_size(hash)
+    }
+
+    fn contains_many(&self, hashes: &[u64]) -> Result<Vec<bool>> {
+        self.inner.contains_many(hashes)
+    }
+
+    fn hashes(&self) -> Result<Vec<u64>> {
+        self.inner.hashes()
+    }
+
+    fn is_empty(&self) -> Result<bool> {
+        self.inner.is_empty()
+    }
+
+    fn hasher_fingerprint(&self) -> Result<Option<[u8; 32]>> {
+        self.inner.hasher_fingerprint()
+    }
+
+    fn set_hasher_fingerprint(&mut self, fingerprint: [u8; 32]) -> Result<()> {
+        self.inner.set_hasher_fingerprint(fingerprint)
+    }
+
+    fn init_hasher_fingerprint(&mut self, fingerprint: [u8; 32]) -> Result<Option<[u8; 32]>> {
+        self.inner.init_hasher_fingerprint(fingerprint)
+    }
+}
@@ -5,6 +5,9 @@ pub enum Error {
     #[error("Chunk not found")]
     NotFound,
 
+    #[error("Chunk failed integrity check")]
+    Integrity,
+
     #[error("Internal error: {0}")]
     Internal(#[from] anyhow::Error),
 }
@@ -0,0 +1,187 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use anyhow::{anyhow, Context};
+
+use super::{
+    error::{Error, Result},
+    traits::ChunkStore,
+};
+
+const FINGERPRINT_FILE: &str = "hasher-fingerprint";
+
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Chunk store keeping every chunk in its own file below a root directory.
+///
+/// Chunks are named by their hash in hex and spread over 256 subdirectories
+/// by the first byte of the hash. Writes go to a temporary file that is then
+/// renamed into place, so readers never see partially written chunks.
+#[derive(Debug)]
+pub struct FileChunkStore {
+    root: PathBuf,
+}
+
+impl FileChunkStore {
+    /// Opens the store at `root`, creating the directory if needed.
+    pub fn new(root: impl Into<PathBuf>) -> Result<Self> {
+        let root = root.into();
+        fs::create_dir_all(&root).context("Filesystem error")?;
+        Ok(Self { root })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn path(&self, hash: u64) -> PathBuf {
+        let name = format!("{hash:016x}");
+        self.root.join(&name[..2]).join(name)
+    }
+
+    fn temp_path(path: &Path) -> PathBuf {
+        path.with_extension(format!(
+            "tmp-{}-{}",
+            std::process::id(),
+            TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ))
+    }
+
+    fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+        let temp = Self::temp_path(path);
+        fs::write(&temp, contents).context("Filesystem error")?;
+        if let Err(err) = fs::rename(&temp, path) {
+            let _ = fs::remove_file(&temp);
+            return Err(anyhow::Error::new(err).context("Filesystem error").into());
+        }
+        Ok(())
+    }
+
+    /// Calls `visit` with the hash of every stored chunk until it returns
+    /// `true`, and returns whether it did.
+    fn visit_hashes(&self, visit: &mut dyn FnMut(u64) -> bool) -> Result<bool> {
+        for dir in fs::read_dir(&self.root).context("Filesystem error")? {
+            let dir = dir.context("Filesystem error")?;
+            if !dir.file_type().context("Filesystem error")?.is_dir() {
+                continue;
+            }
+            for file in fs::read_dir(dir.path()).context("Filesystem error")? {
+                let file = file.context("Filesystem error")?;
+                let name = file.file_name();
+                let Some(name) = name.to_str() else {
+                    continue;
+                };
+                // Skips temporary files, whose names carry an extension.
+                if name.len() == 16 {
+                    if let Ok(hash) = u64::from_str_radix(name, 16) {
+                        if visit(hash) {
+                            return Ok(true);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(false)
+    }
+}
+
+impl ChunkStore for FileChunkStore {
+    fn get(&self, hash: &u64) -> Result<Vec<u8>> {
+        match fs::read(self.path(*hash)) {
+            Ok(chunk) => Ok(chunk),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Err(Error::NotFound),
+            Err(err) => Err(anyhow::Error::new(err).context("Filesystem error").into()),
+        }
+    }
+
+    fn upsert(&mut self, hash: u64, chunk: Vec<u8>) -> Result<()> {
+        self.upsert_shared(hash, chunk)
+    }
+
+    fn writes_shared(&self) -> bool {
+        true
+    }
+
+    /// Chunks are renamed into place, so concurrent writers of a chunk leave
+    /// one complete copy.
+    fn upsert_shared(&self, hash: u64, chunk: Vec<u8>) -> Result<()> {
+        let path = self.path(hash);
+        let dir = path.parent().expect("Chunk paths have a parent");
+        fs::create_dir_all(dir).context("Filesystem error")?;
+        Self::write_atomic(&path, &chunk)
+    }
+
+    fn remove(&mut self, hash: &u64) -> Result<()> {
+        match fs::remove_file(self.path(*hash)) {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Err(Error::NotFound),
+            Err(err) => Err(anyhow::Error::new(err).context("Filesystem error").into()),
+        }
+    }
+
+    fn stored_size(&self, hash: &u64) -> Result<usize> {
+        match fs::metadata(self.path(*hash)) {
+            Ok(metadata) => Ok(metadata.len() as usize),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Err(Error::NotFound),
+            Err(err) => Err(anyhow::Error::new(err).context("Filesystem error").into()),
+        }
+    }
+
+    fn contains_many(&self, hashes: &[u64]) -> Result<Vec<bool>> {
+        hashes
+            .iter()
+            .map(|hash| {
+                self.path(*hash)
+                    .try_exists()
+                    .context("Filesystem error")
+                    .map_err(Into::into)
+            })
+            .collect()
+    }
+
+    fn hashes(&self) -> Result<Vec<u64>> {
+        let mut hashes = vec![];
+        self.visit_hashes(&mut |hash| {
+            hashes.push(hash);
+            false
+        })?;
+        Ok(hashes)
+    }
+
+    fn is_empty(&self) -> Result<bool> {
+        Ok(!self.visit_hashes(&mut |_| true)?)
+    }
+
+    fn hasher_fingerprint(&self) -> Result<Option<[u8; 32]>> {
+        match fs::read(self.root.join(FINGERPRINT_FILE)) {
+            Ok(fingerprint) => fingerprint
+                .try_into()
+                .map(Some)
+                .map_err(|_| anyhow!("Hasher fingerprint has the wrong length").into()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(anyhow::Error::new(err).context("Filesystem error").into()),
+        }
+    }
+
+    fn set_hasher_fingerprint(&mut self, fingerprint: [u8; 32]) -> Result<()> {
+        Self::write_atomic(&self.root.join(FINGERPRINT_FILE), &fingerprint)
+    }
+
+    /// Links a complete temporary file into place, which fails if another
+    /// process recorded a fingerprint first.
+    fn init_hasher_fingerprint(&mut self, fingerprint: [u8; 32]) -> Result<Option<[u8; 32]>> {
+        let path = self.root.join(FINGERPRINT_FILE);
+        let temp = Self::temp_path(&path);
+        fs::write(&temp, fingerprint).context("Filesystem error")?;
+        let linked = fs::hard_link(&temp, &path);
+        let _ = fs::remove_file(&temp);
+        match linked {
+            Ok(()) => Ok(None),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => self.hasher_fingerprint(),
+            Err(err) => Err(anyhow::Error::new(err).context("Filesystem error").into()),
+        }
+    }
+}
@@ -8,11 +8,17 @@ use super::{
 };
 
 #[derive(Debug)]
-pub struct MemoryChunkStore(HashMap<u64, Vec<u8>, BuildHasherDefault<NoHashHasher<u64>>>);
+pub struct MemoryChunkStore {
+    chunks: HashMap<u64, Vec<u8>, BuildHasherDefault<NoHashHasher<u64>>>,
+    hasher_fingerprint: Option<[u8; 32]>,
+}
 
 impl MemoryChunkStore {
     pub fn new() -> Self {
-        Self(HashMap::with_hasher(BuildHasherDefault::default()))
+        Self {
+            chunks: HashMap::with_hasher(BuildHasherDefault::default()),
+            hasher_fingerprint: None,
+        }
     }
 }
 
@@ -24,7 +30,7 @@ impl Default for MemoryChunkStore {
 
 impl ChunkStore for MemoryChunkStore {
     fn get(&self, hash: &u64) -> Result<Vec<u8>> {
-        if let Some(chunk) = self.0.get(hash) {
+        if let Some(chunk) = self.chunks.get(hash) {
             Ok(chunk.to_owned())
         } else {
             Err(Error::NotFound)
@@ -32,15 +38,43 @@ impl ChunkStore for MemoryChunkStore {
     }
 
     fn upsert(&mut self, hash: u64, chunk: Vec<u8>) -> Result<()> {
-        self.0.insert(hash, chunk);
+        self.chunks.insert(hash, chunk);
         Ok(())
     }
 
     fn remove(&mut self, hash: &u64) -> Result<()> {
-        if self.0.remove(hash).is_some() {
+        if self.chunks.remove(hash).is_some() {
             Ok(())
         } else {
             Err(Error::NotFound)
         }
     }
+
+    fn stored_size(&self, hash: &u64) -> Result<usize> {
+        self.chunks.get(hash).map(Vec::len).ok_or(Error::NotFound)
+    }
+
+    fn contains_many(&self, hashes: &[u64]) -> Result<Vec<bool>> {
+        Ok(hashes
+            .iter()
+            .map(|hash| self.chunks.contains_key(hash))
+            .collect())
+    }
+
+    fn hashes(&self) -> Result<Vec<u64>> {
+        Ok(self.chunks.keys().copied().collect())
+    }
+
+    fn is_empty(&self) -> Result<bool> {
+        Ok(self.chunks.is_empty())
+    }
+
+    fn hasher_fingerprint(&self) -> Result<Option<[u8; 32]>> {
+        Ok(self.hasher_fingerprint)
+    }
+
+    fn set_hasher_fingerprint(&mut self, fingerprint: [u8; 32]) -> Result<()> {
+        self.hasher_fingerprint = Some(fingerprint);
+        Ok(())
+    }
 }
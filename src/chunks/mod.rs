@@ -1,9 +1,29 @@
+mod cached;
+mod compressed;
+mod encrypted;
 mod error;
+mod file;
 mod memory;
 mod redis;
+mod remote;
+mod replicated;
+mod sharded;
+mod tiered;
 mod traits;
 
-pub use self::redis::RedisChunkStore;
+pub use self::redis::{
+    RedisChunkStore, RedisClusterChunkStore, RedisKeyFormat, RedisPoolOptions,
+    RedisSentinelChunkStore, SentinelConnectionManager,
+};
+pub use cached::{CacheStats, CachedChunkStore};
+pub use compressed::{CompressedChunkStore, Compression};
+pub use encrypted::EncryptedChunkStore;
 pub use error::{Error, Result};
+pub use file::FileChunkStore;
 pub use memory::MemoryChunkStore;
+pub use remote::{ChunkServer, ChunkServerOptions, RemoteChunkStore};
+pub use replicated::ReplicatedChunkStore;
+pub use sharded::ShardedChunkStore;
+pub use tiered::{Eviction, TieredChunkStore, WritePolicy};
+pub(crate) use traits::contains_missing;
 pub use traits::ChunkStore;
@@ -0,0 +1,30 @@
+use redis::{
+    cluster::{cluster_pipe, ClusterConnection},
+    Connection, RedisResult,
+};
+
+/// Connections that can check whether many keys exist.
+pub trait ExistsMany {
+    fn exists_many(&mut self, keys: &[String]) -> RedisResult<Vec<bool>>;
+}
+
+impl ExistsMany for Connection {
+    fn exists_many(&mut self, keys: &[String]) -> RedisResult<Vec<bool>> {
+        let mut pipe = redis::pipe();
+        for key in keys {
+            pipe.exists(key);
+        }
+        pipe.query(self)
+    }
+}
+
+impl ExistsMany for ClusterConnection {
+    /// The cluster pipeline sends the commands of each node as one batch.
+    fn exists_many(&mut self, keys: &[String]) -> RedisResult<Vec<bool>> {
+        let mut pipe = cluster_pipe();
+        for key in keys {
+            pipe.exists(key);
+        }
+        pipe.query(self)
+    }
+}
@@ -0,0 +1,118 @@
+/// How chunk hashes are mapped onto Redis keys.
+///
+/// The default format writes the bare hash, which is what every store used
+/// before prefixes existed. With `hash_tag` enabled the hash is wrapped in
+/// braces, so in a cluster `cdcfs:chunk:{42}` lives in the same slot as the
+/// legacy key `42` and
+/// [`RedisChunkStore::migrate_keys`](super::RedisChunkStore::migrate_keys)
+/// can rename it in place.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RedisKeyFormat {
+    prefix: String,
+    hash_tag: bool,
+}
+
+impl RedisKeyFormat {
+    pub fn new(prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            hash_tag: false,
+        }
+    }
+
+    pub fn hash_tag(mut self, hash_tag: bool) -> Self {
+        self.hash_tag = hash_tag;
+        self
+    }
+
+    pub fn key(&self, hash: u64) -> String {
+        if self.hash_tag {
+            format!("{}{{{hash}}}", self.prefix)
+        } else {
+            format!("{}{hash}", self.prefix)
+        }
+    }
+
+    /// Key holding the fingerprint of the keyed hasher used with the store.
+    /// It never parses as a chunk key, and with `hash_tag` enabled it stays in
+    /// the slot of the legacy key like chunk keys do.
+    pub fn hasher_fingerprint_key(&self) -> String {
+        if self.hash_tag {
+            format!("{}{{hasher-fingerprint}}", self.prefix)
+        } else {
+            format!("{}hasher-fingerprint", self.prefix)
+        }
+    }
+
+    /// Inverse of [`RedisKeyFormat::key`], `None` for keys not written by this format.
+    pub fn parse(&self, key: &str) -> Option<u64> {
+        let hash = key.strip_prefix(self.prefix.as_str())?;
+        let hash = if self.hash_tag {
+            hash.strip_prefix('{')?.strip_suffix('}')?
+        } else {
+            hash
+        };
+        if hash.is_empty() || !hash.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        hash.parse().ok()
+    }
+
+    /// `SCAN` pattern matching every key this format can produce.
+    pub(super) fn pattern(&self) -> String {
+        let mut pattern = String::with_capacity(self.prefix.len() + 1);
+        for c in self.prefix.chars() {
+            if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+                pattern.push('\\');
+            }
+            pattern.push(c);
+        }
+        pattern.push('*');
+        pattern
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RedisKeyFormat;
+
+    #[test]
+    fn it_round_trips_keys() {
+        let formats = [
+            RedisKeyFormat::default(),
+            RedisKeyFormat::new("cdcfs:chunk:"),
+            RedisKeyFormat::new("cdcfs:chunk:").hash_tag(true),
+        ];
+        for format in formats {
+            for hash in [0, 42, u64::MAX] {
+                assert_eq!(format.parse(&format.key(hash)), Some(hash));
+            }
+        }
+    }
+
+    #[test]
+    fn it_formats_hash_tags() {
+        let format = RedisKeyFormat::new("cdcfs:chunk:").hash_tag(true);
+        assert_eq!(format.key(42), "cdcfs:chunk:{42}");
+        assert_eq!(format.parse("cdcfs:chunk:42"), None);
+        assert_eq!(RedisKeyFormat::default().key(42), "42");
+    }
+
+    #[test]
+    fn it_ignores_foreign_keys() {
+        let format = RedisKeyFormat::default();
+        assert_eq!(format.parse("cdcfs:chunk:42"), None);
+        assert_eq!(format.parse("+42"), None);
+        assert_eq!(format.parse(""), None);
+        assert_eq!(format.parse("18446744073709551616"), None);
+        assert_eq!(format.parse(&format.hasher_fingerprint_key()), None);
+        let format = RedisKeyFormat::new("cdcfs:chunk:").hash_tag(true);
+        assert_eq!(format.parse(&format.hasher_fingerprint_key()), None);
+    }
+
+    #[test]
+    fn it_escapes_patterns() {
+        assert_eq!(RedisKeyFormat::new("a*[b]:").pattern(), "a\\*\\[b\\]:*");
+        assert_eq!(RedisKeyFormat::default().pattern(), "*");
+    }
+}
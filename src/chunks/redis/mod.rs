@@ -0,0 +1,374 @@
+mod exists;
+mod keys;
+mod scan;
+mod sentinel;
+
+use std::{fmt::Debug, time::Duration};
+
+use anyhow::{anyhow, Context};
+use r2d2::{CustomizeConnection, ManageConnection, Pool};
+use redis::{
+    cluster::{ClusterClient, ClusterConnection},
+    Client, Commands, Connection, ConnectionInfo, ConnectionLike, IntoConnectionInfo, RedisError,
+};
+
+use self::{exists::ExistsMany, scan::ScanKeys};
+use super::{error::Result, traits::ChunkStore, Error};
+
+pub use keys::RedisKeyFormat;
+pub use sentinel::SentinelConnectionManager;
+
+/// Chunk store backed by a pool of Redis connections.
+///
+/// The connection manager decides how connections are opened: `Client` talks
+/// to a single server, `ClusterClient` routes every key to the node owning its
+/// slot and `SentinelConnectionManager` follows the master through failovers.
+pub struct RedisChunkStore<M: ManageConnection = Client> {
+    pool: Pool<M>,
+    keys: RedisKeyFormat,
+    /// Settings of a cluster's seed node, for reaching each master directly.
+    node: Option<ConnectionInfo>,
+}
+
+pub type RedisClusterChunkStore = RedisChunkStore<ClusterClient>;
+pub type RedisSentinelChunkStore = RedisChunkStore<SentinelConnectionManager>;
+
+impl RedisChunkStore {
+    pub fn new<T: IntoConnectionInfo>(params: T) -> Result<RedisChunkStore> {
+        RedisPoolOptions::new().connect(params)
+    }
+}
+
+impl RedisClusterChunkStore {
+    pub fn cluster<T: IntoConnectionInfo>(nodes: Vec<T>) -> Result<RedisClusterChunkStore> {
+        RedisPoolOptions::new().connect_cluster(nodes)
+    }
+}
+
+impl RedisSentinelChunkStore {
+    pub fn sentinel<T: IntoConnectionInfo>(
+        sentinels: Vec<T>,
+        service_name: impl Into<String>,
+    ) -> Result<RedisSentinelChunkStore> {
+        RedisPoolOptions::new().connect_sentinel(sentinels, service_name)
+    }
+}
+
+impl<M> RedisChunkStore<M>
+where
+    M: ManageConnection<Error = RedisError>,
+    M::Connection: ConnectionLike,
+{
+    pub fn with_key_format(mut self, keys: RedisKeyFormat) -> Self {
+        self.keys = keys;
+        self
+    }
+
+    pub fn key_format(&self) -> &RedisKeyFormat {
+        &self.keys
+    }
+
+    /// Round-trips a `PING` through the pool, failing if no connection can be
+    /// checked out or the server does not answer.
+    pub fn health_check(&self) -> Result<()> {
+        let mut conn = self.pool.get().context("Redis error")?;
+        let _: String = redis::cmd("PING")
+            .query(&mut *conn)
+            .context("Redis error")?;
+        Ok(())
+    }
+}
+
+impl<M> RedisChunkStore<M>
+where
+    M: ManageConnection<Connection = Connection, Error = RedisError>,
+{
+    /// Renames every key written in the `from` format to this store's format,
+    /// the recorded hasher fingerprint included, and returns the number of
+    /// chunks moved.
+    ///
+    /// `SCAN` only sees the node it is sent to, so a cluster is migrated by
+    /// running this against each master with a store built by
+    /// [`RedisChunkStore::new`]. Enabling `hash_tag` on the target format keeps
+    /// each key in its original slot, which `RENAME` requires.
+    pub fn migrate_keys(&self, from: &RedisKeyFormat) -> Result<usize> {
+        if *from == self.keys {
+            return Ok(0);
+        }
+
+        let mut conn = self.pool.get().context("Redis error")?;
+        let scanned = conn
+            .scan_keys(&from.pattern(), None)
+            .context("Redis error")?;
+        let fingerprint_key = from.hasher_fingerprint_key();
+        let has_fingerprint = scanned.contains(&fingerprint_key);
+        let keys: Vec<(String, u64)> = scanned
+            .into_iter()
+            .filter_map(|key| from.parse(&key).map(|hash| (key, hash)))
+            .collect();
+
+        for batch in keys.chunks(MIGRATION_BATCH_SIZE) {
+            let mut pipe = redis::pipe();
+            for (key, hash) in batch {
+                pipe.rename(key, self.keys.key(*hash)).ignore();
+            }
+            pipe.query::<()>(&mut *conn).context("Redis error")?;
+        }
+        // In a cluster the fingerprint only turns up on the master owning it.
+        if has_fingerprint {
+            conn.rename::<_, _, ()>(fingerprint_key, self.keys.hasher_fingerprint_key())
+                .context("Redis error")?;
+        }
+
+        Ok(keys.len())
+    }
+}
+
+const MIGRATION_BATCH_SIZE: usize = 1000;
+
+impl<M: ManageConnection> Debug for RedisChunkStore<M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedisChunkStore")
+            .field("pool", &self.pool.state())
+            .field("keys", &self.keys)
+            .finish()
+    }
+}
+
+/// Configuration of the connection pool backing a [`RedisChunkStore`].
+///
+/// Connections are opened lazily and validated on checkout, so a connection
+/// broken by a server restart is discarded and transparently replaced.
+#[derive(Clone, Debug)]
+pub struct RedisPoolOptions {
+    max_connections: u32,
+    min_idle: Option<u32>,
+    connection_timeout: Duration,
+    idle_timeout: Option<Duration>,
+    max_lifetime: Option<Duration>,
+    io_timeout: Option<Duration>,
+}
+
+impl RedisPoolOptions {
+    pub fn new() -> Self {
+        Self {
+            max_connections: 10,
+            min_idle: None,
+            connection_timeout: Duration::from_secs(30),
+            idle_timeout: Some(Duration::from_secs(10 * 60)),
+            max_lifetime: Some(Duration::from_secs(30 * 60)),
+            io_timeout: None,
+        }
+    }
+
+    pub fn max_connections(mut self, max: u32) -> Self {
+        self.max_connections = max;
+        self
+    }
+
+    pub fn min_idle(mut self, min: Option<u32>) -> Self {
+        self.min_idle = min;
+        self
+    }
+
+    /// How long to wait for a pooled connection before giving up.
+    pub fn connection_timeout(mut self, timeout: Duration) -> Self {
+        self.connection_timeout = timeout;
+        self
+    }
+
+    pub fn idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    pub fn max_lifetime(mut self, lifetime: Option<Duration>) -> Self {
+        self.max_lifetime = lifetime;
+        self
+    }
+
+    /// Read and write timeout applied to every connection in the pool.
+    pub fn io_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.io_timeout = timeout;
+        self
+    }
+
+    pub fn connect<T: IntoConnectionInfo>(self, params: T) -> Result<RedisChunkStore> {
+        let client = Client::open(params).context("Redis error")?;
+        Ok(self.build(client))
+    }
+
+    pub fn connect_cluster<T: IntoConnectionInfo>(
+        self,
+        nodes: Vec<T>,
+    ) -> Result<RedisClusterChunkStore> {
+        let nodes = nodes
+            .into_iter()
+            .map(IntoConnectionInfo::into_connection_info)
+            .collect::<redis::RedisResult<Vec<_>>>()
+            .context("Redis error")?;
+        let node = nodes.first().cloned();
+        let client = ClusterClient::new(nodes).context("Redis error")?;
+        Ok(RedisChunkStore {
+            node,
+            ..self.build(client)
+        })
+    }
+
+    /// Connects to the master currently elected for `service_name` by the
+    /// given sentinels.
+    pub fn connect_sentinel<T: IntoConnectionInfo>(
+        self,
+        sentinels: Vec<T>,
+        service_name: impl Into<String>,
+    ) -> Result<RedisSentinelChunkStore> {
+        let manager = SentinelConnectionManager::new(sentinels, service_name.into())?;
+        Ok(self.build(manager))
+    }
+
+    fn build<M>(self, manager: M) -> RedisChunkStore<M>
+    where
+        M: ManageConnection<Error = RedisError>,
+        IoTimeout: CustomizeConnection<M::Connection, RedisError>,
+    {
+        let pool = Pool::builder()
+            .max_size(self.max_connections)
+            .min_idle(self.min_idle)
+            .connection_timeout(self.connection_timeout)
+            .idle_timeout(self.idle_timeout)
+            .max_lifetime(self.max_lifetime)
+            .test_on_check_out(true)
+            .connection_customizer(Box::new(IoTimeout(self.io_timeout)))
+            .build_unchecked(manager);
+        RedisChunkStore {
+            pool,
+            keys: RedisKeyFormat::default(),
+            node: None,
+        }
+    }
+}
+
+impl Default for RedisPoolOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug)]
+struct IoTimeout(Option<Duration>);
+
+impl CustomizeConnection<Connection, RedisError> for IoTimeout {
+    fn on_acquire(&self, conn: &mut Connection) -> std::result::Result<(), RedisError> {
+        conn.set_read_timeout(self.0)?;
+        conn.set_write_timeout(self.0)
+    }
+}
+
+impl CustomizeConnection<ClusterConnection, RedisError> for IoTimeout {
+    fn on_acquire(&self, conn: &mut ClusterConnection) -> std::result::Result<(), RedisError> {
+        conn.set_read_timeout(self.0)?;
+        conn.set_write_timeout(self.0)
+    }
+}
+
+impl<M> ChunkStore for RedisChunkStore<M>
+where
+    M: ManageConnection,
+    M::Connection: ConnectionLike + ExistsMany + ScanKeys,
+{
+    fn get(&self, hash: &u64) -> Result<Vec<u8>> {
+        let mut conn = self.pool.get().context("Redis error")?;
+        let val: Option<Vec<u8>> = conn.get(self.keys.key(*hash)).context("Redis error")?;
+        val.ok_or(Error::NotFound)
+    }
+
+    fn upsert(&mut self, hash: u64, chunk: Vec<u8>) -> Result<()> {
+        self.upsert_shared(hash, chunk)
+    }
+
+    fn writes_shared(&self) -> bool {
+        true
+    }
+
+    fn upsert_shared(&self, hash: u64, chunk: Vec<u8>) -> Result<()> {
+        let mut conn = self.pool.get().context("Redis error")?;
+        conn.set::<_, _, ()>(self.keys.key(hash), chunk)
+            .context("Redis error")?;
+        Ok(())
+    }
+
+    fn remove(&mut self, hash: &u64) -> Result<()> {
+        let mut conn = self.pool.get().context("Redis error")?;
+        let removed: usize = conn.del(self.keys.key(*hash)).context("Redis error")?;
+        if removed == 0 {
+            return Err(Error::NotFound);
+        }
+        Ok(())
+    }
+
+    fn stored_size(&self, hash: &u64) -> Result<usize> {
+        let key = self.keys.key(*hash);
+        let mut conn = self.pool.get().context("Redis error")?;
+        let len: usize = conn.strlen(&key).context("Redis error")?;
+        // Missing keys have a length of 0 as well.
+        if len == 0 && !conn.exists::<_, bool>(&key).context("Redis error")? {
+            return Err(Error::NotFound);
+        }
+        Ok(len)
+    }
+
+    fn contains_many(&self, hashes: &[u64]) -> Result<Vec<bool>> {
+        let keys: Vec<String> = hashes.iter().map(|hash| self.keys.key(*hash)).collect();
+        let mut conn = self.pool.get().context("Redis error")?;
+        Ok(conn.exists_many(&keys).context("Redis error")?)
+    }
+
+    fn hashes(&self) -> Result<Vec<u64>> {
+        let mut conn = self.pool.get().context("Redis error")?;
+        let keys = conn
+            .scan_keys(&self.keys.pattern(), self.node.as_ref())
+            .context("Redis error")?;
+        Ok(keys.iter().filter_map(|key| self.keys.parse(key)).collect())
+    }
+
+    fn is_empty(&self) -> Result<bool> {
+        let mut conn = self.pool.get().context("Redis error")?;
+        let found = conn
+            .scan_any(&self.keys.pattern(), self.node.as_ref(), &mut |key| {
+                self.keys.parse(key).is_some()
+            })
+            .context("Redis error")?;
+        Ok(!found)
+    }
+
+    fn hasher_fingerprint(&self) -> Result<Option<[u8; 32]>> {
+        let mut conn = self.pool.get().context("Redis error")?;
+        let val: Option<Vec<u8>> = conn
+            .get(self.keys.hasher_fingerprint_key())
+            .context("Redis error")?;
+        val.map(|val| {
+            val.try_into()
+                .map_err(|_| anyhow!("Malformed hasher fingerprint").into())
+        })
+        .transpose()
+    }
+
+    fn set_hasher_fingerprint(&mut self, fingerprint: [u8; 32]) -> Result<()> {
+        let mut conn = self.pool.get().context("Redis error")?;
+        conn.set::<_, _, ()>(self.keys.hasher_fingerprint_key(), &fingerprint[..])
+            .context("Redis error")?;
+        Ok(())
+    }
+
+    fn init_hasher_fingerprint(&mut self, fingerprint: [u8; 32]) -> Result<Option<[u8; 32]>> {
+        let mut conn = self.pool.get().context("Redis error")?;
+        let recorded: bool = conn
+            .set_nx(self.keys.hasher_fingerprint_key(), &fingerprint[..])
+            .context("Redis error")?;
+        drop(conn);
+        if recorded {
+            return Ok(None);
+        }
+        self.hasher_fingerprint()
+    }
+}
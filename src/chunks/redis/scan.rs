@@ -0,0 +1,134 @@
+use redis::{
+    cluster::ClusterConnection, from_redis_value, Client, Commands, Connection, ConnectionAddr,
+    ConnectionInfo, ErrorKind, RedisError, RedisResult, Value,
+};
+
+/// Connections that can enumerate the keys matching a pattern.
+pub trait ScanKeys {
+    /// `node` holds the settings the store was connected with, used to reach
+    /// nodes the connection does not route `SCAN` to.
+    fn scan_keys(
+        &mut self,
+        pattern: &str,
+        node: Option<&ConnectionInfo>,
+    ) -> RedisResult<Vec<String>>;
+
+    /// Whether any key matching `pattern` satisfies `found`, scanning no
+    /// further than the first one that does.
+    fn scan_any(
+        &mut self,
+        pattern: &str,
+        node: Option<&ConnectionInfo>,
+        found: &mut dyn FnMut(&str) -> bool,
+    ) -> RedisResult<bool>;
+}
+
+impl ScanKeys for Connection {
+    fn scan_keys(
+        &mut self,
+        pattern: &str,
+        _node: Option<&ConnectionInfo>,
+    ) -> RedisResult<Vec<String>> {
+        Ok(self.scan_match(pattern)?.collect())
+    }
+
+    fn scan_any(
+        &mut self,
+        pattern: &str,
+        _node: Option<&ConnectionInfo>,
+        found: &mut dyn FnMut(&str) -> bool,
+    ) -> RedisResult<bool> {
+        Ok(self
+            .scan_match::<_, String>(pattern)?
+            .any(|key| found(&key)))
+    }
+}
+
+impl ScanKeys for ClusterConnection {
+    /// `SCAN` only sees the node it is sent to, so every master is scanned
+    /// over a connection of its own, with the settings of `node`.
+    fn scan_keys(
+        &mut self,
+        pattern: &str,
+        node: Option<&ConnectionInfo>,
+    ) -> RedisResult<Vec<String>> {
+        let node = cluster_node(node)?;
+        let mut keys = vec![];
+        for (host, port) in masters(self)? {
+            let mut conn = connect_master(node, host, port)?;
+            keys.extend(conn.scan_match::<_, String>(pattern)?);
+        }
+        Ok(keys)
+    }
+
+    fn scan_any(
+        &mut self,
+        pattern: &str,
+        node: Option<&ConnectionInfo>,
+        found: &mut dyn FnMut(&str) -> bool,
+    ) -> RedisResult<bool> {
+        let node = cluster_node(node)?;
+        for (host, port) in masters(self)? {
+            let mut conn = connect_master(node, host, port)?;
+            if conn.scan_any(pattern, None, found)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+fn cluster_node(node: Option<&ConnectionInfo>) -> RedisResult<&ConnectionInfo> {
+    node.ok_or_else(|| {
+        RedisError::from((
+            ErrorKind::ClientError,
+            "Missing connection settings of the cluster nodes",
+        ))
+    })
+}
+
+/// Connects to the master at `host` and `port` with the settings of `node`.
+fn connect_master(node: &ConnectionInfo, host: String, port: u16) -> RedisResult<Connection> {
+    let addr = match &node.addr {
+        ConnectionAddr::TcpTls {
+            insecure,
+            tls_params,
+            ..
+        } => ConnectionAddr::TcpTls {
+            host,
+            port,
+            insecure: *insecure,
+            tls_params: tls_params.clone(),
+        },
+        _ => ConnectionAddr::Tcp(host, port),
+    };
+    let info = ConnectionInfo {
+        addr,
+        redis: node.redis.clone(),
+    };
+    Client::open(info)?.get_connection()
+}
+
+/// Host and port of every master serving slots, from `CLUSTER SLOTS`.
+fn masters(conn: &mut ClusterConnection) -> RedisResult<Vec<(String, u16)>> {
+    let malformed = || RedisError::from((ErrorKind::TypeError, "Malformed CLUSTER SLOTS reply"));
+
+    let ranges: Vec<Value> = redis::cmd("CLUSTER").arg("SLOTS").query(conn)?;
+    let mut masters = vec![];
+    for range in ranges {
+        let Value::Bulk(range) = range else {
+            return Err(malformed());
+        };
+        let Some(Value::Bulk(master)) = range.get(2) else {
+            return Err(malformed());
+        };
+        let (Some(host), Some(port)) = (master.first(), master.get(1)) else {
+            return Err(malformed());
+        };
+        let master = (from_redis_value(host)?, from_redis_value(port)?);
+        if !masters.contains(&master) {
+            masters.push(master);
+        }
+    }
+    Ok(masters)
+}
@@ -0,0 +1,54 @@
+use std::sync::Mutex;
+
+use anyhow::Context;
+use r2d2::ManageConnection;
+use redis::{
+    sentinel::{SentinelClient, SentinelServerType},
+    Connection, ConnectionLike, ErrorKind, IntoConnectionInfo, RedisError, Value,
+};
+
+use crate::chunks::Result;
+
+/// Pool manager that asks the sentinels for the current master whenever a new
+/// connection is opened.
+///
+/// Pooled connections are only handed out while the server they point to still
+/// reports itself as master, so connections to a demoted master are dropped
+/// after a failover instead of failing writes with `READONLY`.
+pub struct SentinelConnectionManager(Mutex<SentinelClient>);
+
+impl SentinelConnectionManager {
+    pub fn new<T: IntoConnectionInfo>(sentinels: Vec<T>, service_name: String) -> Result<Self> {
+        let client =
+            SentinelClient::build(sentinels, service_name, None, SentinelServerType::Master)
+                .context("Redis error")?;
+        Ok(Self(Mutex::new(client)))
+    }
+}
+
+impl ManageConnection for SentinelConnectionManager {
+    type Connection = Connection;
+    type Error = RedisError;
+
+    fn connect(&self) -> std::result::Result<Connection, RedisError> {
+        self.0
+            .lock()
+            .expect("Sentinel client lock poisoned")
+            .get_connection()
+    }
+
+    fn is_valid(&self, conn: &mut Connection) -> std::result::Result<(), RedisError> {
+        let role: Vec<Value> = redis::cmd("ROLE").query(conn)?;
+        match role.first() {
+            Some(Value::Data(role)) if role == b"master" => Ok(()),
+            _ => Err(RedisError::from((
+                ErrorKind::ReadOnly,
+                "Server is no longer the master",
+            ))),
+        }
+    }
+
+    fn has_broken(&self, conn: &mut Connection) -> bool {
+        !conn.is_open()
+    }
+}
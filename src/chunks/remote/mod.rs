@@ -0,0 +1,282 @@
+mod protocol;
+mod server;
+
+use std::{
+    collections::HashSet,
+    io::{BufReader, BufWriter},
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context};
+
+use self::protocol::{prove, read_frame, secret_key, write_frame, Request, Response};
+use super::{
+    error::{Error, Result},
+    traits::ChunkStore,
+};
+
+pub use server::{ChunkServer, ChunkServerOptions};
+
+/// Hashes offered to the server per round trip when negotiating.
+const HAVE_BATCH_SIZE: usize = 4096;
+
+/// Chunk store talking to a [`ChunkServer`] over TCP.
+///
+/// Requests share a single connection, which is reopened on the next request
+/// after a network error. Requests are not retried, as a write may have
+/// reached the server before the connection broke.
+#[derive(Debug)]
+pub struct RemoteChunkStore {
+    addr: SocketAddr,
+    connection: Mutex<Option<Connection>>,
+    timeout: Option<Duration>,
+    key: Option<[u8; 32]>,
+}
+
+#[derive(Debug)]
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+}
+
+impl RemoteChunkStore {
+    /// Connects to the server at `addr`.
+    pub fn new(addr: impl ToSocketAddrs) -> Result<Self> {
+        let addr = addr
+            .to_socket_addrs()
+            .context("Network error")?
+            .next()
+            .ok_or_else(|| anyhow!("Address did not resolve"))?;
+        let store = Self {
+            addr,
+            connection: Mutex::new(None),
+            timeout: None,
+            key: None,
+        };
+        store.with_connection(|_| Ok(()))?;
+        Ok(store)
+    }
+
+    /// Read and write timeout of the connection, none by default.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        *self
+            .connection
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner) = None;
+        self
+    }
+
+    /// Authenticates with `secret`, which must match the one the server was
+    /// started with, see [`ChunkServerOptions::with_secret`].
+    pub fn with_secret(mut self, secret: impl AsRef<[u8]>) -> Self {
+        self.key = Some(secret_key(secret.as_ref()));
+        *self
+            .connection
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner) = None;
+        self
+    }
+
+    /// Offers `hashes` to the server and returns those it does not have yet,
+    /// in the order they were offered.
+    pub fn want(&self, hashes: &[u64]) -> Result<Vec<u64>> {
+        let mut wanted = vec![];
+        for batch in hashes.chunks(HAVE_BATCH_SIZE) {
+            match self.request(Request::Have(batch.to_vec()))? {
+                Response::Hashes(hashes) => wanted.extend(hashes),
+                response => return Err(unexpected(response)),
+            }
+        }
+        Ok(wanted)
+    }
+
+    /// Copies the chunks of `hashes` the server is missing from `source` and
+    /// returns how many were sent.
+    pub fn push(&mut self, source: &impl ChunkStore, hashes: &[u64]) -> Result<usize> {
+        let wanted: HashSet<u64> = self.want(hashes)?.into_iter().collect();
+        for hash in &wanted {
+            self.upsert(*hash, source.get(hash)?)?;
+        }
+        Ok(wanted.len())
+    }
+
+    fn request(&self, request: Request) -> Result<Response> {
+        let response = self.with_connection(|connection| {
+            write_frame(&mut connection.writer, &request.encode())?;
+            // Long hash lists arrive in pages ahead of the final reply.
+            let mut pages = vec![];
+            loop {
+                match connection.read_response()? {
+                    Response::HashPage(page) => pages.extend(page),
+                    Response::Hashes(hashes) if !pages.is_empty() => {
+                        pages.extend(hashes);
+                        return Ok(Response::Hashes(pages));
+                    }
+                    response => return Ok(response),
+                }
+            }
+        })?;
+
+        match response {
+            Response::NotFound => Err(Error::NotFound),
+            Response::Integrity => Err(Error::Integrity),
+            Response::Error(message) => Err(anyhow!(message).context("Server error").into()),
+            response => Ok(response),
+        }
+    }
+
+    /// Runs `f` on the open connection, connecting first if needed. The
+    /// connection is dropped if `f` fails, as it may be left mid-frame.
+    fn with_connection<T>(
+        &self,
+        f: impl FnOnce(&mut Connection) -> std::io::Result<T>,
+    ) -> Result<T> {
+        let mut guard = self
+            .connection
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let connection = match &mut *guard {
+            Some(connection) => connection,
+            None => guard.insert(self.connect().context("Network error")?),
+        };
+
+        match f(connection) {
+            Ok(value) => Ok(value),
+            Err(err) => {
+                *guard = None;
+                Err(anyhow::Error::new(err).context("Network error").into())
+            }
+        }
+    }
+
+    fn connect(&self) -> std::io::Result<Connection> {
+        let stream = TcpStream::connect(self.addr)?;
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(self.timeout)?;
+        stream.set_write_timeout(self.timeout)?;
+        let mut connection = Connection {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: BufWriter::new(stream),
+        };
+        if let Some(key) = &self.key {
+            connection.authenticate(key)?;
+        }
+        Ok(connection)
+    }
+}
+
+impl Connection {
+    fn read_response(&mut self) -> std::io::Result<Response> {
+        let frame = read_frame(&mut self.reader)?.ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "Server closed the connection",
+            )
+        })?;
+        Response::decode(&frame)
+    }
+
+    /// Proves knowledge of the secret behind `key` by hashing a fresh nonce
+    /// of the server with it.
+    fn authenticate(&mut self, key: &[u8; 32]) -> std::io::Result<()> {
+        write_frame(&mut self.writer, &Request::Challenge.encode())?;
+        let nonce = match self.read_response()? {
+            Response::Nonce(nonce) => nonce,
+            response => return Err(rejected(response)),
+        };
+        write_frame(
+            &mut self.writer,
+            &Request::Authenticate(prove(key, &nonce)).encode(),
+        )?;
+        match self.read_response()? {
+            Response::Ok => Ok(()),
+            response => Err(rejected(response)),
+        }
+    }
+}
+
+fn rejected(response: Response) -> std::io::Error {
+    let message = match response {
+        Response::Error(message) => message,
+        response => format!("Unexpected response {response:?}"),
+    };
+    std::io::Error::new(std::io::ErrorKind::PermissionDenied, message)
+}
+
+fn unexpected(response: Response) -> Error {
+    anyhow!("Unexpected response {response:?}").into()
+}
+
+impl ChunkStore for RemoteChunkStore {
+    fn get(&self, hash: &u64) -> Result<Vec<u8>> {
+        match self.request(Request::Get(*hash))? {
+            Response::Chunk(chunk) => Ok(chunk),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    fn upsert(&mut self, hash: u64, chunk: Vec<u8>) -> Result<()> {
+        self.upsert_shared(hash, chunk)
+    }
+
+    fn writes_shared(&self) -> bool {
+        true
+    }
+
+    fn upsert_shared(&self, hash: u64, chunk: Vec<u8>) -> Result<()> {
+        match self.request(Request::Upsert(hash, chunk))? {
+            Response::Ok => Ok(()),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    fn remove(&mut self, hash: &u64) -> Result<()> {
+        match self.request(Request::Remove(*hash))? {
+            Response::Ok => Ok(()),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    fn stored_size(&self, hash: &u64) -> Result<usize> {
+        match self.request(Request::StoredSize(*hash))? {
+            Response::Size(size) => Ok(size as usize),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    fn contains_many(&self, hashes: &[u64]) -> Result<Vec<bool>> {
+        let wanted: HashSet<u64> = self.want(hashes)?.into_iter().collect();
+        Ok(hashes.iter().map(|hash| !wanted.contains(hash)).collect())
+    }
+
+    fn hashes(&self) -> Result<Vec<u64>> {
+        match self.request(Request::Hashes)? {
+            Response::Hashes(hashes) => Ok(hashes),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    fn hasher_fingerprint(&self) -> Result<Option<[u8; 32]>> {
+        match self.request(Request::Fingerprint)? {
+            Response::Fingerprint(fingerprint) => Ok(fingerprint),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    fn set_hasher_fingerprint(&mut self, fingerprint: [u8; 32]) -> Result<()> {
+        match self.request(Request::SetFingerprint(fingerprint))? {
+            Response::Ok => Ok(()),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    fn init_hasher_fingerprint(&mut self, fingerprint: [u8; 32]) -> Result<Option<[u8; 32]>> {
+        match self.request(Request::InitFingerprint(fingerprint))? {
+            Response::Fingerprint(fingerprint) => Ok(fingerprint),
+            response => Err(unexpected(response)),
+        }
+    }
+}
@@ -0,0 +1,296 @@
+use std::io::{self, Read, Write};
+
+/// Largest frame accepted, which bounds the memory a peer can make us
+/// allocate.
+const MAX_FRAME_SIZE: u32 = 64 * 1024 * 1024;
+
+/// Hashes sent per frame of a long hash list, 8 MiB worth.
+pub(super) const HASH_PAGE_SIZE: usize = 1024 * 1024;
+
+/// Context the key proving knowledge of a server's secret is derived with.
+const SECRET_CONTEXT: &str = "cdcfs 2026-10-18 chunk server secret";
+
+const GET: u8 = 1;
+const UPSERT: u8 = 2;
+const REMOVE: u8 = 3;
+const HASHES: u8 = 4;
+const HAVE: u8 = 5;
+const FINGERPRINT: u8 = 6;
+const SET_FINGERPRINT: u8 = 7;
+const INIT_FINGERPRINT: u8 = 8;
+const CHALLENGE: u8 = 9;
+const AUTHENTICATE: u8 = 10;
+const STORED_SIZE: u8 = 11;
+
+const OK: u8 = 0;
+const CHUNK: u8 = 1;
+const HASH_LIST: u8 = 2;
+const NO_FINGERPRINT: u8 = 3;
+const SOME_FINGERPRINT: u8 = 4;
+const NOT_FOUND: u8 = 5;
+const INTEGRITY: u8 = 6;
+const ERROR: u8 = 7;
+const NONCE: u8 = 8;
+const HASH_PAGE: u8 = 9;
+const SIZE: u8 = 10;
+
+/// Message sent to a [`ChunkServer`](super::ChunkServer).
+///
+/// Encoded as a tag byte followed by the fields as big-endian integers. Chunk
+/// bytes run to the end of the frame, hash lists are prefixed with their
+/// length.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum Request {
+    Get(u64),
+    Upsert(u64, Vec<u8>),
+    Remove(u64),
+    /// Asks how many bytes a chunk takes up in the server's store.
+    StoredSize(u64),
+    Hashes,
+    /// Offers chunks by hash, answered with the hashes the server wants.
+    Have(Vec<u64>),
+    Fingerprint,
+    SetFingerprint([u8; 32]),
+    /// Records a fingerprint unless one is recorded, answered with the one
+    /// recorded before.
+    InitFingerprint([u8; 32]),
+    /// Asks for a nonce to authenticate with.
+    Challenge,
+    /// Proves knowledge of the server's secret, see [`prove`].
+    Authenticate([u8; 32]),
+}
+
+/// Answer of a [`ChunkServer`](super::ChunkServer), encoded like
+/// [`Request`].
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum Response {
+    Ok,
+    Chunk(Vec<u8>),
+    Size(u64),
+    Hashes(Vec<u64>),
+    /// Part of a hash list too long for one frame. More pages follow, and the
+    /// last one is sent as [`Response::Hashes`].
+    HashPage(Vec<u64>),
+    Fingerprint(Option<[u8; 32]>),
+    Nonce([u8; 32]),
+    NotFound,
+    Integrity,
+    Error(String),
+}
+
+impl Request {
+    pub(super) fn encode(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        match self {
+            Self::Get(hash) => {
+                buf.push(GET);
+                buf.extend(hash.to_be_bytes());
+            }
+            Self::Upsert(hash, chunk) => {
+                buf.push(UPSERT);
+                buf.extend(hash.to_be_bytes());
+                buf.extend(chunk);
+            }
+            Self::Remove(hash) => {
+                buf.push(REMOVE);
+                buf.extend(hash.to_be_bytes());
+            }
+            Self::StoredSize(hash) => {
+                buf.push(STORED_SIZE);
+                buf.extend(hash.to_be_bytes());
+            }
+            Self::Hashes => buf.push(HASHES),
+            Self::Have(hashes) => {
+                buf.push(HAVE);
+                put_hashes(&mut buf, hashes);
+            }
+            Self::Fingerprint => buf.push(FINGERPRINT),
+            Self::SetFingerprint(fingerprint) => {
+                buf.push(SET_FINGERPRINT);
+                buf.extend(fingerprint);
+            }
+            Self::InitFingerprint(fingerprint) => {
+                buf.push(INIT_FINGERPRINT);
+                buf.extend(fingerprint);
+            }
+            Self::Challenge => buf.push(CHALLENGE),
+            Self::Authenticate(proof) => {
+                buf.push(AUTHENTICATE);
+                buf.extend(proof);
+            }
+        }
+        buf
+    }
+
+    pub(super) fn decode(frame: &[u8]) -> io::Result<Self> {
+        let (tag, mut body) = frame.split_first().ok_or_else(|| invalid("Empty frame"))?;
+        let request = match *tag {
+            GET => Self::Get(take_u64(&mut body)?),
+            UPSERT => {
+                let hash = take_u64(&mut body)?;
+                return Ok(Self::Upsert(hash, body.to_vec()));
+            }
+            REMOVE => Self::Remove(take_u64(&mut body)?),
+            STORED_SIZE => Self::StoredSize(take_u64(&mut body)?),
+            HASHES => Self::Hashes,
+            HAVE => Self::Have(take_hashes(&mut body)?),
+            FINGERPRINT => Self::Fingerprint,
+            SET_FINGERPRINT => Self::SetFingerprint(take_32_bytes(&mut body)?),
+            INIT_FINGERPRINT => Self::InitFingerprint(take_32_bytes(&mut body)?),
+            CHALLENGE => Self::Challenge,
+            AUTHENTICATE => Self::Authenticate(take_32_bytes(&mut body)?),
+            tag => return Err(invalid(format!("Unknown request {tag}"))),
+        };
+        finish(body)?;
+        Ok(request)
+    }
+}
+
+impl Response {
+    pub(super) fn encode(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        match self {
+            Self::Ok => buf.push(OK),
+            Self::Chunk(chunk) => {
+                buf.push(CHUNK);
+                buf.extend(chunk);
+            }
+            Self::Size(size) => {
+                buf.push(SIZE);
+                buf.extend(size.to_be_bytes());
+            }
+            Self::Hashes(hashes) => {
+                buf.push(HASH_LIST);
+                put_hashes(&mut buf, hashes);
+            }
+            Self::HashPage(hashes) => {
+                buf.push(HASH_PAGE);
+                put_hashes(&mut buf, hashes);
+            }
+            Self::Fingerprint(None) => buf.push(NO_FINGERPRINT),
+            Self::Fingerprint(Some(fingerprint)) => {
+                buf.push(SOME_FINGERPRINT);
+                buf.extend(fingerprint);
+            }
+            Self::Nonce(nonce) => {
+                buf.push(NONCE);
+                buf.extend(nonce);
+            }
+            Self::NotFound => buf.push(NOT_FOUND),
+            Self::Integrity => buf.push(INTEGRITY),
+            Self::Error(message) => {
+                buf.push(ERROR);
+                buf.extend(message.as_bytes());
+            }
+        }
+        buf
+    }
+
+    pub(super) fn decode(frame: &[u8]) -> io::Result<Self> {
+        let (tag, mut body) = frame.split_first().ok_or_else(|| invalid("Empty frame"))?;
+        let response = match *tag {
+            OK => Self::Ok,
+            CHUNK => return Ok(Self::Chunk(body.to_vec())),
+            SIZE => Self::Size(take_u64(&mut body)?),
+            HASH_LIST => Self::Hashes(take_hashes(&mut body)?),
+            HASH_PAGE => Self::HashPage(take_hashes(&mut body)?),
+            NO_FINGERPRINT => Self::Fingerprint(None),
+            SOME_FINGERPRINT => Self::Fingerprint(Some(take_32_bytes(&mut body)?)),
+            NONCE => Self::Nonce(take_32_bytes(&mut body)?),
+            NOT_FOUND => Self::NotFound,
+            INTEGRITY => Self::Integrity,
+            ERROR => return Ok(Self::Error(String::from_utf8_lossy(body).into_owned())),
+            tag => return Err(invalid(format!("Unknown response {tag}"))),
+        };
+        finish(body)?;
+        Ok(response)
+    }
+}
+
+/// Key a client proves knowledge of `secret` with, so that neither side keeps
+/// the secret itself around.
+pub(super) fn secret_key(secret: &[u8]) -> [u8; 32] {
+    blake3::derive_key(SECRET_CONTEXT, secret)
+}
+
+/// Answer to a server's `nonce`, which only a holder of the key can give and
+/// which is useless for any other connection.
+pub(super) fn prove(key: &[u8; 32], nonce: &[u8; 32]) -> [u8; 32] {
+    blake3::keyed_hash(key, nonce).into()
+}
+
+/// Writes `frame` prefixed with its length as a big-endian `u32`.
+pub(super) fn write_frame(writer: &mut impl Write, frame: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(frame.len())
+        .ok()
+        .filter(|len| *len <= MAX_FRAME_SIZE)
+        .ok_or_else(|| invalid("Frame too large"))?;
+    writer.write_all(&len.to_be_bytes())?;
+    writer.write_all(frame)?;
+    writer.flush()
+}
+
+/// Reads the next frame, `None` if the peer closed the connection in between
+/// frames.
+pub(super) fn read_frame(reader: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => (),
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    }
+    let len = u32::from_be_bytes(len);
+    if len > MAX_FRAME_SIZE {
+        return Err(invalid("Frame too large"));
+    }
+
+    let mut frame = vec![0; len as usize];
+    reader.read_exact(&mut frame)?;
+    Ok(Some(frame))
+}
+
+fn put_hashes(buf: &mut Vec<u8>, hashes: &[u64]) {
+    buf.extend((hashes.len() as u32).to_be_bytes());
+    for hash in hashes {
+        buf.extend(hash.to_be_bytes());
+    }
+}
+
+fn take<'a>(body: &mut &'a [u8], len: usize) -> io::Result<&'a [u8]> {
+    if body.len() < len {
+        return Err(invalid("Truncated frame"));
+    }
+    let (head, tail) = body.split_at(len);
+    *body = tail;
+    Ok(head)
+}
+
+fn take_u64(body: &mut &[u8]) -> io::Result<u64> {
+    let bytes = take(body, 8)?;
+    Ok(u64::from_be_bytes(bytes.try_into().expect("Took 8 bytes")))
+}
+
+fn take_hashes(body: &mut &[u8]) -> io::Result<Vec<u64>> {
+    let len = take(body, 4)?;
+    let len = u32::from_be_bytes(len.try_into().expect("Took 4 bytes")) as usize;
+    if body.len() != len * 8 {
+        return Err(invalid("Hash list does not match its length"));
+    }
+    (0..len).map(|_| take_u64(body)).collect()
+}
+
+fn take_32_bytes(body: &mut &[u8]) -> io::Result<[u8; 32]> {
+    Ok(take(body, 32)?.try_into().expect("Took 32 bytes"))
+}
+
+fn finish(body: &[u8]) -> io::Result<()> {
+    if body.is_empty() {
+        Ok(())
+    } else {
+        Err(invalid("Trailing bytes in frame"))
+    }
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
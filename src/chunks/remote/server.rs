@@ -0,0 +1,361 @@
+use std::{
+    fmt::Debug,
+    io::{self, BufReader, BufWriter, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, PoisonError, RwLock,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use anyhow::Context;
+use chacha20poly1305::aead::{rand_core::RngCore, OsRng};
+
+use super::protocol::{
+    prove, read_frame, secret_key, write_frame, Request, Response, HASH_PAGE_SIZE,
+};
+use crate::chunks::{error::Result, traits::ChunkStore, Error};
+
+/// Serves a chunk store over TCP to [`RemoteChunkStore`](super::RemoteChunkStore)
+/// clients.
+///
+/// Every connection is handled by its own thread, up to
+/// [`ChunkServerOptions::with_max_connections`] at once. Reads share the
+/// store, writes take it exclusively. The server runs until it is dropped or
+/// [`into_inner`](Self::into_inner) is called.
+///
+/// Started with [`bind`](Self::bind), the server lets anyone who can reach it
+/// read, overwrite and remove chunks. Use [`ChunkServerOptions`] to require a
+/// shared secret or to refuse writes. Even then, traffic is neither encrypted
+/// nor protected from tampering once a connection is authenticated, so the
+/// server belongs on a trusted network or behind a TLS tunnel.
+pub struct ChunkServer<C: ChunkStore + Send + Sync + 'static> {
+    store: Arc<RwLock<C>>,
+    addr: SocketAddr,
+    stopping: Arc<AtomicBool>,
+    connections: Arc<Mutex<Vec<Connection>>>,
+    acceptor: Option<JoinHandle<()>>,
+}
+
+/// Handle to shut a connection down with, and its thread.
+type Connection = (TcpStream, JoinHandle<()>);
+
+/// Access control and limits of a [`ChunkServer`].
+#[derive(Clone)]
+pub struct ChunkServerOptions {
+    key: Option<[u8; 32]>,
+    read_only: bool,
+    max_connections: usize,
+    auth_timeout: Duration,
+}
+
+impl Default for ChunkServerOptions {
+    fn default() -> Self {
+        Self {
+            key: None,
+            read_only: false,
+            max_connections: 256,
+            auth_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl ChunkServerOptions {
+    /// Options letting every client read and write.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only serves clients knowing `secret`, see
+    /// [`RemoteChunkStore::with_secret`](super::RemoteChunkStore::with_secret).
+    pub fn with_secret(mut self, secret: impl AsRef<[u8]>) -> Self {
+        self.key = Some(secret_key(secret.as_ref()));
+        self
+    }
+
+    /// Refuses every request changing the store.
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Turns connections away beyond `max_connections` open ones, 256 by
+    /// default.
+    pub fn with_max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = max_connections;
+        self
+    }
+
+    /// Drops clients that send nothing for `timeout` before authenticating,
+    /// 10 seconds by default. Only applies with a secret.
+    pub fn with_auth_timeout(mut self, timeout: Duration) -> Self {
+        self.auth_timeout = timeout;
+        self
+    }
+
+    /// Starts serving `store` on `addr`. Binding to port 0 picks a free port,
+    /// see [`ChunkServer::local_addr`].
+    pub fn bind<C: ChunkStore + Send + Sync + 'static>(
+        self,
+        addr: impl ToSocketAddrs,
+        store: C,
+    ) -> Result<ChunkServer<C>> {
+        ChunkServer::start(addr, store, self)
+    }
+}
+
+impl Debug for ChunkServerOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChunkServerOptions")
+            .field("secret", &self.key.is_some())
+            .field("read_only", &self.read_only)
+            .field("max_connections", &self.max_connections)
+            .field("auth_timeout", &self.auth_timeout)
+            .finish()
+    }
+}
+
+impl<C: ChunkStore + Send + Sync + 'static> ChunkServer<C> {
+    /// Starts serving `store` on `addr` to every client. Binding to port 0
+    /// picks a free port, see [`local_addr`](Self::local_addr).
+    pub fn bind(addr: impl ToSocketAddrs, store: C) -> Result<Self> {
+        Self::start(addr, store, ChunkServerOptions::new())
+    }
+
+    fn start(addr: impl ToSocketAddrs, store: C, options: ChunkServerOptions) -> Result<Self> {
+        let listener = TcpListener::bind(addr).context("Network error")?;
+        let addr = listener.local_addr().context("Network error")?;
+        let store = Arc::new(RwLock::new(store));
+        let stopping = Arc::new(AtomicBool::new(false));
+        let connections = Arc::new(Mutex::new(vec![]));
+
+        let acceptor = {
+            let store = Arc::clone(&store);
+            let stopping = Arc::clone(&stopping);
+            let connections = Arc::clone(&connections);
+            thread::spawn(move || accept(listener, store, options, stopping, connections))
+        };
+
+        Ok(Self {
+            store,
+            addr,
+            stopping,
+            connections,
+            acceptor: Some(acceptor),
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Stops serving, closing every open connection, and returns the store.
+    pub fn into_inner(mut self) -> C {
+        self.stop();
+        let store = Arc::clone(&self.store);
+        drop(self);
+        match Arc::try_unwrap(store) {
+            Ok(store) => store.into_inner().unwrap_or_else(PoisonError::into_inner),
+            Err(_) => unreachable!("Every connection thread has been joined"),
+        }
+    }
+
+    fn stop(&mut self) {
+        let Some(acceptor) = self.acceptor.take() else {
+            return;
+        };
+        self.stopping.store(true, Ordering::SeqCst);
+        // Wakes the acceptor up, which then sees that it is stopping.
+        let _ = TcpStream::connect(self.addr);
+        let _ = acceptor.join();
+
+        let connections = std::mem::take(
+            &mut *self
+                .connections
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+        );
+        for (stream, handle) in connections {
+            let _ = stream.shutdown(Shutdown::Both);
+            let _ = handle.join();
+        }
+    }
+}
+
+impl<C: ChunkStore + Send + Sync + 'static> Drop for ChunkServer<C> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl<C: ChunkStore + Send + Sync + 'static> Debug for ChunkServer<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChunkServer")
+            .field("addr", &self.addr)
+            .field("store", &self.store)
+            .finish_non_exhaustive()
+    }
+}
+
+fn accept<C: ChunkStore + Send + Sync + 'static>(
+    listener: TcpListener,
+    store: Arc<RwLock<C>>,
+    options: ChunkServerOptions,
+    stopping: Arc<AtomicBool>,
+    connections: Arc<Mutex<Vec<Connection>>>,
+) {
+    for stream in listener.incoming() {
+        if stopping.load(Ordering::SeqCst) {
+            return;
+        }
+        let Ok(mut stream) = stream else {
+            continue;
+        };
+        let mut connections = connections.lock().unwrap_or_else(PoisonError::into_inner);
+        connections.retain(|(_, handle)| !handle.is_finished());
+        if connections.len() >= options.max_connections {
+            // Answers the first request, the socket buffer takes it without blocking.
+            let response = Response::Error("Too many connections".to_owned());
+            let _ = write_frame(&mut stream, &response.encode());
+            let _ = stream.shutdown(Shutdown::Both);
+            continue;
+        }
+        let Ok(control) = stream.try_clone() else {
+            continue;
+        };
+
+        let store = Arc::clone(&store);
+        let options = options.clone();
+        let handle = thread::spawn(move || serve(stream, &store, &options));
+        connections.push((control, handle));
+    }
+}
+
+/// Answers requests until the client disconnects, sends a malformed frame or
+/// fails to authenticate.
+fn serve<C: ChunkStore>(stream: TcpStream, store: &RwLock<C>, options: &ChunkServerOptions) {
+    let _ = stream.set_nodelay(true);
+    // Keeps clients that never authenticate from holding a connection.
+    if options.key.is_some() && stream.set_read_timeout(Some(options.auth_timeout)).is_err() {
+        let _ = stream.shutdown(Shutdown::Both);
+        return;
+    }
+    let Ok(reader) = stream.try_clone() else {
+        return;
+    };
+    let mut reader = BufReader::new(reader);
+    let mut writer = BufWriter::new(stream);
+    let mut authenticated = options.key.is_none();
+    let mut nonce = None;
+
+    while let Ok(Some(frame)) = read_frame(&mut reader) {
+        let (response, valid) = match Request::decode(&frame) {
+            Ok(Request::Challenge) => {
+                let mut challenge = [0; 32];
+                OsRng.fill_bytes(&mut challenge);
+                nonce = Some(challenge);
+                (Response::Nonce(challenge), true)
+            }
+            Ok(Request::Authenticate(proof)) => match (&options.key, nonce.take()) {
+                (None, _) => (Response::Ok, true),
+                // Compares in constant time.
+                (Some(key), Some(nonce))
+                    if blake3::Hash::from(prove(key, &nonce)) == blake3::Hash::from(proof) =>
+                {
+                    authenticated = true;
+                    let _ = writer.get_ref().set_read_timeout(None);
+                    (Response::Ok, true)
+                }
+                _ => (Response::Error("Authentication failed".to_owned()), false),
+            },
+            Ok(_) if !authenticated => {
+                (Response::Error("Authentication required".to_owned()), false)
+            }
+            Ok(request) => (handle(request, store, options.read_only), true),
+            Err(err) => (Response::Error(err.to_string()), false),
+        };
+        if write_response(&mut writer, response).is_err() || !valid {
+            break;
+        }
+    }
+    // The server keeps a handle to the stream, so dropping ours does not
+    // close the connection.
+    let _ = writer.get_ref().shutdown(Shutdown::Both);
+}
+
+/// Writes `response`, splitting hash lists too long for one frame into pages.
+fn write_response(writer: &mut impl Write, response: Response) -> io::Result<()> {
+    match response {
+        Response::Hashes(hashes) if hashes.len() > HASH_PAGE_SIZE => {
+            let mut pages = hashes.chunks(HASH_PAGE_SIZE).peekable();
+            while let Some(page) = pages.next() {
+                let response = match pages.peek() {
+                    Some(_) => Response::HashPage(page.to_vec()),
+                    None => Response::Hashes(page.to_vec()),
+                };
+                write_frame(writer, &response.encode())?;
+            }
+            Ok(())
+        }
+        response => write_frame(writer, &response.encode()),
+    }
+}
+
+fn handle<C: ChunkStore>(request: Request, store: &RwLock<C>, read_only: bool) -> Response {
+    let read = || store.read().unwrap_or_else(PoisonError::into_inner);
+    let write = || store.write().unwrap_or_else(PoisonError::into_inner);
+
+    let writes = matches!(
+        request,
+        Request::Upsert(..)
+            | Request::Remove(_)
+            | Request::SetFingerprint(_)
+            | Request::InitFingerprint(_)
+    );
+    if read_only && writes {
+        return Response::Error("Server is read-only".to_owned());
+    }
+
+    let result = match request {
+        Request::Get(hash) => read().get(&hash).map(Response::Chunk),
+        Request::Upsert(hash, chunk) => write().upsert(hash, chunk).map(|_| Response::Ok),
+        Request::Remove(hash) => write().remove(&hash).map(|_| Response::Ok),
+        Request::StoredSize(hash) => read()
+            .stored_size(&hash)
+            .map(|size| Response::Size(size as u64)),
+        Request::Hashes => read().hashes().map(Response::Hashes),
+        Request::Have(hashes) => read().contains_many(&hashes).map(|found| {
+            let wanted = hashes
+                .into_iter()
+                .zip(found)
+                .filter(|(_, found)| !found)
+                .map(|(hash, _)| hash)
+                .collect();
+            Response::Hashes(wanted)
+        }),
+        Request::Fingerprint => read().hasher_fingerprint().map(Response::Fingerprint),
+        Request::SetFingerprint(fingerprint) => write()
+            .set_hasher_fingerprint(fingerprint)
+            .map(|_| Response::Ok),
+        Request::InitFingerprint(fingerprint) => write()
+            .init_hasher_fingerprint(fingerprint)
+            .map(Response::Fingerprint),
+        Request::Challenge | Request::Authenticate(_) => {
+            unreachable!("Authentication is handled by the connection")
+        }
+    };
+
+    match result {
+        Ok(response) => response,
+        Err(Error::NotFound) => Response::NotFound,
+        Err(Error::Integrity) => Response::Integrity,
+        Err(err) => {
+            // The cause may name paths or connection strings, so it is only
+            // logged.
+            tracing::error!("Request failed: {err:?}");
+            Response::Error("Internal error".to_owned())
+        }
+    }
+}
@@ -0,0 +1,433 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    sync::{
+        mpsc::{self, Sender},
+        Arc, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard,
+    },
+    thread::{self, JoinHandle},
+};
+
+use super::{
+    error::{Error, Result},
+    traits::{contains_missing, ChunkStore},
+};
+
+/// Chunk store keeping a copy of every chunk in each of its replicas.
+///
+/// Writes go to every replica and succeed once the write quorum has
+/// acknowledged them. Reads try the replicas in order until one returns the
+/// chunk and at least the read quorum has answered, so with the default read
+/// quorum of one a healthy first replica serves every read on its own.
+///
+/// Replicas found to be missing a chunk, or holding a copy that fails their
+/// integrity check, get the chunk copied back from another replica by a
+/// background thread, without holding up the read that noticed it. Every
+/// replica has a lock of its own, so a repair only holds up access to the
+/// replica it is copying to.
+///
+/// Replicas failing to remove a chunk keep a tombstone for it instead, until
+/// a retry succeeds. The chunk counts as removed meanwhile, so it is neither
+/// served nor copied back to the other replicas. Tombstones are only kept in
+/// memory: once the store is dropped, chunks whose removal had not reached
+/// every replica are served and re-replicated again. Callers can record
+/// [`pending_removes`](Self::pending_removes) before shutting down and remove
+/// those chunks again after reopening the replicas.
+pub struct ReplicatedChunkStore<C: ChunkStore + Send + Sync + 'static> {
+    replicas: Arc<Vec<RwLock<C>>>,
+    /// Replicas still holding a removed chunk, by hash.
+    tombstones: Arc<Mutex<HashMap<u64, HashSet<usize>>>>,
+    write_quorum: usize,
+    read_quorum: usize,
+    repairs: Option<Sender<Repair>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+/// Work done on the replica at `target` in the background.
+enum Repair {
+    /// Copy `hash` into the replica from any replica holding it.
+    Copy { hash: u64, target: usize },
+    /// Retry removing `hash` from the replica.
+    Remove { hash: u64, target: usize },
+}
+
+impl<C: ChunkStore + Send + Sync + 'static> ReplicatedChunkStore<C> {
+    /// Replicates chunks across `replicas` with a majority write quorum and a
+    /// read quorum of one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `replicas` is empty.
+    pub fn new(replicas: Vec<C>) -> Self {
+        assert!(!replicas.is_empty(), "At least one replica is required");
+        let write_quorum = replicas.len() / 2 + 1;
+        let replicas: Arc<Vec<_>> = Arc::new(replicas.into_iter().map(RwLock::new).collect());
+        let tombstones = Arc::new(Mutex::new(HashMap::new()));
+
+        let (repairs, queue) = mpsc::channel();
+        let worker = {
+            let replicas = Arc::clone(&replicas);
+            let tombstones = Arc::clone(&tombstones);
+            thread::spawn(move || {
+                for repair in queue {
+                    // Failed repairs are retried whenever a read finds the copy missing again.
+                    let _ = run_repair(&replicas, &tombstones, repair);
+                }
+            })
+        };
+
+        Self {
+            replicas,
+            tombstones,
+            write_quorum,
+            read_quorum: 1,
+            repairs: Some(repairs),
+            worker: Some(worker),
+        }
+    }
+
+    /// Number of replicas that have to acknowledge a write.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the quorum is zero or larger than the number of replicas.
+    pub fn with_write_quorum(mut self, write_quorum: usize) -> Self {
+        self.assert_quorum(write_quorum);
+        self.write_quorum = write_quorum;
+        self
+    }
+
+    /// Number of replicas that have to answer a read.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the quorum is zero or larger than the number of replicas.
+    pub fn with_read_quorum(mut self, read_quorum: usize) -> Self {
+        self.assert_quorum(read_quorum);
+        self.read_quorum = read_quorum;
+        self
+    }
+
+    /// Waits for pending re-replication and returns the replicas.
+    pub fn into_inner(mut self) -> Vec<C> {
+        self.stop_worker();
+        let replicas = std::mem::take(&mut self.replicas);
+        match Arc::try_unwrap(replicas) {
+            Ok(replicas) => replicas
+                .into_iter()
+                .map(|replica| replica.into_inner().unwrap_or_else(PoisonError::into_inner))
+                .collect(),
+            Err(_) => unreachable!("The worker has stopped"),
+        }
+    }
+
+    /// Hashes of the removed chunks still held by a replica that failed to
+    /// remove them.
+    pub fn pending_removes(&self) -> Vec<u64> {
+        self.tombstones().keys().copied().collect()
+    }
+
+    fn assert_quorum(&self, quorum: usize) {
+        assert!(
+            (1..=self.replicas.len()).contains(&quorum),
+            "Quorum must be between 1 and the number of replicas"
+        );
+    }
+
+    fn stop_worker(&mut self) {
+        drop(self.repairs.take());
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+
+    fn schedule_repair(&self, repair: Repair) {
+        if let Some(repairs) = &self.repairs {
+            let _ = repairs.send(repair);
+        }
+    }
+
+    fn tombstones(&self) -> MutexGuard<'_, HashMap<u64, HashSet<usize>>> {
+        self.tombstones
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Retries the removes of `hash` that failed, if any, and tells whether
+    /// there were.
+    fn retry_removes(&self, hash: u64) -> bool {
+        let targets: Vec<usize> = match self.tombstones().get(&hash) {
+            Some(targets) => targets.iter().copied().collect(),
+            None => return false,
+        };
+        for target in targets {
+            self.schedule_repair(Repair::Remove { hash, target });
+        }
+        true
+    }
+
+    /// Applies `write` to every replica and checks the write quorum. Replicas
+    /// failing the write are handed to `on_failure`.
+    fn write_all(
+        &mut self,
+        mut write: impl FnMut(&mut C) -> Result<()>,
+        mut on_failure: impl FnMut(&Self, usize),
+    ) -> Result<()> {
+        let mut acknowledged = 0;
+        let mut failed = vec![];
+        let mut last_error = None;
+        for (idx, replica) in self.replicas.iter().enumerate() {
+            match write(&mut write_replica(replica)) {
+                Ok(()) => acknowledged += 1,
+                Err(err) => {
+                    failed.push(idx);
+                    last_error = Some(err);
+                }
+            }
+        }
+
+        for idx in failed {
+            on_failure(self, idx);
+        }
+        match last_error {
+            Some(err) if acknowledged < self.write_quorum => Err(err),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<C: ChunkStore + Send + Sync + 'static> Drop for ReplicatedChunkStore<C> {
+    fn drop(&mut self) {
+        self.stop_worker();
+    }
+}
+
+impl<C: ChunkStore + Send + Sync + 'static> Debug for ReplicatedChunkStore<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReplicatedChunkStore")
+            .field("replicas", &self.replicas)
+            .field("write_quorum", &self.write_quorum)
+            .field("read_quorum", &self.read_quorum)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C: ChunkStore + Send + Sync + 'static> ChunkStore for ReplicatedChunkStore<C> {
+    fn get(&self, hash: &u64) -> Result<Vec<u8>> {
+        if self.retry_removes(*hash) {
+            return Err(Error::NotFound);
+        }
+        let mut answered = 0;
+        let mut chunk = None;
+        let mut lacking = vec![];
+        let mut last_error = None;
+        for (idx, replica) in self.replicas.iter().enumerate() {
+            if chunk.is_some() && answered >= self.read_quorum {
+                break;
+            }
+            match read_replica(replica).get(hash) {
+                Ok(found) => {
+                    answered += 1;
+                    chunk.get_or_insert(found);
+                }
+                Err(Error::NotFound | Error::Integrity) => {
+                    answered += 1;
+                    lacking.push(idx);
+                }
+                Err(err) => last_error = Some(err),
+            }
+        }
+
+        match (chunk, last_error) {
+            (_, Some(err)) if answered < self.read_quorum => Err(err),
+            (Some(chunk), _) => {
+                for target in lacking {
+                    self.schedule_repair(Repair::Copy {
+                        hash: *hash,
+                        target,
+                    });
+                }
+                Ok(chunk)
+            }
+            (None, _) => Err(Error::NotFound),
+        }
+    }
+
+    fn upsert(&mut self, hash: u64, chunk: Vec<u8>) -> Result<()> {
+        // Replicas left holding an earlier copy now hold the current one.
+        self.tombstones().remove(&hash);
+        self.write_all(
+            |replica| replica.upsert(hash, chunk.clone()),
+            |store, target| store.schedule_repair(Repair::Copy { hash, target }),
+        )
+    }
+
+    fn remove(&mut self, hash: &u64) -> Result<()> {
+        let hash = *hash;
+        // Every replica holds a tombstone while the chunk is being removed, so
+        // that repairs do not copy it back to replicas already done.
+        let mut removed = self
+            .tombstones()
+            .insert(hash, (0..self.replicas.len()).collect())
+            .is_some();
+        let mut failed = vec![];
+        let result = self.write_all(
+            |replica| match replica.remove(&hash) {
+                Ok(()) => {
+                    removed = true;
+                    Ok(())
+                }
+                Err(Error::NotFound) => Ok(()),
+                Err(err) => Err(err),
+            },
+            |_, target| failed.push(target),
+        );
+        if result.is_ok() && !failed.is_empty() {
+            self.tombstones().insert(hash, failed.into_iter().collect());
+        } else {
+            self.tombstones().remove(&hash);
+        }
+        result?;
+        if removed {
+            Ok(())
+        } else {
+            Err(Error::NotFound)
+        }
+    }
+
+    /// Asks the replicas in order about the chunks none has reported yet, and
+    /// leaves replicas lacking chunks to be repaired by reads.
+    fn contains_many(&self, hashes: &[u64]) -> Result<Vec<bool>> {
+        let mut answered = 0;
+        let mut found = vec![false; hashes.len()];
+        // Removed chunks are not looked for.
+        let removed: Vec<usize> = {
+            let tombstones = self.tombstones();
+            (0..hashes.len())
+                .filter(|idx| tombstones.contains_key(&hashes[*idx]))
+                .collect()
+        };
+        for idx in &removed {
+            found[*idx] = true;
+        }
+        let mut last_error = None;
+        for replica in self.replicas.iter() {
+            if answered >= self.read_quorum && found.iter().all(|found| *found) {
+                break;
+            }
+            match contains_missing(&*read_replica(replica), hashes, &mut found) {
+                Ok(()) => answered += 1,
+                Err(err) => last_error = Some(err),
+            }
+        }
+
+        for idx in removed {
+            found[idx] = false;
+        }
+        match last_error {
+            Some(err) if answered < self.read_quorum => Err(err),
+            _ => Ok(found),
+        }
+    }
+
+    fn hashes(&self) -> Result<Vec<u64>> {
+        let mut answered = 0;
+        let mut hashes = HashSet::new();
+        let mut last_error = None;
+        for replica in self.replicas.iter() {
+            match read_replica(replica).hashes() {
+                Ok(found) => {
+                    answered += 1;
+                    hashes.extend(found);
+                }
+                Err(err) => last_error = Some(err),
+            }
+        }
+
+        let tombstones = self.tombstones();
+        hashes.retain(|hash| !tombstones.contains_key(hash));
+        match last_error {
+            Some(err) if answered < self.read_quorum => Err(err),
+            _ => Ok(hashes.into_iter().collect()),
+        }
+    }
+
+    fn hasher_fingerprint(&self) -> Result<Option<[u8; 32]>> {
+        let mut answered = 0;
+        let mut fingerprint = None;
+        let mut last_error = None;
+        for replica in self.replicas.iter() {
+            match read_replica(replica).hasher_fingerprint() {
+                Ok(found) => {
+                    answered += 1;
+                    fingerprint = fingerprint.or(found);
+                }
+                Err(err) => last_error = Some(err),
+            }
+        }
+
+        match last_error {
+            Some(err) if answered < self.read_quorum => Err(err),
+            _ => Ok(fingerprint),
+        }
+    }
+
+    fn set_hasher_fingerprint(&mut self, fingerprint: [u8; 32]) -> Result<()> {
+        self.write_all(
+            |replica| replica.set_hasher_fingerprint(fingerprint),
+            |_, _| (),
+        )
+    }
+}
+
+fn read_replica<C>(replica: &RwLock<C>) -> RwLockReadGuard<'_, C> {
+    replica.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn write_replica<C>(replica: &RwLock<C>) -> RwLockWriteGuard<'_, C> {
+    replica.write().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Runs `repair`, reading the source replica and writing the target one
+/// under their own locks.
+fn run_repair<C: ChunkStore>(
+    replicas: &[RwLock<C>],
+    tombstones: &Mutex<HashMap<u64, HashSet<usize>>>,
+    repair: Repair,
+) -> Result<()> {
+    let tombstones = || tombstones.lock().unwrap_or_else(PoisonError::into_inner);
+    match repair {
+        Repair::Copy { hash, target } => {
+            // Holding the target keeps a remove from passing it between the
+            // check and the copy.
+            let mut replica = write_replica(&replicas[target]);
+            // The chunk was removed since the repair was scheduled.
+            if tombstones().contains_key(&hash) {
+                return Ok(());
+            }
+            let chunk = replicas
+                .iter()
+                .enumerate()
+                .filter(|(idx, _)| *idx != target)
+                .find_map(|(_, replica)| read_replica(replica).get(&hash).ok());
+            let Some(chunk) = chunk else {
+                return Ok(());
+            };
+            replica.upsert(hash, chunk)
+        }
+        Repair::Remove { hash, target } => {
+            match write_replica(&replicas[target]).remove(&hash) {
+                Ok(()) | Err(Error::NotFound) => (),
+                Err(err) => return Err(err),
+            }
+            let mut tombstones = tombstones();
+            if let Some(targets) = tombstones.get_mut(&hash) {
+                targets.remove(&target);
+                if targets.is_empty() {
+                    tombstones.remove(&hash);
+                }
+            }
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,428 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard,
+    },
+};
+
+use anyhow::anyhow;
+
+use super::{
+    error::{Error, Result},
+    traits::{contains_missing, ChunkStore},
+};
+
+const DEFAULT_VIRTUAL_NODES: usize = 128;
+
+/// Chunk store spreading chunks across shards with a consistent hash ring.
+///
+/// Every shard is placed on the ring at a number of points derived from its
+/// name, and a chunk belongs to the shard owning the first point at or after
+/// its hash. Adding or removing a shard therefore only changes the owner of
+/// the chunks next to that shard's points.
+///
+/// Changing the shards does not move any data by itself. Until
+/// [`rebalance`](Self::rebalance) has moved every chunk to its new owner,
+/// reads fall back to the other shards and removals reach all of them, so the
+/// store stays fully usable while data is moved in small batches. Rebalancing
+/// only needs a shared reference, so reads carry on from other threads while
+/// it runs: a chunk is written to its owner before it is removed from the
+/// previous one.
+///
+/// Whether chunks sit on their owners is only known for changes made through
+/// the store itself. A store assembled from shards that already hold chunks,
+/// e.g. after a restart, is treated as unbalanced, with reads of missing
+/// chunks asking every shard, until [`rebalance`](Self::rebalance) has run to
+/// completion.
+#[derive(Debug)]
+pub struct ShardedChunkStore<C: ChunkStore> {
+    shards: Vec<Shard<C>>,
+    ring: BTreeMap<u64, usize>,
+    virtual_nodes: usize,
+    balanced: AtomicBool,
+}
+
+#[derive(Debug)]
+struct Shard<C> {
+    name: String,
+    store: RwLock<C>,
+    /// Shards taken off the ring keep serving reads until they are drained.
+    on_ring: bool,
+    /// Chunks rebalancing has yet to check, listed once after the ring
+    /// changes and consumed batch by batch.
+    pending: Mutex<Option<Vec<u64>>>,
+}
+
+impl<C> Shard<C> {
+    fn new(name: String, store: C) -> Self {
+        Self {
+            name,
+            store: RwLock::new(store),
+            on_ring: true,
+            pending: Mutex::new(None),
+        }
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, C> {
+        self.store.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, C> {
+        self.store.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn store_mut(&mut self) -> &mut C {
+        self.store.get_mut().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<C: ChunkStore> ShardedChunkStore<C> {
+    pub fn new() -> Self {
+        Self {
+            shards: vec![],
+            ring: BTreeMap::new(),
+            virtual_nodes: DEFAULT_VIRTUAL_NODES,
+            balanced: AtomicBool::new(true),
+        }
+    }
+
+    /// Number of ring points per shard. More points spread chunks more evenly.
+    /// Changing it moves chunks between existing shards, so it is best set
+    /// before adding any.
+    pub fn with_virtual_nodes(mut self, virtual_nodes: usize) -> Self {
+        self.virtual_nodes = virtual_nodes.max(1);
+        if !self.shards.is_empty() {
+            *self.balanced.get_mut() = false;
+        }
+        self.build_ring();
+        self
+    }
+
+    pub fn with_shard(mut self, name: impl Into<String>, store: C) -> Result<Self> {
+        self.add_shard(name, store)?;
+        Ok(self)
+    }
+
+    /// Names of the shards on the ring.
+    pub fn shards(&self) -> impl Iterator<Item = &str> {
+        self.shards
+            .iter()
+            .filter(|shard| shard.on_ring)
+            .map(|shard| shard.name.as_str())
+    }
+
+    /// Name of the shard `hash` belongs to.
+    pub fn owner(&self, hash: u64) -> Option<&str> {
+        self.owner_idx(hash)
+            .map(|idx| self.shards[idx].name.as_str())
+    }
+
+    /// Adds a shard to the ring. Chunks it now owns stay where they are until
+    /// the store is rebalanced. Fails if the shard was written with a
+    /// different hasher than the shards already on the ring.
+    pub fn add_shard(&mut self, name: impl Into<String>, mut store: C) -> Result<()> {
+        let name = name.into();
+        if self.shards.iter().any(|shard| shard.name == name) {
+            return Err(anyhow!("Shard {name} already exists").into());
+        }
+        if let Some(fingerprint) = self.hasher_fingerprint()? {
+            match store.init_hasher_fingerprint(fingerprint)? {
+                Some(recorded) if recorded != fingerprint => {
+                    return Err(anyhow!("Shard {name} was written with a different hasher").into());
+                }
+                _ => (),
+            }
+        }
+
+        // Only chunks already stored can end up on the wrong shard.
+        if !self.shards.is_empty() && (!store.is_empty()? || !self.shards_are_empty()?) {
+            *self.balanced.get_mut() = false;
+        }
+        self.shards.push(Shard::new(name, store));
+        self.build_ring();
+        Ok(())
+    }
+
+    /// Takes a shard off the ring. It keeps serving reads until rebalancing
+    /// has moved its chunks to the remaining shards, after which it can be
+    /// taken out with [`take_drained_shards`](Self::take_drained_shards).
+    pub fn remove_shard(&mut self, name: &str) -> Result<()> {
+        let shard = self
+            .shards
+            .iter_mut()
+            .find(|shard| shard.on_ring && shard.name == name)
+            .ok_or_else(|| anyhow!("Shard {name} does not exist"))?;
+        shard.on_ring = false;
+        *self.balanced.get_mut() = false;
+        self.build_ring();
+        Ok(())
+    }
+
+    /// Moves up to `limit` chunks stored on the wrong shard to their owner and
+    /// returns how many were moved. Returns zero once the store is balanced.
+    ///
+    /// Every shard is listed once after the shards change, and each call picks
+    /// up where the previous one stopped.
+    pub fn rebalance(&self, limit: usize) -> Result<usize> {
+        if self.is_balanced() {
+            return Ok(0);
+        }
+        if self.ring.is_empty() {
+            return Err(anyhow!("Cannot rebalance without shards on the ring").into());
+        }
+
+        let mut moved = 0;
+        for (idx, shard) in self.shards.iter().enumerate() {
+            let mut pending = shard.pending.lock().unwrap_or_else(PoisonError::into_inner);
+            let pending = match &mut *pending {
+                Some(pending) => pending,
+                None => pending.insert(self.misplaced(idx)?),
+            };
+            while moved < limit {
+                let Some(hash) = pending.pop() else {
+                    break;
+                };
+                match self.move_chunk(idx, hash) {
+                    Ok(true) => moved += 1,
+                    Ok(false) => (),
+                    Err(err) => {
+                        pending.push(hash);
+                        return Err(err);
+                    }
+                }
+            }
+            if moved == limit {
+                return Ok(moved);
+            }
+        }
+
+        self.balanced.store(true, Ordering::Release);
+        Ok(moved)
+    }
+
+    /// Takes out the shards that were removed from the ring and no longer
+    /// hold any chunks.
+    pub fn take_drained_shards(&mut self) -> Result<Vec<(String, C)>> {
+        let mut drained = vec![];
+        let mut idx = 0;
+        while idx < self.shards.len() {
+            let shard = &mut self.shards[idx];
+            if !shard.on_ring && shard.store_mut().hashes()?.is_empty() {
+                let shard = self.shards.remove(idx);
+                let store = shard
+                    .store
+                    .into_inner()
+                    .unwrap_or_else(PoisonError::into_inner);
+                drained.push((shard.name, store));
+            } else {
+                idx += 1;
+            }
+        }
+        self.build_ring();
+        Ok(drained)
+    }
+
+    /// Whether every chunk is stored on the shard it belongs to, as far as
+    /// the store knows.
+    fn is_balanced(&self) -> bool {
+        self.balanced.load(Ordering::Acquire)
+    }
+
+    /// Whether no shard holds any chunks, stopping at the first that does.
+    fn shards_are_empty(&self) -> Result<bool> {
+        for shard in &self.shards {
+            if !shard.read().is_empty()? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Rebuilds the ring, after which every shard has to be listed again.
+    fn build_ring(&mut self) {
+        self.ring.clear();
+        for (idx, shard) in self.shards.iter_mut().enumerate() {
+            *shard
+                .pending
+                .get_mut()
+                .unwrap_or_else(PoisonError::into_inner) = None;
+            if !shard.on_ring {
+                continue;
+            }
+            for node in 0..self.virtual_nodes {
+                self.ring.insert(ring_point(&shard.name, node), idx);
+            }
+        }
+    }
+
+    fn owner_idx(&self, hash: u64) -> Option<usize> {
+        self.ring
+            .range(hash..)
+            .next()
+            .or_else(|| self.ring.iter().next())
+            .map(|(_, idx)| *idx)
+    }
+
+    /// Hashes of the chunks on the shard at `idx` that belong elsewhere.
+    fn misplaced(&self, idx: usize) -> Result<Vec<u64>> {
+        let mut hashes = self.shards[idx].read().hashes()?;
+        hashes.retain(|hash| self.owner_idx(*hash) != Some(idx));
+        Ok(hashes)
+    }
+
+    /// Moves a chunk from the shard at `idx` to its owner, returning whether
+    /// it was still there to move.
+    fn move_chunk(&self, idx: usize, hash: u64) -> Result<bool> {
+        let owner = self.owner_idx(hash).expect("Ring is not empty");
+        let chunk = match self.shards[idx].read().get(&hash) {
+            Ok(chunk) => chunk,
+            Err(Error::NotFound) => return Ok(false),
+            Err(err) => return Err(err),
+        };
+        self.shards[owner].write().upsert(hash, chunk)?;
+        match self.shards[idx].write().remove(&hash) {
+            Ok(()) | Err(Error::NotFound) => Ok(true),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl<C: ChunkStore> Default for ShardedChunkStore<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: ChunkStore> ChunkStore for ShardedChunkStore<C> {
+    fn get(&self, hash: &u64) -> Result<Vec<u8>> {
+        let Some(owner) = self.owner_idx(*hash) else {
+            return Err(Error::NotFound);
+        };
+        match self.shards[owner].read().get(hash) {
+            Err(Error::NotFound) if !self.is_balanced() => (),
+            result => return result,
+        }
+
+        for (idx, shard) in self.shards.iter().enumerate() {
+            if idx == owner {
+                continue;
+            }
+            match shard.read().get(hash) {
+                Err(Error::NotFound) => (),
+                result => return result,
+            }
+        }
+        // Rebalancing may have moved the chunk to its owner in between.
+        self.shards[owner].read().get(hash)
+    }
+
+    fn upsert(&mut self, hash: u64, chunk: Vec<u8>) -> Result<()> {
+        let owner = self
+            .owner_idx(hash)
+            .ok_or_else(|| anyhow!("No shards on the ring"))?;
+        self.shards[owner].store_mut().upsert(hash, chunk)
+    }
+
+    fn remove(&mut self, hash: &u64) -> Result<()> {
+        let Some(owner) = self.owner_idx(*hash) else {
+            return Err(Error::NotFound);
+        };
+        if self.is_balanced() {
+            return self.shards[owner].store_mut().remove(hash);
+        }
+
+        // Until rebalanced, stale copies may be left on a previous owner.
+        let mut removed = false;
+        for shard in &mut self.shards {
+            match shard.store_mut().remove(hash) {
+                Ok(()) => removed = true,
+                Err(Error::NotFound) => (),
+                Err(err) => return Err(err),
+            }
+        }
+        if removed {
+            Ok(())
+        } else {
+            Err(Error::NotFound)
+        }
+    }
+
+    /// Asks every shard about the chunks it owns in one batch, and the other
+    /// shards about those not found until the store is rebalanced.
+    fn contains_many(&self, hashes: &[u64]) -> Result<Vec<bool>> {
+        let mut owned: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        for (idx, hash) in hashes.iter().enumerate() {
+            if let Some(owner) = self.owner_idx(*hash) {
+                owned.entry(owner).or_default().push(idx);
+            }
+        }
+
+        let mut found = vec![false; hashes.len()];
+        for (owner, idxs) in &owned {
+            let batch: Vec<u64> = idxs.iter().map(|idx| hashes[*idx]).collect();
+            let present = self.shards[*owner].read().contains_many(&batch)?;
+            for (idx, present) in idxs.iter().zip(present) {
+                found[*idx] = present;
+            }
+        }
+        if !self.is_balanced() {
+            for shard in &self.shards {
+                contains_missing(&*shard.read(), hashes, &mut found)?;
+            }
+            // Rebalancing may have moved chunks to their owners in between.
+            for (owner, idxs) in &owned {
+                let missing: Vec<usize> = idxs.iter().copied().filter(|idx| !found[*idx]).collect();
+                if missing.is_empty() {
+                    continue;
+                }
+                let batch: Vec<u64> = missing.iter().map(|idx| hashes[*idx]).collect();
+                let present = self.shards[*owner].read().contains_many(&batch)?;
+                for (idx, present) in missing.into_iter().zip(present) {
+                    found[idx] = present;
+                }
+            }
+        }
+        Ok(found)
+    }
+
+    fn hashes(&self) -> Result<Vec<u64>> {
+        let mut hashes = HashSet::new();
+        for shard in &self.shards {
+            hashes.extend(shard.read().hashes()?);
+        }
+        Ok(hashes.into_iter().collect())
+    }
+
+    fn is_empty(&self) -> Result<bool> {
+        self.shards_are_empty()
+    }
+
+    fn hasher_fingerprint(&self) -> Result<Option<[u8; 32]>> {
+        for shard in &self.shards {
+            if let Some(fingerprint) = shard.read().hasher_fingerprint()? {
+                return Ok(Some(fingerprint));
+            }
+        }
+        Ok(None)
+    }
+
+    fn set_hasher_fingerprint(&mut self, fingerprint: [u8; 32]) -> Result<()> {
+        for shard in &mut self.shards {
+            shard.store_mut().set_hasher_fingerprint(fingerprint)?;
+        }
+        Ok(())
+    }
+}
+
+/// Position of a shard's virtual node on the ring, stable across processes.
+/// The name is length prefixed so that no two name and node pairs hash the
+/// same bytes.
+fn ring_point(name: &str, node: usize) -> u64 {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&(name.len() as u64).to_le_bytes());
+    hasher.update(name.as_bytes());
+    hasher.update(&(node as u64).to_le_bytes());
+    let hash = hasher.finalize();
+    u64::from_le_bytes(hash.as_bytes()[..8].try_into().expect("Hash has 32 bytes"))
+}
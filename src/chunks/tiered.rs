@@ -0,0 +1,379 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::Debug,
+    sync::{Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard},
+};
+
+use super::{
+    error::{Error, Result},
+    traits::{contains_missing, ChunkStore},
+};
+
+/// When a [`TieredChunkStore`] writes chunks to its cold tier.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WritePolicy {
+    /// Every write reaches the cold tier before it returns.
+    #[default]
+    WriteThrough,
+    /// Writes only reach the hot tier, and chunks are written to the cold
+    /// tier when evicted or on [`TieredChunkStore::flush`].
+    WriteBack,
+}
+
+/// Which chunk a [`TieredChunkStore`] evicts from its hot tier first.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Eviction {
+    /// The least recently used chunk.
+    #[default]
+    Lru,
+    /// The least frequently used chunk, the least recently used among equals.
+    Lfu,
+}
+
+/// Chunk store keeping a bounded working set of chunks in a fast hot tier in
+/// front of a slow cold tier holding the full data set.
+///
+/// Chunks read from the cold tier are promoted to the hot tier, and chunks
+/// are evicted from it once they take up more than the byte budget. The hot
+/// tier is owned by this store: it is expected to start out empty, and chunks
+/// it already holds are not accounted for.
+///
+/// With [`WritePolicy::WriteBack`], chunks only in the hot tier are lost
+/// unless [`flush`](Self::flush) is called before the store is dropped.
+pub struct TieredChunkStore<H: ChunkStore, C: ChunkStore> {
+    tiers: Mutex<Tiers<H>>,
+    /// Locked on its own, so that slow cold reads do not hold up the hot tier.
+    /// Taken after `tiers` when both are needed.
+    cold: RwLock<C>,
+    write_policy: WritePolicy,
+    eviction: Eviction,
+    budget: usize,
+}
+
+struct Tiers<H> {
+    hot: H,
+    entries: HashMap<u64, Entry>,
+    /// Hot chunks by eviction priority, lowest first.
+    order: BTreeSet<(u64, u64, u64)>,
+    used: usize,
+    clock: u64,
+}
+
+#[derive(Clone, Copy)]
+struct Entry {
+    size: usize,
+    last_used: u64,
+    uses: u64,
+    /// Written to the hot tier only.
+    dirty: bool,
+}
+
+impl<H: ChunkStore, C: ChunkStore> TieredChunkStore<H, C> {
+    /// Keeps up to `budget` bytes of chunks in the `hot` tier.
+    pub fn new(hot: H, cold: C, budget: usize) -> Self {
+        Self {
+            tiers: Mutex::new(Tiers {
+                hot,
+                entries: HashMap::new(),
+                order: BTreeSet::new(),
+                used: 0,
+                clock: 0,
+            }),
+            cold: RwLock::new(cold),
+            write_policy: WritePolicy::default(),
+            eviction: Eviction::default(),
+            budget,
+        }
+    }
+
+    pub fn with_write_policy(mut self, write_policy: WritePolicy) -> Self {
+        self.write_policy = write_policy;
+        self
+    }
+
+    pub fn with_eviction(mut self, eviction: Eviction) -> Self {
+        self.eviction = eviction;
+        self
+    }
+
+    /// Bytes of chunks currently held by the hot tier.
+    pub fn hot_bytes(&self) -> usize {
+        self.lock().used
+    }
+
+    /// Whether `hash` is currently held by the hot tier.
+    pub fn is_hot(&self, hash: &u64) -> bool {
+        self.lock().entries.contains_key(hash)
+    }
+
+    /// Writes every chunk only held by the hot tier to the cold tier.
+    pub fn flush(&mut self) -> Result<usize> {
+        let tiers = self.tiers.get_mut().unwrap_or_else(PoisonError::into_inner);
+        let cold = self.cold.get_mut().unwrap_or_else(PoisonError::into_inner);
+        let dirty: Vec<u64> = tiers
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.dirty)
+            .map(|(hash, _)| *hash)
+            .collect();
+        for hash in &dirty {
+            let chunk = tiers.hot.get(hash)?;
+            cold.upsert(*hash, chunk)?;
+            if let Some(entry) = tiers.entries.get_mut(hash) {
+                entry.dirty = false;
+            }
+        }
+        Ok(dirty.len())
+    }
+
+    /// Flushes pending writes and returns the hot and cold tiers.
+    pub fn into_inner(mut self) -> Result<(H, C)> {
+        self.flush()?;
+        let tiers = self
+            .tiers
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+        let cold = self
+            .cold
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+        Ok((tiers.hot, cold))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Tiers<H>> {
+        self.tiers.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn cold(&self) -> RwLockReadGuard<'_, C> {
+        self.cold.read().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<H: ChunkStore> Tiers<H> {
+    fn priority(eviction: Eviction, hash: u64, entry: &Entry) -> (u64, u64, u64) {
+        match eviction {
+            Eviction::Lru => (entry.last_used, 0, hash),
+            Eviction::Lfu => (entry.uses, entry.last_used, hash),
+        }
+    }
+
+    fn touch(&mut self, eviction: Eviction, hash: u64) {
+        self.clock += 1;
+        let clock = self.clock;
+        if let Some(entry) = self.entries.get_mut(&hash) {
+            self.order.remove(&Self::priority(eviction, hash, entry));
+            entry.last_used = clock;
+            entry.uses += 1;
+            self.order.insert(Self::priority(eviction, hash, entry));
+        }
+    }
+
+    /// Stores `chunk` in the hot tier and evicts other chunks to stay within
+    /// `budget`, writing those only held by the hot tier to `cold`.
+    fn insert_hot<C: ChunkStore>(
+        &mut self,
+        cold: &RwLock<C>,
+        eviction: Eviction,
+        budget: usize,
+        hash: u64,
+        chunk: Vec<u8>,
+        dirty: bool,
+    ) -> Result<()> {
+        let size = chunk.len();
+        self.hot.upsert(hash, chunk)?;
+        self.forget(eviction, hash);
+
+        self.clock += 1;
+        let entry = Entry {
+            size,
+            last_used: self.clock,
+            uses: 1,
+            dirty,
+        };
+        self.order.insert(Self::priority(eviction, hash, &entry));
+        self.entries.insert(hash, entry);
+        self.used += size;
+
+        while self.used > budget {
+            let Some(&(_, _, victim)) = self.order.iter().find(|(_, _, other)| *other != hash)
+            else {
+                break;
+            };
+            self.evict(cold, eviction, victim)?;
+        }
+        Ok(())
+    }
+
+    fn evict<C: ChunkStore>(
+        &mut self,
+        cold: &RwLock<C>,
+        eviction: Eviction,
+        hash: u64,
+    ) -> Result<()> {
+        let Some(entry) = self.entries.get(&hash).copied() else {
+            return Ok(());
+        };
+        if entry.dirty {
+            let chunk = self.hot.get(&hash)?;
+            cold.write()
+                .unwrap_or_else(PoisonError::into_inner)
+                .upsert(hash, chunk)?;
+        }
+        match self.hot.remove(&hash) {
+            Ok(()) | Err(Error::NotFound) => (),
+            Err(err) => return Err(err),
+        }
+        self.forget(eviction, hash);
+        Ok(())
+    }
+
+    /// Stops tracking `hash` without touching either tier.
+    fn forget(&mut self, eviction: Eviction, hash: u64) -> Option<Entry> {
+        let entry = self.entries.remove(&hash)?;
+        self.order.remove(&Self::priority(eviction, hash, &entry));
+        self.used -= entry.size;
+        Some(entry)
+    }
+}
+
+impl<H: ChunkStore, C: ChunkStore> Debug for TieredChunkStore<H, C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let tiers = self.lock();
+        f.debug_struct("TieredChunkStore")
+            .field("hot", &tiers.hot)
+            .field("cold", &*self.cold())
+            .field("write_policy", &self.write_policy)
+            .field("eviction", &self.eviction)
+            .field("budget", &self.budget)
+            .field("used", &tiers.used)
+            .finish()
+    }
+}
+
+impl<H: ChunkStore, C: ChunkStore> ChunkStore for TieredChunkStore<H, C> {
+    fn get(&self, hash: &u64) -> Result<Vec<u8>> {
+        {
+            let mut tiers = self.lock();
+            if let Some(entry) = tiers.entries.get(hash).copied() {
+                match tiers.hot.get(hash) {
+                    Ok(chunk) => {
+                        tiers.touch(self.eviction, *hash);
+                        return Ok(chunk);
+                    }
+                    // The hot tier dropped the chunk behind our back.
+                    Err(Error::NotFound) if !entry.dirty => {
+                        tiers.forget(self.eviction, *hash);
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+
+        // Other reads go on while the chunk is fetched. Only writes, which
+        // need exclusive access, could change the tiers in between.
+        let chunk = self.cold().get(hash)?;
+        if chunk.len() <= self.budget {
+            self.lock().insert_hot(
+                &self.cold,
+                self.eviction,
+                self.budget,
+                *hash,
+                chunk.clone(),
+                false,
+            )?;
+        }
+        Ok(chunk)
+    }
+
+    fn upsert(&mut self, hash: u64, chunk: Vec<u8>) -> Result<()> {
+        let tiers = self.tiers.get_mut().unwrap_or_else(PoisonError::into_inner);
+        let cold = self.cold.get_mut().unwrap_or_else(PoisonError::into_inner);
+        let write_back = self.write_policy == WritePolicy::WriteBack;
+        if chunk.len() > self.budget {
+            cold.upsert(hash, chunk)?;
+            if tiers.entries.contains_key(&hash) {
+                tiers.forget(self.eviction, hash);
+                let _ = tiers.hot.remove(&hash);
+            }
+            return Ok(());
+        }
+
+        if !write_back {
+            cold.upsert(hash, chunk.clone())?;
+        }
+        tiers.insert_hot(
+            &self.cold,
+            self.eviction,
+            self.budget,
+            hash,
+            chunk,
+            write_back,
+        )
+    }
+
+    fn remove(&mut self, hash: &u64) -> Result<()> {
+        let tiers = self.tiers.get_mut().unwrap_or_else(PoisonError::into_inner);
+        let hot = match tiers.forget(self.eviction, *hash) {
+            Some(entry) => {
+                match tiers.hot.remove(hash) {
+                    Ok(()) | Err(Error::NotFound) => (),
+                    Err(err) => return Err(err),
+                }
+                Some(entry.dirty)
+            }
+            None => None,
+        };
+
+        let cold = self.cold.get_mut().unwrap_or_else(PoisonError::into_inner);
+        match (cold.remove(hash), hot) {
+            (Ok(()), _) => Ok(()),
+            (Err(Error::NotFound), Some(_)) => Ok(()),
+            (Err(err), _) => Err(err),
+        }
+    }
+
+    /// Asks the cold tier about every chunk the hot tier does not hold alone,
+    /// without promoting any.
+    fn contains_many(&self, hashes: &[u64]) -> Result<Vec<bool>> {
+        let mut found: Vec<bool> = {
+            let tiers = self.lock();
+            hashes
+                .iter()
+                .map(|hash| tiers.entries.get(hash).is_some_and(|entry| entry.dirty))
+                .collect()
+        };
+        contains_missing(&*self.cold(), hashes, &mut found)?;
+        Ok(found)
+    }
+
+    fn hashes(&self) -> Result<Vec<u64>> {
+        let tiers = self.lock();
+        let mut hashes: HashSet<u64> = self.cold().hashes()?.into_iter().collect();
+        hashes.extend(
+            tiers
+                .entries
+                .iter()
+                .filter(|(_, entry)| entry.dirty)
+                .map(|(hash, _)| *hash),
+        );
+        Ok(hashes.into_iter().collect())
+    }
+
+    fn hasher_fingerprint(&self) -> Result<Option<[u8; 32]>> {
+        self.cold().hasher_fingerprint()
+    }
+
+    fn set_hasher_fingerprint(&mut self, fingerprint: [u8; 32]) -> Result<()> {
+        self.cold
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .set_hasher_fingerprint(fingerprint)
+    }
+
+    fn init_hasher_fingerprint(&mut self, fingerprint: [u8; 32]) -> Result<Option<[u8; 32]>> {
+        self.cold
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .init_hasher_fingerprint(fingerprint)
+    }
+}
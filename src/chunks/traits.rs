@@ -1,6 +1,8 @@
 use core::fmt::Debug;
 
-use super::error::Result;
+use anyhow::anyhow;
+
+use super::error::{Error, Result};
 
 pub trait ChunkStore: Debug {
     fn get(&self, hash: &u64) -> Result<Vec<u8>>;
@@ -8,4 +10,161 @@ pub trait ChunkStore: Debug {
     fn upsert(&mut self, hash: u64, chunk: Vec<u8>) -> Result<()>;
 
     fn remove(&mut self, hash: &u64) -> Result<()>;
+
+    /// Whether chunks can be stored with [`upsert_shared`](Self::upsert_shared),
+    /// letting writers carry on side by side with each other and with readers.
+    fn writes_shared(&self) -> bool {
+        false
+    }
+
+    /// Stores `chunk` through a shared reference, for stores that synchronize
+    /// concurrent writes themselves.
+    ///
+    /// Fails by default, for stores that need exclusive access to write, see
+    /// [`writes_shared`](Self::writes_shared).
+    fn upsert_shared(&self, _hash: u64, _chunk: Vec<u8>) -> Result<()> {
+        Err(anyhow!("Chunk store cannot be written through a shared reference").into())
+    }
+
+    /// Bytes the chunk stored under `hash` takes up in this store, which for
+    /// stores that compress or encrypt chunks differs from the length `get`
+    /// returns.
+    ///
+    /// Reads the chunk by default. Stores that transform chunks, or can tell
+    /// their length without reading them, should override this.
+    fn stored_size(&self, hash: &u64) -> Result<usize> {
+        Ok(self.get(hash)?.len())
+    }
+
+    /// Whether each of `hashes` is stored, in the same order.
+    ///
+    /// Reads every chunk by default, counting the ones failing their integrity
+    /// check as missing so that syncs send them again. Stores that can check
+    /// many hashes in one round trip should override this.
+    fn contains_many(&self, hashes: &[u64]) -> Result<Vec<bool>> {
+        hashes
+            .iter()
+            .map(|hash| match self.get(hash) {
+                Ok(_) => Ok(true),
+                Err(Error::NotFound | Error::Integrity) => Ok(false),
+                Err(err) => Err(err),
+            })
+            .collect()
+    }
+
+    /// Hashes of every stored chunk, in no particular order.
+    ///
+    /// The whole listing is held in memory, 8 bytes per chunk. Fails by
+    /// default, for stores that cannot enumerate their chunks, which leaves
+    /// them without [`System::fsck`](crate::System::fsck), garbage collection
+    /// and the other whole-store operations.
+    fn hashes(&self) -> Result<Vec<u64>> {
+        Err(anyhow!("Chunk store cannot list its chunks").into())
+    }
+
+    /// Whether no chunks are stored.
+    ///
+    /// Lists every chunk by default. Stores that can stop at the first chunk
+    /// should override this.
+    fn is_empty(&self) -> Result<bool> {
+        Ok(self.hashes()?.is_empty())
+    }
+
+    /// Fingerprint of the keyed hasher the stored chunk ids were derived
+    /// with, if one has been recorded.
+    ///
+    /// Stores that cannot record fingerprints never have one.
+    fn hasher_fingerprint(&self) -> Result<Option<[u8; 32]>> {
+        Ok(None)
+    }
+
+    /// Records `fingerprint`, replacing the one recorded before.
+    ///
+    /// Fails by default, for stores that cannot record fingerprints.
+    fn set_hasher_fingerprint(&mut self, _fingerprint: [u8; 32]) -> Result<()> {
+        Err(anyhow!("Chunk store cannot record a hasher fingerprint").into())
+    }
+
+    /// Records `fingerprint` unless one is recorded already, and returns the
+    /// one recorded before, if any.
+    ///
+    /// Stores shared between processes should override this to check and
+    /// record at once, so that two systems opening an empty store with
+    /// different hashers cannot both record theirs.
+    fn init_hasher_fingerprint(&mut self, fingerprint: [u8; 32]) -> Result<Option<[u8; 32]>> {
+        let recorded = self.hasher_fingerprint()?;
+        if recorded.is_none() {
+            self.set_hasher_fingerprint(fingerprint)?;
+        }
+        Ok(recorded)
+    }
+}
+
+impl<C: ChunkStore + ?Sized> ChunkStore for Box<C> {
+    fn get(&self, hash: &u64) -> Result<Vec<u8>> {
+        (**self).get(hash)
+    }
+
+    fn upsert(&mut self, hash: u64, chunk: Vec<u8>) -> Result<()> {
+        (**self).upsert(hash, chunk)
+    }
+
+    fn remove(&mut self, hash: &u64) -> Result<()> {
+        (**self).remove(hash)
+    }
+
+    fn writes_shared(&self) -> bool {
+        (**self).writes_shared()
+    }
+
+    fn upsert_shared(&self, hash: u64, chunk: Vec<u8>) -> Result<()> {
+        (**self).upsert_shared(hash, chunk)
+    }
+
+    fn stored_size(&self, hash: &u64) -> Result<usize> {
+        (**self).stored_size(hash)
+    }
+
+    fn contains_many(&self, hashes: &[u64]) -> Result<Vec<bool>> {
+        (**self).contains_many(hashes)
+    }
+
+    fn hashes(&self) -> Result<Vec<u64>> {
+        (**self).hashes()
+    }
+
+    fn is_empty(&self) -> Result<bool> {
+        (**self).is_empty()
+    }
+
+    fn hasher_fingerprint(&self) -> Result<Option<[u8; 32]>> {
+        (**self).hasher_fingerprint()
+    }
+
+    fn set_hasher_fingerprint(&mut self, fingerprint: [u8; 32]) -> Result<()> {
+        (**self).set_hasher_fingerprint(fingerprint)
+    }
+
+    fn init_hasher_fingerprint(&mut self, fingerprint: [u8; 32]) -> Result<Option<[u8; 32]>> {
+        (**self).init_hasher_fingerprint(fingerprint)
+    }
+}
+
+/// Asks `store` about the hashes not `found` yet and marks those it holds.
+/// Lets stores built from others answer [`ChunkStore::contains_many`] with a
+/// batch per inner store.
+pub(crate) fn contains_missing<C: ChunkStore + ?Sized>(
+    store: &C,
+    hashes: &[u64],
+    found: &mut [bool],
+) -> Result<()> {
+    let missing: Vec<usize> = (0..hashes.len()).filter(|idx| !found[*idx]).collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let batch: Vec<u64> = missing.iter().map(|idx| hashes[*idx]).collect();
+    for (idx, present) in missing.into_iter().zip(store.contains_many(&batch)?) {
+        found[idx] = present;
+    }
+    Ok(())
 }
@@ -0,0 +1,89 @@
+use std::hash::{BuildHasher, Hasher};
+
+use highway::{HighwayHasher, Key};
+
+/// A [`BuildHasher`] keyed with a secret, so chunk ids cannot be predicted
+/// from file contents without knowing the key.
+///
+/// The fingerprint identifies the key and algorithm without revealing the
+/// key. It is recorded in the chunk store so that a store is never shared by
+/// hashers with different keys.
+pub trait KeyedBuildHasher: BuildHasher {
+    fn fingerprint(&self) -> [u8; 32];
+}
+
+#[derive(Clone, Debug)]
+pub struct BuildKeyedHighwayHasher {
+    key: Key,
+    fingerprint: [u8; 32],
+}
+
+impl BuildKeyedHighwayHasher {
+    pub fn new(key: &[u8; 32]) -> Self {
+        let words = [0, 1, 2, 3].map(|i| {
+            u64::from_le_bytes(key[i * 8..i * 8 + 8].try_into().expect("Key has 32 bytes"))
+        });
+        Self {
+            key: Key(words),
+            fingerprint: blake3::derive_key("cdcfs keyed HighwayHash chunk id fingerprint", key),
+        }
+    }
+}
+
+impl BuildHasher for BuildKeyedHighwayHasher {
+    type Hasher = HighwayHasher;
+
+    fn build_hasher(&self) -> HighwayHasher {
+        HighwayHasher::new(self.key)
+    }
+}
+
+impl KeyedBuildHasher for BuildKeyedHighwayHasher {
+    fn fingerprint(&self) -> [u8; 32] {
+        self.fingerprint
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BuildKeyedBlake3Hasher {
+    key: [u8; 32],
+    fingerprint: [u8; 32],
+}
+
+impl BuildKeyedBlake3Hasher {
+    pub fn new(key: &[u8; 32]) -> Self {
+        Self {
+            key: *key,
+            fingerprint: blake3::derive_key("cdcfs keyed BLAKE3 chunk id fingerprint", key),
+        }
+    }
+}
+
+impl BuildHasher for BuildKeyedBlake3Hasher {
+    type Hasher = Blake3Hasher;
+
+    fn build_hasher(&self) -> Blake3Hasher {
+        Blake3Hasher(blake3::Hasher::new_keyed(&self.key))
+    }
+}
+
+impl KeyedBuildHasher for BuildKeyedBlake3Hasher {
+    fn fingerprint(&self) -> [u8; 32] {
+        self.fingerprint
+    }
+}
+
+/// Keyed BLAKE3 truncated to the 64 bit chunk ids used by the stores.
+#[derive(Clone, Debug)]
+pub struct Blake3Hasher(blake3::Hasher);
+
+impl Hasher for Blake3Hasher {
+    fn finish(&self) -> u64 {
+        let hash = self.0.finalize();
+        u64::from_le_bytes(hash.as_bytes()[..8].try_into().expect("Hash has 32 bytes"))
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+}
@@ -0,0 +1,160 @@
+use std::{fs, net::SocketAddr, path::Path, path::PathBuf};
+
+use serde::Deserialize;
+
+use super::error::{Error, Result};
+use crate::{
+    chunks::ChunkStore, s3::Buckets, BuildKeyedBlake3Hasher, FileChunkStore, MemoryChunkStore,
+    RedisChunkStore,
+};
+
+/// Settings of the `cdcfs-server` and `cdcfs` binaries, read from a TOML
+/// file:
+///
+/// ```toml
+/// listen = "0.0.0.0:8080"
+/// hash_key = "<64 hex digits>"
+///
+/// [chunks]
+/// backend = "redis"
+/// url = "redis://127.0.0.1:6379"
+///
+/// [meta]
+/// backend = "postgres"
+/// url = "postgres://postgres@127.0.0.1:5432/cdcfs"
+///
+/// [s3]
+/// listen = "0.0.0.0:9000"
+/// buckets = { photos = "photos/" }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default = "default_listen")]
+    pub listen: SocketAddr,
+    /// Hex encoded key of the BLAKE3 hasher chunk ids are derived with. It is
+    /// required, as chunk ids derived with a publicly known key reveal which
+    /// files a store holds. The key is recorded in the chunk store, which then
+    /// refuses to be served with another key.
+    #[serde(default)]
+    pub hash_key: Option<String>,
+    pub chunks: ChunkBackend,
+    pub meta: MetaBackend,
+    /// Also serves the S3 API, see [`s3::serve`](crate::s3::serve).
+    #[serde(default)]
+    pub s3: Option<S3Config>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(tag = "backend", rename_all = "lowercase", deny_unknown_fields)]
+pub enum ChunkBackend {
+    Memory,
+    Redis { url: String },
+    File { path: PathBuf },
+}
+
+/// Where file metas are kept. Memory and file key files by any string,
+/// Postgres by integer id.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(tag = "backend", rename_all = "lowercase", deny_unknown_fields)]
+pub enum MetaBackend {
+    Memory,
+    Postgres { url: String },
+    File { path: PathBuf },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct S3Config {
+    pub listen: SocketAddr,
+    pub buckets: Buckets,
+}
+
+fn default_listen() -> SocketAddr {
+    ([127, 0, 0, 1], 8080).into()
+}
+
+impl Config {
+    /// Config using the given backends, with every other setting at its
+    /// default.
+    pub fn new(chunks: ChunkBackend, meta: MetaBackend) -> Self {
+        Self {
+            listen: default_listen(),
+            hash_key: None,
+            chunks,
+            meta,
+            s3: None,
+        }
+    }
+
+    pub fn with_hash_key(mut self, hash_key: impl Into<String>) -> Self {
+        self.hash_key = Some(hash_key.into());
+        self
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// Hasher keyed with `hash_key`, which must be set.
+    pub fn hasher(&self) -> Result<BuildKeyedBlake3Hasher> {
+        let hash_key = self.hash_key.as_ref().ok_or(Error::MissingHashKey)?;
+        let mut key = [0; 32];
+        hex::decode_to_slice(hash_key, &mut key).map_err(|_| Error::InvalidHashKey)?;
+        Ok(BuildKeyedBlake3Hasher::new(&key))
+    }
+}
+
+impl std::str::FromStr for Config {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(toml::from_str(s)?)
+    }
+}
+
+impl ChunkBackend {
+    pub fn open(&self) -> Result<Box<dyn ChunkStore + Send + Sync>> {
+        Ok(match self {
+            Self::Memory => Box::new(MemoryChunkStore::new()),
+            Self::Redis { url } => Box::new(RedisChunkStore::new(url.as_str())?),
+            Self::File { path } => Box::new(FileChunkStore::new(path)?),
+        })
+    }
+}
+
+/// Parses `memory`, a `redis://` or `rediss://` URL, or the path of a file
+/// chunk store.
+impl std::str::FromStr for ChunkBackend {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "memory" => Ok(Self::Memory),
+            url if url.starts_with("redis://") || url.starts_with("rediss://") => Ok(Self::Redis {
+                url: url.to_owned(),
+            }),
+            url if url.contains("://") => Err(Error::UnsupportedBackend(url.to_owned())),
+            path => Ok(Self::File { path: path.into() }),
+        }
+    }
+}
+
+/// Parses `memory`, a `postgres://` or `postgresql://` URL, or the path of a
+/// file meta store.
+impl std::str::FromStr for MetaBackend {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "memory" => Ok(Self::Memory),
+            url if url.starts_with("postgres://") || url.starts_with("postgresql://") => {
+                Ok(Self::Postgres {
+                    url: url.to_owned(),
+                })
+            }
+            url if url.contains("://") => Err(Error::UnsupportedBackend(url.to_owned())),
+            path => Ok(Self::File { path: path.into() }),
+        }
+    }
+}
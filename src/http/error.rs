@@ -0,0 +1,27 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Chunk store error: {0}")]
+    ChunkStore(#[from] crate::chunks::Error),
+    #[error("Meta store error: {0}")]
+    MetaStore(#[from] crate::meta::Error),
+    #[error("System error: {0}")]
+    System(#[from] crate::system::Error),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid config: {0}")]
+    Config(#[from] toml::de::Error),
+    #[error("Unsupported backend: {0}")]
+    UnsupportedBackend(String),
+    #[error("Hash key must be 64 hex digits")]
+    InvalidHashKey,
+    #[error("No hash key configured")]
+    MissingHashKey,
+    #[error("The S3 API needs a meta store keyed by strings")]
+    S3NeedsStringKeys,
+    #[error("Server error: {0}")]
+    Server(#[from] hyper::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
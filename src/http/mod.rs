@@ -0,0 +1,355 @@
+mod config;
+mod error;
+mod range;
+
+use std::{
+    convert::Infallible, hash::BuildHasher, net::TcpListener, ops::Range, str::FromStr, sync::Arc,
+};
+
+use hyper::{
+    body::HttpBody,
+    header::{ACCEPT_RANGES, ALLOW, CONTENT_LENGTH, CONTENT_RANGE, ETAG, IF_NONE_MATCH, RANGE},
+    http::response::Builder,
+    service::{make_service_fn, service_fn},
+    Body, HeaderMap, Method, Request, Response, Server, StatusCode,
+};
+use percent_encoding::percent_decode_str;
+use tokio::sync::RwLock;
+
+use crate::{
+    chunks::ChunkStore,
+    meta::{self, Meta, MetaStore},
+    system::{self, System, Upload},
+};
+
+pub use config::{ChunkBackend, Config, MetaBackend, S3Config};
+pub use error::{Error, Result};
+pub(crate) use range::ByteRange;
+
+/// Bytes read per step when streaming a file, so that writers are not locked
+/// out for the whole download.
+const READ_WINDOW: usize = 1024 * 1024;
+
+/// Bytes of a request body collected before they are handed to the system,
+/// so that the system is not taken for every body frame.
+pub(crate) const WRITE_WINDOW: usize = 1024 * 1024;
+
+/// System shared by the requests in flight. Reads share it, and so do writes
+/// while storing chunks if the chunk store supports
+/// [`ChunkStore::upsert_shared`], taking it only to write the meta. With other
+/// chunk stores writes take it for each window of the body.
+pub type SharedSystem<C, M, H> = Arc<RwLock<System<C, M, H>>>;
+
+/// Serves `system` as an object API on `listener` until the future is
+/// dropped:
+///
+/// - `PUT /objects/{key}` writes the request body to the file.
+/// - `GET /objects/{key}` streams the file, or a single byte range of it.
+/// - `HEAD /objects/{key}` returns the headers `GET` would.
+/// - `DELETE /objects/{key}` removes the file, if there is one.
+///
+/// Keys are percent-decoded and parsed with `FromStr`. Responses carry an
+/// `ETag` derived from the file's chunk list.
+pub async fn serve<K, C, M, H>(listener: TcpListener, system: SharedSystem<C, M, H>) -> Result<()>
+where
+    K: FromStr + Send + Sync + 'static,
+    C: ChunkStore + Send + Sync + 'static,
+    M: MetaStore<Key = K> + Send + Sync + 'static,
+    H: BuildHasher + Send + Sync + 'static,
+{
+    listener.set_nonblocking(true)?;
+    let make_service = make_service_fn(move |_| {
+        let system = Arc::clone(&system);
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                handle(Arc::clone(&system), request)
+            }))
+        }
+    });
+    Server::from_tcp(listener)?.serve(make_service).await?;
+    Ok(())
+}
+
+async fn handle<K, C, M, H>(
+    system: SharedSystem<C, M, H>,
+    request: Request<Body>,
+) -> std::result::Result<Response<Body>, Infallible>
+where
+    K: FromStr + Send + Sync + 'static,
+    C: ChunkStore + Send + Sync + 'static,
+    M: MetaStore<Key = K> + Send + Sync + 'static,
+    H: BuildHasher + Send + Sync + 'static,
+{
+    let Some(key) = request.uri().path().strip_prefix("/objects/") else {
+        return Ok(status(StatusCode::NOT_FOUND));
+    };
+    let Some(key) = percent_decode_str(key)
+        .decode_utf8()
+        .ok()
+        .and_then(|key| key.parse::<K>().ok())
+    else {
+        return Ok(status(StatusCode::BAD_REQUEST));
+    };
+
+    let response = match *request.method() {
+        Method::PUT => put(&system, &key, request.into_body()).await,
+        Method::GET => get(&system, &key, request.headers(), true).await,
+        Method::HEAD => get(&system, &key, request.headers(), false).await,
+        Method::DELETE => delete(&system, &key).await,
+        _ => Ok(Response::builder()
+            .status(StatusCode::METHOD_NOT_ALLOWED)
+            .header(ALLOW, "GET, HEAD, PUT, DELETE")
+            .body(Body::empty())
+            .expect("Response is valid")),
+    };
+    Ok(response.unwrap_or_else(error_response))
+}
+
+async fn put<K, C, M, H>(
+    system: &SharedSystem<C, M, H>,
+    key: &K,
+    mut body: Body,
+) -> system::Result<Response<Body>>
+where
+    C: ChunkStore + Send + Sync + 'static,
+    M: MetaStore<Key = K> + Send + Sync + 'static,
+    H: BuildHasher + Send + Sync + 'static,
+{
+    let mut upload = Upload::new();
+    let mut window = Vec::with_capacity(WRITE_WINDOW);
+    while let Some(bytes) = body.data().await {
+        let Ok(bytes) = bytes else {
+            // The client went away, so there is nobody to answer.
+            return Ok(status(StatusCode::BAD_REQUEST));
+        };
+        window.extend_from_slice(&bytes);
+        if window.len() >= WRITE_WINDOW {
+            upload = upload_window(system, upload, std::mem::take(&mut window)).await?;
+        }
+    }
+    let upload = upload_last_window(system, upload, window).await?;
+
+    let mut system = system.write().await;
+    system.finish_upload(key, upload).await?;
+    let meta = system.meta_store().get(key).await?;
+    Ok(Response::builder()
+        .status(StatusCode::NO_CONTENT)
+        .header(ETAG, etag(&meta))
+        .body(Body::empty())
+        .expect("Response is valid"))
+}
+
+/// Answers `GET`, or `HEAD` without a body, honouring a single byte range
+/// and `If-None-Match`.
+pub(crate) async fn get<K, C, M, H>(
+    system: &SharedSystem<C, M, H>,
+    key: &K,
+    headers: &HeaderMap,
+    with_body: bool,
+) -> system::Result<Response<Body>>
+where
+    C: ChunkStore + Send + Sync + 'static,
+    M: MetaStore<Key = K> + Send + Sync + 'static,
+    H: BuildHasher + Send + Sync + 'static,
+{
+    let meta = system.read().await.meta_store().get(key).await?;
+    let etag = etag(&meta);
+    let response = Response::builder()
+        .header(ETAG, &etag)
+        .header(ACCEPT_RANGES, "bytes");
+
+    let if_none_match = headers.get(IF_NONE_MATCH).and_then(|v| v.to_str().ok());
+    if if_none_match.is_some_and(|tags| etag_matches(tags, &etag)) {
+        return Ok(empty(response.status(StatusCode::NOT_MODIFIED)));
+    }
+
+    let range = headers.get(RANGE).and_then(|v| v.to_str().ok());
+    let (response, range) = match ByteRange::parse(range, meta.size) {
+        ByteRange::Full => (response.status(StatusCode::OK), 0..meta.size),
+        ByteRange::Partial(range) => {
+            let content_range = format!("bytes {}-{}/{}", range.start, range.end - 1, meta.size);
+            let response = response
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(CONTENT_RANGE, content_range);
+            (response, range)
+        }
+        ByteRange::Unsatisfiable => {
+            let response = response
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(CONTENT_RANGE, format!("bytes */{}", meta.size));
+            return Ok(empty(response));
+        }
+    };
+
+    let response = response.header(CONTENT_LENGTH, range.len());
+    let body = if with_body && !range.is_empty() {
+        stream_range(Arc::clone(system), meta, range)
+    } else {
+        Body::empty()
+    };
+    Ok(response.body(body).expect("Response is valid"))
+}
+
+/// Stores the chunks `window` completes on a blocking thread, as chunk stores
+/// do blocking I/O.
+pub(crate) async fn upload_window<C, M, H>(
+    system: &SharedSystem<C, M, H>,
+    upload: Upload,
+    window: Vec<u8>,
+) -> system::Result<Upload>
+where
+    C: ChunkStore + Send + Sync + 'static,
+    M: MetaStore + Send + Sync + 'static,
+    H: BuildHasher + Send + Sync + 'static,
+{
+    store_window(system, upload, window, false).await
+}
+
+/// Stores `window` and the chunks still buffered in `upload` on a blocking
+/// thread, once the body has ended, leaving only the meta to be written.
+pub(crate) async fn upload_last_window<C, M, H>(
+    system: &SharedSystem<C, M, H>,
+    upload: Upload,
+    window: Vec<u8>,
+) -> system::Result<Upload>
+where
+    C: ChunkStore + Send + Sync + 'static,
+    M: MetaStore + Send + Sync + 'static,
+    H: BuildHasher + Send + Sync + 'static,
+{
+    store_window(system, upload, window, true).await
+}
+
+/// Shares the system while storing chunks if the chunk store can be written
+/// through a shared reference, and only takes it otherwise.
+async fn store_window<C, M, H>(
+    system: &SharedSystem<C, M, H>,
+    mut upload: Upload,
+    window: Vec<u8>,
+    last: bool,
+) -> system::Result<Upload>
+where
+    C: ChunkStore + Send + Sync + 'static,
+    M: MetaStore + Send + Sync + 'static,
+    H: BuildHasher + Send + Sync + 'static,
+{
+    let shared = Arc::clone(system).read_owned().await;
+    let stored = if shared.uploads_shared() {
+        tokio::task::spawn_blocking(move || {
+            shared.upload_shared(&mut upload, &window)?;
+            if last {
+                shared.flush_upload_shared(&mut upload)?;
+            }
+            Ok(upload)
+        })
+        .await
+    } else {
+        drop(shared);
+        let mut system = Arc::clone(system).write_owned().await;
+        tokio::task::spawn_blocking(move || {
+            system.upload(&mut upload, &window)?;
+            if last {
+                system.flush_upload(&mut upload)?;
+            }
+            Ok(upload)
+        })
+        .await
+    };
+    stored.map_err(std::io::Error::other)?
+}
+
+async fn delete<K, C, M, H>(
+    system: &SharedSystem<C, M, H>,
+    key: &K,
+) -> system::Result<Response<Body>>
+where
+    C: ChunkStore,
+    M: MetaStore<Key = K>,
+    H: BuildHasher,
+{
+    system.write().await.delete(key).await?;
+    Ok(status(StatusCode::NO_CONTENT))
+}
+
+/// Streams `range` of the file in windows, taking the system for one window
+/// at a time. The chunks were looked up before the first window, so the
+/// whole stream shows the same version of the file.
+fn stream_range<C, M, H>(system: SharedSystem<C, M, H>, meta: Meta, range: Range<usize>) -> Body
+where
+    C: ChunkStore + Send + Sync + 'static,
+    M: MetaStore + Send + Sync + 'static,
+    H: BuildHasher + Send + Sync + 'static,
+{
+    let (mut sender, body) = Body::channel();
+    let meta = Arc::new(meta);
+    tokio::spawn(async move {
+        let mut start = range.start;
+        while start < range.end {
+            let end = range.end.min(start + READ_WINDOW);
+            let system = Arc::clone(&system).read_owned().await;
+            let meta = Arc::clone(&meta);
+            let window =
+                tokio::task::spawn_blocking(move || system.read_range_of(&meta, start..end)).await;
+            match window {
+                Ok(Ok(bytes)) => {
+                    if sender.send_data(bytes.into()).await.is_err() {
+                        return;
+                    }
+                }
+                // The status is sent already, so the client only sees the
+                // body end early.
+                Ok(Err(err)) => {
+                    tracing::error!("Cannot read range: {err:?}");
+                    return sender.abort();
+                }
+                Err(err) => {
+                    tracing::error!("Cannot read range: {err:?}");
+                    return sender.abort();
+                }
+            }
+            start = end;
+        }
+    });
+    body
+}
+
+/// Quoted digest of the file's size and chunk list, which changes whenever
+/// its contents do.
+pub(crate) fn etag(meta: &Meta) -> String {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&(meta.size as u64).to_le_bytes());
+    for hash in &meta.hashes {
+        hasher.update(&hash.to_le_bytes());
+    }
+    format!("\"{}\"", &hasher.finalize().to_hex()[..32])
+}
+
+/// Whether an `If-None-Match` list names `etag`, comparing weakly.
+fn etag_matches(tags: &str, etag: &str) -> bool {
+    tags.split(',')
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+/// Answers with the status matching `err`. Details of internal errors are
+/// only logged, as they may name backends, addresses or keys.
+fn error_response(err: system::Error) -> Response<Body> {
+    match err {
+        system::Error::MetaStore(meta::Error::NotFound) => status(StatusCode::NOT_FOUND),
+        err => {
+            tracing::error!("Request failed: {err:?}");
+            Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Body::from("Internal server error"))
+                .expect("Response is valid")
+        }
+    }
+}
+
+fn status(status: StatusCode) -> Response<Body> {
+    empty(Response::builder().status(status))
+}
+
+fn empty(response: Builder) -> Response<Body> {
+    response.body(Body::empty()).expect("Response is valid")
+}
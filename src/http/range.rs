@@ -0,0 +1,84 @@
+use std::ops::Range;
+
+/// Outcome of parsing a `Range` header against a file of known size.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum ByteRange {
+    /// The header is missing, malformed or asks for several ranges, and the
+    /// whole file is served.
+    Full,
+    Partial(Range<usize>),
+    /// The range starts past the end of the file.
+    Unsatisfiable,
+}
+
+impl ByteRange {
+    /// Parses a single `bytes=first-last`, `bytes=first-` or `bytes=-suffix`
+    /// range.
+    pub(crate) fn parse(header: Option<&str>, size: usize) -> Self {
+        let Some(spec) = header.and_then(|header| header.trim().strip_prefix("bytes=")) else {
+            return Self::Full;
+        };
+        if spec.contains(',') {
+            return Self::Full;
+        }
+        let Some((first, last)) = spec.trim().split_once('-') else {
+            return Self::Full;
+        };
+
+        match (first.parse::<usize>(), last.parse::<usize>()) {
+            (Ok(first), Ok(last)) if first <= last => {
+                Self::from_bounds(first, last.saturating_add(1), size)
+            }
+            (Ok(first), Err(_)) if last.is_empty() => Self::from_bounds(first, size, size),
+            (Err(_), Ok(suffix)) if first.is_empty() => {
+                if suffix == 0 {
+                    Self::Unsatisfiable
+                } else {
+                    Self::from_bounds(size.saturating_sub(suffix), size, size)
+                }
+            }
+            _ => Self::Full,
+        }
+    }
+
+    fn from_bounds(start: usize, end: usize, size: usize) -> Self {
+        if start >= size {
+            Self::Unsatisfiable
+        } else {
+            Self::Partial(start..end.min(size))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ByteRange;
+
+    #[test]
+    fn it_parses_ranges() {
+        let parse = |header| ByteRange::parse(Some(header), 100);
+        assert_eq!(parse("bytes=0-9"), ByteRange::Partial(0..10));
+        assert_eq!(parse("bytes=90-200"), ByteRange::Partial(90..100));
+        assert_eq!(
+            parse("bytes=0-18446744073709551615"),
+            ByteRange::Partial(0..100)
+        );
+        assert_eq!(
+            parse("bytes=100-18446744073709551615"),
+            ByteRange::Unsatisfiable
+        );
+        assert_eq!(parse("bytes=95-"), ByteRange::Partial(95..100));
+        assert_eq!(parse("bytes=-10"), ByteRange::Partial(90..100));
+        assert_eq!(parse("bytes=-200"), ByteRange::Partial(0..100));
+        assert_eq!(parse("bytes=100-"), ByteRange::Unsatisfiable);
+        assert_eq!(parse("bytes=-0"), ByteRange::Unsatisfiable);
+        assert_eq!(parse("bytes=9-0"), ByteRange::Full);
+        assert_eq!(parse("bytes=0-1,5-6"), ByteRange::Full);
+        assert_eq!(parse("items=0-1"), ByteRange::Full);
+        assert_eq!(ByteRange::parse(None, 100), ByteRange::Full);
+        assert_eq!(
+            ByteRange::parse(Some("bytes=-5"), 0),
+            ByteRange::Unsatisfiable
+        );
+    }
+}
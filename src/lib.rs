@@ -1,5 +1,9 @@
+pub mod analyze;
 pub mod chunks;
+mod hashers;
+pub mod http;
 pub mod meta;
+pub mod s3;
 pub mod system;
 
 use std::hash::BuildHasherDefault;
@@ -8,8 +12,11 @@ use highway::HighwayHasher;
 use twox_hash::Xxh3Hash64;
 use wyhash::WyHash;
 
-pub use self::chunks::{MemoryChunkStore, RedisChunkStore};
-pub use self::meta::{MemoryMetaStore, PostgresMetaStore};
+pub use self::chunks::{FileChunkStore, MemoryChunkStore, RedisChunkStore, RemoteChunkStore};
+pub use self::hashers::{
+    Blake3Hasher, BuildKeyedBlake3Hasher, BuildKeyedHighwayHasher, KeyedBuildHasher,
+};
+pub use self::meta::{FileMetaStore, MemoryMetaStore, PostgresMetaStore};
 pub use self::system::System;
 
 pub type BuildWyHasher = BuildHasherDefault<WyHash>;
@@ -0,0 +1,234 @@
+use core::fmt::Debug;
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use tokio::task::JoinHandle;
+
+use super::{
+    changes::Subscription,
+    error::Result,
+    traits::{Meta, MetaStore},
+};
+
+/// Meta store wrapper keeping recently read metas in memory.
+///
+/// Cached metas expire after the TTL, and the least recently used ones are
+/// evicted once more than `capacity` are cached. Writes through this wrapper
+/// invalidate the cached meta right away. Writes by other processes are only
+/// seen once the cached meta expires, unless the inner store can announce
+/// changes, see [`CachedMetaStore::with_notifications`].
+pub struct CachedMetaStore<M: MetaStore>
+where
+    M::Key: Clone + Eq + Hash,
+{
+    inner: M,
+    cache: Arc<Mutex<MetaCache<M::Key>>>,
+    listener: Option<JoinHandle<()>>,
+}
+
+pub(crate) struct MetaCache<K> {
+    entries: HashMap<K, CacheEntry>,
+    /// Cached keys by the tick they were last used at.
+    recency: BTreeMap<u64, K>,
+    ttl: Duration,
+    capacity: usize,
+    clock: u64,
+    /// Bumped by every invalidation, so that metas read from the inner store
+    /// while one happened are not cached.
+    generation: u64,
+}
+
+struct CacheEntry {
+    meta: Meta,
+    expires: Instant,
+    last_used: u64,
+}
+
+impl<M: MetaStore> CachedMetaStore<M>
+where
+    M::Key: Clone + Eq + Hash,
+{
+    /// Caches up to `capacity` metas read from `inner` for at most `ttl`.
+    pub fn new(inner: M, ttl: Duration, capacity: usize) -> Self {
+        Self {
+            inner,
+            cache: Arc::new(Mutex::new(MetaCache {
+                entries: HashMap::new(),
+                recency: BTreeMap::new(),
+                ttl,
+                capacity,
+                clock: 0,
+                generation: 0,
+            })),
+            listener: None,
+        }
+    }
+
+    pub fn inner(&self) -> &M {
+        &self.inner
+    }
+
+    /// Number of metas currently cached, including expired ones not yet
+    /// evicted.
+    pub fn cached(&self) -> usize {
+        lock(&self.cache).entries.len()
+    }
+
+    /// Drops every cached meta.
+    pub fn clear(&self) {
+        lock(&self.cache).clear();
+    }
+
+    pub(crate) fn cache(&self) -> Arc<Mutex<MetaCache<M::Key>>> {
+        Arc::clone(&self.cache)
+    }
+
+    /// Keeps `listener` running as long as this store, which it can use to
+    /// invalidate the cache.
+    pub(crate) fn set_listener(&mut self, listener: JoinHandle<()>) {
+        if let Some(previous) = self.listener.replace(listener) {
+            previous.abort();
+        }
+    }
+}
+
+impl<K: Clone + Eq + Hash> MetaCache<K> {
+    fn get(&mut self, key: &K) -> Option<Meta> {
+        let entry = self.entries.get(key)?;
+        if entry.expires <= Instant::now() {
+            self.evict(key);
+            return None;
+        }
+
+        self.clock += 1;
+        let entry = self.entries.get_mut(key)?;
+        self.recency.remove(&entry.last_used);
+        entry.last_used = self.clock;
+        self.recency.insert(self.clock, key.clone());
+        Some(entry.meta.clone())
+    }
+
+    fn insert(&mut self, key: K, meta: Meta, generation: u64) {
+        if self.capacity == 0 || self.generation != generation {
+            return;
+        }
+        self.evict(&key);
+
+        self.clock += 1;
+        self.recency.insert(self.clock, key.clone());
+        self.entries.insert(
+            key,
+            CacheEntry {
+                meta,
+                expires: Instant::now() + self.ttl,
+                last_used: self.clock,
+            },
+        );
+
+        while self.entries.len() > self.capacity {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+    }
+
+    pub(crate) fn invalidate(&mut self, key: &K) {
+        self.generation += 1;
+        self.evict(key);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.generation += 1;
+        self.entries.clear();
+        self.recency.clear();
+    }
+
+    fn evict(&mut self, key: &K) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.recency.remove(&entry.last_used);
+        }
+    }
+}
+
+pub(crate) fn lock<K>(cache: &Mutex<MetaCache<K>>) -> std::sync::MutexGuard<'_, MetaCache<K>> {
+    cache.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl<M: MetaStore> Drop for CachedMetaStore<M>
+where
+    M::Key: Clone + Eq + Hash,
+{
+    fn drop(&mut self) {
+        if let Some(listener) = self.listener.take() {
+            listener.abort();
+        }
+    }
+}
+
+impl<M: MetaStore> Debug for CachedMetaStore<M>
+where
+    M::Key: Clone + Eq + Hash,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let cache = lock(&self.cache);
+        f.debug_struct("CachedMetaStore")
+            .field("inner", &self.inner)
+            .field("ttl", &cache.ttl)
+            .field("capacity", &cache.capacity)
+            .field("cached", &cache.entries.len())
+            .field("notifications", &self.listener.is_some())
+            .finish()
+    }
+}
+
+#[async_trait]
+impl<M> MetaStore for CachedMetaStore<M>
+where
+    M: MetaStore + Send + Sync,
+    M::Key: Clone + Eq + Hash + Send + Sync,
+{
+    type Key = M::Key;
+
+    async fn get(&self, key: &Self::Key) -> Result<Meta> {
+        let generation = {
+            let mut cache = lock(&self.cache);
+            if let Some(meta) = cache.get(key) {
+                return Ok(meta);
+            }
+            cache.generation
+        };
+
+        let meta = self.inner.get(key).await?;
+        lock(&self.cache).insert(key.clone(), meta.clone(), generation);
+        Ok(meta)
+    }
+
+    async fn upsert(&mut self, key: &Self::Key, meta: Meta) -> Result<()> {
+        lock(&self.cache).invalidate(key);
+        self.inner.upsert(key, meta).await
+    }
+
+    async fn remove(&mut self, key: &Self::Key) -> Result<()> {
+        lock(&self.cache).invalidate(key);
+        self.inner.remove(key).await
+    }
+
+    async fn list(&self) -> Result<Vec<(Self::Key, Meta)>> {
+        self.inner.list().await
+    }
+
+    async fn copy(&mut self, from: &Self::Key, to: &Self::Key) -> Result<()> {
+        lock(&self.cache).invalidate(to);
+        self.inner.copy(from, to).await
+    }
+
+    async fn subscribe(&self) -> Result<Subscription<Self::Key>> {
+        self.inner.subscribe().await
+    }
+}
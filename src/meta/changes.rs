@@ -0,0 +1,90 @@
+use anyhow::anyhow;
+use tokio::{
+    sync::{broadcast, mpsc},
+    task::JoinHandle,
+};
+
+use super::{
+    error::{Error, Result},
+    traits::Meta,
+};
+
+/// What happened to a file in a [`Change`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChangeKind<K> {
+    Created,
+    Updated,
+    Deleted,
+    /// The file was created or overwritten with the meta of `from`.
+    Copied {
+        from: K,
+    },
+}
+
+/// A mutation of a meta store, as delivered by a [`Subscription`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change<K> {
+    /// Increases with every change of the store, in the order they happened.
+    pub sequence: u64,
+    pub key: K,
+    pub kind: ChangeKind<K>,
+    /// Meta before the change, `None` if the file did not exist.
+    pub old: Option<Meta>,
+    /// Meta after the change, `None` if the file was deleted.
+    pub new: Option<Meta>,
+}
+
+/// Stream of the changes made to a meta store after subscribing.
+#[derive(Debug)]
+pub struct Subscription<K> {
+    receiver: Receiver<K>,
+}
+
+#[derive(Debug)]
+enum Receiver<K> {
+    Broadcast(broadcast::Receiver<Change<K>>),
+    /// Changes forwarded by a task that is stopped with the subscription.
+    Task(mpsc::Receiver<Result<Change<K>>>, JoinHandle<()>),
+}
+
+impl<K: Clone> Subscription<K> {
+    pub(crate) fn broadcast(receiver: broadcast::Receiver<Change<K>>) -> Self {
+        Self {
+            receiver: Receiver::Broadcast(receiver),
+        }
+    }
+
+    pub(crate) fn task(receiver: mpsc::Receiver<Result<Change<K>>>, task: JoinHandle<()>) -> Self {
+        Self {
+            receiver: Receiver::Task(receiver, task),
+        }
+    }
+
+    /// Waits for the next change.
+    ///
+    /// Fails with [`Error::Lagged`] if the subscriber fell too far behind and
+    /// changes were dropped, after which it continues with the oldest change
+    /// still available.
+    pub async fn recv(&mut self) -> Result<Change<K>> {
+        match &mut self.receiver {
+            Receiver::Broadcast(receiver) => match receiver.recv().await {
+                Ok(change) => Ok(change),
+                Err(broadcast::error::RecvError::Lagged(missed)) => Err(Error::Lagged { missed }),
+                Err(broadcast::error::RecvError::Closed) => Err(closed()),
+            },
+            Receiver::Task(receiver, _) => receiver.recv().await.unwrap_or_else(|| Err(closed())),
+        }
+    }
+}
+
+impl<K> Drop for Subscription<K> {
+    fn drop(&mut self) {
+        if let Receiver::Task(_, task) = &self.receiver {
+            task.abort();
+        }
+    }
+}
+
+fn closed() -> Error {
+    anyhow!("Change feed closed").into()
+}
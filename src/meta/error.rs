@@ -5,6 +5,9 @@ pub enum Error {
     #[error("File not found")]
     NotFound,
 
+    #[error("Subscriber fell behind and missed {missed} changes")]
+    Lagged { missed: u64 },
+
     #[error("Internal error: {0}")]
     Internal(#[from] anyhow::Error),
 }
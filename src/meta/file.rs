@@ -0,0 +1,190 @@
+use core::fmt::{Debug, Display};
+use std::{
+    fs, io,
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use tokio::sync::broadcast;
+
+use super::{
+    changes::{Change, ChangeKind, Subscription},
+    error::{Error, Result},
+    traits::{Meta, MetaStore},
+};
+
+/// Changes kept for subscribers that have not received them yet.
+const CHANGES_CAPACITY: usize = 1024;
+
+/// Characters escaped in file names. Leaves names free of dots, so they never
+/// clash with temporary files.
+const NAME_ESCAPES: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'_');
+
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Meta store keeping the meta of every file as JSON in a file of its own
+/// below a root directory.
+///
+/// Files are named by their percent-encoded key, so keys must round-trip
+/// through [`Display`] and [`FromStr`]. Like in
+/// [`FileChunkStore`](crate::FileChunkStore), writes go to a temporary file
+/// that is renamed into place. Changes are only published to subscribers of
+/// the same instance.
+pub struct FileMetaStore<K> {
+    root: PathBuf,
+    changes: broadcast::Sender<Change<K>>,
+    sequence: u64,
+    keys: PhantomData<K>,
+}
+
+impl<K: Clone> FileMetaStore<K> {
+    /// Opens the store at `root`, creating the directory if needed.
+    pub fn new(root: impl Into<PathBuf>) -> Result<Self> {
+        let root = root.into();
+        fs::create_dir_all(&root).context("Filesystem error")?;
+        Ok(Self {
+            root,
+            changes: broadcast::channel(CHANGES_CAPACITY).0,
+            sequence: 0,
+            keys: PhantomData,
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn publish(&mut self, key: K, kind: ChangeKind<K>, old: Option<Meta>, new: Option<Meta>) {
+        self.sequence += 1;
+        // Fails only when nobody is subscribed.
+        let _ = self.changes.send(Change {
+            sequence: self.sequence,
+            key,
+            kind,
+            old,
+            new,
+        });
+    }
+}
+
+impl<K: Display> FileMetaStore<K> {
+    fn path(&self, key: &K) -> PathBuf {
+        let name = utf8_percent_encode(&key.to_string(), NAME_ESCAPES).to_string();
+        self.root.join(name)
+    }
+
+    fn read(path: &Path) -> Result<Option<Meta>> {
+        match fs::read(path) {
+            Ok(contents) => Ok(Some(
+                serde_json::from_slice(&contents).context("Malformed meta file")?,
+            )),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(anyhow::Error::new(err).context("Filesystem error").into()),
+        }
+    }
+
+    fn write(&self, path: &Path, meta: &Meta) -> Result<()> {
+        let contents = serde_json::to_vec(meta).context("Malformed meta")?;
+        let temp = self.root.join(format!(
+            ".tmp-{}-{}",
+            std::process::id(),
+            TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&temp, contents).context("Filesystem error")?;
+        if let Err(err) = fs::rename(&temp, path) {
+            let _ = fs::remove_file(&temp);
+            return Err(anyhow::Error::new(err).context("Filesystem error").into());
+        }
+        Ok(())
+    }
+}
+
+impl<K> Debug for FileMetaStore<K> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileMetaStore")
+            .field("root", &self.root)
+            .field("sequence", &self.sequence)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl<Key> MetaStore for FileMetaStore<Key>
+where
+    Key: Debug + Display + FromStr + Clone + Send + Sync,
+{
+    type Key = Key;
+
+    async fn get(&self, key: &Key) -> Result<Meta> {
+        Self::read(&self.path(key))?.ok_or(Error::NotFound)
+    }
+
+    async fn upsert(&mut self, key: &Key, meta: Meta) -> Result<()> {
+        let path = self.path(key);
+        let old = Self::read(&path)?;
+        self.write(&path, &meta)?;
+        let kind = match old {
+            Some(_) => ChangeKind::Updated,
+            None => ChangeKind::Created,
+        };
+        self.publish(key.to_owned(), kind, old, Some(meta));
+        Ok(())
+    }
+
+    async fn remove(&mut self, key: &Key) -> Result<()> {
+        let path = self.path(key);
+        let Some(old) = Self::read(&path)? else {
+            return Ok(());
+        };
+        match fs::remove_file(&path) {
+            Ok(()) => (),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(anyhow::Error::new(err).context("Filesystem error").into()),
+        }
+        self.publish(key.to_owned(), ChangeKind::Deleted, Some(old), None);
+        Ok(())
+    }
+
+    async fn list(&self) -> Result<Vec<(Key, Meta)>> {
+        let mut files = vec![];
+        for entry in fs::read_dir(&self.root).context("Filesystem error")? {
+            let entry = entry.context("Filesystem error")?;
+            let name = entry.file_name();
+            // Skips temporary files and names that are not keys.
+            let Some(key) = name
+                .to_str()
+                .filter(|name| !name.starts_with('.'))
+                .and_then(|name| percent_decode_str(name).decode_utf8().ok())
+                .and_then(|name| name.parse().ok())
+            else {
+                continue;
+            };
+            // The file may have been removed since listing the directory.
+            if let Some(meta) = Self::read(&entry.path())? {
+                files.push((key, meta));
+            }
+        }
+        Ok(files)
+    }
+
+    async fn copy(&mut self, from: &Key, to: &Key) -> Result<()> {
+        let meta = self.get(from).await?;
+        let path = self.path(to);
+        let old = Self::read(&path)?;
+        self.write(&path, &meta)?;
+        let kind = ChangeKind::Copied {
+            from: from.to_owned(),
+        };
+        self.publish(to.to_owned(), kind, old, Some(meta));
+        Ok(())
+    }
+
+    async fn subscribe(&self) -> Result<Subscription<Key>> {
+        Ok(Subscription::broadcast(self.changes.subscribe()))
+    }
+}
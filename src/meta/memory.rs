@@ -2,22 +2,47 @@ use core::fmt::Debug;
 use std::{collections::HashMap, hash::Hash};
 
 use async_trait::async_trait;
+use tokio::sync::broadcast;
 
 use super::{
+    changes::{Change, ChangeKind, Subscription},
     error::{Error, Result},
     traits::{Meta, MetaStore},
 };
 
+/// Changes kept for subscribers that have not received them yet.
+const CHANGES_CAPACITY: usize = 1024;
+
 #[derive(Debug)]
-pub struct MemoryMetaStore<K: Eq + Hash>(HashMap<K, Meta>);
+pub struct MemoryMetaStore<K: Eq + Hash> {
+    files: HashMap<K, Meta>,
+    changes: broadcast::Sender<Change<K>>,
+    sequence: u64,
+}
 
-impl<K: Eq + Hash> MemoryMetaStore<K> {
+impl<K: Clone + Eq + Hash> MemoryMetaStore<K> {
     pub fn new() -> Self {
-        Self(HashMap::new())
+        Self {
+            files: HashMap::new(),
+            changes: broadcast::channel(CHANGES_CAPACITY).0,
+            sequence: 0,
+        }
+    }
+
+    fn publish(&mut self, key: K, kind: ChangeKind<K>, old: Option<Meta>, new: Option<Meta>) {
+        self.sequence += 1;
+        // Fails only when nobody is subscribed.
+        let _ = self.changes.send(Change {
+            sequence: self.sequence,
+            key,
+            kind,
+            old,
+            new,
+        });
     }
 }
 
-impl<K: Eq + Hash> Default for MemoryMetaStore<K> {
+impl<K: Clone + Eq + Hash> Default for MemoryMetaStore<K> {
     fn default() -> Self {
         Self::new()
     }
@@ -28,16 +53,48 @@ impl<Key: Debug + Clone + Eq + Hash + Send + Sync> MetaStore for MemoryMetaStore
     type Key = Key;
 
     async fn get(&self, key: &Key) -> Result<Meta> {
-        self.0.get(key).map(|v| v.to_owned()).ok_or(Error::NotFound)
+        self.files
+            .get(key)
+            .map(|v| v.to_owned())
+            .ok_or(Error::NotFound)
     }
 
     async fn upsert(&mut self, key: &Key, meta: Meta) -> Result<()> {
-        self.0.insert(key.to_owned(), meta);
+        let old = self.files.insert(key.to_owned(), meta.clone());
+        let kind = match old {
+            Some(_) => ChangeKind::Updated,
+            None => ChangeKind::Created,
+        };
+        self.publish(key.to_owned(), kind, old, Some(meta));
         Ok(())
     }
 
     async fn remove(&mut self, key: &Key) -> Result<()> {
-        self.0.remove(key);
+        if let Some(old) = self.files.remove(key) {
+            self.publish(key.to_owned(), ChangeKind::Deleted, Some(old), None);
+        }
         Ok(())
     }
+
+    async fn list(&self) -> Result<Vec<(Key, Meta)>> {
+        Ok(self
+            .files
+            .iter()
+            .map(|(key, meta)| (key.to_owned(), meta.to_owned()))
+            .collect())
+    }
+
+    async fn copy(&mut self, from: &Key, to: &Key) -> Result<()> {
+        let meta = self.get(from).await?;
+        let old = self.files.insert(to.to_owned(), meta.clone());
+        let kind = ChangeKind::Copied {
+            from: from.to_owned(),
+        };
+        self.publish(to.to_owned(), kind, old, Some(meta));
+        Ok(())
+    }
+
+    async fn subscribe(&self) -> Result<Subscription<Key>> {
+        Ok(Subscription::broadcast(self.changes.subscribe()))
+    }
 }
@@ -1,9 +1,15 @@
+mod cached;
+mod changes;
 mod error;
+mod file;
 mod memory;
 mod postgres;
 mod traits;
 
+pub use cached::CachedMetaStore;
+pub use changes::{Change, ChangeKind, Subscription};
 pub use error::{Error, Result};
+pub use file::FileMetaStore;
 pub use memory::MemoryMetaStore;
 pub use postgres::PostgresMetaStore;
 pub use traits::{Meta, MetaStore};
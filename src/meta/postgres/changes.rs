@@ -0,0 +1,238 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Context};
+use sqlx::{postgres::PgListener, query, query_as, PgPool};
+use tokio::sync::mpsc;
+
+use crate::meta::{
+    changes::{Change, ChangeKind, Subscription},
+    error::{Error, Result},
+};
+
+use super::{DbValue, PostgresMetaStore};
+
+/// Channel the `file_changes` outbox announces new changes on, see the
+/// `record_file_changes` migration.
+const CHANGES_CHANNEL: &str = "cdcfs_file_changes";
+
+/// Changes read from the outbox per query.
+const BATCH_SIZE: i64 = 256;
+
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+struct DbChange {
+    sequence: i64,
+    file_id: i32,
+    kind: String,
+    copied_from: Option<i32>,
+    old_hashes: Option<Vec<i64>>,
+    old_size: Option<i64>,
+    old_sealed_keys: Option<Vec<u8>>,
+    old_chunk_sizes: Option<Vec<i32>>,
+    new_hashes: Option<Vec<i64>>,
+    new_size: Option<i64>,
+    new_sealed_keys: Option<Vec<u8>>,
+    new_chunk_sizes: Option<Vec<i32>>,
+}
+
+impl TryFrom<DbChange> for Change<i32> {
+    type Error = Error;
+
+    fn try_from(value: DbChange) -> Result<Self> {
+        let kind = match (value.kind.as_str(), value.copied_from) {
+            ("created", _) => ChangeKind::Created,
+            ("updated", _) => ChangeKind::Updated,
+            ("deleted", _) => ChangeKind::Deleted,
+            ("copied", Some(from)) => ChangeKind::Copied { from },
+            (kind, _) => return Err(anyhow!("Unknown file change {kind:?}").into()),
+        };
+        let old = value.old_hashes.zip(value.old_size).map(|(hashes, size)| {
+            DbValue {
+                hashes,
+                size,
+                sealed_keys: value.old_sealed_keys,
+                chunk_sizes: value.old_chunk_sizes,
+            }
+            .into()
+        });
+        let new = value.new_hashes.zip(value.new_size).map(|(hashes, size)| {
+            DbValue {
+                hashes,
+                size,
+                sealed_keys: value.new_sealed_keys,
+                chunk_sizes: value.new_chunk_sizes,
+            }
+            .into()
+        });
+
+        Ok(Self {
+            sequence: value.sequence as u64,
+            key: value.file_id,
+            kind,
+            old,
+            new,
+        })
+    }
+}
+
+impl PostgresMetaStore {
+    /// Subscribes to the changes recorded after `sequence`, replaying the ones
+    /// already in the outbox first.
+    ///
+    /// Changes are kept in the outbox until [`prune_changes`] removes them, so
+    /// a consumer that stores the sequence of the last change it handled can
+    /// resume from there after a restart.
+    ///
+    /// [`prune_changes`]: Self::prune_changes
+    pub async fn subscribe_after(&self, sequence: u64) -> Result<Subscription<i32>> {
+        let listener = self.changes_listener().await?;
+        Ok(forward_changes(self.pool().clone(), listener, sequence))
+    }
+
+    /// Removes the changes up to and including `sequence` from the outbox,
+    /// returning how many were removed. Nothing else does, so the outbox
+    /// grows with every write until a consumer calls this with the sequence
+    /// of the last change it no longer needs.
+    pub async fn prune_changes(&self, sequence: u64) -> Result<u64> {
+        let result = query!(
+            r#"
+                DELETE FROM
+                    file_changes
+                WHERE
+                    sequence <= $1
+            "#,
+            sequence as i64
+        )
+        .execute(self.pool())
+        .await
+        .context("Database error")?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub(super) async fn subscribe_latest(&self) -> Result<Subscription<i32>> {
+        // Listens before looking up the latest change, so that no change
+        // recorded in between is missed.
+        let listener = self.changes_listener().await?;
+        sequence_changes(self.pool()).await?;
+        let latest = query!(
+            r#"
+                SELECT
+                    COALESCE(MAX(sequence), 0) AS "sequence!"
+                FROM
+                    file_changes
+            "#
+        )
+        .fetch_one(self.pool())
+        .await
+        .context("Database error")?
+        .sequence;
+
+        Ok(forward_changes(
+            self.pool().clone(),
+            listener,
+            latest as u64,
+        ))
+    }
+
+    async fn changes_listener(&self) -> Result<PgListener> {
+        let mut listener = PgListener::connect_with(self.pool())
+            .await
+            .context("Database error")?;
+        listener
+            .listen(CHANGES_CHANNEL)
+            .await
+            .context("Database error")?;
+        Ok(listener)
+    }
+}
+
+/// Spawns a task reading the outbox after `sequence` whenever a change is
+/// announced, and again after reconnecting, since announcements sent in the
+/// meantime are lost.
+fn forward_changes(pool: PgPool, mut listener: PgListener, sequence: u64) -> Subscription<i32> {
+    let (sender, receiver) = mpsc::channel(BATCH_SIZE as usize);
+    let task = tokio::spawn(async move {
+        let mut cursor = sequence;
+        loop {
+            match fetch_changes(&pool, cursor).await {
+                Ok(changes) => {
+                    let drained = changes.len() < BATCH_SIZE as usize;
+                    for change in changes {
+                        cursor = change.sequence;
+                        if sender.send(Ok(change)).await.is_err() {
+                            return;
+                        }
+                    }
+                    if !drained {
+                        continue;
+                    }
+                }
+                Err(err) => {
+                    if sender.send(Err(err)).await.is_err() {
+                        return;
+                    }
+                    tokio::time::sleep(RETRY_DELAY).await;
+                    continue;
+                }
+            }
+
+            if listener.try_recv().await.is_err() {
+                tokio::time::sleep(RETRY_DELAY).await;
+            }
+        }
+    });
+
+    Subscription::task(receiver, task)
+}
+
+/// Numbers the changes committed since the last call, see the
+/// `record_file_changes` migration.
+async fn sequence_changes(pool: &PgPool) -> Result<()> {
+    query!(
+        r#"
+            SELECT sequence_file_changes() AS "sequenced!"
+        "#
+    )
+    .fetch_one(pool)
+    .await
+    .context("Database error")?;
+
+    Ok(())
+}
+
+async fn fetch_changes(pool: &PgPool, after: u64) -> Result<Vec<Change<i32>>> {
+    sequence_changes(pool).await?;
+    let rows = query_as!(
+        DbChange,
+        r#"
+            SELECT
+                sequence AS "sequence!",
+                file_id,
+                kind,
+                copied_from,
+                old_hashes,
+                old_size,
+                old_sealed_keys,
+                old_chunk_sizes,
+                new_hashes,
+                new_size,
+                new_sealed_keys,
+                new_chunk_sizes
+            FROM
+                file_changes
+            WHERE
+                sequence > $1
+            ORDER BY
+                sequence
+            LIMIT $2
+        "#,
+        after as i64,
+        BATCH_SIZE
+    )
+    .fetch_all(pool)
+    .await
+    .context("Database error")?;
+
+    rows.into_iter().map(TryInto::try_into).collect()
+}
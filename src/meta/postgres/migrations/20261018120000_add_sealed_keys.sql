@@ -0,0 +1 @@
+ALTER TABLE files ADD COLUMN sealed_keys bytea;
@@ -0,0 +1,14 @@
+CREATE FUNCTION notify_file_change() RETURNS trigger AS $$
+BEGIN
+	IF TG_OP = 'DELETE' THEN
+		PERFORM pg_notify('cdcfs_files', OLD.id::text);
+	ELSE
+		PERFORM pg_notify('cdcfs_files', NEW.id::text);
+	END IF;
+	RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER files_notify_change
+	AFTER INSERT OR UPDATE OR DELETE ON files
+	FOR EACH ROW EXECUTE FUNCTION notify_file_change();
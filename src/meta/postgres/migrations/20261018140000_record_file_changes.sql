@@ -0,0 +1,85 @@
+CREATE TABLE file_changes(
+	id bigserial PRIMARY KEY,
+	sequence bigint UNIQUE,
+	file_id int NOT NULL,
+	kind text NOT NULL,
+	copied_from int,
+	old_hashes bigint[],
+	old_size bigint,
+	old_sealed_keys bytea,
+	new_hashes bigint[],
+	new_size bigint,
+	new_sealed_keys bytea
+);
+
+CREATE SEQUENCE file_changes_sequence_seq OWNED BY file_changes.sequence;
+CREATE INDEX file_changes_unsequenced ON file_changes (id) WHERE sequence IS NULL;
+
+-- Records every change of the files table in the file_changes outbox and
+-- announces it. Changes are recorded without a sequence, so that writers do
+-- not take turns, and readers number those already committed with
+-- sequence_file_changes(). The changes of a file keep the order they were
+-- made in, since its row lock orders them.
+CREATE FUNCTION record_file_change() RETURNS trigger AS $$
+DECLARE
+	copied_from int := NULLIF(current_setting('cdcfs.copied_from', true), '')::int;
+BEGIN
+	IF TG_OP = 'DELETE' THEN
+		INSERT INTO file_changes (file_id, kind, old_hashes, old_size, old_sealed_keys)
+		VALUES (OLD.id, 'deleted', OLD.hashes, OLD.size, OLD.sealed_keys);
+	ELSIF TG_OP = 'UPDATE' THEN
+		INSERT INTO file_changes (
+			file_id, kind, copied_from,
+			old_hashes, old_size, old_sealed_keys,
+			new_hashes, new_size, new_sealed_keys
+		)
+		VALUES (
+			NEW.id, CASE WHEN copied_from IS NULL THEN 'updated' ELSE 'copied' END, copied_from,
+			OLD.hashes, OLD.size, OLD.sealed_keys,
+			NEW.hashes, NEW.size, NEW.sealed_keys
+		);
+	ELSE
+		INSERT INTO file_changes (
+			file_id, kind, copied_from,
+			new_hashes, new_size, new_sealed_keys
+		)
+		VALUES (
+			NEW.id, CASE WHEN copied_from IS NULL THEN 'created' ELSE 'copied' END, copied_from,
+			NEW.hashes, NEW.size, NEW.sealed_keys
+		);
+	END IF;
+
+	PERFORM pg_notify('cdcfs_file_changes', '');
+	RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER files_record_change
+	AFTER INSERT OR UPDATE OR DELETE ON files
+	FOR EACH ROW EXECUTE FUNCTION record_file_change();
+
+-- Numbers the committed changes without a sequence in the order they were
+-- recorded, returning how many there were. Holds the lock until the calling
+-- transaction ends, so that a later caller only numbers changes after these,
+-- and sequences become visible in increasing order.
+CREATE FUNCTION sequence_file_changes() RETURNS bigint AS $$
+DECLARE
+	sequenced bigint;
+BEGIN
+	PERFORM pg_advisory_xact_lock(hashtext('cdcfs_file_changes'));
+
+	-- Volatile functions are evaluated after sorting.
+	UPDATE file_changes
+	SET sequence = pending.sequence
+	FROM (
+		SELECT id, nextval('file_changes_sequence_seq') AS sequence
+		FROM file_changes
+		WHERE sequence IS NULL
+		ORDER BY id
+	) AS pending
+	WHERE file_changes.id = pending.id;
+
+	GET DIAGNOSTICS sequenced = ROW_COUNT;
+	RETURN sequenced;
+END;
+$$ LANGUAGE plpgsql;
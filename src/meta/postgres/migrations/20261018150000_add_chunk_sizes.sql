@@ -0,0 +1,45 @@
+ALTER TABLE files ADD COLUMN chunk_sizes int[];
+
+ALTER TABLE file_changes
+	ADD COLUMN old_chunk_sizes int[],
+	ADD COLUMN new_chunk_sizes int[];
+
+CREATE OR REPLACE FUNCTION record_file_change() RETURNS trigger AS $$
+DECLARE
+	copied_from int := NULLIF(current_setting('cdcfs.copied_from', true), '')::int;
+BEGIN
+	IF TG_OP = 'DELETE' THEN
+		INSERT INTO file_changes (
+			file_id, kind,
+			old_hashes, old_size, old_sealed_keys, old_chunk_sizes
+		)
+		VALUES (
+			OLD.id, 'deleted',
+			OLD.hashes, OLD.size, OLD.sealed_keys, OLD.chunk_sizes
+		);
+	ELSIF TG_OP = 'UPDATE' THEN
+		INSERT INTO file_changes (
+			file_id, kind, copied_from,
+			old_hashes, old_size, old_sealed_keys, old_chunk_sizes,
+			new_hashes, new_size, new_sealed_keys, new_chunk_sizes
+		)
+		VALUES (
+			NEW.id, CASE WHEN copied_from IS NULL THEN 'updated' ELSE 'copied' END, copied_from,
+			OLD.hashes, OLD.size, OLD.sealed_keys, OLD.chunk_sizes,
+			NEW.hashes, NEW.size, NEW.sealed_keys, NEW.chunk_sizes
+		);
+	ELSE
+		INSERT INTO file_changes (
+			file_id, kind, copied_from,
+			new_hashes, new_size, new_sealed_keys, new_chunk_sizes
+		)
+		VALUES (
+			NEW.id, CASE WHEN copied_from IS NULL THEN 'created' ELSE 'copied' END, copied_from,
+			NEW.hashes, NEW.size, NEW.sealed_keys, NEW.chunk_sizes
+		);
+	END IF;
+
+	PERFORM pg_notify('cdcfs_file_changes', '');
+	RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
@@ -1,8 +1,12 @@
+mod changes;
+mod notify;
+
 use anyhow::Context;
 use async_trait::async_trait;
 use sqlx::{migrate, postgres::PgPoolOptions, query, query_as, PgPool};
 
 use super::{
+    changes::Subscription,
     error::{Error, Result},
     traits::{Meta, MetaStore},
 };
@@ -22,11 +26,37 @@ impl PostgresMetaStore {
             .context("Database error")?;
         Ok(Self(pool))
     }
+
+    pub(crate) fn pool(&self) -> &PgPool {
+        &self.0
+    }
 }
 
 struct DbValue {
     hashes: Vec<i64>,
     size: i64,
+    sealed_keys: Option<Vec<u8>>,
+    chunk_sizes: Option<Vec<i32>>,
+}
+
+struct DbEntry {
+    id: i32,
+    hashes: Vec<i64>,
+    size: i64,
+    sealed_keys: Option<Vec<u8>>,
+    chunk_sizes: Option<Vec<i32>>,
+}
+
+impl From<DbEntry> for (i32, Meta) {
+    fn from(value: DbEntry) -> Self {
+        let meta = DbValue {
+            hashes: value.hashes,
+            size: value.size,
+            sealed_keys: value.sealed_keys,
+            chunk_sizes: value.chunk_sizes,
+        };
+        (value.id, meta.into())
+    }
 }
 
 impl From<DbValue> for Meta {
@@ -34,6 +64,10 @@ impl From<DbValue> for Meta {
         Self {
             hashes: value.hashes.into_iter().map(|v| v as u64).collect(),
             size: value.size as usize,
+            sealed_keys: value.sealed_keys,
+            chunk_sizes: value
+                .chunk_sizes
+                .map(|sizes| sizes.into_iter().map(|v| v as u32).collect()),
         }
     }
 }
@@ -43,6 +77,10 @@ impl From<Meta> for DbValue {
         Self {
             hashes: value.hashes.into_iter().map(|v| v as i64).collect(),
             size: value.size as i64,
+            sealed_keys: value.sealed_keys,
+            chunk_sizes: value
+                .chunk_sizes
+                .map(|sizes| sizes.into_iter().map(|v| v as i32).collect()),
         }
     }
 }
@@ -57,7 +95,9 @@ impl MetaStore for PostgresMetaStore {
             r#"
                 SELECT
                     hashes,
-                    size
+                    size,
+                    sealed_keys,
+                    chunk_sizes
                 FROM
                     files f
                 WHERE
@@ -80,20 +120,28 @@ impl MetaStore for PostgresMetaStore {
                 INSERT INTO files (
                     id,
                     hashes,
-                    size
+                    size,
+                    sealed_keys,
+                    chunk_sizes
                 )
                 VALUES (
                     $1,
                     $2,
-                    $3
+                    $3,
+                    $4,
+                    $5
                 )
                 ON CONFLICT (id) DO UPDATE SET
                     hashes = EXCLUDED.hashes,
-                    size = EXCLUDED.size
+                    size = EXCLUDED.size,
+                    sealed_keys = EXCLUDED.sealed_keys,
+                    chunk_sizes = EXCLUDED.chunk_sizes
             "#,
             key,
             &meta.hashes,
-            meta.size
+            meta.size,
+            meta.sealed_keys,
+            meta.chunk_sizes.as_deref()
         )
         .execute(&self.0)
         .await
@@ -118,4 +166,87 @@ impl MetaStore for PostgresMetaStore {
 
         Ok(())
     }
+
+    async fn list(&self) -> Result<Vec<(Self::Key, Meta)>> {
+        let rows = query_as!(
+            DbEntry,
+            r#"
+                SELECT
+                    id,
+                    hashes,
+                    size,
+                    sealed_keys,
+                    chunk_sizes
+                FROM
+                    files
+                ORDER BY
+                    id
+            "#
+        )
+        .fetch_all(&self.0)
+        .await
+        .context("Database error")?;
+
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    async fn copy(&mut self, from: &Self::Key, to: &Self::Key) -> Result<()> {
+        let mut transaction = self.0.begin().await.context("Database error")?;
+
+        // Makes the `record_file_changes` trigger record a copy.
+        query!(
+            r#"
+                SELECT set_config('cdcfs.copied_from', $1, true)
+            "#,
+            from.to_string()
+        )
+        .fetch_one(&mut *transaction)
+        .await
+        .context("Database error")?;
+
+        let result = query!(
+            r#"
+                INSERT INTO files (
+                    id,
+                    hashes,
+                    size,
+                    sealed_keys,
+                    chunk_sizes
+                )
+                SELECT
+                    $2,
+                    hashes,
+                    size,
+                    sealed_keys,
+                    chunk_sizes
+                FROM
+                    files f
+                WHERE
+                    f.id = $1
+                ON CONFLICT (id) DO UPDATE SET
+                    hashes = EXCLUDED.hashes,
+                    size = EXCLUDED.size,
+                    sealed_keys = EXCLUDED.sealed_keys,
+                    chunk_sizes = EXCLUDED.chunk_sizes
+            "#,
+            from,
+            to
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Database error")?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound);
+        }
+        transaction.commit().await.context("Database error")?;
+
+        Ok(())
+    }
+
+    /// Subscribes to the changes recorded from now on, see
+    /// [`PostgresMetaStore::subscribe_after`] to resume from earlier changes.
+    async fn subscribe(&self) -> Result<Subscription<Self::Key>> {
+        self.subscribe_latest().await
+    }
 }
@@ -0,0 +1,55 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use sqlx::postgres::PgListener;
+
+use crate::meta::{
+    cached::{lock, CachedMetaStore},
+    error::Result,
+};
+
+use super::PostgresMetaStore;
+
+/// Channel the `files` table announces changed ids on, see the
+/// `notify_file_changes` migration.
+const FILES_CHANNEL: &str = "cdcfs_files";
+
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+impl CachedMetaStore<PostgresMetaStore> {
+    /// Invalidates cached metas whenever any process changes the file in the
+    /// database, using Postgres `LISTEN/NOTIFY`.
+    ///
+    /// Notifications sent while the listener is reconnecting are lost, so the
+    /// whole cache is dropped whenever the connection is.
+    pub async fn with_notifications(mut self) -> Result<Self> {
+        let mut listener = PgListener::connect_with(self.inner().pool())
+            .await
+            .context("Database error")?;
+        listener
+            .listen(FILES_CHANNEL)
+            .await
+            .context("Database error")?;
+
+        let cache = self.cache();
+        lock(&cache).clear();
+        let task = tokio::spawn(async move {
+            loop {
+                match listener.try_recv().await {
+                    Ok(Some(notification)) => match notification.payload().parse::<i32>() {
+                        Ok(id) => lock(&cache).invalidate(&id),
+                        Err(_) => lock(&cache).clear(),
+                    },
+                    Ok(None) => lock(&cache).clear(),
+                    Err(_) => {
+                        lock(&cache).clear();
+                        tokio::time::sleep(RETRY_DELAY).await;
+                    }
+                }
+            }
+        });
+        self.set_listener(task);
+
+        Ok(self)
+    }
+}
@@ -1,22 +1,55 @@
 use core::fmt::Debug;
 
+use anyhow::anyhow;
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
 
-use super::error::Result;
+use super::{changes::Subscription, error::Result};
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Meta {
     pub hashes: Vec<u64>,
     pub size: usize,
+    /// Chunk keys of a file written with convergent encryption, encrypted
+    /// with the owner's key.
+    pub sealed_keys: Option<Vec<u8>>,
+    /// Plaintext length of every chunk, which lets reads of a byte range skip
+    /// to the first chunk overlapping it. `None` for files written before
+    /// sizes were recorded.
+    pub chunk_sizes: Option<Vec<u32>>,
 }
 
 #[async_trait]
-pub trait MetaStore: Debug {
-    type Key: Debug;
+pub trait MetaStore: Debug + Send + Sync {
+    type Key: Debug + Sync;
 
     async fn get(&self, key: &Self::Key) -> Result<Meta>;
 
     async fn upsert(&mut self, key: &Self::Key, meta: Meta) -> Result<()>;
 
     async fn remove(&mut self, key: &Self::Key) -> Result<()>;
+
+    /// Every stored file with its meta, in no particular order.
+    ///
+    /// The whole listing is held in memory. Fails by default, for stores that
+    /// cannot enumerate their files, which leaves them without
+    /// [`System::fsck`](crate::System::fsck) and the other whole-store
+    /// operations.
+    async fn list(&self) -> Result<Vec<(Self::Key, Meta)>> {
+        Err(anyhow!("Meta store cannot list its files").into())
+    }
+
+    /// Copies the meta of `from` to `to`, by default by reading and writing
+    /// it. Stores that can copy in one step, or report the change as a copy,
+    /// override this.
+    async fn copy(&mut self, from: &Self::Key, to: &Self::Key) -> Result<()> {
+        let meta = self.get(from).await?;
+        self.upsert(to, meta).await
+    }
+
+    /// Subscribes to the changes made to the store from now on. Fails by
+    /// default, for stores that cannot report their changes.
+    async fn subscribe(&self) -> Result<Subscription<Self::Key>> {
+        Err(anyhow!("Meta store cannot report its changes").into())
+    }
 }
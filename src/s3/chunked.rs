@@ -0,0 +1,135 @@
+/// Decodes the `aws-chunked` content encoding SDKs use to stream uploads of
+/// unknown length.
+///
+/// Every chunk is its size in hex, optionally followed by `;chunk-signature=`,
+/// a line break, the data and another line break. A zero sized chunk ends the
+/// body and may be followed by trailing headers such as checksums. Neither
+/// signatures nor trailers are checked.
+#[derive(Debug, Default)]
+pub(super) struct AwsChunkedDecoder {
+    state: State,
+    line: Vec<u8>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+enum State {
+    #[default]
+    Header,
+    Data(usize),
+    /// Line break after the data, with the number of bytes left of it.
+    DataEnd(usize),
+    Trailers,
+    Done,
+}
+
+/// Longest chunk header or trailer accepted.
+const MAX_LINE: usize = 4096;
+
+impl AwsChunkedDecoder {
+    /// Decodes the next piece of the body, appending the data to `out`.
+    pub(super) fn decode(&mut self, mut input: &[u8], out: &mut Vec<u8>) -> Result<(), String> {
+        while !input.is_empty() {
+            match self.state {
+                State::Header | State::Trailers => {
+                    let Some(line) = self.take_line(&mut input)? else {
+                        break;
+                    };
+                    self.state = match self.state {
+                        State::Header => {
+                            let size = line.split(|b| *b == b';').next().unwrap_or_default();
+                            let size = std::str::from_utf8(size)
+                                .ok()
+                                .and_then(|size| usize::from_str_radix(size.trim(), 16).ok())
+                                .ok_or("Invalid chunk size")?;
+                            if size == 0 {
+                                State::Trailers
+                            } else {
+                                State::Data(size)
+                            }
+                        }
+                        _ if line.is_empty() => State::Done,
+                        _ => State::Trailers,
+                    };
+                }
+                State::Data(left) => {
+                    let (data, rest) = input.split_at(left.min(input.len()));
+                    out.extend_from_slice(data);
+                    input = rest;
+                    self.state = match left - data.len() {
+                        0 => State::DataEnd(2),
+                        left => State::Data(left),
+                    };
+                }
+                State::DataEnd(left) => {
+                    let skipped = left.min(input.len());
+                    input = &input[skipped..];
+                    self.state = match left - skipped {
+                        0 => State::Header,
+                        left => State::DataEnd(left),
+                    };
+                }
+                State::Done => return Err("Data after the last chunk".to_owned()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the body did not end within a chunk.
+    pub(super) fn finish(&self) -> Result<(), String> {
+        match self.state {
+            State::Trailers | State::Done => Ok(()),
+            _ => Err("Body ended within a chunk".to_owned()),
+        }
+    }
+
+    /// Takes the rest of the current line from `input`, `None` if it does not
+    /// end in this piece of the body.
+    fn take_line(&mut self, input: &mut &[u8]) -> Result<Option<Vec<u8>>, String> {
+        let end = input.iter().position(|b| *b == b'\n');
+        let (head, rest) = input.split_at(end.map_or(input.len(), |end| end + 1));
+        self.line.extend_from_slice(head);
+        *input = rest;
+        if self.line.len() > MAX_LINE {
+            return Err("Chunk header too long".to_owned());
+        }
+        if end.is_none() {
+            return Ok(None);
+        }
+
+        let mut line = std::mem::take(&mut self.line);
+        while matches!(line.last(), Some(b'\n' | b'\r')) {
+            line.pop();
+        }
+        Ok(Some(line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AwsChunkedDecoder;
+
+    #[test]
+    fn it_decodes_split_bodies() {
+        let body = b"5;chunk-signature=abc\r\nHello\r\n7\r\n World!\r\n0\r\nx-amz-checksum-crc32:AAAAAA==\r\n\r\n";
+        for split in 1..body.len() {
+            let mut decoder = AwsChunkedDecoder::default();
+            let mut out = vec![];
+            for piece in body.chunks(split) {
+                decoder.decode(piece, &mut out).unwrap();
+            }
+            decoder.finish().unwrap();
+            assert_eq!(out, b"Hello World!");
+        }
+    }
+
+    #[test]
+    fn it_rejects_truncated_bodies() {
+        let mut decoder = AwsChunkedDecoder::default();
+        let mut out = vec![];
+        decoder.decode(b"a\r\nshort", &mut out).unwrap();
+        assert!(decoder.finish().is_err());
+        assert!(AwsChunkedDecoder::default()
+            .decode(b"zz\r\n", &mut out)
+            .is_err());
+    }
+}
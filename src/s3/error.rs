@@ -0,0 +1,106 @@
+use hyper::{header::CONTENT_TYPE, Body, Response, StatusCode};
+
+use super::xml::{self, ErrorResponse};
+use crate::{meta, system};
+
+/// Error answered with an S3 error document.
+#[derive(Debug)]
+pub(super) struct S3Error {
+    status: StatusCode,
+    code: &'static str,
+    message: String,
+}
+
+impl S3Error {
+    fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub(super) fn no_such_bucket(name: &str) -> Self {
+        let message = format!("Bucket {name} does not exist");
+        Self::new(StatusCode::NOT_FOUND, "NoSuchBucket", message)
+    }
+
+    pub(super) fn no_such_upload() -> Self {
+        let message = "Upload does not exist, or was completed or aborted";
+        Self::new(StatusCode::NOT_FOUND, "NoSuchUpload", message)
+    }
+
+    pub(super) fn invalid_argument(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "InvalidArgument", message)
+    }
+
+    pub(super) fn invalid_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "InvalidRequest", message)
+    }
+
+    pub(super) fn invalid_part(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "InvalidPart", message)
+    }
+
+    pub(super) fn invalid_part_order() -> Self {
+        let message = "Parts must be listed in ascending order";
+        Self::new(StatusCode::BAD_REQUEST, "InvalidPartOrder", message)
+    }
+
+    pub(super) fn malformed_xml() -> Self {
+        let message = "Request body is not a valid document";
+        Self::new(StatusCode::BAD_REQUEST, "MalformedXML", message)
+    }
+
+    pub(super) fn incomplete_body() -> Self {
+        let message = "Request body ended early";
+        Self::new(StatusCode::BAD_REQUEST, "IncompleteBody", message)
+    }
+
+    pub(super) fn not_implemented() -> Self {
+        let message = "Operation is not supported";
+        Self::new(StatusCode::NOT_IMPLEMENTED, "NotImplemented", message)
+    }
+
+    /// Answers with the error document, or only the status for `HEAD`
+    /// requests.
+    pub(super) fn into_response(self, head: bool) -> Response<Body> {
+        let response = Response::builder().status(self.status);
+        let body = if head {
+            Body::empty()
+        } else {
+            Body::from(xml::to_string(&ErrorResponse {
+                code: self.code,
+                message: &self.message,
+            }))
+        };
+        response
+            .header(CONTENT_TYPE, "application/xml")
+            .body(body)
+            .expect("Response is valid")
+    }
+}
+
+impl From<system::Error> for S3Error {
+    fn from(err: system::Error) -> Self {
+        match err {
+            system::Error::MetaStore(meta::Error::NotFound) => {
+                let message = "Key does not exist";
+                Self::new(StatusCode::NOT_FOUND, "NoSuchKey", message)
+            }
+            // Details may name backends, addresses or keys, so they are
+            // only logged.
+            err => {
+                tracing::error!("Request failed: {err:?}");
+                let message = "We encountered an internal error. Please try again.";
+                Self::new(StatusCode::INTERNAL_SERVER_ERROR, "InternalError", message)
+            }
+        }
+    }
+}
+
+impl From<meta::Error> for S3Error {
+    fn from(err: meta::Error) -> Self {
+        system::Error::from(err).into()
+    }
+}
@@ -0,0 +1,409 @@
+mod chunked;
+mod error;
+mod multipart;
+mod xml;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::Infallible,
+    hash::BuildHasher,
+    net::TcpListener,
+    sync::{atomic::AtomicU64, Arc, Mutex},
+};
+
+use hyper::{
+    body::HttpBody,
+    header::{CONTENT_ENCODING, CONTENT_TYPE, ETAG},
+    service::{make_service_fn, service_fn},
+    Body, HeaderMap, Method, Request, Response, Server, StatusCode,
+};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+
+use self::{
+    chunked::AwsChunkedDecoder,
+    error::S3Error,
+    multipart::PendingUpload,
+    xml::{
+        Bucket, BucketList, CommonPrefix, CopyObjectResult, ListAllMyBucketsResult,
+        ListBucketResult, Object, NAMESPACE,
+    },
+};
+use crate::{
+    chunks::ChunkStore,
+    http::{self, SharedSystem},
+    meta::MetaStore,
+    system::Upload,
+};
+
+/// Key prefix of the parts of multipart uploads, which are kept out of every
+/// bucket's listing.
+const MULTIPART_PREFIX: &str = ".cdcfs-multipart/";
+
+/// Most keys returned by one `ListObjectsV2` request.
+const MAX_KEYS: usize = 1000;
+
+/// Characters left alone when keys are URL encoded in listings.
+const KEY_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~')
+    .remove(b'/');
+
+/// Buckets served by [`serve`], by name, each mapped to the prefix its
+/// objects' keys are stored under.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct Buckets(BTreeMap<String, String>);
+
+impl Buckets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serves the files whose keys start with `prefix` as bucket `name`,
+    /// without the prefix.
+    pub fn with_bucket(mut self, name: impl Into<String>, prefix: impl Into<String>) -> Self {
+        self.0.insert(name.into(), prefix.into());
+        self
+    }
+
+    fn prefix(&self, name: &str) -> Result<&str, S3Error> {
+        self.0
+            .get(name)
+            .map(String::as_str)
+            .ok_or_else(|| S3Error::no_such_bucket(name))
+    }
+}
+
+/// Serves `system` on `listener` as a subset of the S3 REST API, with
+/// path-style addressing, until the future is dropped.
+///
+/// Supported are `ListBuckets`, `HeadBucket`, `ListObjectsV2`, `PutObject`,
+/// `GetObject` with a single range, `HeadObject`, `DeleteObject`,
+/// `CopyObject`, and multipart uploads. Requests are not authenticated, so
+/// signatures are accepted without being checked.
+pub async fn serve<C, M, H>(
+    listener: TcpListener,
+    system: SharedSystem<C, M, H>,
+    buckets: Buckets,
+) -> http::Result<()>
+where
+    C: ChunkStore + Send + Sync + 'static,
+    M: MetaStore<Key = String> + Send + Sync + 'static,
+    H: BuildHasher + Send + Sync + 'static,
+{
+    let frontend = Arc::new(Frontend {
+        system,
+        buckets,
+        uploads: Mutex::new(HashMap::new()),
+        uploads_started: AtomicU64::new(0),
+    });
+
+    listener.set_nonblocking(true)?;
+    let make_service = make_service_fn(move |_| {
+        let frontend = Arc::clone(&frontend);
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let frontend = Arc::clone(&frontend);
+                async move {
+                    let head = request.method() == Method::HEAD;
+                    let response = frontend.route(request).await;
+                    Ok::<_, Infallible>(response.unwrap_or_else(|err| err.into_response(head)))
+                }
+            }))
+        }
+    });
+    Server::from_tcp(listener)?.serve(make_service).await?;
+    Ok(())
+}
+
+struct Frontend<C: ChunkStore, M: MetaStore, H: BuildHasher> {
+    system: SharedSystem<C, M, H>,
+    buckets: Buckets,
+    uploads: Mutex<HashMap<String, PendingUpload>>,
+    uploads_started: AtomicU64,
+}
+
+impl<C, M, H> Frontend<C, M, H>
+where
+    C: ChunkStore + Send + Sync + 'static,
+    M: MetaStore<Key = String> + Send + Sync + 'static,
+    H: BuildHasher + Send + Sync + 'static,
+{
+    async fn route(&self, request: Request<Body>) -> Result<Response<Body>, S3Error> {
+        let query = parse_query(request.uri().query());
+        let path = request.uri().path().trim_start_matches('/');
+        let (bucket, key) = match path.split_once('/') {
+            Some((bucket, key)) if !key.is_empty() => (bucket, Some(decode(key)?)),
+            Some((bucket, _)) => (bucket, None),
+            None => (path, None),
+        };
+        let bucket = decode(bucket)?;
+
+        if bucket.is_empty() {
+            return match *request.method() {
+                Method::GET => Ok(self.list_buckets()),
+                _ => Err(S3Error::not_implemented()),
+            };
+        }
+        let prefix = self.buckets.prefix(&bucket)?;
+
+        let Some(key) = key else {
+            return match *request.method() {
+                Method::GET if query.get("list-type").map(String::as_str) == Some("2") => {
+                    self.list_objects(&bucket, prefix, &query).await
+                }
+                Method::HEAD => Ok(empty(StatusCode::OK)),
+                _ => Err(S3Error::not_implemented()),
+            };
+        };
+        let path = format!("{prefix}{key}");
+        if path.starts_with(MULTIPART_PREFIX) {
+            return Err(S3Error::invalid_argument("Key is reserved"));
+        }
+
+        match *request.method() {
+            Method::GET | Method::HEAD => {
+                let with_body = request.method() == Method::GET;
+                Ok(http::get(&self.system, &path, request.headers(), with_body).await?)
+            }
+            Method::PUT if query.contains_key("uploadId") => {
+                self.upload_part(&bucket, &key, &query, request).await
+            }
+            Method::PUT if request.headers().contains_key("x-amz-copy-source") => {
+                self.copy_object(&path, request.headers()).await
+            }
+            Method::PUT => {
+                let etag = self.receive(&path, request).await?;
+                Ok(Response::builder()
+                    .header(ETAG, etag)
+                    .body(Body::empty())
+                    .expect("Response is valid"))
+            }
+            Method::DELETE if query.contains_key("uploadId") => {
+                self.abort_upload(&bucket, &key, &query).await
+            }
+            Method::DELETE => {
+                self.system.write().await.delete(&path).await?;
+                Ok(empty(StatusCode::NO_CONTENT))
+            }
+            Method::POST if query.contains_key("uploads") => Ok(self.create_upload(&bucket, &key)),
+            Method::POST if query.contains_key("uploadId") => {
+                self.complete_upload(&bucket, &key, &path, &query, request)
+                    .await
+            }
+            _ => Err(S3Error::not_implemented()),
+        }
+    }
+
+    fn list_buckets(&self) -> Response<Body> {
+        let buckets = self
+            .buckets
+            .0
+            .keys()
+            .map(|name| Bucket { name: name.clone() })
+            .collect();
+        xml_response(&ListAllMyBucketsResult {
+            xmlns: NAMESPACE,
+            buckets: BucketList { buckets },
+        })
+    }
+
+    async fn list_objects(
+        &self,
+        bucket: &str,
+        bucket_prefix: &str,
+        query: &HashMap<String, String>,
+    ) -> Result<Response<Body>, S3Error> {
+        let prefix = query.get("prefix").cloned().unwrap_or_default();
+        let delimiter = query.get("delimiter").filter(|d| !d.is_empty()).cloned();
+        let max_keys = match query.get("max-keys") {
+            Some(max_keys) => max_keys
+                .parse::<usize>()
+                .map_err(|_| S3Error::invalid_argument("Invalid max-keys"))?
+                .min(MAX_KEYS),
+            None => MAX_KEYS,
+        };
+        let continuation_token = query.get("continuation-token").cloned();
+        let start_after = query.get("start-after").cloned();
+        let after = continuation_token.as_ref().or(start_after.as_ref());
+
+        let mut files: Vec<_> = self
+            .system
+            .read()
+            .await
+            .meta_store()
+            .list()
+            .await?
+            .into_iter()
+            .filter(|(path, _)| !path.starts_with(MULTIPART_PREFIX))
+            .filter_map(|(path, meta)| Some((path.strip_prefix(bucket_prefix)?.to_owned(), meta)))
+            .filter(|(key, _)| key.starts_with(&prefix))
+            .filter(|(key, _)| after.is_none_or(|after| key > after))
+            .collect();
+        files.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+
+        let mut result = ListBucketResult {
+            xmlns: NAMESPACE,
+            name: bucket.to_owned(),
+            max_keys,
+            ..Default::default()
+        };
+        let mut last = None;
+        for (key, meta) in files {
+            let common_prefix = delimiter.as_ref().and_then(|delimiter| {
+                let end = key[prefix.len()..].find(delimiter.as_str())?;
+                Some(key[..prefix.len() + end + delimiter.len()].to_owned())
+            });
+            if let Some(common_prefix) = &common_prefix {
+                let seen = last.as_ref() == Some(common_prefix);
+                if seen || after.is_some_and(|after| common_prefix <= after) {
+                    continue;
+                }
+            }
+            if result.key_count == max_keys {
+                result.is_truncated = true;
+                result.next_continuation_token = last;
+                break;
+            }
+
+            result.key_count += 1;
+            match common_prefix {
+                Some(common_prefix) => {
+                    last = Some(common_prefix.clone());
+                    result.common_prefixes.push(CommonPrefix {
+                        prefix: common_prefix,
+                    });
+                }
+                None => {
+                    last = Some(key.clone());
+                    result.contents.push(Object {
+                        key,
+                        etag: http::etag(&meta),
+                        size: meta.size,
+                        storage_class: "STANDARD",
+                    });
+                }
+            }
+        }
+
+        if query.get("encoding-type").map(String::as_str) == Some("url") {
+            let encode = |key: &str| utf8_percent_encode(key, KEY_ENCODE_SET).to_string();
+            for object in &mut result.contents {
+                object.key = encode(&object.key);
+            }
+            for common_prefix in &mut result.common_prefixes {
+                common_prefix.prefix = encode(&common_prefix.prefix);
+            }
+            result.prefix = encode(&prefix);
+            result.delimiter = delimiter.as_deref().map(encode);
+            result.start_after = start_after.as_deref().map(encode);
+            result.encoding_type = Some("url".to_owned());
+        } else {
+            result.prefix = prefix;
+            result.delimiter = delimiter;
+            result.start_after = start_after;
+        }
+        result.continuation_token = continuation_token;
+        Ok(xml_response(&result))
+    }
+
+    async fn copy_object(
+        &self,
+        path: &String,
+        headers: &HeaderMap,
+    ) -> Result<Response<Body>, S3Error> {
+        let source = headers["x-amz-copy-source"]
+            .to_str()
+            .map_err(|_| S3Error::invalid_argument("Invalid copy source"))?;
+        // Versions are not kept, so the current one is copied.
+        let source = source.split_once("?versionId=").map_or(source, |(s, _)| s);
+        let source = decode(source.trim_start_matches('/'))?;
+        let (bucket, key) = source
+            .split_once('/')
+            .ok_or_else(|| S3Error::invalid_argument("Invalid copy source"))?;
+        let source = format!("{}{key}", self.buckets.prefix(bucket)?);
+        if source.starts_with(MULTIPART_PREFIX) {
+            return Err(S3Error::invalid_argument("Key is reserved"));
+        }
+
+        let mut system = self.system.write().await;
+        system.copy(&source, path).await?;
+        let meta = system.meta_store().get(path).await?;
+        Ok(xml_response(&CopyObjectResult {
+            xmlns: NAMESPACE,
+            etag: http::etag(&meta),
+        }))
+    }
+
+    /// Writes the request body to `path` and returns the file's ETag.
+    async fn receive(&self, path: &String, request: Request<Body>) -> Result<String, S3Error> {
+        let mut decoder = is_aws_chunked(request.headers()).then(AwsChunkedDecoder::default);
+        let mut body = request.into_body();
+        let mut upload = Upload::new();
+        let mut window = Vec::with_capacity(http::WRITE_WINDOW);
+        while let Some(bytes) = body.data().await {
+            let bytes = bytes.map_err(|_| S3Error::incomplete_body())?;
+            match &mut decoder {
+                Some(decoder) => decoder
+                    .decode(&bytes, &mut window)
+                    .map_err(S3Error::invalid_request)?,
+                None => window.extend_from_slice(&bytes),
+            }
+            if window.len() >= http::WRITE_WINDOW {
+                let window = std::mem::take(&mut window);
+                upload = http::upload_window(&self.system, upload, window).await?;
+            }
+        }
+        if let Some(decoder) = &decoder {
+            decoder.finish().map_err(S3Error::invalid_request)?;
+        }
+        let upload = http::upload_last_window(&self.system, upload, window).await?;
+
+        let mut system = self.system.write().await;
+        system.finish_upload(path, upload).await?;
+        Ok(http::etag(&system.meta_store().get(path).await?))
+    }
+}
+
+fn is_aws_chunked(headers: &HeaderMap) -> bool {
+    let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+    header(CONTENT_ENCODING.as_str()).is_some_and(|encoding| encoding.contains("aws-chunked"))
+        || header("x-amz-content-sha256").is_some_and(|sha| sha.starts_with("STREAMING-"))
+}
+
+fn parse_query(query: Option<&str>) -> HashMap<String, String> {
+    query
+        .unwrap_or_default()
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let decode = |s: &str| percent_decode_str(s).decode_utf8_lossy().into_owned();
+            (decode(name), decode(value))
+        })
+        .collect()
+}
+
+fn decode(s: &str) -> Result<String, S3Error> {
+    percent_decode_str(s)
+        .decode_utf8()
+        .map(|s| s.into_owned())
+        .map_err(|_| S3Error::invalid_argument("Keys must be UTF-8"))
+}
+
+fn xml_response(value: &impl Serialize) -> Response<Body> {
+    Response::builder()
+        .header(CONTENT_TYPE, "application/xml")
+        .body(Body::from(xml::to_string(value)))
+        .expect("Response is valid")
+}
+
+fn empty(status: StatusCode) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(Body::empty())
+        .expect("Response is valid")
+}
@@ -0,0 +1,256 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    hash::BuildHasher,
+    sync::{atomic::Ordering, PoisonError},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use hyper::{header::ETAG, Body, Request, Response, StatusCode};
+
+use super::{
+    empty,
+    error::S3Error,
+    xml::{
+        CompleteMultipartUpload, CompleteMultipartUploadResult, InitiateMultipartUploadResult,
+        NAMESPACE,
+    },
+    xml_response, Frontend, MULTIPART_PREFIX,
+};
+use crate::{chunks::ChunkStore, http, meta::MetaStore};
+
+/// Highest part number S3 allows.
+const MAX_PART_NUMBER: u32 = 10_000;
+
+/// Multipart upload in progress.
+///
+/// Each part is written to a file of its own under [`MULTIPART_PREFIX`], and
+/// completing the upload concatenates them. Uploads are only tracked in
+/// memory, so the parts of those pending when the server stops are left
+/// behind.
+#[derive(Debug)]
+pub(super) struct PendingUpload {
+    bucket: String,
+    key: String,
+    parts: BTreeSet<u32>,
+}
+
+impl<C, M, H> Frontend<C, M, H>
+where
+    C: ChunkStore + Send + Sync + 'static,
+    M: MetaStore<Key = String> + Send + Sync + 'static,
+    H: BuildHasher + Send + Sync + 'static,
+{
+    pub(super) fn create_upload(&self, bucket: &str, key: &str) -> Response<Body> {
+        let started = self.uploads_started.fetch_add(1, Ordering::Relaxed);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(bucket.as_bytes());
+        hasher.update(key.as_bytes());
+        hasher.update(&started.to_le_bytes());
+        hasher.update(&now.as_nanos().to_le_bytes());
+        let upload_id = hasher.finalize().to_hex()[..32].to_owned();
+
+        self.uploads
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(
+                upload_id.clone(),
+                PendingUpload {
+                    bucket: bucket.to_owned(),
+                    key: key.to_owned(),
+                    parts: BTreeSet::new(),
+                },
+            );
+
+        xml_response(&InitiateMultipartUploadResult {
+            xmlns: NAMESPACE,
+            bucket: bucket.to_owned(),
+            key: key.to_owned(),
+            upload_id,
+        })
+    }
+
+    pub(super) async fn upload_part(
+        &self,
+        bucket: &str,
+        key: &str,
+        query: &HashMap<String, String>,
+        request: Request<Body>,
+    ) -> Result<Response<Body>, S3Error> {
+        let upload_id = &query["uploadId"];
+        let part_number = query
+            .get("partNumber")
+            .and_then(|number| number.parse().ok())
+            .filter(|number| (1..=MAX_PART_NUMBER).contains(number))
+            .ok_or_else(|| S3Error::invalid_argument("Part number must be between 1 and 10000"))?;
+        self.uploaded_parts(upload_id, bucket, key)?;
+
+        let path = part_path(upload_id, part_number);
+        let etag = self.receive(&path, request).await?;
+
+        // The upload may have been completed or aborted in the meantime.
+        let recorded = self
+            .uploads
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_mut(upload_id)
+            .map(|upload| upload.parts.insert(part_number))
+            .is_some();
+        if !recorded {
+            self.system.write().await.delete(&path).await?;
+            return Err(S3Error::no_such_upload());
+        }
+
+        Ok(Response::builder()
+            .header(ETAG, etag)
+            .body(Body::empty())
+            .expect("Response is valid"))
+    }
+
+    pub(super) async fn complete_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        path: &String,
+        query: &HashMap<String, String>,
+        request: Request<Body>,
+    ) -> Result<Response<Body>, S3Error> {
+        let upload_id = &query["uploadId"];
+        self.uploaded_parts(upload_id, bucket, key)?;
+
+        let body = hyper::body::to_bytes(request.into_body())
+            .await
+            .map_err(|_| S3Error::incomplete_body())?;
+        let completed: CompleteMultipartUpload =
+            quick_xml::de::from_reader(&body[..]).map_err(|_| S3Error::malformed_xml())?;
+        if completed.parts.is_empty() {
+            return Err(S3Error::malformed_xml());
+        }
+        let ascending = completed
+            .parts
+            .windows(2)
+            .all(|pair| pair[0].part_number < pair[1].part_number);
+        if !ascending {
+            return Err(S3Error::invalid_part_order());
+        }
+
+        // Parts finishing from now on are not recorded but deleted by their
+        // own requests, so every part left behind is known here. The upload
+        // is put back should completing it fail.
+        let upload = self.take_upload(upload_id, bucket, key)?;
+        let etag = match self
+            .concat_parts(upload_id, path, &upload, &completed)
+            .await
+        {
+            Ok(etag) => etag,
+            Err(err) => {
+                self.uploads
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .insert(upload_id.clone(), upload);
+                return Err(err);
+            }
+        };
+
+        let mut system = self.system.write().await;
+        for part_number in upload.parts {
+            system.delete(&part_path(upload_id, part_number)).await?;
+        }
+
+        Ok(xml_response(&CompleteMultipartUploadResult {
+            xmlns: NAMESPACE,
+            bucket: bucket.to_owned(),
+            key: key.to_owned(),
+            etag,
+        }))
+    }
+
+    /// Checks the parts `completed` lists against those of `upload`, writes
+    /// them to `path` and returns its ETag.
+    async fn concat_parts(
+        &self,
+        upload_id: &str,
+        path: &String,
+        upload: &PendingUpload,
+        completed: &CompleteMultipartUpload,
+    ) -> Result<String, S3Error> {
+        // Only the meta store is read while checking the parts, so other
+        // requests carry on meanwhile.
+        let system = self.system.read().await;
+        let mut paths = Vec::with_capacity(completed.parts.len());
+        for part in &completed.parts {
+            if !upload.parts.contains(&part.part_number) {
+                let message = format!("Part {} was not uploaded", part.part_number);
+                return Err(S3Error::invalid_part(message));
+            }
+            let part_path = part_path(upload_id, part.part_number);
+            let meta = system.meta_store().get(&part_path).await?;
+            if http::etag(&meta).trim_matches('"') != part.etag.trim_matches('"') {
+                let message = format!("ETag of part {} does not match", part.part_number);
+                return Err(S3Error::invalid_part(message));
+            }
+            paths.push(part_path);
+        }
+        drop(system);
+
+        let mut system = self.system.write().await;
+        system.concat(path, &paths).await?;
+        Ok(http::etag(&system.meta_store().get(path).await?))
+    }
+
+    pub(super) async fn abort_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        query: &HashMap<String, String>,
+    ) -> Result<Response<Body>, S3Error> {
+        let upload_id = &query["uploadId"];
+        let upload = self.take_upload(upload_id, bucket, key)?;
+
+        let mut system = self.system.write().await;
+        for part_number in upload.parts {
+            system.delete(&part_path(upload_id, part_number)).await?;
+        }
+        Ok(empty(StatusCode::NO_CONTENT))
+    }
+
+    /// Numbers of the parts uploaded so far, if `upload_id` is pending for
+    /// the object.
+    fn uploaded_parts(
+        &self,
+        upload_id: &str,
+        bucket: &str,
+        key: &str,
+    ) -> Result<BTreeSet<u32>, S3Error> {
+        self.uploads
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(upload_id)
+            .filter(|upload| upload.bucket == bucket && upload.key == key)
+            .map(|upload| upload.parts.clone())
+            .ok_or_else(S3Error::no_such_upload)
+    }
+
+    /// Stops tracking `upload_id` if it is pending for the object, and
+    /// returns it with the parts recorded until then.
+    fn take_upload(
+        &self,
+        upload_id: &str,
+        bucket: &str,
+        key: &str,
+    ) -> Result<PendingUpload, S3Error> {
+        let mut uploads = self.uploads.lock().unwrap_or_else(PoisonError::into_inner);
+        match uploads.get(upload_id) {
+            Some(upload) if upload.bucket == bucket && upload.key == key => {
+                Ok(uploads.remove(upload_id).expect("Upload is pending"))
+            }
+            _ => Err(S3Error::no_such_upload()),
+        }
+    }
+}
+
+fn part_path(upload_id: &str, part_number: u32) -> String {
+    format!("{MULTIPART_PREFIX}{upload_id}/{part_number:05}")
+}
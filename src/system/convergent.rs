@@ -0,0 +1,114 @@
+use std::fmt::Debug;
+
+use chacha20poly1305::{
+    aead::{Aead, OsRng, Payload},
+    AeadCore, KeyInit, XChaCha20Poly1305, XNonce,
+};
+
+use crate::chunks;
+
+use super::error::{Error, Result};
+
+const KEY_SIZE: usize = 32;
+const NONCE_SIZE: usize = 24;
+
+pub(crate) type ChunkKey = [u8; KEY_SIZE];
+
+/// Convergent encryption of chunks for a [`System`].
+///
+/// Every chunk is encrypted with a key derived from a keyed BLAKE3 hash of its
+/// plaintext, so identical chunks encrypt to identical ciphertext and keep
+/// deduplicating across owners sharing the same `convergence_secret`. Chunks
+/// are addressed by the hash of their ciphertext, and the chunk keys of a file
+/// are sealed into its `Meta` with the owner's key.
+///
+/// [`System`]: super::System
+pub struct ConvergentEncryption {
+    convergence_secret: [u8; KEY_SIZE],
+    owner: XChaCha20Poly1305,
+}
+
+impl ConvergentEncryption {
+    pub fn new(convergence_secret: &[u8; KEY_SIZE], owner_key: &[u8; KEY_SIZE]) -> Self {
+        Self {
+            convergence_secret: *convergence_secret,
+            owner: XChaCha20Poly1305::new(owner_key.into()),
+        }
+    }
+
+    /// Returns the key of `chunk` together with its ciphertext.
+    pub(crate) fn encrypt_chunk(&self, chunk: &[u8]) -> Result<(ChunkKey, Vec<u8>)> {
+        let key: ChunkKey = blake3::keyed_hash(&self.convergence_secret, chunk).into();
+        // Keys are unique per plaintext, so a fixed nonce never repeats under a key.
+        let ciphertext = XChaCha20Poly1305::new(&key.into())
+            .encrypt(&XNonce::default(), chunk)
+            .map_err(|_| anyhow::anyhow!("Encryption error"))
+            .map_err(chunks::Error::from)?;
+        Ok((key, ciphertext))
+    }
+
+    /// Encrypts the chunk keys of a file, bound to its chunk hashes.
+    pub(crate) fn seal_keys(&self, hashes: &[u64], keys: &[ChunkKey]) -> Result<Vec<u8>> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let aad = associated_data(hashes);
+        let ciphertext = self
+            .owner
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: &keys.concat(),
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| Error::KeysUnavailable)?;
+
+        let mut sealed = Vec::with_capacity(NONCE_SIZE + ciphertext.len());
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    pub(crate) fn open_keys(&self, hashes: &[u64], sealed: &[u8]) -> Result<Vec<ChunkKey>> {
+        if sealed.len() < NONCE_SIZE {
+            return Err(Error::KeysUnavailable);
+        }
+
+        let (nonce, ciphertext) = sealed.split_at(NONCE_SIZE);
+        let aad = associated_data(hashes);
+        let keys = self
+            .owner
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| Error::KeysUnavailable)?;
+        if keys.len() != hashes.len() * KEY_SIZE {
+            return Err(Error::KeysUnavailable);
+        }
+
+        Ok(keys
+            .chunks_exact(KEY_SIZE)
+            .map(|key| key.try_into().expect("Chunk keys have a fixed size"))
+            .collect())
+    }
+}
+
+impl Debug for ConvergentEncryption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConvergentEncryption")
+            .finish_non_exhaustive()
+    }
+}
+
+pub(crate) fn decrypt_chunk(key: &ChunkKey, ciphertext: &[u8]) -> chunks::Result<Vec<u8>> {
+    XChaCha20Poly1305::new(key.into())
+        .decrypt(&XNonce::default(), ciphertext)
+        .map_err(|_| chunks::Error::Integrity)
+}
+
+fn associated_data(hashes: &[u64]) -> Vec<u8> {
+    hashes.iter().flat_map(|hash| hash.to_le_bytes()).collect()
+}
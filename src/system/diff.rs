@@ -0,0 +1,140 @@
+use std::{collections::HashSet, hash::BuildHasher, ops::Range};
+
+use serde::Serialize;
+
+use crate::{
+    chunks::ChunkStore,
+    meta::{Meta, MetaStore},
+};
+
+use super::{error::Result, r#impl::System};
+
+/// Outcome of [`System::diff`](super::System::diff).
+///
+/// A chunk counts as shared if the other file contains it anywhere, so
+/// content that moved within the file is shared as well.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Diff {
+    pub a: DiffSide,
+    pub b: DiffSide,
+}
+
+impl Diff {
+    /// Fraction of the bytes of both files that they share, 1 if both are
+    /// empty.
+    pub fn similarity(&self) -> f64 {
+        let size = self.a.size + self.b.size;
+        if size == 0 {
+            return 1.0;
+        }
+        (self.a.shared_bytes() + self.b.shared_bytes()) as f64 / size as f64
+    }
+}
+
+/// One file of a [`Diff`]. Its shared and differing ranges together cover
+/// the file without overlapping.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct DiffSide {
+    pub size: usize,
+    /// Byte ranges the other file contains as well, in ascending order.
+    pub shared: Vec<Range<usize>>,
+    /// Byte ranges the other file does not contain, in ascending order.
+    pub differing: Vec<Range<usize>>,
+}
+
+impl DiffSide {
+    pub fn shared_bytes(&self) -> usize {
+        self.shared.iter().map(ExactSizeIterator::len).sum()
+    }
+
+    pub fn differing_bytes(&self) -> usize {
+        self.differing.iter().map(ExactSizeIterator::len).sum()
+    }
+
+    /// Appends the next `len` bytes of the file, merging them into the last
+    /// range of the same kind if they adjoin it.
+    pub(super) fn push(&mut self, len: usize, shared: bool) {
+        let start = self.size;
+        self.size += len;
+        if len == 0 {
+            return;
+        }
+        let ranges = if shared {
+            &mut self.shared
+        } else {
+            &mut self.differing
+        };
+        match ranges.last_mut() {
+            Some(last) if last.end == start => last.end = self.size,
+            _ => ranges.push(start..self.size),
+        }
+    }
+}
+
+impl<K, C, M, H> System<C, M, H>
+where
+    C: ChunkStore,
+    M: MetaStore<Key = K>,
+    H: BuildHasher,
+{
+    /// Compares two files by their chunk lists, telling which of their byte
+    /// ranges the other file shares.
+    ///
+    /// No content is read, except for chunks whose size the meta does not
+    /// record, as in files written before chunk sizes were recorded.
+    pub async fn diff(&self, a: &K, b: &K) -> Result<Diff> {
+        let a = self.meta_store.get(a).await?;
+        let b = self.meta_store.get(b).await?;
+        self.diff_of(&a, &b)
+    }
+
+    /// Compares the files described by two metas, see [`diff`](Self::diff).
+    pub fn diff_of(&self, a: &Meta, b: &Meta) -> Result<Diff> {
+        let a_hashes: HashSet<u64> = a.hashes.iter().copied().collect();
+        let b_hashes: HashSet<u64> = b.hashes.iter().copied().collect();
+        Ok(Diff {
+            a: self.diff_side(a, &b_hashes)?,
+            b: self.diff_side(b, &a_hashes)?,
+        })
+    }
+
+    fn diff_side(&self, meta: &Meta, other: &HashSet<u64>) -> Result<DiffSide> {
+        let mut side = DiffSide::default();
+        // Chunk keys are only needed to read chunks of unknown size.
+        let mut keys = None;
+        for (idx, hash) in meta.hashes.iter().enumerate() {
+            let known_size = meta.chunk_sizes.as_ref().and_then(|sizes| sizes.get(idx));
+            let size = match known_size {
+                Some(size) => *size as usize,
+                None => {
+                    if keys.is_none() {
+                        keys = Some(self.chunk_keys(meta)?);
+                    }
+                    let keys = keys.as_ref().and_then(Option::as_ref);
+                    self.read_chunk(hash, keys.map(|keys| &keys[idx]))?.len()
+                }
+            };
+            side.push(size, other.contains(hash));
+        }
+        Ok(side)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_merges_adjoining_ranges() {
+        let mut side = DiffSide::default();
+        for (len, shared) in [(10, true), (5, true), (0, false), (3, false), (7, true)] {
+            side.push(len, shared);
+        }
+        assert_eq!(side.size, 25);
+        assert_eq!(side.shared, [0..15, 18..25]);
+        assert_eq!(side.differing.len(), 1);
+        assert_eq!(side.differing[0], 15..18);
+        assert_eq!(side.shared_bytes(), 22);
+        assert_eq!(side.differing_bytes(), 3);
+    }
+}
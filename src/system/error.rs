@@ -10,6 +10,12 @@ pub enum Error {
     Io(#[from] std::io::Error),
     #[error("Chunking error: {0}")]
     Chunking(#[from] fastcdc::v2020::Error),
+    #[error("Chunk keys of the file could not be sealed or unsealed")]
+    KeysUnavailable,
+    #[error("Chunk store was written with a different hasher key")]
+    HasherMismatch,
+    #[error("Chunk {hash} does not match its hash")]
+    Corrupt { hash: u64 },
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,166 @@
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    hash::BuildHasher,
+};
+
+use serde::Serialize;
+
+use crate::{
+    chunks::{self, ChunkStore},
+    meta::MetaStore,
+};
+
+use super::{
+    convergent::{decrypt_chunk, ChunkKey},
+    error::{Error, Result},
+    r#impl::{hash_chunk, System},
+};
+
+/// Outcome of [`System::fsck`](super::System::fsck).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct FsckReport<K> {
+    pub files: Vec<FileReport<K>>,
+    /// Stored chunks that no file references.
+    pub orphan_chunks: Vec<u64>,
+}
+
+impl<K> FsckReport<K> {
+    /// Whether every file is intact. Orphan chunks waste space but do not
+    /// damage any file, so they do not count.
+    pub fn is_clean(&self) -> bool {
+        self.files.iter().all(|file| file.status == FileStatus::Ok)
+    }
+
+    pub fn damaged(&self) -> impl Iterator<Item = &FileReport<K>> {
+        self.files
+            .iter()
+            .filter(|file| file.status != FileStatus::Ok)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct FileReport<K> {
+    pub key: K,
+    pub status: FileStatus,
+    /// Referenced chunks absent from the chunk store.
+    pub missing_chunks: Vec<u64>,
+    /// Referenced chunks whose contents no longer match their hash.
+    pub corrupt_chunks: Vec<u64>,
+    /// Size recorded in the file's meta.
+    pub expected_size: usize,
+    /// Combined length of the file's readable chunks.
+    pub actual_size: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileStatus {
+    Ok,
+    /// Chunks are missing or corrupt, or they do not add up to the file size.
+    Damaged,
+    /// The file is encrypted and its chunk keys cannot be unsealed. Its chunks
+    /// are intact, but their plaintext size could not be checked.
+    KeysUnavailable,
+}
+
+impl<K, C, M, H> System<C, M, H>
+where
+    C: ChunkStore,
+    M: MetaStore<Key = K>,
+    H: BuildHasher,
+{
+    /// Checks that every chunk referenced by a file exists, still matches its
+    /// hash and that the chunks add up to the file's size, and lists the
+    /// chunks no file references.
+    pub async fn fsck(&self) -> Result<FsckReport<K>> {
+        let mut checked = HashMap::new();
+        let mut referenced = HashSet::new();
+        let mut files = vec![];
+
+        for (key, meta) in self.meta_store.list().await? {
+            let (keys, keys_unavailable) = match self.chunk_keys(&meta) {
+                Ok(keys) => (keys, false),
+                Err(Error::KeysUnavailable) => (None, true),
+                Err(err) => return Err(err),
+            };
+
+            let mut missing_chunks = vec![];
+            let mut corrupt_chunks = vec![];
+            // Chunks repeating within the file are reported once.
+            let mut reported = HashSet::new();
+            let mut actual_size = 0;
+            for (idx, hash) in meta.hashes.iter().enumerate() {
+                referenced.insert(*hash);
+                let chunk_key = keys.as_ref().map(|keys| &keys[idx]);
+                let check = match checked.entry((*hash, chunk_key.is_some())) {
+                    Entry::Occupied(entry) => *entry.get(),
+                    Entry::Vacant(entry) => *entry.insert(self.check_chunk(hash, chunk_key)?),
+                };
+                match check {
+                    ChunkCheck::Intact(len) => actual_size += len,
+                    ChunkCheck::Missing if reported.insert(*hash) => missing_chunks.push(*hash),
+                    ChunkCheck::Corrupt if reported.insert(*hash) => corrupt_chunks.push(*hash),
+                    ChunkCheck::Missing | ChunkCheck::Corrupt => (),
+                }
+            }
+
+            let status = if !missing_chunks.is_empty() || !corrupt_chunks.is_empty() {
+                FileStatus::Damaged
+            } else if keys_unavailable {
+                FileStatus::KeysUnavailable
+            } else if actual_size != meta.size {
+                FileStatus::Damaged
+            } else {
+                FileStatus::Ok
+            };
+
+            files.push(FileReport {
+                key,
+                status,
+                missing_chunks,
+                corrupt_chunks,
+                expected_size: meta.size,
+                actual_size,
+            });
+        }
+
+        let mut orphan_chunks: Vec<u64> = self
+            .chunk_store
+            .hashes()?
+            .into_iter()
+            .filter(|hash| !referenced.contains(hash))
+            .collect();
+        orphan_chunks.sort_unstable();
+
+        Ok(FsckReport {
+            files,
+            orphan_chunks,
+        })
+    }
+
+    fn check_chunk(&self, hash: &u64, key: Option<&ChunkKey>) -> Result<ChunkCheck> {
+        let chunk = match self.chunk_store.get(hash) {
+            Ok(chunk) => chunk,
+            Err(chunks::Error::NotFound) => return Ok(ChunkCheck::Missing),
+            Err(chunks::Error::Integrity) => return Ok(ChunkCheck::Corrupt),
+            Err(err) => return Err(err.into()),
+        };
+        if hash_chunk(&self.hasher, &chunk) != *hash {
+            return Ok(ChunkCheck::Corrupt);
+        }
+        match key {
+            Some(key) => match decrypt_chunk(key, &chunk) {
+                Ok(chunk) => Ok(ChunkCheck::Intact(chunk.len())),
+                Err(_) => Ok(ChunkCheck::Corrupt),
+            },
+            None => Ok(ChunkCheck::Intact(chunk.len())),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum ChunkCheck {
+    Intact(usize),
+    Missing,
+    Corrupt,
+}
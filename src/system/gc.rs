@@ -0,0 +1,123 @@
+use std::{collections::HashSet, hash::BuildHasher, time::Duration};
+
+use serde::Serialize;
+
+use crate::{
+    chunks::{self, ChunkStore},
+    meta::MetaStore,
+};
+
+use super::{error::Result, r#impl::System};
+
+/// How [`System::gc_with`](super::System::gc_with) collects garbage.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GcOptions {
+    pub(super) grace: Duration,
+    pub(super) count_bytes: bool,
+}
+
+impl GcOptions {
+    /// Options removing every orphan at once, without counting bytes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Waits `grace` after finding the orphans and only removes those still
+    /// unreferenced then, sparing the chunks of files being written
+    /// meanwhile.
+    pub fn with_grace(mut self, grace: Duration) -> Self {
+        self.grace = grace;
+        self
+    }
+
+    /// Reads every orphan before removing it to fill in
+    /// [`GcReport::bytes_freed`].
+    pub fn with_count_bytes(mut self, count_bytes: bool) -> Self {
+        self.count_bytes = count_bytes;
+        self
+    }
+}
+
+/// Outcome of [`System::gc`](super::System::gc).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct GcReport {
+    /// Chunks no file referenced, which were removed.
+    pub removed: Vec<u64>,
+    /// Combined length of the removed chunks as returned by the chunk store,
+    /// if [counted](GcOptions::with_count_bytes).
+    pub bytes_freed: Option<usize>,
+}
+
+impl<K, C, M, H> System<C, M, H>
+where
+    C: ChunkStore,
+    M: MetaStore<Key = K>,
+    H: BuildHasher,
+{
+    /// Stored chunks that no file references, sorted by hash. Unlike
+    /// [`fsck`](Self::fsck) this does not read any chunk.
+    pub async fn orphan_chunks(&self) -> Result<Vec<u64>> {
+        let referenced: HashSet<u64> = self
+            .meta_store
+            .list()
+            .await?
+            .into_iter()
+            .flat_map(|(_, meta)| meta.hashes)
+            .collect();
+        let mut orphans: Vec<u64> = self
+            .chunk_store
+            .hashes()?
+            .into_iter()
+            .filter(|hash| !referenced.contains(hash))
+            .collect();
+        orphans.sort_unstable();
+        Ok(orphans)
+    }
+
+    /// Removes the chunks no file references, which deleting and
+    /// overwriting files leaves behind.
+    ///
+    /// Chunks are stored before the meta of the file referencing them is
+    /// written, so collecting garbage while another process writes to the
+    /// same stores may remove chunks of the file being written. Use
+    /// [`gc_with`](Self::gc_with) and a grace period in that case.
+    pub async fn gc(&mut self) -> Result<GcReport> {
+        self.gc_with(&GcOptions::new()).await
+    }
+
+    /// Removes the chunks no file references, as configured by `options`.
+    ///
+    /// With a grace period, a chunk is only removed if no file referenced it
+    /// both before and after the wait, so writes finishing within the grace
+    /// period keep their chunks.
+    pub async fn gc_with(&mut self, options: &GcOptions) -> Result<GcReport> {
+        let mut orphans = self.orphan_chunks().await?;
+        if !options.grace.is_zero() {
+            tokio::time::sleep(options.grace).await;
+            let still: HashSet<u64> = self.orphan_chunks().await?.into_iter().collect();
+            orphans.retain(|hash| still.contains(hash));
+        }
+
+        let mut report = GcReport {
+            bytes_freed: options.count_bytes.then_some(0),
+            ..Default::default()
+        };
+        for hash in orphans {
+            if let Some(bytes_freed) = &mut report.bytes_freed {
+                match self.chunk_store.get(&hash) {
+                    Ok(chunk) => *bytes_freed += chunk.len(),
+                    Err(chunks::Error::NotFound) => continue,
+                    // Corrupt chunks are garbage as well.
+                    Err(chunks::Error::Integrity) => (),
+                    Err(err) => return Err(err.into()),
+                }
+            }
+            match self.chunk_store.remove(&hash) {
+                Ok(()) => report.removed.push(hash),
+                Err(chunks::Error::NotFound) => (),
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(report)
+    }
+}
@@ -2,27 +2,40 @@ use std::{
     fmt::Debug,
     hash::{BuildHasher, Hasher},
     io::Read,
+    ops::Range,
+    sync::atomic::{AtomicBool, Ordering},
 };
 
 use fastcdc::v2020::{FastCDC, StreamCDC};
 
 use crate::{
     chunks::ChunkStore,
-    meta::{Meta, MetaStore},
+    meta::{Meta, MetaStore, Subscription},
+    KeyedBuildHasher,
 };
 
-use super::{error::Result, reader::Reader};
+use super::{
+    convergent::{decrypt_chunk, ChunkKey, ConvergentEncryption},
+    error::{Error, Result},
+    reader::Reader,
+    repair::ReadRepair,
+    upload::Upload,
+};
 
 #[derive(Debug)]
 pub struct System<C: ChunkStore, M: MetaStore, H: BuildHasher> {
-    chunk_store: C,
-    meta_store: M,
-    hasher: H,
+    pub(super) chunk_store: C,
+    pub(super) meta_store: M,
+    pub(super) hasher: H,
+    encryption: Option<ConvergentEncryption>,
+    verify_on_read: bool,
+    /// Whether the chunk store is known to hold chunk ids of this hasher.
+    hasher_checked: AtomicBool,
 }
 
-static AVG_SIZE: u32 = u32::pow(2, 14);
-static MIN_SIZE: u32 = AVG_SIZE / 4;
-static MAX_SIZE: u32 = AVG_SIZE * 4;
+pub(crate) static AVG_SIZE: u32 = u32::pow(2, 14);
+pub(crate) static MIN_SIZE: u32 = AVG_SIZE / 4;
+pub(crate) static MAX_SIZE: u32 = AVG_SIZE * 4;
 
 impl<K, C, M, H> System<C, M, H>
 where
@@ -31,41 +44,187 @@ where
     M: MetaStore<Key = K>,
     H: BuildHasher,
 {
+    /// Creates a system hashing chunks with an unkeyed hasher.
+    ///
+    /// Writing fails with [`Error::HasherMismatch`] if the chunk store has
+    /// recorded the fingerprint of a keyed hasher, see
+    /// [`new_keyed`](Self::new_keyed).
     pub fn new(chunk_store: C, meta_store: M, hasher: H) -> Self {
         Self {
             chunk_store,
             meta_store,
             hasher,
+            encryption: None,
+            verify_on_read: false,
+            hasher_checked: AtomicBool::new(false),
+        }
+    }
+
+    /// Creates a system hashing chunks with a keyed hasher.
+    ///
+    /// The hasher's fingerprint is recorded in the chunk store on first use,
+    /// and opening the store with a different key or algorithm afterwards
+    /// fails with [`Error::HasherMismatch`]. So does opening a store that
+    /// holds chunks but no fingerprint, which an unkeyed hasher wrote. Their
+    /// files move to a keyed hasher by reading them and writing them to a
+    /// system on an empty chunk store.
+    pub fn new_keyed(mut chunk_store: C, meta_store: M, hasher: H) -> Result<Self>
+    where
+        H: KeyedBuildHasher,
+    {
+        if chunk_store.hasher_fingerprint()?.is_none() && !chunk_store.is_empty()? {
+            return Err(Error::HasherMismatch);
         }
+        let fingerprint = hasher.fingerprint();
+        match chunk_store.init_hasher_fingerprint(fingerprint)? {
+            Some(recorded) if recorded != fingerprint => return Err(Error::HasherMismatch),
+            _ => (),
+        }
+        let mut system = Self::new(chunk_store, meta_store, hasher);
+        *system.hasher_checked.get_mut() = true;
+        Ok(system)
+    }
+
+    /// Encrypts every chunk written from now on, see [`ConvergentEncryption`].
+    pub fn with_convergent_encryption(mut self, encryption: ConvergentEncryption) -> Self {
+        self.encryption = Some(encryption);
+        self
+    }
+
+    /// Rehashes every chunk read from the chunk store and fails with
+    /// [`Error::Corrupt`] instead of returning data that does not match its id.
+    pub fn with_verify_on_read(mut self, verify_on_read: bool) -> Self {
+        self.verify_on_read = verify_on_read;
+        self
+    }
+
+    /// Repairs chunks while reading them, see [`ReadRepair`].
+    ///
+    /// Chunks are fetched from `replica` by the same ids as from the chunk
+    /// store, so the replica has to hold the same hasher's chunks, stored the
+    /// way this system writes them.
+    pub fn with_read_repair<R>(self, replica: R) -> System<ReadRepair<C, R, H>, M, H>
+    where
+        R: ChunkStore,
+        H: Clone,
+    {
+        System {
+            chunk_store: ReadRepair::new(self.chunk_store, replica, self.hasher.clone()),
+            meta_store: self.meta_store,
+            hasher: self.hasher,
+            encryption: self.encryption,
+            verify_on_read: self.verify_on_read,
+            hasher_checked: self.hasher_checked,
+        }
+    }
+
+    pub fn chunk_store(&self) -> &C {
+        &self.chunk_store
+    }
+
+    pub fn meta_store(&self) -> &M {
+        &self.meta_store
+    }
+
+    pub fn into_parts(self) -> (C, M, H) {
+        (self.chunk_store, self.meta_store, self.hasher)
     }
 
     pub async fn copy(&mut self, from: &K, to: &K) -> Result<()> {
-        let meta = self.meta_store.get(from).await?;
-        self.meta_store.upsert(to, meta).await?;
+        self.meta_store.copy(from, to).await?;
         Ok(())
     }
 
+    /// Subscribes to the files created, updated, deleted and copied from now
+    /// on, as far as the meta store supports it.
+    ///
+    /// [`PostgresMetaStore`](crate::PostgresMetaStore) records every change in
+    /// an outbox table, whether or not anyone subscribes, and keeps it until
+    /// [`prune_changes`](crate::PostgresMetaStore::prune_changes) removes it.
+    /// Deployments writing to Postgres have to prune the outbox periodically
+    /// to keep it from growing forever.
+    pub async fn subscribe(&self) -> Result<Subscription<K>> {
+        Ok(self.meta_store.subscribe().await?)
+    }
+
     pub async fn read(&self, key: &K) -> Result<Vec<u8>> {
         let meta = self.meta_store.get(key).await?;
+        let keys = self.chunk_keys(&meta)?;
         let mut result = Vec::with_capacity(meta.size);
-        for hash in &meta.hashes {
-            let chunk = self.chunk_store.get(hash)?;
+        for (idx, hash) in meta.hashes.iter().enumerate() {
+            let chunk = self.read_chunk(hash, keys.as_ref().map(|keys| &keys[idx]))?;
             result.extend_from_slice(&chunk);
         }
         Ok(result)
     }
 
-    pub async fn read_stream(&self, key: &K) -> Result<Reader<C>> {
+    /// Reads the bytes of `range` of a file, clamped to its size.
+    pub async fn read_range(&self, key: &K, range: Range<usize>) -> Result<Vec<u8>> {
+        let meta = self.meta_store.get(key).await?;
+        self.read_range_of(&meta, range)
+    }
+
+    /// Reads the bytes of `range` of the file described by `meta`, clamped to
+    /// its size.
+    ///
+    /// Only the chunks overlapping the range are fetched, unless the file was
+    /// written before chunk sizes were recorded and every chunk before the
+    /// range has to be read to find it.
+    pub fn read_range_of(&self, meta: &Meta, range: Range<usize>) -> Result<Vec<u8>> {
+        let end = range.end.min(meta.size);
+        let start = range.start.min(end);
+        let keys = self.chunk_keys(meta)?;
+
+        let mut result = Vec::with_capacity(end - start);
+        let mut offset = 0;
+        for (idx, hash) in meta.hashes.iter().enumerate() {
+            if offset >= end {
+                break;
+            }
+            let known_size = meta
+                .chunk_sizes
+                .as_ref()
+                .and_then(|sizes| sizes.get(idx))
+                .map(|size| *size as usize);
+            if let Some(size) = known_size {
+                if offset + size <= start {
+                    offset += size;
+                    continue;
+                }
+            }
+
+            let chunk = self.read_chunk(hash, keys.as_ref().map(|keys| &keys[idx]))?;
+            if offset + chunk.len() > start {
+                let from = start.saturating_sub(offset);
+                let to = (end - offset).min(chunk.len());
+                result.extend_from_slice(&chunk[from..to]);
+            }
+            offset += chunk.len();
+        }
+        Ok(result)
+    }
+
+    pub async fn read_stream(&self, key: &K) -> Result<Reader<'_, C>> {
         let meta = self.meta_store.get(key).await?;
+        let keys = self.chunk_keys(&meta)?;
 
-        Ok(Reader::new(meta.hashes.into(), &self.chunk_store))
+        let mut reader = Reader::new(meta.hashes.into(), &self.chunk_store);
+        if let Some(keys) = keys {
+            reader = reader.with_keys(keys.into());
+        }
+        if self.verify_on_read {
+            let hasher = &self.hasher;
+            reader = reader.with_verifier(Box::new(move |bytes| hash_chunk(hasher, bytes)));
+        }
+        Ok(reader)
     }
 
     pub async fn read_into(&self, key: &K, writer: &mut impl std::io::Write) -> Result<()> {
         let meta = self.meta_store.get(key).await?;
+        let keys = self.chunk_keys(&meta)?;
 
-        for hash in &meta.hashes {
-            let chunk = self.chunk_store.get(hash)?;
+        for (idx, hash) in meta.hashes.iter().enumerate() {
+            let chunk = self.read_chunk(hash, keys.as_ref().map(|keys| &keys[idx]))?;
             writer.write_all(&chunk)?;
         }
 
@@ -78,12 +237,12 @@ where
     {
         let contents = source.as_ref();
         let chunker = FastCDC::new(contents, MIN_SIZE, AVG_SIZE, MAX_SIZE);
-        let mut hashes = vec![];
+        let mut upload = Upload::new();
         for chunk in chunker {
             let bytes = contents[chunk.offset..chunk.offset + chunk.length].to_vec();
-            hashes.push(self.write_chunk(bytes)?);
+            self.store_chunk(bytes, &mut upload)?;
         }
-        self.write_meta(key, hashes, contents.len()).await
+        self.write_meta(key, upload).await
     }
 
     pub async fn write_stream<S>(&mut self, key: &K, source: S) -> Result<()>
@@ -91,14 +250,111 @@ where
         S: Read,
     {
         let chunker = StreamCDC::new(source, MIN_SIZE, AVG_SIZE, MAX_SIZE);
-        let mut hashes = vec![];
-        let mut size: usize = 0;
+        let mut upload = Upload::new();
         for chunk in chunker {
-            let chunk = chunk?;
-            hashes.push(self.write_chunk(chunk.data)?);
-            size += chunk.length;
+            self.store_chunk(chunk?.data, &mut upload)?;
         }
-        self.write_meta(key, hashes, size).await
+        self.write_meta(key, upload).await
+    }
+
+    /// Adds `bytes` to `upload`, storing every chunk that is complete.
+    ///
+    /// Unlike [`write_stream`](Self::write_stream), the caller pushes the
+    /// bytes as they arrive, e.g. from a request body, and the system is only
+    /// borrowed for the duration of each call. The file appears once
+    /// [`finish_upload`](Self::finish_upload) is called.
+    pub fn upload(&mut self, upload: &mut Upload, bytes: &[u8]) -> Result<()> {
+        upload.buffer.extend_from_slice(bytes);
+        while let Some(chunk) = upload.complete_chunk() {
+            self.store_chunk(chunk, upload)?;
+        }
+        Ok(())
+    }
+
+    /// Like [`upload`](Self::upload), but only borrows the system shared,
+    /// for chunk stores that support [`ChunkStore::upsert_shared`].
+    pub fn upload_shared(&self, upload: &mut Upload, bytes: &[u8]) -> Result<()> {
+        upload.buffer.extend_from_slice(bytes);
+        while let Some(chunk) = upload.complete_chunk() {
+            self.store_chunk_shared(chunk, upload)?;
+        }
+        Ok(())
+    }
+
+    /// Stores the chunks still buffered in `upload`, once all of the file's
+    /// bytes have been added.
+    ///
+    /// Lets callers do the chunk store I/O apart from the meta write in
+    /// [`finish_upload`](Self::finish_upload).
+    pub fn flush_upload(&mut self, upload: &mut Upload) -> Result<()> {
+        for chunk in upload.take_rest() {
+            self.store_chunk(chunk, upload)?;
+        }
+        Ok(())
+    }
+
+    /// Like [`flush_upload`](Self::flush_upload), but only borrows the system
+    /// shared, see [`upload_shared`](Self::upload_shared).
+    pub fn flush_upload_shared(&self, upload: &mut Upload) -> Result<()> {
+        for chunk in upload.take_rest() {
+            self.store_chunk_shared(chunk, upload)?;
+        }
+        Ok(())
+    }
+
+    /// Whether uploads can store their chunks with
+    /// [`upload_shared`](Self::upload_shared).
+    pub fn uploads_shared(&self) -> bool {
+        self.chunk_store.writes_shared()
+    }
+
+    /// Stores the rest of `upload` and writes the file to `key`.
+    pub async fn finish_upload(&mut self, key: &K, mut upload: Upload) -> Result<()> {
+        self.flush_upload(&mut upload)?;
+        self.write_meta(key, upload).await
+    }
+
+    /// Writes the files `parts` one after another to `key`, reusing their
+    /// chunks instead of reading them.
+    ///
+    /// The chunk boundaries of each part are kept, so the result may dedup a
+    /// little worse than the same bytes written at once. Parts must either all
+    /// be encrypted or all be plaintext.
+    pub async fn concat(&mut self, key: &K, parts: &[K]) -> Result<()> {
+        let mut hashes = vec![];
+        let mut keys = vec![];
+        let mut chunk_sizes = Some(vec![]);
+        let mut size = 0;
+        let mut encrypted = None;
+        for part in parts {
+            let meta = self.meta_store.get(part).await?;
+            let part_keys = self.chunk_keys(&meta)?;
+            if *encrypted.get_or_insert(part_keys.is_some()) != part_keys.is_some() {
+                return Err(Error::KeysUnavailable);
+            }
+
+            keys.extend(part_keys.into_iter().flatten());
+            chunk_sizes = chunk_sizes.zip(meta.chunk_sizes).map(|(mut sizes, part)| {
+                sizes.extend(part);
+                sizes
+            });
+            hashes.extend(meta.hashes);
+            size += meta.size;
+        }
+
+        let sealed_keys = match (encrypted, &self.encryption) {
+            (Some(true), Some(encryption)) => Some(encryption.seal_keys(&hashes, &keys)?),
+            (Some(true), None) => return Err(Error::KeysUnavailable),
+            _ => None,
+        };
+        let meta = Meta {
+            hashes,
+            size,
+            sealed_keys,
+            chunk_sizes,
+        };
+        self.meta_store.upsert(key, meta).await?;
+        Ok(())
     }
 
     pub async fn delete(&mut self, key: &K) -> Result<()> {
@@ -106,18 +362,91 @@ where
         Ok(())
     }
 
-    fn write_chunk(&mut self, bytes: Vec<u8>) -> Result<u64> {
-        let mut hasher = self.hasher.build_hasher();
-        hasher.write(&bytes);
-        let hash = hasher.finish();
+    pub(super) fn read_chunk(&self, hash: &u64, key: Option<&ChunkKey>) -> Result<Vec<u8>> {
+        let chunk = self.chunk_store.get(hash)?;
+        if self.verify_on_read && hash_chunk(&self.hasher, &chunk) != *hash {
+            return Err(Error::Corrupt { hash: *hash });
+        }
+        match key {
+            Some(key) => Ok(decrypt_chunk(key, &chunk)?),
+            None => Ok(chunk),
+        }
+    }
+
+    /// Unseals the chunk keys of an encrypted file, `None` for plaintext files.
+    pub(super) fn chunk_keys(&self, meta: &Meta) -> Result<Option<Vec<ChunkKey>>> {
+        match (&meta.sealed_keys, &self.encryption) {
+            (None, _) => Ok(None),
+            (Some(sealed), Some(encryption)) => {
+                encryption.open_keys(&meta.hashes, sealed).map(Some)
+            }
+            (Some(_), None) => Err(Error::KeysUnavailable),
+        }
+    }
+
+    /// Fails if the chunk store recorded a keyed hasher this system does not
+    /// hash with, before chunk ids of both end up in it. Only asks the store
+    /// before the first write.
+    pub(super) fn check_hasher(&self) -> Result<()> {
+        if !self.hasher_checked.load(Ordering::Acquire) {
+            if self.chunk_store.hasher_fingerprint()?.is_some() {
+                return Err(Error::HasherMismatch);
+            }
+            self.hasher_checked.store(true, Ordering::Release);
+        }
+        Ok(())
+    }
 
+    /// Encrypts and stores a chunk, recording it in `upload`.
+    fn store_chunk(&mut self, bytes: Vec<u8>, upload: &mut Upload) -> Result<()> {
+        let size = bytes.len();
+        let (hash, bytes, key) = self.seal_chunk(bytes)?;
         self.chunk_store.upsert(hash, bytes)?;
+        upload.record(hash, size, key);
+        Ok(())
+    }
 
-        Ok(hash)
+    /// Like [`store_chunk`](Self::store_chunk), through a shared reference.
+    fn store_chunk_shared(&self, bytes: Vec<u8>, upload: &mut Upload) -> Result<()> {
+        let size = bytes.len();
+        let (hash, bytes, key) = self.seal_chunk(bytes)?;
+        self.chunk_store.upsert_shared(hash, bytes)?;
+        upload.record(hash, size, key);
+        Ok(())
+    }
+
+    /// Encrypts a chunk if the system encrypts, and returns its id, the bytes
+    /// to store and its key.
+    fn seal_chunk(&self, bytes: Vec<u8>) -> Result<(u64, Vec<u8>, Option<ChunkKey>)> {
+        self.check_hasher()?;
+        let (key, bytes) = match &self.encryption {
+            Some(encryption) => {
+                let (key, ciphertext) = encryption.encrypt_chunk(&bytes)?;
+                (Some(key), ciphertext)
+            }
+            None => (None, bytes),
+        };
+        Ok((hash_chunk(&self.hasher, &bytes), bytes, key))
     }
 
-    async fn write_meta(&mut self, key: &K, hashes: Vec<u64>, size: usize) -> Result<()> {
-        self.meta_store.upsert(key, Meta { hashes, size }).await?;
+    async fn write_meta(&mut self, key: &K, upload: Upload) -> Result<()> {
+        let sealed_keys = match &self.encryption {
+            Some(encryption) => Some(encryption.seal_keys(&upload.hashes, &upload.keys)?),
+            None => None,
+        };
+        let meta = Meta {
+            hashes: upload.hashes,
+            size: upload.size,
+            sealed_keys,
+            chunk_sizes: Some(upload.chunk_sizes),
+        };
+        self.meta_store.upsert(key, meta).await?;
         Ok(())
     }
 }
+
+pub(crate) fn hash_chunk<H: BuildHasher>(hasher: &H, bytes: &[u8]) -> u64 {
+    let mut hasher = hasher.build_hasher();
+    hasher.write(bytes);
+    hasher.finish()
+}
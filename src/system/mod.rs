@@ -1,7 +1,26 @@
+mod convergent;
+mod diff;
 mod error;
+mod fsck;
+mod gc;
 mod r#impl;
 mod reader;
+mod repair;
+mod similarity;
+mod stats;
+mod sync;
+mod upload;
 
+pub use convergent::ConvergentEncryption;
+pub use diff::{Diff, DiffSide};
 pub use error::{Error, Result};
+pub use fsck::{FileReport, FileStatus, FsckReport};
+pub use gc::{GcOptions, GcReport};
 pub use r#impl::System;
+pub(crate) use r#impl::{hash_chunk, AVG_SIZE, MAX_SIZE, MIN_SIZE};
 pub use reader::Reader;
+pub use repair::{ReadRepair, RepairReport};
+pub use similarity::{SimilarFile, SimilarityIndex};
+pub use stats::{Bucket, FileStats, Histogram, Stats};
+pub use sync::{SyncReport, SyncScope};
+pub use upload::Upload;
@@ -7,10 +7,19 @@ use bytes::Buf;
 
 use crate::chunks::ChunkStore;
 
+use super::{
+    convergent::{decrypt_chunk, ChunkKey},
+    error::Error,
+};
+
+type Verifier<'a> = Box<dyn Fn(&[u8]) -> u64 + 'a>;
+
 pub struct Reader<'a, C: ChunkStore> {
     buf: Cursor<Vec<u8>>,
     chunk_store: &'a C,
     hashes: VecDeque<u64>,
+    keys: Option<VecDeque<ChunkKey>>,
+    verifier: Option<Verifier<'a>>,
 }
 
 impl<'a, C: ChunkStore> Reader<'a, C> {
@@ -19,8 +28,22 @@ impl<'a, C: ChunkStore> Reader<'a, C> {
             buf: Cursor::new(vec![]),
             chunk_store,
             hashes,
+            keys: None,
+            verifier: None,
         }
     }
+
+    /// Rehashes each chunk and fails the read if it does not match its id.
+    pub(crate) fn with_verifier(mut self, verifier: Verifier<'a>) -> Self {
+        self.verifier = Some(verifier);
+        self
+    }
+
+    /// Decrypts each chunk with the matching convergent encryption key.
+    pub(crate) fn with_keys(mut self, keys: VecDeque<ChunkKey>) -> Self {
+        self.keys = Some(keys);
+        self
+    }
 }
 
 impl<'a, C: ChunkStore> Read for Reader<'a, C> {
@@ -29,10 +52,19 @@ impl<'a, C: ChunkStore> Read for Reader<'a, C> {
             let Some(hash) = self.hashes.pop_front() else {
                 return Ok(0);
             };
-            let chunk = self
+            let mut chunk = self
                 .chunk_store
                 .get(&hash)
-                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, format!("{e}")))?;
+                .map_err(|e| std::io::Error::other(format!("{e}")))?;
+            if let Some(verifier) = &self.verifier {
+                if verifier(&chunk) != hash {
+                    return Err(std::io::Error::other(Error::Corrupt { hash }));
+                }
+            }
+            if let Some(key) = self.keys.as_mut().and_then(|keys| keys.pop_front()) {
+                chunk = decrypt_chunk(&key, &chunk)
+                    .map_err(|e| std::io::Error::other(format!("{e}")))?;
+            }
             self.buf = Cursor::new(chunk);
         }
 
@@ -0,0 +1,203 @@
+use std::{
+    collections::BTreeSet,
+    fmt::Debug,
+    hash::BuildHasher,
+    sync::{PoisonError, RwLock},
+};
+
+use serde::Serialize;
+
+use crate::{
+    chunks::{contains_missing, ChunkStore, Error, Result},
+    meta::MetaStore,
+};
+
+use super::{
+    fsck::FsckReport,
+    r#impl::{hash_chunk, System},
+};
+
+/// Outcome of [`System::repair`](super::System::repair).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct RepairReport {
+    /// Chunks copied from the replica back into the chunk store.
+    pub repaired: Vec<u64>,
+    /// Chunks the replica is missing as well, or only has corrupt copies of.
+    pub unrecoverable: Vec<u64>,
+}
+
+impl RepairReport {
+    pub fn is_complete(&self) -> bool {
+        self.unrecoverable.is_empty()
+    }
+}
+
+impl<K, C, M, H> System<C, M, H>
+where
+    C: ChunkStore,
+    M: MetaStore<Key = K>,
+    H: BuildHasher,
+{
+    /// Rewrites the missing and corrupt chunks of a [`fsck`](Self::fsck)
+    /// report with copies fetched from `replica`.
+    ///
+    /// Copies are only used if they match their id. Files whose chunks add up
+    /// to the wrong size cannot be repaired from chunks and are left as is.
+    pub fn repair<R: ChunkStore>(
+        &mut self,
+        report: &FsckReport<K>,
+        replica: &R,
+    ) -> super::Result<RepairReport> {
+        let damaged: BTreeSet<u64> = report
+            .damaged()
+            .flat_map(|file| file.missing_chunks.iter().chain(&file.corrupt_chunks))
+            .copied()
+            .collect();
+
+        self.check_hasher()?;
+        let mut result = RepairReport::default();
+        for hash in damaged {
+            let chunk = match replica.get(&hash) {
+                Ok(chunk) if hash_chunk(&self.hasher, &chunk) == hash => chunk,
+                Ok(_) | Err(Error::NotFound | Error::Integrity) => {
+                    result.unrecoverable.push(hash);
+                    continue;
+                }
+                Err(err) => return Err(err.into()),
+            };
+            self.chunk_store.upsert(hash, chunk)?;
+            result.repaired.push(hash);
+        }
+        Ok(result)
+    }
+}
+
+/// Chunk store used by a [`System`] with read repair enabled, see
+/// [`System::with_read_repair`].
+///
+/// Every chunk read from the primary store is rehashed. Chunks that are
+/// missing, fail the primary's own integrity check or do not match their id
+/// are fetched from the replica instead, verified, and rewritten into the
+/// primary before being returned. Writes only go to the primary.
+///
+/// [`System`]: super::System
+/// [`System::with_read_repair`]: super::System::with_read_repair
+pub struct ReadRepair<C: ChunkStore, R: ChunkStore, H: BuildHasher> {
+    primary: RwLock<C>,
+    replica: R,
+    hasher: H,
+}
+
+impl<C: ChunkStore, R: ChunkStore, H: BuildHasher> ReadRepair<C, R, H> {
+    pub(crate) fn new(primary: C, replica: R, hasher: H) -> Self {
+        Self {
+            primary: RwLock::new(primary),
+            replica,
+            hasher,
+        }
+    }
+
+    pub fn replica(&self) -> &R {
+        &self.replica
+    }
+
+    pub fn into_inner(self) -> (C, R) {
+        let primary = self
+            .primary
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+        (primary, self.replica)
+    }
+
+    fn repair(&self, hash: &u64, err: Error) -> Result<Vec<u8>> {
+        let chunk = match self.replica.get(hash) {
+            Ok(chunk) if hash_chunk(&self.hasher, &chunk) == *hash => chunk,
+            Ok(_) | Err(Error::NotFound | Error::Integrity) => return Err(err),
+            Err(err) => return Err(err),
+        };
+        self.primary
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .upsert(*hash, chunk.clone())?;
+        Ok(chunk)
+    }
+}
+
+impl<C: ChunkStore, R: ChunkStore, H: BuildHasher> Debug for ReadRepair<C, R, H> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReadRepair")
+            .field("primary", &self.primary)
+            .field("replica", &self.replica)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C: ChunkStore, R: ChunkStore, H: BuildHasher> ChunkStore for ReadRepair<C, R, H> {
+    fn get(&self, hash: &u64) -> Result<Vec<u8>> {
+        let chunk = self
+            .primary
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(hash);
+        match chunk {
+            Ok(chunk) if hash_chunk(&self.hasher, &chunk) == *hash => Ok(chunk),
+            Ok(_) => self.repair(hash, Error::Integrity),
+            Err(err @ (Error::NotFound | Error::Integrity)) => self.repair(hash, err),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn upsert(&mut self, hash: u64, chunk: Vec<u8>) -> Result<()> {
+        self.primary
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .upsert(hash, chunk)
+    }
+
+    fn remove(&mut self, hash: &u64) -> Result<()> {
+        self.primary
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(hash)
+    }
+
+    /// Asks the replica about the chunks the primary lacks, without repairing
+    /// them. Corrupt copies count as missing, as they do for other stores.
+    fn contains_many(&self, hashes: &[u64]) -> Result<Vec<bool>> {
+        let mut found = self
+            .primary
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .contains_many(hashes)?;
+        contains_missing(&self.replica, hashes, &mut found)?;
+        Ok(found)
+    }
+
+    fn hashes(&self) -> Result<Vec<u64>> {
+        self.primary
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .hashes()
+    }
+
+    fn hasher_fingerprint(&self) -> Result<Option<[u8; 32]>> {
+        self.primary
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .hasher_fingerprint()
+    }
+
+    fn set_hasher_fingerprint(&mut self, fingerprint: [u8; 32]) -> Result<()> {
+        self.primary
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .set_hasher_fingerprint(fingerprint)
+    }
+
+    fn init_hasher_fingerprint(&mut self, fingerprint: [u8; 32]) -> Result<Option<[u8; 32]>> {
+        self.primary
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .init_hasher_fingerprint(fingerprint)
+    }
+}
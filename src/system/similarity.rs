@@ -0,0 +1,280 @@
+use std::{
+    collections::{HashMap, HashSet},
+    hash::{BuildHasher, Hash},
+};
+
+use serde::Serialize;
+
+use crate::{
+    chunks::ChunkStore,
+    meta::{self, Change, MetaStore},
+};
+
+use super::{error::Result, r#impl::System};
+
+/// Values in a file's MinHash sketch.
+const SKETCH_SIZE: usize = 128;
+/// Sketch values hashed together into one locality-sensitive band.
+const BAND_ROWS: usize = 4;
+const BANDS: usize = SKETCH_SIZE / BAND_ROWS;
+/// Lowest similarity the bands find nearly all files of. At this
+/// similarity, a file shares at least one band with probability 0.99. Queries
+/// for less similar files compare against every sketch instead.
+const BANDED_MIN_SIMILARITY: f64 = 0.6;
+/// How far below the asked similarity the index estimates of candidates may
+/// be, about four standard deviations of a MinHash estimate.
+const SIMILARITY_TOLERANCE: f64 = 0.15;
+
+/// Index of stored files by the chunks they contain, answering which files
+/// share at least a given fraction of their chunks with another.
+///
+/// Files are compared by the Jaccard similarity of their chunk sets: the
+/// number of distinct chunks both contain divided by the number either
+/// contains. The index keeps a MinHash sketch of every file, which estimates
+/// the similarity of two files to within about 0.05, and groups files by
+/// bands of their sketches so that queries for similar files only need to
+/// look at those sharing a band.
+///
+/// Build it with [`System::similarity_index`](super::System::similarity_index)
+/// and keep it up to date by [applying](Self::apply) the changes of a
+/// [subscription](super::System::subscribe). [`System::similar`] confirms its
+/// estimates with the exact similarity.
+///
+/// [`System::similar`]: super::System::similar
+#[derive(Clone, Debug)]
+pub struct SimilarityIndex<K> {
+    sketches: HashMap<K, Sketch>,
+    /// Keys of the files by the hash of each band of their sketch.
+    bands: Vec<HashMap<u64, HashSet<K>>>,
+}
+
+/// A file found by a similarity query.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SimilarFile<K> {
+    pub key: K,
+    /// Jaccard similarity of the chunk sets, estimated or exact depending on
+    /// the query.
+    pub similarity: f64,
+}
+
+impl<K: Clone + Eq + Hash> SimilarityIndex<K> {
+    pub fn new() -> Self {
+        Self {
+            sketches: HashMap::new(),
+            bands: vec![HashMap::new(); BANDS],
+        }
+    }
+
+    /// Number of files in the index.
+    pub fn len(&self) -> usize {
+        self.sketches.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sketches.is_empty()
+    }
+
+    /// Adds the file with the chunk `hashes` under `key`, replacing what was
+    /// indexed under it. Empty files are similar to no file and are left out.
+    pub fn insert(&mut self, key: K, hashes: &[u64]) {
+        self.remove(&key);
+        let Some(sketch) = Sketch::of(hashes) else {
+            return;
+        };
+        for (band, keys) in sketch.bands().zip(&mut self.bands) {
+            keys.entry(band).or_default().insert(key.clone());
+        }
+        self.sketches.insert(key, sketch);
+    }
+
+    pub fn remove(&mut self, key: &K) {
+        let Some(sketch) = self.sketches.remove(key) else {
+            return;
+        };
+        for (band, keys) in sketch.bands().zip(&mut self.bands) {
+            if let Some(bucket) = keys.get_mut(&band) {
+                bucket.remove(key);
+                if bucket.is_empty() {
+                    keys.remove(&band);
+                }
+            }
+        }
+    }
+
+    /// Updates the index with a change of the meta store.
+    pub fn apply(&mut self, change: &Change<K>) {
+        match &change.new {
+            Some(meta) => self.insert(change.key.clone(), &meta.hashes),
+            None => self.remove(&change.key),
+        }
+    }
+
+    /// Indexed files whose estimated similarity to a file with the chunk
+    /// `hashes` is at least `min_similarity`, most similar first.
+    pub fn query(&self, hashes: &[u64], min_similarity: f64) -> Vec<SimilarFile<K>> {
+        let Some(sketch) = Sketch::of(hashes) else {
+            return vec![];
+        };
+
+        let estimate = |key: &K| SimilarFile {
+            key: key.clone(),
+            similarity: sketch.similarity(&self.sketches[key]),
+        };
+        let mut found: Vec<_> = if min_similarity >= BANDED_MIN_SIMILARITY {
+            let candidates: HashSet<&K> = sketch
+                .bands()
+                .zip(&self.bands)
+                .filter_map(|(band, keys)| keys.get(&band))
+                .flatten()
+                .collect();
+            candidates.into_iter().map(estimate).collect()
+        } else {
+            self.sketches.keys().map(estimate).collect()
+        };
+        found.retain(|file| file.similarity >= min_similarity);
+        found.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+        found
+    }
+}
+
+impl<K: Clone + Eq + Hash> Default for SimilarityIndex<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, C, M, H> System<C, M, H>
+where
+    C: ChunkStore,
+    M: MetaStore<Key = K>,
+    H: BuildHasher,
+{
+    /// Indexes every stored file by its chunks, for finding similar files
+    /// with [`similar`](Self::similar).
+    pub async fn similarity_index(&self) -> Result<SimilarityIndex<K>>
+    where
+        K: Clone + Eq + Hash,
+    {
+        let mut index = SimilarityIndex::new();
+        for (key, meta) in self.meta_store.list().await? {
+            index.insert(key, &meta.hashes);
+        }
+        Ok(index)
+    }
+
+    /// Files sharing at least `min_similarity` of their distinct chunks with
+    /// the file at `key`, by Jaccard similarity and most similar first.
+    ///
+    /// The `index` narrows the search down to likely candidates, whose exact
+    /// similarity is then computed from their metas. Files no longer stored
+    /// are skipped, and files not yet indexed are not found.
+    pub async fn similar(
+        &self,
+        index: &SimilarityIndex<K>,
+        key: &K,
+        min_similarity: f64,
+    ) -> Result<Vec<SimilarFile<K>>>
+    where
+        K: Clone + Eq + Hash,
+    {
+        let meta = self.meta_store.get(key).await?;
+        let hashes: HashSet<u64> = meta.hashes.iter().copied().collect();
+        let estimated = (min_similarity - SIMILARITY_TOLERANCE).max(0.0);
+
+        let mut found = vec![];
+        for candidate in index.query(&meta.hashes, estimated) {
+            if candidate.key == *key {
+                continue;
+            }
+            let other = match self.meta_store.get(&candidate.key).await {
+                Ok(other) => other,
+                Err(meta::Error::NotFound) => continue,
+                Err(err) => return Err(err.into()),
+            };
+            let similarity = jaccard(&hashes, &other.hashes);
+            if similarity >= min_similarity {
+                found.push(SimilarFile {
+                    key: candidate.key,
+                    similarity,
+                });
+            }
+        }
+        found.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+        Ok(found)
+    }
+}
+
+/// MinHash sketch of a chunk set: for each of [`SKETCH_SIZE`] permutations of
+/// the chunk hashes, the smallest permuted hash. Two sketches agree in each
+/// value with probability equal to the Jaccard similarity of their sets.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Sketch([u64; SKETCH_SIZE]);
+
+impl Sketch {
+    /// Sketch of a non-empty chunk set.
+    fn of(hashes: &[u64]) -> Option<Self> {
+        if hashes.is_empty() {
+            return None;
+        }
+        let mut mins = [u64::MAX; SKETCH_SIZE];
+        for hash in hashes {
+            for (seed, min) in mins.iter_mut().enumerate() {
+                *min = (*min).min(mix(*hash, seed as u64));
+            }
+        }
+        Some(Self(mins))
+    }
+
+    fn similarity(&self, other: &Self) -> f64 {
+        let equal = self.0.iter().zip(&other.0).filter(|(a, b)| a == b).count();
+        equal as f64 / SKETCH_SIZE as f64
+    }
+
+    fn bands(&self) -> impl Iterator<Item = u64> + '_ {
+        self.0.chunks(BAND_ROWS).map(|rows| {
+            rows.iter()
+                .fold(0, |band, value| mix(band ^ value, BANDS as u64))
+        })
+    }
+}
+
+/// Exact Jaccard similarity of the chunk sets of two files.
+pub(super) fn jaccard(a: &HashSet<u64>, b: &[u64]) -> f64 {
+    let b: HashSet<u64> = b.iter().copied().collect();
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 1.0;
+    }
+    a.intersection(&b).count() as f64 / union as f64
+}
+
+/// Scrambles `hash` into one of a family of permutations selected by `seed`,
+/// after SplitMix64.
+fn mix(hash: u64, seed: u64) -> u64 {
+    let mut z = hash ^ seed.wrapping_add(1).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_estimates_jaccard_similarity() {
+        // 1000 shared hashes and 500 of each set's own, a similarity of 0.5.
+        let a: Vec<u64> = (0..1500).collect();
+        let b: Vec<u64> = (500..2000).collect();
+        let exact = jaccard(&a.iter().copied().collect(), &b);
+        assert_eq!(exact, 0.5);
+
+        let estimate = Sketch::of(&a).unwrap().similarity(&Sketch::of(&b).unwrap());
+        assert!((estimate - exact).abs() < 0.15, "{estimate}");
+        assert_eq!(
+            Sketch::of(&a),
+            Sketch::of(&a.iter().rev().copied().collect::<Vec<_>>())
+        );
+        assert_eq!(Sketch::of(&[]), None);
+    }
+}
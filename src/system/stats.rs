@@ -0,0 +1,243 @@
+use std::{collections::HashMap, hash::BuildHasher};
+
+use serde::Serialize;
+
+use crate::{
+    chunks::{self, ChunkStore},
+    meta::MetaStore,
+};
+
+use super::{error::Result, r#impl::System};
+
+/// Outcome of [`System::stats`](super::System::stats).
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Stats<K> {
+    pub files: usize,
+    /// Combined size of every file.
+    pub logical_bytes: usize,
+    /// Distinct chunks referenced by the files.
+    pub unique_chunks: usize,
+    /// Bytes the referenced chunks take up in the chunk store, each counted
+    /// once, as reported by
+    /// [`ChunkStore::stored_size`](crate::chunks::ChunkStore::stored_size).
+    /// Chunks missing from the chunk store are left out.
+    pub physical_bytes: usize,
+    /// Logical bytes per physical byte, 1 for empty stores.
+    pub dedup_ratio: f64,
+    /// Length of the referenced chunks, measured like `physical_bytes`.
+    pub chunk_sizes: Histogram,
+    /// How often the referenced chunks are referenced, counting repeats
+    /// within a file.
+    pub ref_counts: Histogram,
+    /// Breakdown of every file, in the order the meta store lists them.
+    pub per_file: Vec<FileStats<K>>,
+}
+
+impl<K> Stats<K> {
+    /// Bytes that would be stored without deduplication but are not.
+    pub fn bytes_saved(&self) -> usize {
+        self.logical_bytes.saturating_sub(self.physical_bytes)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct FileStats<K> {
+    pub key: K,
+    pub size: usize,
+    pub chunks: usize,
+    /// Bytes of the file in chunks that other files reference as well.
+    pub shared_bytes: usize,
+}
+
+impl<K> FileStats<K> {
+    /// Fraction of the file shared with other files, 0 for empty files.
+    pub fn shared_ratio(&self) -> f64 {
+        if self.size == 0 {
+            return 0.0;
+        }
+        self.shared_bytes as f64 / self.size as f64
+    }
+}
+
+impl<K, C, M, H> System<C, M, H>
+where
+    C: ChunkStore,
+    M: MetaStore<Key = K>,
+    H: BuildHasher,
+{
+    /// Reports how much space deduplication saves, overall and per file.
+    ///
+    /// Physical bytes are asked of the chunk store, see
+    /// [`ChunkStore::stored_size`]. Plaintext lengths are taken from the
+    /// metas, so only shared chunks of files written before sizes were
+    /// recorded are read.
+    pub async fn stats(&self) -> Result<Stats<K>> {
+        let files = self.meta_store.list().await?;
+
+        let mut chunks: HashMap<u64, ChunkUsage> = HashMap::new();
+        for (idx, (_, meta)) in files.iter().enumerate() {
+            for (pos, hash) in meta.hashes.iter().enumerate() {
+                let usage = chunks.entry(*hash).or_insert(ChunkUsage {
+                    size: None,
+                    stored: None,
+                    refs: 0,
+                    files: 0,
+                    last_file: idx,
+                });
+                usage.refs += 1;
+                if usage.files == 0 || usage.last_file != idx {
+                    usage.files += 1;
+                    usage.last_file = idx;
+                }
+                // Metas are not trusted to list a size for every chunk.
+                if let Some(size) = meta.chunk_sizes.as_ref().and_then(|sizes| sizes.get(pos)) {
+                    usage.size = Some(*size as usize);
+                }
+            }
+        }
+        for (hash, usage) in &mut chunks {
+            usage.stored = match self.chunk_store.stored_size(hash) {
+                Ok(stored) => Some(stored),
+                Err(chunks::Error::NotFound | chunks::Error::Integrity) => None,
+                Err(err) => return Err(err.into()),
+            };
+        }
+
+        let logical_bytes = files.iter().map(|(_, meta)| meta.size).sum();
+        let physical_bytes = chunks.values().filter_map(|usage| usage.stored).sum();
+        let dedup_ratio = if physical_bytes == 0 {
+            1.0
+        } else {
+            logical_bytes as f64 / physical_bytes as f64
+        };
+        let mut per_file = Vec::with_capacity(files.len());
+        for (key, meta) in files {
+            let mut shared_bytes = 0;
+            for (pos, hash) in meta.hashes.iter().enumerate() {
+                let usage = &chunks[hash];
+                if usage.files < 2 {
+                    continue;
+                }
+                let size = meta.chunk_sizes.as_ref().and_then(|sizes| sizes.get(pos));
+                shared_bytes += match (size, usage.size) {
+                    (Some(size), _) => *size as usize,
+                    (None, Some(size)) => size,
+                    (None, None) => match self.chunk_store.get(hash) {
+                        Ok(chunk) => chunk.len(),
+                        Err(chunks::Error::NotFound | chunks::Error::Integrity) => 0,
+                        Err(err) => return Err(err.into()),
+                    },
+                };
+            }
+            per_file.push(FileStats {
+                key,
+                size: meta.size,
+                chunks: meta.hashes.len(),
+                shared_bytes,
+            });
+        }
+
+        Ok(Stats {
+            files: per_file.len(),
+            logical_bytes,
+            unique_chunks: chunks.len(),
+            physical_bytes,
+            dedup_ratio,
+            chunk_sizes: chunks
+                .values()
+                .filter_map(|usage| usage.stored)
+                .map(|stored| stored as u64)
+                .collect(),
+            ref_counts: chunks.values().map(|usage| usage.refs).collect(),
+            per_file,
+        })
+    }
+}
+
+/// How a chunk is used, gathered by [`System::stats`].
+struct ChunkUsage {
+    /// Plaintext length, if a meta records it.
+    size: Option<usize>,
+    /// Bytes taken up in the chunk store, unless the chunk is missing.
+    stored: Option<usize>,
+    refs: u64,
+    /// Distinct files referencing the chunk.
+    files: usize,
+    /// Index of the last file counted in `files`.
+    last_file: usize,
+}
+
+/// Counts of values in power of two buckets.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Histogram {
+    /// Buckets holding at least one value, in ascending order.
+    pub buckets: Vec<Bucket>,
+}
+
+/// Values from `min` to `max`, both inclusive. Bounds are 0 or powers of two
+/// and one less than the next power of two.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct Bucket {
+    pub min: u64,
+    pub max: u64,
+    pub count: usize,
+}
+
+impl Histogram {
+    pub fn add(&mut self, value: u64) {
+        let (min, max) = match value {
+            0 => (0, 0),
+            value => {
+                let min = 1 << value.ilog2();
+                (min, min - 1 + min)
+            }
+        };
+        match self.buckets.binary_search_by_key(&min, |bucket| bucket.min) {
+            Ok(idx) => self.buckets[idx].count += 1,
+            Err(idx) => self.buckets.insert(idx, Bucket { min, max, count: 1 }),
+        }
+    }
+
+    /// Number of values added.
+    pub fn count(&self) -> usize {
+        self.buckets.iter().map(|bucket| bucket.count).sum()
+    }
+}
+
+impl FromIterator<u64> for Histogram {
+    fn from_iter<T: IntoIterator<Item = u64>>(values: T) -> Self {
+        let mut histogram = Self::default();
+        for value in values {
+            histogram.add(value);
+        }
+        histogram
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_buckets_by_powers_of_two() {
+        let histogram: Histogram = [0, 1, 2, 3, 4, 7, 8, 1000, u64::MAX].into_iter().collect();
+        let buckets: Vec<_> = histogram
+            .buckets
+            .iter()
+            .map(|bucket| (bucket.min, bucket.max, bucket.count))
+            .collect();
+        assert_eq!(
+            buckets,
+            [
+                (0, 0, 1),
+                (1, 1, 1),
+                (2, 3, 2),
+                (4, 7, 2),
+                (8, 15, 1),
+                (512, 1023, 1),
+                (1 << 63, u64::MAX, 1),
+            ]
+        );
+        assert_eq!(histogram.count(), 9);
+    }
+}
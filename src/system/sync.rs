@@ -0,0 +1,183 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt::Display,
+    hash::BuildHasher,
+};
+
+use serde::Serialize;
+
+use crate::{
+    chunks::ChunkStore,
+    meta::{self, Meta, MetaStore},
+};
+
+use super::{
+    error::{Error, Result},
+    r#impl::{hash_chunk, System},
+};
+
+/// Chunk hashes checked against the destination of a sync at once.
+const SYNC_BATCH_SIZE: usize = 1024;
+
+/// Chunk hashed by both systems of a sync to check that they agree.
+const HASHER_PROBE: &[u8] = b"cdcfs hasher probe";
+
+/// Files selected by [`System::sync_to`](super::System::sync_to).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SyncScope<K> {
+    Keys(Vec<K>),
+    /// Every file whose key, formatted with `Display`, starts with the prefix.
+    Prefix(String),
+}
+
+impl<K> From<Vec<K>> for SyncScope<K> {
+    fn from(keys: Vec<K>) -> Self {
+        Self::Keys(keys)
+    }
+}
+
+impl<K> From<&str> for SyncScope<K> {
+    fn from(prefix: &str) -> Self {
+        Self::Prefix(prefix.to_owned())
+    }
+}
+
+impl<K> From<String> for SyncScope<K> {
+    fn from(prefix: String) -> Self {
+        Self::Prefix(prefix)
+    }
+}
+
+/// Outcome of [`System::sync_to`](super::System::sync_to).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct SyncReport {
+    /// Files written to the destination.
+    pub files_synced: usize,
+    /// Files the destination already had with the same meta.
+    pub files_unchanged: usize,
+    pub chunks_sent: usize,
+    pub bytes_sent: usize,
+    /// Bytes of the selected files that were not sent because the destination
+    /// already had their chunks.
+    pub bytes_saved: usize,
+}
+
+impl<K, C, M, H> System<C, M, H>
+where
+    C: ChunkStore,
+    M: MetaStore<Key = K>,
+    H: BuildHasher,
+{
+    /// Copies the selected files to `other`, sending only the chunks it does
+    /// not have yet.
+    ///
+    /// Files are synced in groups: the destination is asked which of the
+    /// group's chunks it lacks in one batch, those are sent, and only then are
+    /// the metas written. An interrupted sync thus never leaves a file without
+    /// its chunks, and running it again skips the files and chunks that made
+    /// it across. Both systems must hash chunks the same way.
+    pub async fn sync_to<C2, M2, H2>(
+        &self,
+        other: &mut System<C2, M2, H2>,
+        scope: impl Into<SyncScope<K>>,
+    ) -> Result<SyncReport>
+    where
+        K: Display,
+        C2: ChunkStore,
+        M2: MetaStore<Key = K>,
+        H2: BuildHasher,
+    {
+        // Unkeyed hashers leave no fingerprint, so their output is compared
+        // as well.
+        if self.chunk_store.hasher_fingerprint()? != other.chunk_store.hasher_fingerprint()?
+            || hash_chunk(&self.hasher, HASHER_PROBE) != hash_chunk(&other.hasher, HASHER_PROBE)
+        {
+            return Err(Error::HasherMismatch);
+        }
+
+        let files = match scope.into() {
+            SyncScope::Keys(keys) => {
+                let mut files = Vec::with_capacity(keys.len());
+                for key in keys {
+                    let meta = self.meta_store.get(&key).await?;
+                    files.push((key, meta));
+                }
+                files
+            }
+            SyncScope::Prefix(prefix) => self
+                .meta_store
+                .list()
+                .await?
+                .into_iter()
+                .filter(|(key, _)| key.to_string().starts_with(&prefix))
+                .collect(),
+        };
+
+        let mut report = SyncReport::default();
+        let mut present = HashSet::new();
+        let mut group = vec![];
+        let mut wanted = BTreeSet::new();
+        for (key, meta) in files {
+            match other.meta_store.get(&key).await {
+                Ok(existing) if existing == meta => {
+                    report.files_unchanged += 1;
+                    report.bytes_saved += meta.size;
+                    continue;
+                }
+                Ok(_) | Err(meta::Error::NotFound) => (),
+                Err(err) => return Err(err.into()),
+            }
+
+            wanted.extend(meta.hashes.iter().filter(|hash| !present.contains(*hash)));
+            group.push((key, meta));
+            if wanted.len() >= SYNC_BATCH_SIZE {
+                self.sync_group(other, &mut group, &mut wanted, &mut present, &mut report)
+                    .await?;
+            }
+        }
+        self.sync_group(other, &mut group, &mut wanted, &mut present, &mut report)
+            .await?;
+
+        Ok(report)
+    }
+
+    /// Sends the `wanted` chunks `other` lacks and writes the metas of `group`.
+    async fn sync_group<C2, M2, H2>(
+        &self,
+        other: &mut System<C2, M2, H2>,
+        group: &mut Vec<(K, Meta)>,
+        wanted: &mut BTreeSet<u64>,
+        present: &mut HashSet<u64>,
+        report: &mut SyncReport,
+    ) -> Result<()>
+    where
+        C2: ChunkStore,
+        M2: MetaStore<Key = K>,
+        H2: BuildHasher,
+    {
+        let wanted: Vec<u64> = std::mem::take(wanted).into_iter().collect();
+        let mut sent = 0;
+        for batch in wanted.chunks(SYNC_BATCH_SIZE) {
+            let found = other.chunk_store.contains_many(batch)?;
+            for (hash, found) in batch.iter().zip(found) {
+                if !found {
+                    let chunk = self.chunk_store.get(hash)?;
+                    sent += chunk.len();
+                    report.chunks_sent += 1;
+                    other.chunk_store.upsert(*hash, chunk)?;
+                }
+                present.insert(*hash);
+            }
+        }
+
+        let mut size = 0;
+        for (key, meta) in group.drain(..) {
+            size += meta.size;
+            other.meta_store.upsert(&key, meta).await?;
+            report.files_synced += 1;
+        }
+        report.bytes_sent += sent;
+        report.bytes_saved += size.saturating_sub(sent);
+        Ok(())
+    }
+}
@@ -0,0 +1,73 @@
+use std::fmt::Debug;
+
+use fastcdc::v2020::FastCDC;
+
+use super::{
+    convergent::ChunkKey,
+    r#impl::{AVG_SIZE, MAX_SIZE, MIN_SIZE},
+};
+
+/// File being written piece by piece, see [`System::upload`].
+///
+/// Bytes are buffered until a chunk boundary can be found, so the file is cut
+/// into the same chunks as if it had been written at once.
+///
+/// [`System::upload`]: super::System::upload
+#[derive(Default)]
+pub struct Upload {
+    pub(super) buffer: Vec<u8>,
+    pub(super) hashes: Vec<u64>,
+    pub(super) chunk_sizes: Vec<u32>,
+    pub(super) keys: Vec<ChunkKey>,
+    pub(super) size: usize,
+}
+
+impl Upload {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bytes received so far, including those not yet cut into chunks.
+    pub fn size(&self) -> usize {
+        self.size + self.buffer.len()
+    }
+
+    /// Takes the next chunk off the buffer if it is complete. A chunk is cut at
+    /// the latest after `MAX_SIZE` bytes, so once that many are buffered the
+    /// next boundary no longer depends on what comes after.
+    pub(super) fn complete_chunk(&mut self) -> Option<Vec<u8>> {
+        if self.buffer.len() < MAX_SIZE as usize {
+            return None;
+        }
+        let length = FastCDC::new(&self.buffer, MIN_SIZE, AVG_SIZE, MAX_SIZE)
+            .next()
+            .map_or(self.buffer.len(), |chunk| chunk.length);
+        Some(self.buffer.drain(..length).collect())
+    }
+
+    /// Cuts the rest of the buffer into chunks, once no more bytes follow.
+    pub(super) fn take_rest(&mut self) -> Vec<Vec<u8>> {
+        let rest = std::mem::take(&mut self.buffer);
+        FastCDC::new(&rest, MIN_SIZE, AVG_SIZE, MAX_SIZE)
+            .map(|chunk| rest[chunk.offset..chunk.offset + chunk.length].to_vec())
+            .collect()
+    }
+
+    /// Records a stored chunk of `size` plaintext bytes.
+    pub(super) fn record(&mut self, hash: u64, size: usize, key: Option<ChunkKey>) {
+        self.hashes.push(hash);
+        self.chunk_sizes.push(size as u32);
+        self.keys.extend(key);
+        self.size += size;
+    }
+}
+
+impl Debug for Upload {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Upload")
+            .field("buffered", &self.buffer.len())
+            .field("hashes", &self.hashes)
+            .field("size", &self.size)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,130 @@
+use std::{fs, io::Cursor};
+
+use cdcfs::{
+    analyze::{Analyzer, ChunkerConfig, Error},
+    chunks::ChunkStore,
+    BuildHighwayHasher, BuildWyHasher, MemoryChunkStore, MemoryMetaStore, System,
+};
+
+const SAMPLES: [&str; 3] = [
+    "tests/fixtures/file-example_PDF_1MB.pdf",
+    "tests/fixtures/file-sample_1MB.docx",
+    "tests/fixtures/file_example_JPG_2500kB.jpg",
+];
+
+#[tokio::test]
+async fn it_counts_like_the_system_stores() {
+    let files: Vec<Vec<u8>> = SAMPLES.iter().map(|path| fs::read(path).unwrap()).collect();
+    // Writes the PDF twice, and its first half once more.
+    let mut corpus = files.clone();
+    corpus.push(files[0].clone());
+    corpus.push(files[0][..files[0].len() / 2].to_vec());
+
+    let analysis = Analyzer::new()
+        .with_hasher("wyhash", BuildWyHasher::default())
+        .analyze(corpus.iter().map(Cursor::new))
+        .unwrap();
+
+    let mut fs = System::new(
+        MemoryChunkStore::new(),
+        MemoryMetaStore::new(),
+        BuildWyHasher::default(),
+    );
+    for (key, file) in corpus.iter().enumerate() {
+        fs.write(&key, file).await.unwrap();
+    }
+    let stats = fs.stats().await.unwrap();
+
+    assert_eq!(analysis.files, corpus.len());
+    assert_eq!(analysis.logical_bytes, stats.logical_bytes as u64);
+    assert_eq!(analysis.outcomes.len(), 1);
+    let outcome = &analysis.outcomes[0];
+    assert_eq!(outcome.config, ChunkerConfig::default());
+    assert_eq!(outcome.hasher, "wyhash");
+    assert_eq!(
+        outcome.chunks,
+        stats.per_file.iter().map(|file| file.chunks).sum::<usize>()
+    );
+    assert_eq!(
+        outcome.unique_chunks,
+        fs.chunk_store().hashes().unwrap().len()
+    );
+    assert_eq!(outcome.physical_bytes, stats.physical_bytes as u64);
+    assert_eq!(outcome.metadata_bytes, outcome.chunks as u64 * 12);
+    assert_eq!(outcome.dedup_ratio(), stats.dedup_ratio);
+    assert!(outcome.throughput() > 0.0);
+}
+
+#[test]
+fn it_compares_configs_and_hashers() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir(dir.path().join("nested")).unwrap();
+    for (idx, sample) in SAMPLES.iter().enumerate() {
+        fs::copy(sample, dir.path().join(format!("{idx}"))).unwrap();
+        fs::copy(sample, dir.path().join("nested").join(format!("{idx}"))).unwrap();
+    }
+
+    let analysis = Analyzer::new()
+        .with_config(ChunkerConfig::with_avg_size(4096))
+        .with_config(ChunkerConfig::with_avg_size(65536))
+        .with_hasher("wyhash", BuildWyHasher::default())
+        .with_hasher("highway", BuildHighwayHasher::default())
+        .analyze_dir(dir.path())
+        .unwrap();
+
+    assert_eq!(analysis.files, 6);
+    let size: u64 = SAMPLES
+        .iter()
+        .map(|path| fs::metadata(path).unwrap().len())
+        .sum();
+    assert_eq!(analysis.logical_bytes, 2 * size);
+    let rows: Vec<_> = analysis
+        .outcomes
+        .iter()
+        .map(|outcome| (outcome.config.avg_size, outcome.hasher.as_str()))
+        .collect();
+    assert_eq!(
+        rows,
+        [
+            (4096, "wyhash"),
+            (4096, "highway"),
+            (65536, "wyhash"),
+            (65536, "highway")
+        ]
+    );
+
+    let (small, large) = (&analysis.outcomes[0], &analysis.outcomes[2]);
+    assert!(small.chunks > large.chunks);
+    assert!(small.metadata_bytes > large.metadata_bytes);
+    for outcome in &analysis.outcomes {
+        // Every file is there twice.
+        assert!(outcome.dedup_ratio() >= 2.0);
+        assert!(outcome.physical_bytes * 2 <= analysis.logical_bytes);
+    }
+    // Hashers only differ in speed.
+    assert_eq!(
+        analysis.outcomes[0].unique_chunks,
+        analysis.outcomes[1].unique_chunks
+    );
+
+    let table = analysis.to_string();
+    assert!(table.starts_with("6 files, "), "{table}");
+    assert!(table.contains("1024/4096/16384"), "{table}");
+    assert_eq!(table.lines().count(), 6);
+}
+
+#[test]
+fn it_rejects_invalid_configs() {
+    let result = Analyzer::new()
+        .with_config(ChunkerConfig::new(4096, 2048, 8192))
+        .analyze([Cursor::new(b"data")]);
+    assert!(matches!(
+        result,
+        Err(Error::InvalidConfig(ChunkerConfig { min_size: 4096, .. }))
+    ));
+
+    let result = Analyzer::new()
+        .with_config(ChunkerConfig::with_avg_size(16))
+        .analyze([Cursor::new(b"data")]);
+    assert!(matches!(result, Err(Error::InvalidConfig(_))));
+}
@@ -0,0 +1,85 @@
+use cdcfs::{
+    chunks::{CacheStats, CachedChunkStore, ChunkStore, Error},
+    BuildWyHasher, MemoryChunkStore, MemoryMetaStore, System,
+};
+
+use super::chunk;
+
+#[test]
+fn it_counts_hits_and_misses() {
+    let mut store = CachedChunkStore::new(MemoryChunkStore::new(), 1_000);
+    store.upsert(1, chunk(1)).unwrap();
+
+    assert_eq!(store.get(&1).unwrap(), chunk(1));
+    assert_eq!(store.get(&1).unwrap(), chunk(1));
+    assert!(matches!(store.get(&2), Err(Error::NotFound)));
+
+    assert_eq!(
+        store.stats(),
+        CacheStats {
+            hits: 1,
+            misses: 2,
+            chunks: 1,
+            bytes: 100,
+        }
+    );
+}
+
+#[test]
+fn it_evicts_least_recently_used_chunks() {
+    let mut store = CachedChunkStore::new(MemoryChunkStore::new(), 250);
+    for i in 1..=3 {
+        store.upsert(i, chunk(i as u8)).unwrap();
+    }
+    store.get(&1).unwrap();
+    store.get(&2).unwrap();
+    store.get(&1).unwrap();
+    store.get(&3).unwrap();
+    assert_eq!(store.stats().bytes, 200);
+
+    store.get(&1).unwrap();
+    store.get(&2).unwrap();
+    assert_eq!(store.stats().hits, 2);
+    assert_eq!(store.stats().misses, 4);
+}
+
+#[test]
+fn it_skips_chunks_larger_than_the_budget() {
+    let mut store = CachedChunkStore::new(MemoryChunkStore::new(), 50);
+    store.upsert(1, chunk(1)).unwrap();
+    store.get(&1).unwrap();
+    store.get(&1).unwrap();
+
+    assert_eq!(store.stats().misses, 2);
+    assert_eq!(store.stats().chunks, 0);
+}
+
+#[test]
+fn it_invalidates_removed_chunks() {
+    let mut store = CachedChunkStore::new(MemoryChunkStore::new(), 1_000);
+    store.upsert(1, chunk(1)).unwrap();
+    store.get(&1).unwrap();
+
+    store.remove(&1).unwrap();
+    assert!(matches!(store.get(&1), Err(Error::NotFound)));
+    assert_eq!(store.stats().bytes, 0);
+}
+
+#[tokio::test]
+async fn it_serves_repeated_file_reads() {
+    let mut fs = System::new(
+        CachedChunkStore::new(MemoryChunkStore::new(), 1 << 20),
+        MemoryMetaStore::new(),
+        BuildWyHasher::default(),
+    );
+    let source = b"Hello World!".repeat(10_000);
+    fs.write(&1, &source).await.unwrap();
+
+    assert_eq!(fs.read(&1).await.unwrap(), source);
+    let misses = fs.chunk_store().stats().misses;
+    assert_eq!(fs.read(&1).await.unwrap(), source);
+
+    let stats = fs.chunk_store().stats();
+    assert_eq!(stats.misses, misses);
+    assert_eq!(stats.hits, misses);
+}
@@ -0,0 +1,88 @@
+use cdcfs::{
+    chunks::{ChunkStore, CompressedChunkStore, Compression, Error},
+    MemoryChunkStore,
+};
+
+#[test]
+fn it_can_read_and_write() {
+    for compression in [Compression::Zstd { level: 3 }, Compression::Lz4] {
+        let source = b"Here are some bytes!".repeat(100);
+        let mut store = CompressedChunkStore::new(MemoryChunkStore::new(), compression);
+        store.upsert(10, source.clone()).unwrap();
+
+        assert_eq!(store.get(&10).unwrap(), source);
+        assert!(store.inner().get(&10).unwrap().len() < source.len());
+        assert_eq!(
+            store.stored_size(&10).unwrap(),
+            store.inner().get(&10).unwrap().len()
+        );
+    }
+}
+
+#[test]
+fn it_stores_incompressible_chunks_raw() {
+    let source: Vec<u8> = (0..=255).collect();
+    let mut store = CompressedChunkStore::new(MemoryChunkStore::new(), Compression::default());
+    store.upsert(10, source.clone()).unwrap();
+
+    assert_eq!(store.inner().get(&10).unwrap()[1..], source);
+    assert_eq!(store.get(&10).unwrap(), source);
+}
+
+#[test]
+fn it_can_read_chunks_written_with_other_codecs() {
+    let zstd = b"Compressed with zstd".repeat(50);
+    let mut store = CompressedChunkStore::new(MemoryChunkStore::new(), Compression::default());
+    store.upsert(1, zstd.clone()).unwrap();
+
+    let lz4 = b"Compressed with lz4".repeat(50);
+    let mut store = CompressedChunkStore::new(store.into_inner(), Compression::Lz4);
+    store.upsert(2, lz4.clone()).unwrap();
+
+    assert_eq!(store.get(&1).unwrap(), zstd);
+    assert_eq!(store.get(&2).unwrap(), lz4);
+}
+
+#[test]
+fn it_can_store_empty_chunks() {
+    let mut store = CompressedChunkStore::new(MemoryChunkStore::new(), Compression::Lz4);
+    store.upsert(10, vec![]).unwrap();
+    assert_eq!(store.get(&10).unwrap(), Vec::<u8>::new());
+}
+
+#[test]
+fn it_rejects_unknown_codecs() {
+    let mut inner = MemoryChunkStore::new();
+    inner.upsert(10, vec![42, 1, 2, 3]).unwrap();
+    let store = CompressedChunkStore::new(inner, Compression::default());
+    assert!(matches!(store.get(&10), Err(Error::Integrity)));
+}
+
+#[test]
+fn it_cannot_read_missing_item() {
+    let store = CompressedChunkStore::new(MemoryChunkStore::new(), Compression::default());
+    assert!(matches!(store.get(&60), Err(Error::NotFound)));
+}
+
+#[test]
+fn it_cannot_remove_missing_item() {
+    let mut store = CompressedChunkStore::new(MemoryChunkStore::new(), Compression::default());
+    assert!(matches!(store.remove(&60), Err(Error::NotFound)));
+}
+
+#[test]
+fn it_rejects_oversized_chunks() {
+    let mut inner = MemoryChunkStore::new();
+    // Expands to 32 MiB, more than any chunk can hold.
+    let mut bomb = vec![1];
+    bomb.extend(zstd::bulk::compress(&vec![0; 32 << 20], 3).unwrap());
+    inner.upsert(1, bomb).unwrap();
+    let mut lz4 = vec![2];
+    lz4.extend_from_slice(&u32::MAX.to_le_bytes());
+    lz4.extend_from_slice(&[0; 16]);
+    inner.upsert(2, lz4).unwrap();
+
+    let store = CompressedChunkStore::new(inner, Compression::default());
+    assert!(matches!(store.get(&1), Err(Error::Integrity)));
+    assert!(matches!(store.get(&2), Err(Error::Integrity)));
+}
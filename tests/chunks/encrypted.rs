@@ -0,0 +1,75 @@
+use cdcfs::{
+    chunks::{ChunkStore, CompressedChunkStore, Compression, EncryptedChunkStore, Error},
+    MemoryChunkStore,
+};
+
+const KEY: &[u8; 32] = b"an example very very secret key.";
+
+#[test]
+fn it_can_read_and_write() {
+    let source = b"Here are some bytes!".to_vec();
+    let mut store = EncryptedChunkStore::new(MemoryChunkStore::new(), KEY);
+    store.upsert(10, source.clone()).unwrap();
+
+    assert_eq!(store.get(&10).unwrap(), source);
+
+    let stored = store.inner().get(&10).unwrap();
+    assert!(!stored
+        .windows(source.len())
+        .any(|window| window == source.as_slice()));
+}
+
+#[test]
+fn it_detects_tampering() {
+    let mut store = EncryptedChunkStore::new(MemoryChunkStore::new(), KEY);
+    store.upsert(10, b"Here are some bytes!".to_vec()).unwrap();
+
+    let mut inner = store.into_inner();
+    let mut stored = inner.get(&10).unwrap();
+    *stored.last_mut().unwrap() ^= 1;
+    inner.upsert(10, stored).unwrap();
+
+    let store = EncryptedChunkStore::new(inner, KEY);
+    assert!(matches!(store.get(&10), Err(Error::Integrity)));
+}
+
+#[test]
+fn it_detects_swapped_chunks() {
+    let mut store = EncryptedChunkStore::new(MemoryChunkStore::new(), KEY);
+    store.upsert(10, b"First chunk".to_vec()).unwrap();
+
+    let mut inner = store.into_inner();
+    let stored = inner.get(&10).unwrap();
+    inner.upsert(20, stored).unwrap();
+
+    let store = EncryptedChunkStore::new(inner, KEY);
+    assert!(matches!(store.get(&20), Err(Error::Integrity)));
+}
+
+#[test]
+fn it_detects_wrong_key() {
+    let mut store = EncryptedChunkStore::new(MemoryChunkStore::new(), KEY);
+    store.upsert(10, b"Here are some bytes!".to_vec()).unwrap();
+
+    let store = EncryptedChunkStore::new(store.into_inner(), &[0; 32]);
+    assert!(matches!(store.get(&10), Err(Error::Integrity)));
+}
+
+#[test]
+fn it_composes_with_compression() {
+    let source = b"Here are some bytes!".repeat(100);
+    let mut store = CompressedChunkStore::new(
+        EncryptedChunkStore::new(MemoryChunkStore::new(), KEY),
+        Compression::default(),
+    );
+    store.upsert(10, source.clone()).unwrap();
+
+    assert_eq!(store.get(&10).unwrap(), source);
+    assert!(store.inner().inner().get(&10).unwrap().len() < source.len());
+}
+
+#[test]
+fn it_cannot_read_missing_item() {
+    let store = EncryptedChunkStore::new(MemoryChunkStore::new(), KEY);
+    assert!(matches!(store.get(&60), Err(Error::NotFound)));
+}
@@ -0,0 +1,102 @@
+use std::fs;
+
+use cdcfs::{
+    chunks::{ChunkStore, Error},
+    FileChunkStore,
+};
+
+#[test]
+fn it_can_read_and_write() {
+    let dir = tempfile::tempdir().unwrap();
+    let source = b"Here are some bytes!".to_vec();
+    let mut store = FileChunkStore::new(dir.path()).unwrap();
+    store.upsert(10, source.clone()).unwrap();
+
+    assert_eq!(store.get(&10).unwrap(), source);
+    assert!(dir.path().join("00").join("000000000000000a").is_file());
+}
+
+#[test]
+fn it_persists_chunks() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut store = FileChunkStore::new(dir.path()).unwrap();
+    store.upsert(u64::MAX, b"persisted".to_vec()).unwrap();
+    store.set_hasher_fingerprint([7; 32]).unwrap();
+    drop(store);
+
+    let store = FileChunkStore::new(dir.path()).unwrap();
+    assert_eq!(store.get(&u64::MAX).unwrap(), b"persisted");
+    assert_eq!(store.hasher_fingerprint().unwrap(), Some([7; 32]));
+}
+
+#[test]
+fn it_records_the_first_hasher_fingerprint() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut first = FileChunkStore::new(dir.path()).unwrap();
+    let mut second = FileChunkStore::new(dir.path()).unwrap();
+    assert_eq!(first.init_hasher_fingerprint([7; 32]).unwrap(), None);
+    assert_eq!(
+        second.init_hasher_fingerprint([8; 32]).unwrap(),
+        Some([7; 32])
+    );
+    assert_eq!(second.hasher_fingerprint().unwrap(), Some([7; 32]));
+    // Leaves no temporary files behind.
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn it_is_empty_without_chunks() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut store = FileChunkStore::new(dir.path()).unwrap();
+    store.set_hasher_fingerprint([7; 32]).unwrap();
+    assert!(store.is_empty().unwrap());
+
+    store.upsert(10, b"chunk".to_vec()).unwrap();
+    assert!(!store.is_empty().unwrap());
+}
+
+#[test]
+fn it_cannot_read_missing_item() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = FileChunkStore::new(dir.path()).unwrap();
+    assert!(matches!(store.get(&60), Err(Error::NotFound)));
+}
+
+#[test]
+fn it_cannot_remove_missing_item() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut store = FileChunkStore::new(dir.path()).unwrap();
+    assert!(matches!(store.remove(&60), Err(Error::NotFound)));
+
+    store.upsert(60, b"chunk".to_vec()).unwrap();
+    store.remove(&60).unwrap();
+    assert!(matches!(store.get(&60), Err(Error::NotFound)));
+}
+
+#[test]
+fn it_can_list_hashes() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut store = FileChunkStore::new(dir.path()).unwrap();
+    store.upsert(10, b"first".to_vec()).unwrap();
+    store.upsert(u64::MAX, b"second".to_vec()).unwrap();
+    store.upsert(20, b"third".to_vec()).unwrap();
+    store.remove(&20).unwrap();
+    store.set_hasher_fingerprint([7; 32]).unwrap();
+
+    let mut hashes = store.hashes().unwrap();
+    hashes.sort_unstable();
+    assert_eq!(hashes, vec![10, u64::MAX]);
+}
+
+#[test]
+fn it_checks_many_hashes_at_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut store = FileChunkStore::new(dir.path()).unwrap();
+    store.upsert(1, b"one".to_vec()).unwrap();
+    store.upsert(3, b"three".to_vec()).unwrap();
+
+    assert_eq!(
+        store.contains_many(&[1, 2, 3]).unwrap(),
+        [true, false, true]
+    );
+}
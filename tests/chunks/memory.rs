@@ -24,3 +24,41 @@ fn it_cannot_remove_missing_item() {
     let mut store = MemoryChunkStore::new();
     assert!(matches!(store.remove(&60), Err(Error::NotFound)));
 }
+
+#[test]
+fn it_can_record_hasher_fingerprint() {
+    let mut store = MemoryChunkStore::new();
+    assert_eq!(store.hasher_fingerprint().unwrap(), None);
+
+    assert_eq!(store.init_hasher_fingerprint([7; 32]).unwrap(), None);
+    assert_eq!(
+        store.init_hasher_fingerprint([8; 32]).unwrap(),
+        Some([7; 32])
+    );
+    assert_eq!(store.hasher_fingerprint().unwrap(), Some([7; 32]));
+
+    store.set_hasher_fingerprint([8; 32]).unwrap();
+    assert_eq!(store.hasher_fingerprint().unwrap(), Some([8; 32]));
+}
+
+#[test]
+fn it_can_list_hashes() {
+    let mut store = MemoryChunkStore::new();
+    store.upsert(10, b"first".to_vec()).unwrap();
+    store.upsert(20, b"second".to_vec()).unwrap();
+    store.remove(&10).unwrap();
+
+    assert_eq!(store.hashes().unwrap(), vec![20]);
+}
+
+#[test]
+fn it_checks_many_hashes_at_once() {
+    let mut store = MemoryChunkStore::new();
+    store.upsert(1, b"one".to_vec()).unwrap();
+    store.upsert(3, b"three".to_vec()).unwrap();
+
+    assert_eq!(
+        store.contains_many(&[1, 2, 3]).unwrap(),
+        [true, false, true]
+    );
+}
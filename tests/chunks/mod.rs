@@ -1,3 +1,18 @@
+mod cached;
+mod compressed;
+mod encrypted;
+mod file;
 mod memory;
 mod proptest;
 mod redis;
+mod redis_cluster;
+mod redis_sentinel;
+mod remote;
+mod replicated;
+mod sharded;
+mod tiered;
+
+/// Chunk of 100 bytes, all `byte`.
+fn chunk(byte: u8) -> Vec<u8> {
+    vec![byte; 100]
+}
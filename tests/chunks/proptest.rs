@@ -1,6 +1,9 @@
 use proptest::prelude::*;
 
-use cdcfs::chunks::{ChunkStore, MemoryChunkStore, RedisChunkStore};
+use cdcfs::chunks::{
+    CachedChunkStore, ChunkServer, ChunkStore, MemoryChunkStore, RedisChunkStore, RemoteChunkStore,
+    ReplicatedChunkStore, ShardedChunkStore, TieredChunkStore, WritePolicy,
+};
 
 use crate::utils::with_redis_ready;
 
@@ -11,29 +14,85 @@ proptest! {
         operations in Operations::arbitrary(),
     ) {
         with_redis_ready(|url| async move {
-            let mut memory_chunk_store = MemoryChunkStore::new();
-            let mut redis_chunk_store = RedisChunkStore::new(url).unwrap();
+            assert_same_as_memory(&operations, RedisChunkStore::new(url).unwrap());
+        });
+    }
+
+    #[test]
+    fn replicated_store_behaves_like_memory(
+        operations in Operations::arbitrary(),
+    ) {
+        let replicas = vec![MemoryChunkStore::new(), MemoryChunkStore::new(), MemoryChunkStore::new()];
+        assert_same_as_memory(&operations, ReplicatedChunkStore::new(replicas).with_read_quorum(2));
+    }
+
+    #[test]
+    fn sharded_store_behaves_like_memory(
+        operations in Operations::arbitrary(),
+    ) {
+        let mut store = ShardedChunkStore::new();
+        for shard in ["a", "b", "c"] {
+            store.add_shard(shard, MemoryChunkStore::new()).unwrap();
+        }
+        assert_same_as_memory(&operations, store);
+    }
+
+    #[test]
+    fn cached_store_behaves_like_memory(
+        operations in Operations::arbitrary(),
+    ) {
+        assert_same_as_memory(&operations, CachedChunkStore::new(MemoryChunkStore::new(), 4096));
+    }
+
+    #[test]
+    fn tiered_store_behaves_like_memory(
+        operations in Operations::arbitrary(),
+    ) {
+        for write_policy in [WritePolicy::WriteThrough, WritePolicy::WriteBack] {
+            let store = TieredChunkStore::new(MemoryChunkStore::new(), MemoryChunkStore::new(), 4096)
+                .with_write_policy(write_policy);
+            assert_same_as_memory(&operations, store);
+        }
+    }
+
+    #[test]
+    fn remote_store_behaves_like_memory(
+        operations in Operations::arbitrary(),
+    ) {
+        let server = ChunkServer::bind("127.0.0.1:0", MemoryChunkStore::new()).unwrap();
+        assert_same_as_memory(&operations, RemoteChunkStore::new(server.local_addr()).unwrap());
+    }
+}
 
-            for operation in operations.0.iter() {
-                match operation {
-                    Operation::Insert(chunk_id, chunk) => {
-                        let mem = memory_chunk_store.upsert(*chunk_id, chunk.clone()).map_err(|e|format!("{e:?}"));
-                        let red = redis_chunk_store.upsert(*chunk_id, chunk.clone()).map_err(|e|format!("{e:?}"));
-                        assert_eq!(mem, red);
-                    },
-                    Operation::Get(chunk_id) => {
-                        let memory_chunk = memory_chunk_store.get(chunk_id).map_err(|e|format!("{e:?}"));
-                        let redis_chunk = redis_chunk_store.get(chunk_id).map_err(|e|format!("{e:?}"));
-                        assert_eq!(memory_chunk, redis_chunk);
-                    },
-                    Operation::Remove(chunk_id) => {
-                        let memory_chunk = memory_chunk_store.remove(chunk_id).map_err(|e|format!("{e:?}"));
-                        let redis_chunk = redis_chunk_store.remove(chunk_id).map_err(|e|format!("{e:?}"));
-                        assert_eq!(memory_chunk, redis_chunk);
-                    },
-                }
+fn assert_same_as_memory(operations: &Operations, mut chunk_store: impl ChunkStore) {
+    let mut memory_chunk_store = MemoryChunkStore::new();
+
+    for operation in operations.0.iter() {
+        match operation {
+            Operation::Insert(chunk_id, chunk) => {
+                let mem = memory_chunk_store
+                    .upsert(*chunk_id, chunk.clone())
+                    .map_err(|e| format!("{e:?}"));
+                let other = chunk_store
+                    .upsert(*chunk_id, chunk.clone())
+                    .map_err(|e| format!("{e:?}"));
+                assert_eq!(mem, other);
             }
-        });
+            Operation::Get(chunk_id) => {
+                let memory_chunk = memory_chunk_store
+                    .get(chunk_id)
+                    .map_err(|e| format!("{e:?}"));
+                let other_chunk = chunk_store.get(chunk_id).map_err(|e| format!("{e:?}"));
+                assert_eq!(memory_chunk, other_chunk);
+            }
+            Operation::Remove(chunk_id) => {
+                let memory_chunk = memory_chunk_store
+                    .remove(chunk_id)
+                    .map_err(|e| format!("{e:?}"));
+                let other_chunk = chunk_store.remove(chunk_id).map_err(|e| format!("{e:?}"));
+                assert_eq!(memory_chunk, other_chunk);
+            }
+        }
     }
 }
 
@@ -1,7 +1,10 @@
+use std::time::Duration;
+
 use cdcfs::{
-    chunks::{ChunkStore, Error},
+    chunks::{ChunkStore, Error, RedisKeyFormat, RedisPoolOptions},
     RedisChunkStore,
 };
+use redis::Commands;
 
 use crate::utils::with_redis_ready;
 
@@ -33,3 +36,128 @@ fn it_cannot_remove_missing_item() {
         assert!(matches!(store.remove(&60), Err(Error::NotFound)));
     });
 }
+
+#[test]
+fn it_passes_health_check() {
+    with_redis_ready(|url| async move {
+        let store = RedisChunkStore::new(url).unwrap();
+        store.health_check().unwrap();
+    });
+}
+
+#[test]
+fn it_fails_health_check_without_server() {
+    let store = RedisPoolOptions::new()
+        .connection_timeout(Duration::from_millis(100))
+        .connect("redis://127.0.0.1:1")
+        .unwrap();
+    assert!(matches!(store.health_check(), Err(Error::Internal(_))));
+}
+
+#[test]
+fn it_reuses_pooled_connections() {
+    with_redis_ready(|url| async move {
+        let mut store = RedisPoolOptions::new()
+            .max_connections(1)
+            .io_timeout(Some(Duration::from_secs(1)))
+            .connect(url)
+            .unwrap();
+
+        for i in 0..100 {
+            store.upsert(i, vec![i as u8; 16]).unwrap();
+        }
+        for i in 0..100 {
+            assert_eq!(store.get(&i).unwrap(), vec![i as u8; 16]);
+        }
+    });
+}
+
+#[test]
+fn it_namespaces_keys() {
+    with_redis_ready(|url| async move {
+        let format = RedisKeyFormat::new("cdcfs:chunk:");
+        let mut store = RedisChunkStore::new(url.as_str())
+            .unwrap()
+            .with_key_format(format.clone());
+        store.upsert(10, b"namespaced".to_vec()).unwrap();
+
+        let mut conn = redis::Client::open(url).unwrap().get_connection().unwrap();
+        let raw: Option<Vec<u8>> = conn.get(format.key(10)).unwrap();
+        assert_eq!(raw.as_deref(), Some(&b"namespaced"[..]));
+        assert!(!conn.exists::<_, bool>(10).unwrap());
+    });
+}
+
+#[test]
+fn it_migrates_legacy_keys() {
+    with_redis_ready(|url| async move {
+        let mut legacy = RedisChunkStore::new(url.as_str()).unwrap();
+        for i in 0..2_500 {
+            legacy.upsert(i, vec![i as u8; 4]).unwrap();
+        }
+        legacy.set_hasher_fingerprint([7; 32]).unwrap();
+
+        let mut conn = redis::Client::open(url.as_str())
+            .unwrap()
+            .get_connection()
+            .unwrap();
+        conn.set::<_, _, ()>("unrelated", "value").unwrap();
+
+        let format = RedisKeyFormat::new("cdcfs:chunk:").hash_tag(true);
+        let store = RedisChunkStore::new(url.as_str())
+            .unwrap()
+            .with_key_format(format);
+        assert_eq!(
+            store.migrate_keys(&RedisKeyFormat::default()).unwrap(),
+            2_500
+        );
+
+        for i in 0..2_500 {
+            assert_eq!(store.get(&i).unwrap(), vec![i as u8; 4]);
+            assert!(matches!(legacy.get(&i), Err(Error::NotFound)));
+        }
+        assert_eq!(store.hasher_fingerprint().unwrap(), Some([7; 32]));
+        assert_eq!(legacy.hasher_fingerprint().unwrap(), None);
+        assert_eq!(conn.get::<_, String>("unrelated").unwrap(), "value");
+    });
+}
+
+#[test]
+fn it_can_record_hasher_fingerprint() {
+    with_redis_ready(|url| async move {
+        let mut store = RedisChunkStore::new(url)
+            .unwrap()
+            .with_key_format(RedisKeyFormat::new("cdcfs:chunk:"));
+        assert_eq!(store.hasher_fingerprint().unwrap(), None);
+
+        assert_eq!(store.init_hasher_fingerprint([7; 32]).unwrap(), None);
+        assert_eq!(
+            store.init_hasher_fingerprint([8; 32]).unwrap(),
+            Some([7; 32])
+        );
+        assert_eq!(store.hasher_fingerprint().unwrap(), Some([7; 32]));
+
+        store.set_hasher_fingerprint([8; 32]).unwrap();
+        assert_eq!(store.hasher_fingerprint().unwrap(), Some([8; 32]));
+    });
+}
+
+#[test]
+fn it_can_list_hashes() {
+    with_redis_ready(|url| async move {
+        let mut store = RedisChunkStore::new(url.as_str())
+            .unwrap()
+            .with_key_format(RedisKeyFormat::new("cdcfs:chunk:"));
+        for i in 0..100 {
+            store.upsert(i, vec![i as u8]).unwrap();
+        }
+        store.set_hasher_fingerprint([7; 32]).unwrap();
+
+        let mut conn = redis::Client::open(url).unwrap().get_connection().unwrap();
+        conn.set::<_, _, ()>("unrelated", "value").unwrap();
+
+        let mut hashes = store.hashes().unwrap();
+        hashes.sort_unstable();
+        assert_eq!(hashes, (0..100).collect::<Vec<u64>>());
+    });
+}
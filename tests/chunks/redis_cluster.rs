@@ -0,0 +1,44 @@
+use cdcfs::chunks::{ChunkStore, Error, RedisClusterChunkStore};
+
+use crate::utils::with_redis_cluster_ready;
+
+#[test]
+fn it_can_read_and_write() {
+    with_redis_cluster_ready(3, |urls| async move {
+        let mut store = RedisClusterChunkStore::cluster(urls).unwrap();
+
+        let source = b"Here are some bytes!".to_vec();
+        store.upsert(10, source.clone()).unwrap();
+
+        let result = store.get(&10).unwrap();
+        assert_eq!(result, source);
+    });
+}
+
+#[test]
+fn it_routes_hashes_across_slots() {
+    with_redis_cluster_ready(3, |urls| async move {
+        let mut store = RedisClusterChunkStore::cluster(urls).unwrap();
+        store.health_check().unwrap();
+
+        for hash in 0..1_000u64 {
+            let hash = hash.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+            store.upsert(hash, hash.to_le_bytes().to_vec()).unwrap();
+        }
+        // Lists the chunks of every master.
+        let mut hashes = store.hashes().unwrap();
+        hashes.sort_unstable();
+        let mut expected: Vec<u64> = (0..1_000u64)
+            .map(|hash| hash.wrapping_mul(0x9e37_79b9_7f4a_7c15))
+            .collect();
+        expected.sort_unstable();
+        assert_eq!(hashes, expected);
+
+        for hash in 0..1_000u64 {
+            let hash = hash.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+            assert_eq!(store.get(&hash).unwrap(), hash.to_le_bytes());
+            store.remove(&hash).unwrap();
+            assert!(matches!(store.get(&hash), Err(Error::NotFound)));
+        }
+    });
+}
@@ -0,0 +1,20 @@
+use cdcfs::chunks::{ChunkStore, Error, RedisSentinelChunkStore};
+
+use crate::utils::with_redis_sentinel_ready;
+
+#[test]
+fn it_can_read_and_write() {
+    with_redis_sentinel_ready("cdcfs", |sentinels| async move {
+        let mut store = RedisSentinelChunkStore::sentinel(sentinels, "cdcfs").unwrap();
+        store.health_check().unwrap();
+
+        let source = b"Here are some bytes!".to_vec();
+        store.upsert(10, source.clone()).unwrap();
+
+        let result = store.get(&10).unwrap();
+        assert_eq!(result, source);
+
+        store.remove(&10).unwrap();
+        assert!(matches!(store.get(&10), Err(Error::NotFound)));
+    });
+}
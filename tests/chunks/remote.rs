@@ -0,0 +1,291 @@
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    thread,
+    time::{Duration, Instant},
+};
+
+use cdcfs::{
+    chunks::{ChunkServer, ChunkServerOptions, ChunkStore, Error, RemoteChunkStore, Result},
+    BuildWyHasher, MemoryChunkStore, MemoryMetaStore, System,
+};
+
+fn serve(store: MemoryChunkStore) -> (ChunkServer<MemoryChunkStore>, RemoteChunkStore) {
+    let server = ChunkServer::bind("127.0.0.1:0", store).unwrap();
+    let client = RemoteChunkStore::new(server.local_addr()).unwrap();
+    (server, client)
+}
+
+#[test]
+fn it_can_read_and_write_over_the_network() {
+    let (server, mut client) = serve(MemoryChunkStore::new());
+    client.upsert(1, b"one".to_vec()).unwrap();
+    client.upsert(2, vec![]).unwrap();
+
+    assert_eq!(client.get(&1).unwrap(), b"one");
+    assert_eq!(client.get(&2).unwrap(), b"");
+    assert!(matches!(client.get(&3), Err(Error::NotFound)));
+    assert_eq!(client.stored_size(&1).unwrap(), 3);
+    assert!(matches!(client.stored_size(&3), Err(Error::NotFound)));
+
+    client.remove(&1).unwrap();
+    assert!(matches!(client.remove(&1), Err(Error::NotFound)));
+    assert_eq!(client.hashes().unwrap(), [2]);
+
+    assert_eq!(client.hasher_fingerprint().unwrap(), None);
+    assert_eq!(client.init_hasher_fingerprint([7; 32]).unwrap(), None);
+    assert_eq!(
+        client.init_hasher_fingerprint([8; 32]).unwrap(),
+        Some([7; 32])
+    );
+    assert_eq!(client.hasher_fingerprint().unwrap(), Some([7; 32]));
+
+    let store = server.into_inner();
+    assert_eq!(store.hashes().unwrap(), [2]);
+    assert_eq!(store.hasher_fingerprint().unwrap(), Some([7; 32]));
+}
+
+#[test]
+fn it_negotiates_the_chunks_to_push() {
+    let mut existing = MemoryChunkStore::new();
+    existing.upsert(1, b"one".to_vec()).unwrap();
+    existing.upsert(3, b"three".to_vec()).unwrap();
+    let (server, mut client) = serve(existing);
+
+    let mut local = MemoryChunkStore::new();
+    for hash in 1..=4 {
+        local.upsert(hash, vec![hash as u8; 10]).unwrap();
+    }
+
+    assert_eq!(client.want(&[1, 2, 3, 4]).unwrap(), [2, 4]);
+    assert_eq!(
+        client.contains_many(&[1, 2, 3, 4]).unwrap(),
+        [true, false, true, false]
+    );
+    assert_eq!(client.push(&local, &[1, 2, 3, 4]).unwrap(), 2);
+    assert_eq!(client.want(&[1, 2, 3, 4]).unwrap(), Vec::<u64>::new());
+
+    let store = server.into_inner();
+    assert_eq!(store.get(&1).unwrap(), b"one");
+    assert_eq!(store.get(&2).unwrap(), [2; 10]);
+}
+
+/// Memory store failing the integrity check of chunks reading "corrupt".
+#[derive(Debug, Default)]
+struct CheckedChunkStore(MemoryChunkStore);
+
+impl ChunkStore for CheckedChunkStore {
+    fn get(&self, hash: &u64) -> Result<Vec<u8>> {
+        match self.0.get(hash)? {
+            chunk if chunk == b"corrupt" => Err(Error::Integrity),
+            chunk => Ok(chunk),
+        }
+    }
+
+    fn upsert(&mut self, hash: u64, chunk: Vec<u8>) -> Result<()> {
+        self.0.upsert(hash, chunk)
+    }
+
+    fn remove(&mut self, hash: &u64) -> Result<()> {
+        self.0.remove(hash)
+    }
+}
+
+#[test]
+fn it_pushes_chunks_failing_their_integrity_check() {
+    let mut existing = CheckedChunkStore::default();
+    existing.upsert(1, b"corrupt".to_vec()).unwrap();
+    existing.upsert(3, b"three".to_vec()).unwrap();
+    let server = ChunkServer::bind("127.0.0.1:0", existing).unwrap();
+    let mut client = RemoteChunkStore::new(server.local_addr()).unwrap();
+
+    let mut local = MemoryChunkStore::new();
+    for (hash, chunk) in [(1, "one"), (2, "two"), (3, "three")] {
+        local.upsert(hash, chunk.as_bytes().to_vec()).unwrap();
+    }
+    assert_eq!(client.want(&[1, 2, 3]).unwrap(), [1, 2]);
+    assert_eq!(client.push(&local, &[1, 2, 3]).unwrap(), 2);
+
+    let store = server.into_inner();
+    assert_eq!(store.get(&1).unwrap(), b"one");
+}
+
+#[test]
+fn it_serves_many_clients() {
+    let (server, _) = serve(MemoryChunkStore::new());
+    let handles: Vec<_> = (0..8u64)
+        .map(|client| {
+            let addr = server.local_addr();
+            std::thread::spawn(move || {
+                let mut store = RemoteChunkStore::new(addr).unwrap();
+                for hash in client * 100..client * 100 + 100 {
+                    store.upsert(hash, hash.to_le_bytes().to_vec()).unwrap();
+                    assert_eq!(store.get(&hash).unwrap(), hash.to_le_bytes());
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    assert_eq!(server.into_inner().hashes().unwrap().len(), 800);
+}
+
+#[test]
+fn it_reconnects_after_a_broken_connection() {
+    let (server, mut client) = serve(MemoryChunkStore::new());
+    let addr = server.local_addr();
+    client.upsert(1, b"one".to_vec()).unwrap();
+
+    let store = server.into_inner();
+    assert!(client.get(&1).is_err());
+
+    let _server = ChunkServer::bind(addr, store).unwrap();
+    assert_eq!(client.get(&1).unwrap(), b"one");
+}
+
+#[test]
+fn it_rejects_malformed_frames() {
+    let (server, _) = serve(MemoryChunkStore::new());
+    let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+    stream.write_all(&[0, 0, 0, 1, 42]).unwrap();
+
+    let mut response = vec![];
+    stream.read_to_end(&mut response).unwrap();
+    assert_eq!(response[4], 7, "expected an error response");
+}
+
+#[test]
+fn it_requires_the_secret() {
+    let mut store = MemoryChunkStore::new();
+    store.upsert(1, b"one".to_vec()).unwrap();
+    let server = ChunkServerOptions::new()
+        .with_secret("open sesame")
+        .bind("127.0.0.1:0", store)
+        .unwrap();
+    let addr = server.local_addr();
+
+    let anonymous = RemoteChunkStore::new(addr).unwrap();
+    let Err(Error::Internal(err)) = anonymous.get(&1) else {
+        panic!("expected an error");
+    };
+    assert!(
+        format!("{err:#}").contains("Authentication required"),
+        "{err:#}"
+    );
+
+    let wrong = RemoteChunkStore::new(addr)
+        .unwrap()
+        .with_secret("open barley");
+    let Err(Error::Internal(err)) = wrong.get(&1) else {
+        panic!("expected an error");
+    };
+    assert!(
+        format!("{err:#}").contains("Authentication failed"),
+        "{err:#}"
+    );
+
+    let mut client = RemoteChunkStore::new(addr)
+        .unwrap()
+        .with_secret("open sesame");
+    assert_eq!(client.get(&1).unwrap(), b"one");
+    client.upsert(2, b"two".to_vec()).unwrap();
+    assert_eq!(server.into_inner().hashes().unwrap().len(), 2);
+}
+
+#[test]
+fn it_limits_open_connections() {
+    let server = ChunkServerOptions::new()
+        .with_max_connections(1)
+        .bind("127.0.0.1:0", MemoryChunkStore::new())
+        .unwrap();
+    let idle = TcpStream::connect(server.local_addr()).unwrap();
+
+    let client = RemoteChunkStore::new(server.local_addr()).unwrap();
+    assert!(client.get(&1).is_err());
+
+    drop(idle);
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        let client = RemoteChunkStore::new(server.local_addr()).unwrap();
+        match client.get(&1) {
+            Err(Error::NotFound) => break,
+            result => assert!(Instant::now() < deadline, "{result:?}"),
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn it_drops_clients_that_do_not_authenticate() {
+    let server = ChunkServerOptions::new()
+        .with_secret("open sesame")
+        .with_auth_timeout(Duration::from_millis(50))
+        .bind("127.0.0.1:0", MemoryChunkStore::new())
+        .unwrap();
+    let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    let mut response = vec![];
+    stream.read_to_end(&mut response).unwrap();
+    assert!(response.is_empty());
+
+    // Authenticated clients may stay idle for longer.
+    let client = RemoteChunkStore::new(server.local_addr())
+        .unwrap()
+        .with_secret("open sesame");
+    assert!(matches!(client.get(&1), Err(Error::NotFound)));
+    thread::sleep(Duration::from_millis(100));
+    assert!(matches!(client.get(&1), Err(Error::NotFound)));
+}
+
+#[test]
+fn it_refuses_writes_when_read_only() {
+    let mut store = MemoryChunkStore::new();
+    store.upsert(1, b"one".to_vec()).unwrap();
+    let server = ChunkServerOptions::new()
+        .with_read_only(true)
+        .bind("127.0.0.1:0", store)
+        .unwrap();
+    let mut client = RemoteChunkStore::new(server.local_addr()).unwrap();
+
+    assert_eq!(client.get(&1).unwrap(), b"one");
+    assert!(client.upsert(2, b"two".to_vec()).is_err());
+    assert!(client.remove(&1).is_err());
+    assert!(client.set_hasher_fingerprint([7; 32]).is_err());
+    assert!(client.init_hasher_fingerprint([7; 32]).is_err());
+
+    let store = server.into_inner();
+    assert_eq!(store.hashes().unwrap(), [1]);
+    assert_eq!(store.hasher_fingerprint().unwrap(), None);
+}
+
+#[test]
+fn it_lists_more_hashes_than_fit_a_frame() {
+    let mut store = MemoryChunkStore::new();
+    let count = 1024 * 1024 + 5;
+    for hash in 0..count {
+        store.upsert(hash, vec![]).unwrap();
+    }
+    let (_server, client) = serve(store);
+
+    let mut hashes = client.hashes().unwrap();
+    hashes.sort_unstable();
+    assert_eq!(hashes.len() as u64, count);
+    assert!(hashes.iter().copied().eq(0..count));
+    // The connection is still in sync afterwards.
+    assert!(matches!(client.get(&count), Err(Error::NotFound)));
+}
+
+#[tokio::test]
+async fn it_backs_a_system() {
+    let (_server, client) = serve(MemoryChunkStore::new());
+    let mut fs = System::new(client, MemoryMetaStore::new(), BuildWyHasher::default());
+
+    let source = b"Hello World!".repeat(10_000);
+    fs.write(&1, &source).await.unwrap();
+    assert_eq!(fs.read(&1).await.unwrap(), source);
+}
@@ -0,0 +1,251 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+
+use anyhow::anyhow;
+use cdcfs::{
+    chunks::{ChunkStore, CompressedChunkStore, Compression, Error, ReplicatedChunkStore, Result},
+    MemoryChunkStore,
+};
+
+/// Memory store that can be taken offline, shared with the test so its
+/// contents can be inspected while the replicated store owns it.
+#[derive(Clone, Debug, Default)]
+struct Replica {
+    chunks: Arc<Mutex<MemoryChunkStore>>,
+    down: Arc<AtomicBool>,
+}
+
+impl Replica {
+    fn set_down(&self, down: bool) {
+        self.down.store(down, Ordering::SeqCst);
+    }
+
+    fn check(&self) -> Result<()> {
+        if self.down.load(Ordering::SeqCst) {
+            Err(anyhow!("Replica is down").into())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl ChunkStore for Replica {
+    fn get(&self, hash: &u64) -> Result<Vec<u8>> {
+        self.check()?;
+        self.chunks.lock().unwrap().get(hash)
+    }
+
+    fn upsert(&mut self, hash: u64, chunk: Vec<u8>) -> Result<()> {
+        self.check()?;
+        self.chunks.lock().unwrap().upsert(hash, chunk)
+    }
+
+    fn remove(&mut self, hash: &u64) -> Result<()> {
+        self.check()?;
+        self.chunks.lock().unwrap().remove(hash)
+    }
+
+    fn hashes(&self) -> Result<Vec<u64>> {
+        self.check()?;
+        self.chunks.lock().unwrap().hashes()
+    }
+
+    fn hasher_fingerprint(&self) -> Result<Option<[u8; 32]>> {
+        self.check()?;
+        self.chunks.lock().unwrap().hasher_fingerprint()
+    }
+
+    fn set_hasher_fingerprint(&mut self, fingerprint: [u8; 32]) -> Result<()> {
+        self.check()?;
+        self.chunks
+            .lock()
+            .unwrap()
+            .set_hasher_fingerprint(fingerprint)
+    }
+}
+
+fn replicas() -> [Replica; 3] {
+    Default::default()
+}
+
+#[test]
+fn it_falls_back_to_other_replicas() {
+    let replicas = replicas();
+    let mut store = ReplicatedChunkStore::new(replicas.to_vec());
+    store.upsert(10, b"chunk".to_vec()).unwrap();
+
+    replicas[0].set_down(true);
+    assert_eq!(store.get(&10).unwrap(), b"chunk");
+    replicas[1].set_down(true);
+    assert_eq!(store.get(&10).unwrap(), b"chunk");
+    replicas[2].set_down(true);
+    assert!(matches!(store.get(&10), Err(Error::Internal(_))));
+}
+
+#[test]
+fn it_re_replicates_lost_copies() {
+    let replicas = replicas();
+    let mut store = ReplicatedChunkStore::new(replicas.to_vec());
+    store.upsert(10, b"chunk".to_vec()).unwrap();
+
+    replicas[0].chunks.lock().unwrap().remove(&10).unwrap();
+    replicas[1].chunks.lock().unwrap().remove(&10).unwrap();
+    assert_eq!(store.get(&10).unwrap(), b"chunk");
+
+    store.into_inner();
+    for replica in &replicas {
+        assert_eq!(replica.get(&10).unwrap(), b"chunk");
+    }
+}
+
+#[test]
+fn it_does_not_resurrect_removed_chunks() {
+    let replicas = replicas();
+    let mut store = ReplicatedChunkStore::new(replicas.to_vec());
+    store.upsert(10, b"chunk".to_vec()).unwrap();
+
+    replicas[0].chunks.lock().unwrap().remove(&10).unwrap();
+    assert_eq!(store.get(&10).unwrap(), b"chunk");
+    store.remove(&10).unwrap();
+
+    assert!(matches!(store.get(&10), Err(Error::NotFound)));
+    store.into_inner();
+    for replica in &replicas {
+        assert!(matches!(replica.get(&10), Err(Error::NotFound)));
+    }
+}
+
+#[test]
+fn it_retries_removes_that_failed_on_a_replica() {
+    let replicas = replicas();
+    let mut store = ReplicatedChunkStore::new(replicas.to_vec());
+    store.upsert(10, b"chunk".to_vec()).unwrap();
+    store.upsert(11, b"other".to_vec()).unwrap();
+
+    replicas[2].set_down(true);
+    store.remove(&10).unwrap();
+    store.remove(&11).unwrap();
+    replicas[2].set_down(false);
+
+    // Only the replica that failed the remove still holds the chunk.
+    assert!(matches!(store.get(&10), Err(Error::NotFound)));
+    assert_eq!(store.contains_many(&[10, 11]).unwrap(), [false, false]);
+    assert!(store.hashes().unwrap().is_empty());
+
+    store.upsert(11, b"other".to_vec()).unwrap();
+    assert_eq!(store.get(&11).unwrap(), b"other");
+
+    store.into_inner();
+    for replica in &replicas {
+        assert!(matches!(replica.get(&10), Err(Error::NotFound)));
+        assert_eq!(replica.get(&11).unwrap(), b"other");
+    }
+}
+
+#[test]
+fn it_lists_pending_removes() {
+    let replicas = replicas();
+    let mut store = ReplicatedChunkStore::new(replicas.to_vec());
+    store.upsert(10, b"chunk".to_vec()).unwrap();
+    store.upsert(11, b"other".to_vec()).unwrap();
+    store.remove(&11).unwrap();
+
+    replicas[2].set_down(true);
+    store.remove(&10).unwrap();
+    assert_eq!(store.pending_removes(), [10]);
+    drop(store);
+
+    // Removing the chunk again once the replica is back finishes the job.
+    replicas[2].set_down(false);
+    let mut store = ReplicatedChunkStore::new(replicas.to_vec());
+    store.remove(&10).unwrap();
+    assert!(store.pending_removes().is_empty());
+    store.into_inner();
+    assert!(matches!(replicas[2].get(&10), Err(Error::NotFound)));
+}
+
+#[test]
+fn it_requires_the_write_quorum() {
+    let replicas = replicas();
+    let mut store = ReplicatedChunkStore::new(replicas.to_vec());
+
+    replicas[0].set_down(true);
+    store.upsert(10, b"chunk".to_vec()).unwrap();
+    store.remove(&10).unwrap();
+
+    replicas[1].set_down(true);
+    assert!(matches!(
+        store.upsert(10, b"chunk".to_vec()),
+        Err(Error::Internal(_))
+    ));
+
+    let mut store = store.with_write_quorum(1);
+    store.upsert(10, b"chunk".to_vec()).unwrap();
+    assert_eq!(store.get(&10).unwrap(), b"chunk");
+}
+
+#[test]
+fn it_requires_the_read_quorum() {
+    let replicas = replicas();
+    let mut store = ReplicatedChunkStore::new(replicas.to_vec()).with_read_quorum(2);
+    store.upsert(10, b"chunk".to_vec()).unwrap();
+
+    replicas[1].set_down(true);
+    assert_eq!(store.get(&10).unwrap(), b"chunk");
+    assert!(matches!(store.get(&20), Err(Error::NotFound)));
+
+    replicas[2].set_down(true);
+    assert!(matches!(store.get(&10), Err(Error::Internal(_))));
+    assert!(matches!(store.get(&20), Err(Error::Internal(_))));
+}
+
+#[test]
+fn it_lists_hashes_across_replicas() {
+    let replicas = replicas();
+    let mut store = ReplicatedChunkStore::new(replicas.to_vec());
+    store.upsert(10, b"first".to_vec()).unwrap();
+    replicas[0].set_down(true);
+    store.upsert(20, b"second".to_vec()).unwrap();
+
+    let mut hashes = store.hashes().unwrap();
+    hashes.sort_unstable();
+    assert_eq!(hashes, vec![10, 20]);
+}
+
+#[test]
+fn it_can_mix_store_types() {
+    let replicas: Vec<Box<dyn ChunkStore + Send + Sync>> = vec![
+        Box::new(MemoryChunkStore::new()),
+        Box::new(CompressedChunkStore::new(
+            MemoryChunkStore::new(),
+            Compression::default(),
+        )),
+    ];
+    let mut store = ReplicatedChunkStore::new(replicas).with_write_quorum(2);
+    store.upsert(10, b"chunk".repeat(100)).unwrap();
+
+    let replicas = store.into_inner();
+    for replica in &replicas {
+        assert_eq!(replica.get(&10).unwrap(), b"chunk".repeat(100));
+    }
+}
+
+#[test]
+fn it_checks_chunks_without_re_replicating() {
+    let replicas = replicas();
+    let mut store = ReplicatedChunkStore::new(replicas.to_vec());
+    store.upsert(10, b"chunk".to_vec()).unwrap();
+    store.upsert(11, b"other".to_vec()).unwrap();
+
+    replicas[0].chunks.lock().unwrap().remove(&10).unwrap();
+    replicas[1].set_down(true);
+    assert_eq!(
+        store.contains_many(&[10, 11, 12]).unwrap(),
+        [true, true, false]
+    );
+
+    store.into_inner();
+    assert!(matches!(replicas[0].get(&10), Err(Error::NotFound)));
+}
@@ -0,0 +1,249 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use cdcfs::{
+    chunks::{ChunkStore, Error, ShardedChunkStore},
+    MemoryChunkStore,
+};
+
+const CHUNKS: u64 = 10_000;
+
+/// Spreads chunk ids over the whole `u64` range like real chunk hashes.
+fn chunk_id(i: u64) -> u64 {
+    i.wrapping_mul(0x9E37_79B9_7F4A_7C15)
+}
+
+fn sharded_store(shards: usize) -> ShardedChunkStore<MemoryChunkStore> {
+    let mut store = ShardedChunkStore::new();
+    for shard in 0..shards {
+        store
+            .add_shard(format!("shard-{shard}"), MemoryChunkStore::new())
+            .unwrap();
+    }
+    for i in 0..CHUNKS {
+        store.upsert(chunk_id(i), i.to_le_bytes().to_vec()).unwrap();
+    }
+    store
+}
+
+fn assert_all_readable(store: &ShardedChunkStore<MemoryChunkStore>) {
+    for i in 0..CHUNKS {
+        assert_eq!(store.get(&chunk_id(i)).unwrap(), i.to_le_bytes());
+    }
+}
+
+#[test]
+fn it_can_read_and_write() {
+    let store = sharded_store(4);
+    assert_eq!(store.rebalance(100).unwrap(), 0);
+    assert_all_readable(&store);
+    assert_eq!(store.hashes().unwrap().len(), CHUNKS as usize);
+}
+
+#[test]
+fn it_spreads_chunks_across_shards() {
+    let store = sharded_store(4);
+
+    for shard in store.shards() {
+        let owned = (0..CHUNKS)
+            .filter(|i| store.owner(chunk_id(*i)) == Some(shard))
+            .count();
+        assert!(owned > CHUNKS as usize / 8, "{shard} owns {owned} chunks");
+    }
+}
+
+#[test]
+fn it_only_moves_affected_chunks_when_adding_shards() {
+    let mut store = sharded_store(4);
+    let owners: Vec<_> = (0..CHUNKS)
+        .map(|i| store.owner(chunk_id(i)).unwrap().to_owned())
+        .collect();
+
+    store.add_shard("shard-4", MemoryChunkStore::new()).unwrap();
+    for (i, owner) in owners.iter().enumerate() {
+        let new_owner = store.owner(chunk_id(i as u64)).unwrap();
+        assert!(new_owner == owner || new_owner == "shard-4");
+    }
+    assert_all_readable(&store);
+
+    let mut moved = 0;
+    loop {
+        let batch = store.rebalance(100).unwrap();
+        assert!(batch <= 100);
+        assert_all_readable(&store);
+        if batch == 0 {
+            break;
+        }
+        moved += batch;
+    }
+    assert!(moved > 0 && moved < CHUNKS as usize / 3, "moved {moved}");
+    assert_eq!(store.rebalance(100).unwrap(), 0);
+}
+
+/// Memory chunk store whose chunks can be inspected and moved from outside
+/// the sharded store.
+#[derive(Clone, Debug, Default)]
+struct SharedChunkStore {
+    chunks: Arc<Mutex<HashMap<u64, Vec<u8>>>>,
+    /// Chunk moved here from the other store right after a read misses it,
+    /// as rebalancing would.
+    move_on_miss: Arc<Mutex<Option<(u64, SharedChunkStore)>>>,
+}
+
+impl ChunkStore for SharedChunkStore {
+    fn get(&self, hash: &u64) -> Result<Vec<u8>, Error> {
+        if let Some(chunk) = self.chunks.lock().unwrap().get(hash) {
+            return Ok(chunk.clone());
+        }
+        let mut move_on_miss = self.move_on_miss.lock().unwrap();
+        if let Some((_, source)) = move_on_miss.take_if(|(moved, _)| moved == hash) {
+            let chunk = source.chunks.lock().unwrap().remove(hash).unwrap();
+            self.chunks.lock().unwrap().insert(*hash, chunk);
+        }
+        Err(Error::NotFound)
+    }
+
+    fn upsert(&mut self, hash: u64, chunk: Vec<u8>) -> Result<(), Error> {
+        self.chunks.lock().unwrap().insert(hash, chunk);
+        Ok(())
+    }
+
+    fn remove(&mut self, hash: &u64) -> Result<(), Error> {
+        self.chunks
+            .lock()
+            .unwrap()
+            .remove(hash)
+            .map(|_| ())
+            .ok_or(Error::NotFound)
+    }
+
+    fn hashes(&self) -> Result<Vec<u64>, Error> {
+        Ok(self.chunks.lock().unwrap().keys().copied().collect())
+    }
+}
+
+#[test]
+fn it_finds_chunks_moved_while_reading() {
+    let old = SharedChunkStore::default();
+    let new = SharedChunkStore::default();
+    let mut store = ShardedChunkStore::new();
+    store.add_shard("shard-0", old.clone()).unwrap();
+    for i in 0..100 {
+        store.upsert(chunk_id(i), i.to_le_bytes().to_vec()).unwrap();
+    }
+    store.add_shard("shard-1", new.clone()).unwrap();
+    let mut moving = (0..100).filter(|i| store.owner(chunk_id(*i)) == Some("shard-1"));
+    let (a, b) = (moving.next().unwrap(), moving.next().unwrap());
+
+    // Each chunk reaches its owner after the owner was checked, but before
+    // the previous owner is.
+    *new.move_on_miss.lock().unwrap() = Some((chunk_id(a), old.clone()));
+    assert_eq!(store.get(&chunk_id(a)).unwrap(), a.to_le_bytes());
+    *new.move_on_miss.lock().unwrap() = Some((chunk_id(b), old.clone()));
+    assert_eq!(store.contains_many(&[chunk_id(b)]).unwrap(), [true]);
+    assert!(old.chunks.lock().unwrap().get(&chunk_id(b)).is_none());
+}
+
+#[test]
+fn it_checks_chunks_on_previous_owners() {
+    let mut store = sharded_store(2);
+    store.add_shard("shard-2", MemoryChunkStore::new()).unwrap();
+
+    let hashes: Vec<u64> = (0..CHUNKS + 10).map(chunk_id).collect();
+    let found = store.contains_many(&hashes).unwrap();
+    assert!(found[..CHUNKS as usize].iter().all(|found| *found));
+    assert!(!found[CHUNKS as usize..].iter().any(|found| *found));
+}
+
+#[test]
+fn it_drains_removed_shards() {
+    let mut store = sharded_store(4);
+
+    store.remove_shard("shard-1").unwrap();
+    assert!(store.shards().all(|shard| shard != "shard-1"));
+    assert_all_readable(&store);
+    assert!(store.take_drained_shards().unwrap().is_empty());
+
+    while store.rebalance(1_000).unwrap() > 0 {}
+    assert_all_readable(&store);
+
+    let drained = store.take_drained_shards().unwrap();
+    assert_eq!(drained.len(), 1);
+    assert_eq!(drained[0].0, "shard-1");
+    assert_all_readable(&store);
+}
+
+#[test]
+fn it_removes_stale_copies_while_rebalancing() {
+    let mut store = sharded_store(2);
+    store.add_shard("shard-2", MemoryChunkStore::new()).unwrap();
+    let hash = (0..CHUNKS)
+        .map(chunk_id)
+        .find(|hash| store.owner(*hash) == Some("shard-2"))
+        .unwrap();
+
+    store.upsert(hash, b"rewritten".to_vec()).unwrap();
+    store.remove(&hash).unwrap();
+    assert!(matches!(store.get(&hash), Err(Error::NotFound)));
+    assert!(matches!(store.remove(&hash), Err(Error::NotFound)));
+}
+
+#[test]
+fn it_rebalances_shards_that_already_hold_chunks() {
+    let mut full = MemoryChunkStore::new();
+    for i in 0..CHUNKS {
+        full.upsert(chunk_id(i), i.to_le_bytes().to_vec()).unwrap();
+    }
+    let mut store = ShardedChunkStore::new();
+    store.add_shard("shard-0", MemoryChunkStore::new()).unwrap();
+    store.add_shard("shard-1", full).unwrap();
+    assert_all_readable(&store);
+
+    assert!(store.rebalance(usize::MAX).unwrap() > 0);
+    assert_eq!(store.rebalance(usize::MAX).unwrap(), 0);
+    assert_all_readable(&store);
+}
+
+#[test]
+fn it_rejects_duplicate_and_unknown_shards() {
+    let mut store = sharded_store(2);
+    assert!(store.add_shard("shard-0", MemoryChunkStore::new()).is_err());
+    assert!(store.remove_shard("shard-9").is_err());
+}
+
+#[test]
+fn it_requires_a_shard_to_write() {
+    let mut store = ShardedChunkStore::<MemoryChunkStore>::new();
+    assert!(matches!(store.get(&10), Err(Error::NotFound)));
+    assert!(matches!(
+        store.upsert(10, b"chunk".to_vec()),
+        Err(Error::Internal(_))
+    ));
+}
+
+#[test]
+fn it_records_hasher_fingerprint_on_new_shards() {
+    let mut store = sharded_store(2);
+    store.set_hasher_fingerprint([7; 32]).unwrap();
+    store.add_shard("shard-2", MemoryChunkStore::new()).unwrap();
+
+    store.remove_shard("shard-0").unwrap();
+    store.remove_shard("shard-1").unwrap();
+    while store.rebalance(usize::MAX).unwrap() > 0 {}
+    store.take_drained_shards().unwrap();
+
+    assert_eq!(store.hasher_fingerprint().unwrap(), Some([7; 32]));
+}
+
+#[test]
+fn it_rejects_shards_with_a_different_hasher_fingerprint() {
+    let mut store = sharded_store(2);
+    store.set_hasher_fingerprint([7; 32]).unwrap();
+
+    let mut shard = MemoryChunkStore::new();
+    shard.set_hasher_fingerprint([8; 32]).unwrap();
+    assert!(store.add_shard("shard-2", shard).is_err());
+    assert_eq!(store.shards().count(), 2);
+}
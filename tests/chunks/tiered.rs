@@ -0,0 +1,213 @@
+use std::{
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Mutex,
+    },
+    thread,
+};
+
+use cdcfs::{
+    chunks::{ChunkStore, Error, Eviction, TieredChunkStore, WritePolicy},
+    FileChunkStore, MemoryChunkStore,
+};
+
+use super::chunk;
+
+#[test]
+fn it_writes_through_to_the_cold_tier() {
+    let mut store = TieredChunkStore::new(MemoryChunkStore::new(), MemoryChunkStore::new(), 250);
+    for i in 0..4 {
+        store.upsert(i, chunk(i as u8)).unwrap();
+    }
+    assert!(store.hot_bytes() <= 250);
+
+    for i in 0..4 {
+        assert_eq!(store.get(&i).unwrap(), chunk(i as u8));
+    }
+    let (_, cold) = store.into_inner().unwrap();
+    for i in 0..4 {
+        assert_eq!(cold.get(&i).unwrap(), chunk(i as u8));
+    }
+}
+
+#[test]
+fn it_promotes_chunks_on_read() {
+    let mut cold = MemoryChunkStore::new();
+    cold.upsert(1, chunk(1)).unwrap();
+    let store = TieredChunkStore::new(MemoryChunkStore::new(), cold, 250);
+
+    assert!(!store.is_hot(&1));
+    assert_eq!(store.get(&1).unwrap(), chunk(1));
+    assert!(store.is_hot(&1));
+    assert_eq!(store.hot_bytes(), 100);
+}
+
+#[test]
+fn it_evicts_least_recently_used_chunks() {
+    let mut store = TieredChunkStore::new(MemoryChunkStore::new(), MemoryChunkStore::new(), 250)
+        .with_eviction(Eviction::Lru);
+    store.upsert(1, chunk(1)).unwrap();
+    store.upsert(2, chunk(2)).unwrap();
+    store.get(&1).unwrap();
+    store.upsert(3, chunk(3)).unwrap();
+
+    assert!(store.is_hot(&1));
+    assert!(!store.is_hot(&2));
+    assert!(store.is_hot(&3));
+    assert_eq!(store.hot_bytes(), 200);
+}
+
+#[test]
+fn it_evicts_least_frequently_used_chunks() {
+    let mut store = TieredChunkStore::new(MemoryChunkStore::new(), MemoryChunkStore::new(), 250)
+        .with_eviction(Eviction::Lfu);
+    store.upsert(1, chunk(1)).unwrap();
+    store.upsert(2, chunk(2)).unwrap();
+    for _ in 0..3 {
+        store.get(&1).unwrap();
+    }
+    store.get(&2).unwrap();
+    store.upsert(3, chunk(3)).unwrap();
+    store.get(&3).unwrap();
+    store.upsert(4, chunk(4)).unwrap();
+
+    assert!(store.is_hot(&1));
+    assert!(store.is_hot(&4));
+    assert!(!store.is_hot(&2));
+    assert!(!store.is_hot(&3));
+}
+
+#[test]
+fn it_bypasses_the_hot_tier_for_large_chunks() {
+    let mut store = TieredChunkStore::new(MemoryChunkStore::new(), MemoryChunkStore::new(), 50);
+    store.upsert(1, chunk(1)).unwrap();
+    assert_eq!(store.get(&1).unwrap(), chunk(1));
+    assert!(!store.is_hot(&1));
+    assert_eq!(store.hot_bytes(), 0);
+}
+
+#[test]
+fn it_writes_back_on_eviction_and_flush() {
+    let mut store = TieredChunkStore::new(MemoryChunkStore::new(), MemoryChunkStore::new(), 250)
+        .with_write_policy(WritePolicy::WriteBack);
+    store.upsert(1, chunk(1)).unwrap();
+    store.upsert(2, chunk(2)).unwrap();
+    store.upsert(3, chunk(3)).unwrap();
+
+    let mut hashes = store.hashes().unwrap();
+    hashes.sort_unstable();
+    assert_eq!(hashes, vec![1, 2, 3]);
+    assert_eq!(store.flush().unwrap(), 2);
+    assert_eq!(store.flush().unwrap(), 0);
+
+    let (hot, cold) = store.into_inner().unwrap();
+    assert!(matches!(hot.get(&1), Err(Error::NotFound)));
+    for i in 1..=3 {
+        assert_eq!(cold.get(&i).unwrap(), chunk(i as u8));
+    }
+}
+
+#[test]
+fn it_removes_from_both_tiers() {
+    for write_policy in [WritePolicy::WriteThrough, WritePolicy::WriteBack] {
+        let mut store =
+            TieredChunkStore::new(MemoryChunkStore::new(), MemoryChunkStore::new(), 250)
+                .with_write_policy(write_policy);
+        store.upsert(1, chunk(1)).unwrap();
+        store.remove(&1).unwrap();
+
+        assert!(matches!(store.get(&1), Err(Error::NotFound)));
+        assert!(matches!(store.remove(&1), Err(Error::NotFound)));
+        assert_eq!(store.hot_bytes(), 0);
+    }
+}
+
+#[test]
+fn it_can_use_a_file_cold_tier() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut store = TieredChunkStore::new(
+        MemoryChunkStore::new(),
+        FileChunkStore::new(dir.path()).unwrap(),
+        250,
+    );
+    for i in 0..10 {
+        store.upsert(i, chunk(i as u8)).unwrap();
+    }
+    drop(store);
+
+    let store = TieredChunkStore::new(
+        MemoryChunkStore::new(),
+        FileChunkStore::new(dir.path()).unwrap(),
+        250,
+    );
+    for i in 0..10 {
+        assert_eq!(store.get(&i).unwrap(), chunk(i as u8));
+    }
+}
+
+/// Cold tier whose reads announce themselves and then wait until the test lets
+/// them through.
+#[derive(Debug)]
+struct GatedChunkStore {
+    inner: MemoryChunkStore,
+    entered: Mutex<Sender<()>>,
+    gate: Mutex<Receiver<()>>,
+}
+
+impl ChunkStore for GatedChunkStore {
+    fn get(&self, hash: &u64) -> Result<Vec<u8>, Error> {
+        self.entered.lock().unwrap().send(()).unwrap();
+        self.gate.lock().unwrap().recv().unwrap();
+        self.inner.get(hash)
+    }
+
+    fn upsert(&mut self, hash: u64, chunk: Vec<u8>) -> Result<(), Error> {
+        self.inner.upsert(hash, chunk)
+    }
+
+    fn remove(&mut self, hash: &u64) -> Result<(), Error> {
+        self.inner.remove(hash)
+    }
+}
+
+#[test]
+fn it_serves_hot_chunks_during_cold_reads() {
+    let (entered, cold_reads) = mpsc::channel();
+    let (open, gate) = mpsc::channel();
+    let cold = GatedChunkStore {
+        inner: MemoryChunkStore::new(),
+        entered: Mutex::new(entered),
+        gate: Mutex::new(gate),
+    };
+    let mut store = TieredChunkStore::new(MemoryChunkStore::new(), cold, 250);
+    for i in 1..=3 {
+        store.upsert(i, chunk(i as u8)).unwrap();
+    }
+    assert!(!store.is_hot(&1));
+
+    let store = &store;
+    thread::scope(|scope| {
+        let cold_read = scope.spawn(move || store.get(&1));
+        cold_reads.recv().unwrap();
+        // Would wait forever if the cold read held the hot tier.
+        assert_eq!(store.get(&3).unwrap(), chunk(3));
+        open.send(()).unwrap();
+        assert_eq!(cold_read.join().unwrap().unwrap(), chunk(1));
+    });
+    assert!(store.is_hot(&1));
+}
+
+#[test]
+fn it_checks_chunks_without_promoting_them() {
+    let mut cold = MemoryChunkStore::new();
+    cold.upsert(1, chunk(1)).unwrap();
+    let mut store = TieredChunkStore::new(MemoryChunkStore::new(), cold, 250)
+        .with_write_policy(WritePolicy::WriteBack);
+    store.upsert(2, chunk(2)).unwrap();
+
+    assert_eq!(
+        store.contains_many(&[1, 2, 3]).unwrap(),
+        [true, true, false]
+    );
+    assert!(!store.is_hot(&1));
+}
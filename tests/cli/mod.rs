@@ -0,0 +1,365 @@
+use std::{
+    fs,
+    io::Write,
+    path::Path,
+    process::{Command, Output, Stdio},
+};
+
+use tempfile::TempDir;
+
+/// Hash key the backends of [`Cli::run`] are used with.
+const HASH_KEY: &str = "0202020202020202020202020202020202020202020202020202020202020202";
+
+/// `cdcfs` binary working on file backends in a temporary directory.
+struct Cli {
+    dir: TempDir,
+}
+
+impl Cli {
+    fn new() -> Self {
+        Self {
+            dir: tempfile::tempdir().unwrap(),
+        }
+    }
+
+    fn path(&self, name: &str) -> String {
+        self.dir.path().join(name).to_str().unwrap().to_owned()
+    }
+
+    fn command(&self) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_cdcfs"));
+        for var in [
+            "CDCFS_CONFIG",
+            "CDCFS_CHUNKS",
+            "CDCFS_META",
+            "CDCFS_HASH_KEY",
+        ] {
+            command.env_remove(var);
+        }
+        command
+    }
+
+    fn run_with_input(&self, args: &[&str], input: &[u8]) -> Output {
+        let mut child = self
+            .command()
+            .args([
+                "--chunks",
+                &self.path("chunks"),
+                "--meta",
+                &self.path("meta"),
+            ])
+            .env("CDCFS_HASH_KEY", HASH_KEY)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(input).unwrap();
+        child.wait_with_output().unwrap()
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        self.run_with_input(args, b"")
+    }
+
+    /// Runs the command, asserting that it succeeds, and returns its output.
+    fn stdout(&self, args: &[&str]) -> String {
+        let output = self.run(args);
+        assert!(
+            output.status.success(),
+            "cdcfs {args:?} failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap()
+    }
+}
+
+#[test]
+fn it_puts_and_gets_files() {
+    let cli = Cli::new();
+    let pdf = "tests/fixtures/file-example_PDF_1MB.pdf";
+    let source = fs::read(pdf).unwrap();
+
+    cli.stdout(&["put", "docs/report.pdf", pdf]);
+    let output = cli.run_with_input(&["put", "notes.txt"], b"Hello World!");
+    assert!(output.status.success());
+    cli.stdout(&["cp", "docs/report.pdf", "docs/copy.pdf"]);
+
+    let target = cli.path("report.pdf");
+    cli.stdout(&["get", "docs/report.pdf", &target]);
+    assert_eq!(fs::read(&target).unwrap(), source);
+    assert_eq!(cli.stdout(&["cat", "notes.txt"]), "Hello World!");
+    assert_eq!(cli.stdout(&["get", "notes.txt", "-"]), "Hello World!");
+
+    assert_eq!(
+        cli.stdout(&["ls"]),
+        "1042157\tdocs/copy.pdf\n1042157\tdocs/report.pdf\n12\tnotes.txt\n"
+    );
+    assert_eq!(cli.stdout(&["ls", "notes"]), "12\tnotes.txt\n");
+
+    let stats: serde_json::Value = serde_json::from_str(&cli.stdout(&["stats", "--json"])).unwrap();
+    assert_eq!(stats["files"], 3);
+    assert_eq!(stats["physical_bytes"], 1_042_157 + 12);
+    assert_eq!(stats["per_file"][0]["key"], "docs/copy.pdf");
+    assert_eq!(stats["per_file"][0]["shared_bytes"], 1_042_157);
+    let stats = cli.stdout(&["stats", "--files"]);
+    assert!(stats.contains("bytes saved:    1042157\n"), "{stats}");
+    assert!(stats.contains("   100.0  docs/report.pdf\n"), "{stats}");
+
+    assert_eq!(
+        cli.stdout(&["diff", "docs/copy.pdf", "notes.txt"]),
+        "similarity: 0.0%\n\
+         docs/copy.pdf: 1042157 bytes, 0 shared, 1042157 differing\n  0..1042157\n\
+         notes.txt: 12 bytes, 0 shared, 12 differing\n  0..12\n"
+    );
+    assert_eq!(
+        cli.stdout(&["similar", "docs/copy.pdf"]),
+        " 100.0% docs/report.pdf\n"
+    );
+
+    let stat = cli.stdout(&["stat", "docs/copy.pdf"]);
+    assert!(stat.contains("size:          1042157\n"), "{stat}");
+    assert!(stat.contains("encrypted:     no\n"), "{stat}");
+
+    cli.stdout(&["rm", "docs/report.pdf", "notes.txt"]);
+    assert_eq!(cli.stdout(&["ls"]), "1042157\tdocs/copy.pdf\n");
+
+    let output = cli.run(&["cat", "notes.txt"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(stderr, "cdcfs: Meta store error: File not found\n");
+    // Does not leave an empty file behind.
+    assert!(!cli
+        .run(&["get", "notes.txt", &cli.path("notes.txt")])
+        .status
+        .success());
+    assert!(!Path::new(&cli.path("notes.txt")).exists());
+}
+
+#[test]
+fn it_collects_garbage_and_checks_files() {
+    let cli = Cli::new();
+    cli.stdout(&["put", "a.docx", "tests/fixtures/file-sample_1MB.docx"]);
+    cli.stdout(&["put", "b.jpg", "tests/fixtures/file_example_JPG_2500kB.jpg"]);
+    assert_eq!(
+        cli.stdout(&["fsck"]),
+        "2 files checked, 0 damaged, 0 orphan chunks\n"
+    );
+
+    cli.stdout(&["rm", "b.jpg"]);
+    let dry_run = cli.stdout(&["gc", "--dry-run"]);
+    assert!(dry_run.starts_with("Would remove "), "{dry_run}");
+    let orphans = cli.stdout(&["fsck"]);
+    assert!(!orphans.ends_with(" 0 orphan chunks\n"), "{orphans}");
+
+    let gc = cli.stdout(&["gc", "--force", "--count-bytes"]);
+    assert!(
+        gc.starts_with("Removed ") && gc.contains(", freeing "),
+        "{gc}"
+    );
+    assert_eq!(
+        cli.stdout(&["fsck"]),
+        "1 files checked, 0 damaged, 0 orphan chunks\n"
+    );
+    assert_eq!(
+        cli.stdout(&["gc", "--dry-run"]),
+        "Would remove 0 unreferenced chunks\n"
+    );
+
+    // Truncates a chunk of the remaining file.
+    let chunk = walk(Path::new(&cli.path("chunks")))
+        .into_iter()
+        .find(|path| path.file_name().unwrap().len() == 16)
+        .unwrap();
+    fs::write(chunk, b"").unwrap();
+
+    let output = cli.run(&["fsck", "--json"]);
+    assert_eq!(output.status.code(), Some(1));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["files"][0]["key"], "a.docx");
+    assert_eq!(report["files"][0]["status"], "damaged");
+}
+
+#[test]
+fn it_needs_persistent_stores_to_change_files() {
+    let cli = Cli::new();
+    let run = |chunks: &str, meta: &str, args: &[&str]| {
+        cli.command()
+            .args(["--chunks", chunks, "--meta", meta])
+            .env("CDCFS_HASH_KEY", HASH_KEY)
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    let (chunks, meta) = (cli.path("chunks"), cli.path("meta"));
+    let cases: [(&str, &str, &[&str], &str); 6] = [
+        (
+            &chunks,
+            "memory",
+            &["gc", "--force"],
+            "persistent chunk and meta",
+        ),
+        (&chunks, "memory", &["fsck"], "persistent meta store"),
+        (&chunks, "memory", &["stats"], "persistent meta store"),
+        ("memory", &meta, &["put", "a"], "persistent chunk and meta"),
+        ("memory", &meta, &["rm", "a"], "persistent chunk and meta"),
+        (
+            &chunks,
+            "memory",
+            &["cp", "a", "b"],
+            "persistent chunk and meta",
+        ),
+    ];
+    for (chunks, meta, args, message) in cases {
+        let output = run(chunks, meta, args);
+        assert!(!output.status.success(), "{args:?}");
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains(message), "{args:?}: {stderr}");
+    }
+
+    // Reading is fine.
+    let output = run("memory", &meta, &["ls"]);
+    assert!(output.status.success());
+    assert!(!Path::new(&meta).read_dir().unwrap().any(|_| true));
+}
+
+#[test]
+fn it_needs_a_hash_key() {
+    let cli = Cli::new();
+    let output = cli
+        .command()
+        .args(["--chunks", &cli.path("chunks"), "--meta", &cli.path("meta")])
+        .args(["ls"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("No hash key configured"), "{stderr}");
+}
+
+#[test]
+fn it_reads_config_files() {
+    let cli = Cli::new();
+    let config = cli.path("cdcfs.toml");
+    fs::write(
+        &config,
+        format!(
+            r#"
+            hash_key = "{}"
+            chunks = {{ backend = "file", path = "{}" }}
+            meta = {{ backend = "file", path = "{}" }}
+            "#,
+            "01".repeat(32),
+            cli.path("chunks"),
+            cli.path("meta")
+        ),
+    )
+    .unwrap();
+
+    let output = cli
+        .command()
+        .args(["--config", &config, "put", "x"])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let output = cli
+        .command()
+        .env("CDCFS_CONFIG", &config)
+        .args(["ls"])
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "0\tx\n");
+
+    // The chunk store remembers the hash key.
+    let output = cli.run(&["ls"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("different hasher key"), "{stderr}");
+}
+
+#[test]
+fn it_needs_backends() {
+    let cli = Cli::new();
+    let output = cli.command().arg("ls").output().unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("No backends configured"), "{stderr}");
+}
+
+fn walk(dir: &Path) -> Vec<std::path::PathBuf> {
+    let mut paths = vec![];
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            paths.extend(walk(&path));
+        } else {
+            paths.push(path);
+        }
+    }
+    paths
+}
+
+#[test]
+fn it_rebalances_shards() {
+    let cli = Cli::new();
+    cli.stdout(&["put", "a.docx", "tests/fixtures/file-sample_1MB.docx"]);
+    let shard = |name: &str| format!("{name}={}", cli.path(name));
+
+    let output = cli
+        .command()
+        .args([
+            "rebalance",
+            "--shard",
+            &shard("chunks"),
+            "--shard",
+            &shard("b"),
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.starts_with("Moved ") && stdout != "Moved 0 chunks\n",
+        "{stdout}"
+    );
+    assert!(!walk(Path::new(&cli.path("b"))).is_empty());
+
+    // Moves everything back before the second shard can be taken out.
+    let output = cli
+        .command()
+        .args([
+            "rebalance",
+            "--shard",
+            &shard("chunks"),
+            "--drain",
+            &shard("b"),
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.ends_with("Shard b is drained\n"), "{stdout}");
+    assert_eq!(
+        cli.run(&["cat", "a.docx"]).stdout,
+        fs::read("tests/fixtures/file-sample_1MB.docx").unwrap()
+    );
+}
+
+#[test]
+fn it_analyzes_corpora_without_backends() {
+    let cli = Cli::new();
+    let output = cli
+        .command()
+        .args(["analyze", "tests/fixtures", "--avg-size", "8192"])
+        .args(["--hasher", "xxh3", "--hasher", "blake3", "--json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let analysis: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let outcomes = analysis["outcomes"].as_array().unwrap();
+    assert_eq!(outcomes.len(), 2);
+    assert_eq!(outcomes[0]["config"]["avg_size"], 8192);
+    assert_eq!(outcomes[1]["hasher"], "blake3");
+}
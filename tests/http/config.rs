@@ -0,0 +1,147 @@
+use cdcfs::{
+    http::{ChunkBackend, Config, Error, MetaBackend, S3Config},
+    s3::Buckets,
+};
+
+#[test]
+fn it_parses_configs() {
+    let config: Config = r#"
+        listen = "0.0.0.0:9000"
+        hash_key = "0101010101010101010101010101010101010101010101010101010101010101"
+
+        [chunks]
+        backend = "redis"
+        url = "redis://127.0.0.1:6379"
+
+        [meta]
+        backend = "postgres"
+        url = "postgres://postgres@127.0.0.1:5432/cdcfs"
+    "#
+    .parse()
+    .unwrap();
+
+    assert_eq!(config.listen, ([0, 0, 0, 0], 9000).into());
+    assert_eq!(
+        config.chunks,
+        ChunkBackend::Redis {
+            url: "redis://127.0.0.1:6379".to_owned()
+        }
+    );
+    assert_eq!(
+        config.meta,
+        MetaBackend::Postgres {
+            url: "postgres://postgres@127.0.0.1:5432/cdcfs".to_owned()
+        }
+    );
+    config.hasher().unwrap();
+}
+
+#[test]
+fn it_defaults_to_loopback_and_requires_a_key() {
+    let config: Config = r#"
+        chunks = { backend = "file", path = "/var/lib/cdcfs" }
+        meta = { backend = "memory" }
+    "#
+    .parse()
+    .unwrap();
+
+    assert_eq!(config.listen, ([127, 0, 0, 1], 8080).into());
+    assert_eq!(config.hash_key, None);
+    assert!(matches!(config.hasher(), Err(Error::MissingHashKey)));
+    assert_eq!(
+        config.chunks,
+        ChunkBackend::File {
+            path: "/var/lib/cdcfs".into()
+        }
+    );
+    assert_eq!(config.meta, MetaBackend::Memory);
+    assert_eq!(config.s3, None);
+}
+
+#[test]
+fn it_parses_s3_buckets() {
+    let config: Config = r#"
+        chunks = { backend = "memory" }
+        meta = { backend = "memory" }
+
+        [s3]
+        listen = "127.0.0.1:9000"
+        buckets = { photos = "photos/", docs = "shared/docs/" }
+    "#
+    .parse()
+    .unwrap();
+
+    assert_eq!(
+        config.s3,
+        Some(S3Config {
+            listen: ([127, 0, 0, 1], 9000).into(),
+            buckets: Buckets::new()
+                .with_bucket("photos", "photos/")
+                .with_bucket("docs", "shared/docs/"),
+        })
+    );
+}
+
+#[test]
+fn it_rejects_invalid_configs() {
+    let unknown_backend = r#"
+        chunks = { backend = "tape" }
+        meta = { backend = "memory" }
+    "#;
+    assert!(matches!(
+        unknown_backend.parse::<Config>(),
+        Err(Error::Config(_))
+    ));
+
+    let short_key: Config = r#"
+        hash_key = "0101"
+        chunks = { backend = "memory" }
+        meta = { backend = "memory" }
+    "#
+    .parse()
+    .unwrap();
+    assert!(matches!(short_key.hasher(), Err(Error::InvalidHashKey)));
+}
+
+#[test]
+fn it_parses_backend_specs() {
+    assert_eq!(
+        "memory".parse::<ChunkBackend>().unwrap(),
+        ChunkBackend::Memory
+    );
+    assert_eq!(
+        "redis://127.0.0.1:6379".parse::<ChunkBackend>().unwrap(),
+        ChunkBackend::Redis {
+            url: "redis://127.0.0.1:6379".to_owned()
+        }
+    );
+    assert_eq!(
+        "/var/lib/cdcfs/chunks".parse::<ChunkBackend>().unwrap(),
+        ChunkBackend::File {
+            path: "/var/lib/cdcfs/chunks".into()
+        }
+    );
+    assert_eq!(
+        "postgresql://postgres@db/cdcfs"
+            .parse::<MetaBackend>()
+            .unwrap(),
+        MetaBackend::Postgres {
+            url: "postgresql://postgres@db/cdcfs".to_owned()
+        }
+    );
+    assert_eq!(
+        "meta".parse::<MetaBackend>().unwrap(),
+        MetaBackend::File {
+            path: "meta".into()
+        }
+    );
+
+    assert!(matches!(
+        "postgres://db/cdcfs".parse::<ChunkBackend>(),
+        Err(Error::UnsupportedBackend(_))
+    ));
+    assert!(matches!(
+        "redis://127.0.0.1".parse::<MetaBackend>(),
+        Err(Error::UnsupportedBackend(_))
+    ));
+}
@@ -0,0 +1,2 @@
+mod config;
+mod server;
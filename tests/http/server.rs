@@ -0,0 +1,282 @@
+use std::{fs, net::TcpListener, sync::Arc};
+
+use hyper::{
+    body::to_bytes,
+    header::{CONTENT_LENGTH, CONTENT_RANGE, ETAG, IF_NONE_MATCH, RANGE},
+    Body, Client, Method, Request, Response, StatusCode,
+};
+use tokio::{sync::RwLock, task::JoinHandle};
+
+use cdcfs::{
+    chunks::ChunkServer,
+    http::{self, SharedSystem},
+    BuildWyHasher, FileChunkStore, MemoryChunkStore, MemoryMetaStore, RemoteChunkStore, System,
+};
+
+use crate::utils::Logs;
+
+type TestSystem = SharedSystem<MemoryChunkStore, MemoryMetaStore<String>, BuildWyHasher>;
+
+struct Server {
+    url: String,
+    system: TestSystem,
+    task: JoinHandle<()>,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+fn serve() -> Server {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/objects", listener.local_addr().unwrap());
+    let system = Arc::new(RwLock::new(System::new(
+        MemoryChunkStore::new(),
+        MemoryMetaStore::new(),
+        BuildWyHasher::default(),
+    )));
+    let task = {
+        let system = Arc::clone(&system);
+        tokio::spawn(async move { http::serve(listener, system).await.unwrap() })
+    };
+    Server { url, system, task }
+}
+
+async fn send(
+    method: Method,
+    url: &str,
+    headers: &[(&str, &str)],
+    body: impl Into<Body>,
+) -> Response<Body> {
+    let mut request = Request::builder().method(method).uri(url);
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    Client::new()
+        .request(request.body(body.into()).unwrap())
+        .await
+        .unwrap()
+}
+
+async fn body(response: Response<Body>) -> Vec<u8> {
+    to_bytes(response.into_body()).await.unwrap().to_vec()
+}
+
+fn header<'a>(response: &'a Response<Body>, name: &str) -> &'a str {
+    response.headers()[name].to_str().unwrap()
+}
+
+#[tokio::test]
+async fn it_serves_objects() {
+    let server = serve();
+    let url = format!("{}/docs%2Fsample.pdf", server.url);
+    let source = fs::read("tests/fixtures/file-example_PDF_1MB.pdf").unwrap();
+
+    // Streams the upload in pieces that do not line up with chunks.
+    let (mut sender, upload) = Body::channel();
+    let pieces: Vec<Vec<u8>> = source.chunks(10_000).map(<[u8]>::to_vec).collect();
+    tokio::spawn(async move {
+        for piece in pieces {
+            sender.send_data(piece.into()).await.unwrap();
+        }
+    });
+    let response = send(Method::PUT, &url, &[], upload).await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let etag = header(&response, "etag").to_owned();
+
+    let system = server.system.read().await;
+    assert_eq!(
+        system.read(&"docs/sample.pdf".to_owned()).await.unwrap(),
+        source
+    );
+    drop(system);
+
+    let response = send(Method::GET, &url, &[], Body::empty()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(header(&response, "etag"), etag);
+    assert_eq!(header(&response, "accept-ranges"), "bytes");
+    assert_eq!(body(response).await, source);
+
+    let response = send(Method::HEAD, &url, &[], Body::empty()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        header(&response, CONTENT_LENGTH.as_str()),
+        source.len().to_string()
+    );
+    assert_eq!(header(&response, ETAG.as_str()), etag);
+    assert!(body(response).await.is_empty());
+
+    let response = send(Method::DELETE, &url, &[], Body::empty()).await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = send(Method::GET, &url, &[], Body::empty()).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    // Deleting is idempotent.
+    let response = send(Method::DELETE, &url, &[], Body::empty()).await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn it_serves_byte_ranges() {
+    let server = serve();
+    let url = format!("{}/sample.docx", server.url);
+    let source = fs::read("tests/fixtures/file-sample_1MB.docx").unwrap();
+    send(Method::PUT, &url, &[], source.clone()).await;
+
+    let size = source.len();
+    let cases = [
+        ("bytes=0-99", 0..100),
+        ("bytes=300000-400000", 300_000..400_001),
+        ("bytes=1000000-", 1_000_000..size),
+        ("bytes=-5000", size - 5000..size),
+    ];
+    for (range, expected) in cases {
+        let response = send(Method::GET, &url, &[(RANGE.as_str(), range)], Body::empty()).await;
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT, "{range}");
+        assert_eq!(
+            header(&response, CONTENT_RANGE.as_str()),
+            format!("bytes {}-{}/{size}", expected.start, expected.end - 1)
+        );
+        assert_eq!(body(response).await, source[expected]);
+    }
+
+    let unsatisfiable = format!("bytes={size}-");
+    let response = send(
+        Method::GET,
+        &url,
+        &[(RANGE.as_str(), &unsatisfiable)],
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+    assert_eq!(
+        header(&response, CONTENT_RANGE.as_str()),
+        format!("bytes */{size}")
+    );
+
+    // Several ranges at once are not supported, so the whole file is sent.
+    let response = send(
+        Method::GET,
+        &url,
+        &[(RANGE.as_str(), "bytes=0-1,5-6")],
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(body(response).await, source);
+}
+
+#[tokio::test]
+async fn etags_follow_the_contents() {
+    let server = serve();
+    let url = format!("{}/greeting", server.url);
+
+    let etag_of = |response: Response<Body>| header(&response, "etag").to_owned();
+    let first = etag_of(send(Method::PUT, &url, &[], "Hello World!").await);
+    let same = etag_of(send(Method::PUT, &url, &[], "Hello World!").await);
+    let other = etag_of(send(Method::PUT, &url, &[], "Goodbye World!").await);
+    assert_eq!(first, same);
+    assert_ne!(first, other);
+
+    let response = send(
+        Method::GET,
+        &url,
+        &[(IF_NONE_MATCH.as_str(), &other)],
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert!(body(response).await.is_empty());
+
+    let response = send(
+        Method::GET,
+        &url,
+        &[(IF_NONE_MATCH.as_str(), &first)],
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(body(response).await, b"Goodbye World!");
+}
+
+#[tokio::test]
+async fn it_rejects_invalid_requests() {
+    let server = serve();
+    let root = server.url.trim_end_matches("/objects");
+
+    let response = send(Method::GET, &format!("{root}/files/a"), &[], Body::empty()).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = send(Method::POST, &format!("{}/a", server.url), &[], "a").await;
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(header(&response, "allow"), "GET, HEAD, PUT, DELETE");
+
+    let response = send(
+        Method::GET,
+        &format!("{}/%FF", server.url),
+        &[],
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn it_uploads_side_by_side_to_shared_chunk_stores() {
+    let dir = tempfile::tempdir().unwrap();
+    let system = Arc::new(RwLock::new(System::new(
+        FileChunkStore::new(dir.path()).unwrap(),
+        MemoryMetaStore::<String>::new(),
+        BuildWyHasher::default(),
+    )));
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/objects", listener.local_addr().unwrap());
+    let task = tokio::spawn(async move { http::serve(listener, system).await.unwrap() });
+
+    let sources: Vec<Vec<u8>> = (0..4u8)
+        .map(|i| (0..3_000_000u32).map(|j| (j % 251) as u8 ^ i).collect())
+        .collect();
+    let uploads: Vec<_> = sources
+        .iter()
+        .enumerate()
+        .map(|(i, source)| {
+            let url = format!("{url}/{i}");
+            let source = source.clone();
+            tokio::spawn(async move { send(Method::PUT, &url, &[], source).await })
+        })
+        .collect();
+    for upload in uploads {
+        assert_eq!(upload.await.unwrap().status(), StatusCode::NO_CONTENT);
+    }
+
+    for (i, source) in sources.iter().enumerate() {
+        let response = send(Method::GET, &format!("{url}/{i}"), &[], Body::empty()).await;
+        assert_eq!(&body(response).await, source);
+    }
+    task.abort();
+}
+
+#[tokio::test]
+async fn it_hides_internal_errors() {
+    let (logs, _guard) = Logs::capture();
+    let chunk_server = ChunkServer::bind("127.0.0.1:0", MemoryChunkStore::new()).unwrap();
+    let chunks = RemoteChunkStore::new(chunk_server.local_addr()).unwrap();
+    let system = Arc::new(RwLock::new(System::new(
+        chunks,
+        MemoryMetaStore::<String>::new(),
+        BuildWyHasher::default(),
+    )));
+    drop(chunk_server);
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/objects/a", listener.local_addr().unwrap());
+    let task = tokio::spawn(async move { http::serve(listener, system).await.unwrap() });
+
+    let response = send(Method::PUT, &url, &[], "Hello World!").await;
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(body(response).await, b"Internal server error");
+    let logs = logs.contents();
+    assert!(logs.contains("Request failed: ChunkStore"), "{logs}");
+    task.abort();
+}
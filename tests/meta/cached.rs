@@ -0,0 +1,158 @@
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use with_postgres_ready::with_postgres_ready;
+
+use cdcfs::{
+    meta::{CachedMetaStore, Error, Meta, MetaStore, Result, Subscription},
+    MemoryMetaStore, PostgresMetaStore,
+};
+
+use super::meta;
+
+/// Memory store counting the reads that reach it.
+#[derive(Debug, Default)]
+struct CountingMetaStore {
+    inner: MemoryMetaStore<i32>,
+    gets: AtomicUsize,
+}
+
+impl CountingMetaStore {
+    fn gets(&self) -> usize {
+        self.gets.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait]
+impl MetaStore for CountingMetaStore {
+    type Key = i32;
+
+    async fn get(&self, key: &i32) -> Result<Meta> {
+        self.gets.fetch_add(1, Ordering::SeqCst);
+        self.inner.get(key).await
+    }
+
+    async fn upsert(&mut self, key: &i32, meta: Meta) -> Result<()> {
+        self.inner.upsert(key, meta).await
+    }
+
+    async fn remove(&mut self, key: &i32) -> Result<()> {
+        self.inner.remove(key).await
+    }
+
+    async fn list(&self) -> Result<Vec<(i32, Meta)>> {
+        self.inner.list().await
+    }
+
+    async fn copy(&mut self, from: &i32, to: &i32) -> Result<()> {
+        self.inner.copy(from, to).await
+    }
+
+    async fn subscribe(&self) -> Result<Subscription<i32>> {
+        self.inner.subscribe().await
+    }
+}
+
+#[tokio::test]
+async fn it_serves_repeated_reads_from_the_cache() {
+    let mut store =
+        CachedMetaStore::new(CountingMetaStore::default(), Duration::from_secs(60), 100);
+    store.upsert(&1, meta(1)).await.unwrap();
+
+    for _ in 0..3 {
+        assert_eq!(store.get(&1).await.unwrap(), meta(1));
+    }
+    assert_eq!(store.inner().gets(), 1);
+    assert_eq!(store.cached(), 1);
+}
+
+#[tokio::test]
+async fn it_invalidates_on_upsert_and_remove() {
+    let mut store =
+        CachedMetaStore::new(CountingMetaStore::default(), Duration::from_secs(60), 100);
+    store.upsert(&1, meta(1)).await.unwrap();
+    store.get(&1).await.unwrap();
+
+    store.upsert(&1, meta(2)).await.unwrap();
+    assert_eq!(store.get(&1).await.unwrap(), meta(2));
+
+    store.remove(&1).await.unwrap();
+    assert!(matches!(store.get(&1).await, Err(Error::NotFound)));
+    assert_eq!(store.cached(), 0);
+}
+
+#[tokio::test]
+async fn it_expires_cached_metas() {
+    let mut store =
+        CachedMetaStore::new(CountingMetaStore::default(), Duration::from_millis(50), 100);
+    store.upsert(&1, meta(1)).await.unwrap();
+    store.get(&1).await.unwrap();
+    store.get(&1).await.unwrap();
+    assert_eq!(store.inner().gets(), 1);
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    store.get(&1).await.unwrap();
+    assert_eq!(store.inner().gets(), 2);
+}
+
+#[tokio::test]
+async fn it_evicts_least_recently_used_metas() {
+    let mut store = CachedMetaStore::new(CountingMetaStore::default(), Duration::from_secs(60), 2);
+    for key in 1..=3 {
+        store.upsert(&key, meta(key as usize)).await.unwrap();
+    }
+    store.get(&1).await.unwrap();
+    store.get(&2).await.unwrap();
+    store.get(&1).await.unwrap();
+    store.get(&3).await.unwrap();
+    assert_eq!(store.cached(), 2);
+    assert_eq!(store.inner().gets(), 3);
+
+    store.get(&1).await.unwrap();
+    assert_eq!(store.inner().gets(), 3);
+    store.get(&2).await.unwrap();
+    assert_eq!(store.inner().gets(), 4);
+}
+
+#[test]
+fn it_invalidates_on_notifications_from_other_stores() {
+    with_postgres_ready(|url| async move {
+        let cached = CachedMetaStore::new(
+            PostgresMetaStore::new(&url).await.unwrap(),
+            Duration::from_secs(3600),
+            100,
+        )
+        .with_notifications()
+        .await
+        .unwrap();
+        let mut other = PostgresMetaStore::new(&url).await.unwrap();
+
+        other.upsert(&1, meta(1)).await.unwrap();
+        assert_eq!(cached.get(&1).await.unwrap(), meta(1));
+
+        other.upsert(&1, meta(2)).await.unwrap();
+        let mut updated = false;
+        for _ in 0..100 {
+            if cached.get(&1).await.unwrap() == meta(2) {
+                updated = true;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert!(updated);
+
+        other.remove(&1).await.unwrap();
+        let mut removed = false;
+        for _ in 0..100 {
+            if matches!(cached.get(&1).await, Err(Error::NotFound)) {
+                removed = true;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert!(removed);
+    });
+}
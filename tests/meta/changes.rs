@@ -0,0 +1,179 @@
+use std::time::Duration;
+
+use sqlx::PgPool;
+use with_postgres_ready::with_postgres_ready;
+
+use cdcfs::{
+    meta::{Change, ChangeKind, Error, MetaStore, Subscription},
+    FileMetaStore, MemoryMetaStore, PostgresMetaStore,
+};
+
+use super::meta;
+
+async fn recv(subscription: &mut Subscription<i32>) -> Change<i32> {
+    tokio::time::timeout(Duration::from_secs(10), subscription.recv())
+        .await
+        .expect("No change within 10 seconds")
+        .unwrap()
+}
+
+/// Creates, updates, copies and deletes files and checks the resulting
+/// changes, returning the sequence of the last one.
+async fn assert_records_changes(
+    store: &mut impl MetaStore<Key = i32>,
+    subscription: &mut Subscription<i32>,
+) -> u64 {
+    store.upsert(&1, meta(1)).await.unwrap();
+    store.upsert(&1, meta(2)).await.unwrap();
+    store.copy(&1, &2).await.unwrap();
+    store.remove(&1).await.unwrap();
+    // Neither of these changes anything.
+    store.remove(&1).await.unwrap();
+    assert!(matches!(store.copy(&3, &4).await, Err(Error::NotFound)));
+    store.upsert(&3, meta(3)).await.unwrap();
+
+    let created = recv(subscription).await;
+    assert_eq!(created.key, 1);
+    assert_eq!(created.kind, ChangeKind::Created);
+    assert_eq!((created.old, created.new), (None, Some(meta(1))));
+
+    let updated = recv(subscription).await;
+    assert_eq!(updated.key, 1);
+    assert_eq!(updated.kind, ChangeKind::Updated);
+    assert_eq!((updated.old, updated.new), (Some(meta(1)), Some(meta(2))));
+
+    let copied = recv(subscription).await;
+    assert_eq!(copied.key, 2);
+    assert_eq!(copied.kind, ChangeKind::Copied { from: 1 });
+    assert_eq!((copied.old, copied.new), (None, Some(meta(2))));
+
+    let deleted = recv(subscription).await;
+    assert_eq!(deleted.key, 1);
+    assert_eq!(deleted.kind, ChangeKind::Deleted);
+    assert_eq!((deleted.old, deleted.new), (Some(meta(2)), None));
+
+    let last = recv(subscription).await;
+    assert_eq!(last.key, 3);
+    assert_eq!(last.kind, ChangeKind::Created);
+
+    let sequences = [
+        created.sequence,
+        updated.sequence,
+        copied.sequence,
+        deleted.sequence,
+        last.sequence,
+    ];
+    assert!(sequences.windows(2).all(|pair| pair[0] < pair[1]));
+    last.sequence
+}
+
+#[tokio::test]
+async fn memory_records_changes() {
+    let mut store = MemoryMetaStore::new();
+    store.upsert(&9, meta(9)).await.unwrap();
+
+    let mut subscription = store.subscribe().await.unwrap();
+    assert_records_changes(&mut store, &mut subscription).await;
+}
+
+#[tokio::test]
+async fn file_records_changes() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut store = FileMetaStore::new(dir.path()).unwrap();
+    store.upsert(&9, meta(9)).await.unwrap();
+
+    let mut subscription = store.subscribe().await.unwrap();
+    assert_records_changes(&mut store, &mut subscription).await;
+}
+
+#[tokio::test]
+async fn memory_reports_lagging_subscribers() {
+    let mut store = MemoryMetaStore::new();
+    let mut subscription = store.subscribe().await.unwrap();
+    for id in 0..2000 {
+        store.upsert(&id, meta(1)).await.unwrap();
+    }
+
+    assert!(matches!(
+        subscription.recv().await,
+        Err(Error::Lagged { missed: 976 })
+    ));
+    assert_eq!(recv(&mut subscription).await.key, 976);
+}
+
+#[test]
+fn postgres_records_changes() {
+    with_postgres_ready(|url| async move {
+        let mut store = PostgresMetaStore::new(&url).await.unwrap();
+        store.upsert(&9, meta(9)).await.unwrap();
+
+        let mut subscription = store.subscribe().await.unwrap();
+        assert_records_changes(&mut store, &mut subscription).await;
+    });
+}
+
+#[test]
+fn postgres_delivers_changes_of_other_processes() {
+    with_postgres_ready(|url| async move {
+        let store = PostgresMetaStore::new(&url).await.unwrap();
+        let mut subscription = store.subscribe().await.unwrap();
+
+        let mut other = PostgresMetaStore::new(&url).await.unwrap();
+        let last = assert_records_changes(&mut other, &mut subscription).await;
+
+        other.upsert(&4, meta(4)).await.unwrap();
+        let next = recv(&mut subscription).await;
+        assert_eq!(next.key, 4);
+        assert!(next.sequence > last);
+    });
+}
+
+#[test]
+fn postgres_does_not_serialize_writers() {
+    with_postgres_ready(|url| async move {
+        let mut store = PostgresMetaStore::new(&url).await.unwrap();
+        let mut subscription = store.subscribe().await.unwrap();
+
+        let pool = PgPool::connect(&url).await.unwrap();
+        let mut transaction = pool.begin().await.unwrap();
+        sqlx::query("INSERT INTO files (id, hashes, size) VALUES (1, '{}', 0)")
+            .execute(&mut *transaction)
+            .await
+            .unwrap();
+
+        // Would wait for the open transaction if writers took turns.
+        tokio::time::timeout(Duration::from_secs(10), store.upsert(&2, meta(2)))
+            .await
+            .expect("Write waited for another transaction")
+            .unwrap();
+        let committed_first = recv(&mut subscription).await;
+        assert_eq!(committed_first.key, 2);
+
+        transaction.commit().await.unwrap();
+        let committed_last = recv(&mut subscription).await;
+        assert_eq!(committed_last.key, 1);
+        assert!(committed_last.sequence > committed_first.sequence);
+    });
+}
+
+#[test]
+fn postgres_resumes_from_the_outbox() {
+    with_postgres_ready(|url| async move {
+        let mut store = PostgresMetaStore::new(&url).await.unwrap();
+        for id in 0..300 {
+            store.upsert(&id, meta(1)).await.unwrap();
+        }
+
+        let mut subscription = store.subscribe_after(0).await.unwrap();
+        let mut sequence = 0;
+        for id in 0..300 {
+            let change = recv(&mut subscription).await;
+            assert_eq!(change.key, id);
+            sequence = change.sequence;
+        }
+
+        assert_eq!(store.prune_changes(sequence - 100).await.unwrap(), 200);
+        let mut resumed = store.subscribe_after(0).await.unwrap();
+        assert_eq!(recv(&mut resumed).await.key, 200);
+    });
+}
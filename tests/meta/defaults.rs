@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+
+use cdcfs::{
+    meta::{Error, Meta, MetaStore, Result},
+    BuildWyHasher, MemoryChunkStore, System,
+};
+
+/// Store implementing only the required methods.
+#[derive(Debug, Default)]
+struct MinimalMetaStore(HashMap<i32, Meta>);
+
+#[async_trait]
+impl MetaStore for MinimalMetaStore {
+    type Key = i32;
+
+    async fn get(&self, key: &i32) -> Result<Meta> {
+        self.0.get(key).cloned().ok_or(Error::NotFound)
+    }
+
+    async fn upsert(&mut self, key: &i32, meta: Meta) -> Result<()> {
+        self.0.insert(*key, meta);
+        Ok(())
+    }
+
+    async fn remove(&mut self, key: &i32) -> Result<()> {
+        self.0.remove(key).map(|_| ()).ok_or(Error::NotFound)
+    }
+}
+
+#[tokio::test]
+async fn it_copies_by_reading_and_writing() {
+    let mut fs = System::new(
+        MemoryChunkStore::new(),
+        MinimalMetaStore::default(),
+        BuildWyHasher::default(),
+    );
+    fs.write(&1, b"Hello World!").await.unwrap();
+    fs.copy(&1, &2).await.unwrap();
+    assert_eq!(fs.read(&2).await.unwrap(), b"Hello World!");
+    assert!(matches!(
+        fs.copy(&3, &4).await,
+        Err(cdcfs::system::Error::MetaStore(Error::NotFound))
+    ));
+
+    assert!(fs.subscribe().await.is_err());
+}
@@ -0,0 +1,79 @@
+use std::fs;
+
+use cdcfs::{
+    meta::{Error, Meta, MetaStore},
+    FileMetaStore,
+};
+
+fn meta(size: usize) -> Meta {
+    Meta {
+        hashes: vec![10, 11, 12],
+        size,
+        sealed_keys: Some(b"sealed".to_vec()),
+        chunk_sizes: Some(vec![100, 200, 300]),
+    }
+}
+
+#[tokio::test]
+async fn it_can_read_write_and_remove() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut store = FileMetaStore::new(dir.path()).unwrap();
+    let key = "docs/report.pdf".to_owned();
+
+    assert!(matches!(store.get(&key).await, Err(Error::NotFound)));
+    store.remove(&key).await.unwrap();
+
+    store.upsert(&key, meta(600)).await.unwrap();
+    assert_eq!(store.get(&key).await.unwrap(), meta(600));
+    store.upsert(&key, meta(601)).await.unwrap();
+    assert_eq!(store.get(&key).await.unwrap(), meta(601));
+
+    store.remove(&key).await.unwrap();
+    assert!(matches!(store.get(&key).await, Err(Error::NotFound)));
+}
+
+#[tokio::test]
+async fn it_persists_metas_across_instances() {
+    let dir = tempfile::tempdir().unwrap();
+    let keys = ["a", "docs/b c.txt", ".hidden", "100%", "ünïcödé"].map(String::from);
+
+    let mut store = FileMetaStore::new(dir.path()).unwrap();
+    for (size, key) in keys.iter().enumerate() {
+        store.upsert(key, meta(size)).await.unwrap();
+    }
+    store.copy(&keys[0], &"copy".to_owned()).await.unwrap();
+    drop(store);
+
+    // Every key gets a plain file directly in the root.
+    for entry in fs::read_dir(dir.path()).unwrap() {
+        let entry = entry.unwrap();
+        assert!(entry.file_type().unwrap().is_file());
+        assert!(!entry.file_name().to_str().unwrap().starts_with('.'));
+    }
+
+    let store = FileMetaStore::<String>::new(dir.path()).unwrap();
+    let mut files = store.list().await.unwrap();
+    files.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let mut expected: Vec<_> = keys
+        .iter()
+        .enumerate()
+        .map(|(size, key)| (key.clone(), meta(size)))
+        .chain([("copy".to_owned(), meta(0))])
+        .collect();
+    expected.sort_by(|(a, _), (b, _)| a.cmp(b));
+    assert_eq!(files, expected);
+}
+
+#[tokio::test]
+async fn it_lists_integer_keys() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut store = FileMetaStore::new(dir.path()).unwrap();
+    store.upsert(&1, meta(1)).await.unwrap();
+    store.upsert(&-2, meta(2)).await.unwrap();
+    // Files that are not keys are ignored.
+    fs::write(dir.path().join("notes"), "not a meta").unwrap();
+
+    let mut files = store.list().await.unwrap();
+    files.sort_by_key(|(key, _)| *key);
+    assert_eq!(files, vec![(-2, meta(2)), (1, meta(1))]);
+}
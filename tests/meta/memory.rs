@@ -13,6 +13,8 @@ async fn it_can_read_and_write() {
     let initial_meta = Meta {
         hashes: b"Here's some stuff for hashes".map(Into::into).to_vec(),
         size: 1234,
+        sealed_keys: None,
+        chunk_sizes: None,
     };
     store.upsert(key, initial_meta.clone()).await.unwrap();
     assert_eq!(store.get(key).await.unwrap(), initial_meta);
@@ -20,6 +22,8 @@ async fn it_can_read_and_write() {
     let updated_meta = Meta {
         hashes: b"Here's some stuff other stuff".map(Into::into).to_vec(),
         size: 4321,
+        sealed_keys: None,
+        chunk_sizes: None,
     };
     store.upsert(key, updated_meta.clone()).await.unwrap();
     assert_eq!(store.get(key).await.unwrap(), updated_meta);
@@ -36,6 +40,8 @@ async fn it_can_remove_meta() {
     let meta = Meta {
         hashes: [10; 20].into(),
         size: 1234,
+        sealed_keys: None,
+        chunk_sizes: None,
     };
     store.upsert(key, meta.clone()).await.unwrap();
     assert_eq!(store.get(key).await.unwrap(), meta);
@@ -45,3 +51,21 @@ async fn it_can_remove_meta() {
 
     store.remove(key).await.unwrap();
 }
+
+#[tokio::test]
+async fn it_can_list_meta() {
+    let mut store = MemoryMetaStore::new();
+    assert!(store.list().await.unwrap().is_empty());
+
+    let meta = Meta {
+        hashes: [10; 20].into(),
+        size: 1234,
+        sealed_keys: None,
+        chunk_sizes: None,
+    };
+    store.upsert(&1, meta.clone()).await.unwrap();
+    store.upsert(&2, meta.clone()).await.unwrap();
+    store.remove(&1).await.unwrap();
+
+    assert_eq!(store.list().await.unwrap(), vec![(2, meta)]);
+}
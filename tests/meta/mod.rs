@@ -1,3 +1,19 @@
+mod cached;
+mod changes;
+mod defaults;
+mod file;
 mod memory;
 mod postgres;
 mod proptest;
+
+use cdcfs::meta::Meta;
+
+/// Meta of a file of `size` bytes, made of the same chunk repeated.
+fn meta(size: usize) -> Meta {
+    Meta {
+        hashes: vec![10; 20],
+        size,
+        sealed_keys: None,
+        chunk_sizes: None,
+    }
+}
@@ -18,6 +18,8 @@ fn it_can_read_and_write() {
         let initial_meta = Meta {
             hashes: b"Here's some stuff for hashes".map(Into::into).to_vec(),
             size: 1234,
+            sealed_keys: None,
+            chunk_sizes: None,
         };
         store.upsert(key, initial_meta.clone()).await.unwrap();
         let value = store.get(key).await.unwrap();
@@ -26,6 +28,8 @@ fn it_can_read_and_write() {
         let updated_meta = Meta {
             hashes: b"Here's some stuff other stuff".map(Into::into).to_vec(),
             size: 4321,
+            sealed_keys: Some(b"Some sealed keys".to_vec()),
+            chunk_sizes: Some(vec![1000, 3321]),
         };
         store.upsert(key, updated_meta.clone()).await.unwrap();
         let value = store.get(key).await.unwrap();
@@ -46,6 +50,8 @@ fn it_can_remove_meta() {
         let meta = Meta {
             hashes: [10; 20].into(),
             size: 1234,
+            sealed_keys: None,
+            chunk_sizes: None,
         };
         store.upsert(key, meta.clone()).await.unwrap();
         assert_eq!(store.get(key).await.unwrap(), meta);
@@ -56,3 +62,24 @@ fn it_can_remove_meta() {
         store.remove(key).await.unwrap();
     });
 }
+
+#[test]
+fn it_can_list_meta() {
+    with_postgres_ready(|url| async move {
+        let mut store = PostgresMetaStore::new(&url).await.unwrap();
+
+        let meta = Meta {
+            hashes: [10; 20].into(),
+            size: 1234,
+            sealed_keys: None,
+            chunk_sizes: None,
+        };
+        store.upsert(&21, meta.clone()).await.unwrap();
+        store.upsert(&20, meta.clone()).await.unwrap();
+        store.upsert(&22, meta.clone()).await.unwrap();
+        store.remove(&22).await.unwrap();
+
+        let listed = store.list().await.unwrap();
+        assert_eq!(listed, vec![(20, meta.clone()), (21, meta)]);
+    });
+}
@@ -16,7 +16,7 @@ proptest! {
             for operation in operations.0.iter() {
                 match operation {
                     Operation::Upsert(id, hashes) => {
-                        let meta = Meta { hashes: hashes.clone(), size: 0 };
+                        let meta = Meta { hashes: hashes.clone(), size: 0, sealed_keys: None, chunk_sizes: None };
                         let mem = memory_meta_store.upsert(id, meta.clone()).await.map_err(|e|format!("{e:?}"));
                         let red = postgres_meta_store.upsert(id, meta.clone()).await.map_err(|e|format!("{e:?}"));
                         assert_eq!(mem, red);
@@ -31,6 +31,12 @@ proptest! {
                         let postgres_meta = postgres_meta_store.remove(id).await.map_err(|e|format!("{e:?}"));
                         assert_eq!(memory_meta, postgres_meta);
                     },
+                    Operation::List => {
+                        let mut memory_list = memory_meta_store.list().await.unwrap();
+                        memory_list.sort_by_key(|(id, _)| *id);
+                        let postgres_list = postgres_meta_store.list().await.unwrap();
+                        assert_eq!(memory_list, postgres_list);
+                    },
                 }
             }
         });
@@ -42,6 +48,7 @@ enum Operation {
     Upsert(i32, Vec<u64>),
     Get(i32),
     Remove(i32),
+    List,
 }
 
 #[derive(Debug, Clone)]
@@ -52,7 +59,7 @@ impl Arbitrary for Operations {
     type Strategy = BoxedStrategy<Self>;
 
     fn arbitrary_with(_args: Self::Parameters) -> Self::Strategy {
-        let operations = vec![1, 2, 3];
+        let operations = vec![1, 2, 3, 4];
         (
             prop::collection::vec(any::<i32>(), 10..50),
             prop::collection::vec(
@@ -72,6 +79,7 @@ impl Arbitrary for Operations {
                             1 => Operation::Upsert(ids[idx.index(ids.len())], hashes.to_owned()),
                             2 => Operation::Get(ids[idx.index(ids.len())]),
                             3 => Operation::Remove(ids[idx.index(ids.len())]),
+                            4 => Operation::List,
                             _ => unreachable!(),
                         })
                         .collect(),
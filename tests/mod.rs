@@ -1,4 +1,8 @@
+mod analyze;
 mod chunks;
+mod cli;
+mod http;
 mod meta;
+mod s3;
 mod system;
 mod utils;
@@ -0,0 +1,62 @@
+mod multipart;
+mod objects;
+
+use std::{net::TcpListener, sync::Arc};
+
+use aws_sdk_s3::{
+    config::{BehaviorVersion, Credentials, Region},
+    Client,
+};
+use tokio::{sync::RwLock, task::JoinHandle};
+
+use cdcfs::{
+    http::SharedSystem,
+    s3::{self, Buckets},
+    BuildWyHasher, MemoryChunkStore, MemoryMetaStore, System,
+};
+
+type TestSystem = SharedSystem<MemoryChunkStore, MemoryMetaStore<String>, BuildWyHasher>;
+
+struct Server {
+    client: Client,
+    system: TestSystem,
+    task: JoinHandle<()>,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Serves bucket `photos` from the `photos/` prefix and `docs` from
+/// `shared/docs/`.
+fn serve() -> Server {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    let system = Arc::new(RwLock::new(System::new(
+        MemoryChunkStore::new(),
+        MemoryMetaStore::new(),
+        BuildWyHasher::default(),
+    )));
+    let buckets = Buckets::new()
+        .with_bucket("photos", "photos/")
+        .with_bucket("docs", "shared/docs/");
+    let task = {
+        let system = Arc::clone(&system);
+        tokio::spawn(async move { s3::serve(listener, system, buckets).await.unwrap() })
+    };
+
+    let config = aws_sdk_s3::Config::builder()
+        .behavior_version(BehaviorVersion::latest())
+        .endpoint_url(endpoint)
+        .region(Region::new("us-east-1"))
+        .credentials_provider(Credentials::new("cdcfs", "cdcfs", None, None, "test"))
+        .force_path_style(true)
+        .build();
+    Server {
+        client: Client::from_conf(config),
+        system,
+        task,
+    }
+}
@@ -0,0 +1,188 @@
+use std::fs;
+
+use aws_sdk_s3::{
+    primitives::ByteStream,
+    types::{CompletedMultipartUpload, CompletedPart},
+};
+
+use cdcfs::meta::MetaStore;
+
+use super::{serve, Server};
+
+/// Starts an upload and sends `parts` as parts 1, 2 and so on.
+async fn upload_parts(server: &Server, key: &str, parts: &[&[u8]]) -> (String, Vec<CompletedPart>) {
+    let upload = server
+        .client
+        .create_multipart_upload()
+        .bucket("docs")
+        .key(key)
+        .send()
+        .await
+        .unwrap();
+    let upload_id = upload.upload_id().unwrap().to_owned();
+
+    let mut completed = vec![];
+    for (idx, part) in parts.iter().enumerate() {
+        let part_number = idx as i32 + 1;
+        let uploaded = server
+            .client
+            .upload_part()
+            .bucket("docs")
+            .key(key)
+            .upload_id(&upload_id)
+            .part_number(part_number)
+            .body(ByteStream::from(part.to_vec()))
+            .send()
+            .await
+            .unwrap();
+        completed.push(
+            CompletedPart::builder()
+                .part_number(part_number)
+                .e_tag(uploaded.e_tag().unwrap())
+                .build(),
+        );
+    }
+    (upload_id, completed)
+}
+
+async fn keys(server: &Server) -> Vec<String> {
+    let files = server
+        .system
+        .read()
+        .await
+        .meta_store()
+        .list()
+        .await
+        .unwrap();
+    let mut keys: Vec<_> = files.into_iter().map(|(key, _)| key).collect();
+    keys.sort();
+    keys
+}
+
+#[tokio::test]
+async fn it_completes_multipart_uploads() {
+    let server = serve();
+    let source = fs::read("tests/fixtures/file-example_PDF_1MB.pdf").unwrap();
+    let parts: Vec<&[u8]> = source.chunks(400_000).collect();
+    let (upload_id, completed) = upload_parts(&server, "big.pdf", &parts).await;
+
+    // Parts are hidden from listings until the upload completes.
+    let listing = server
+        .client
+        .list_objects_v2()
+        .bucket("docs")
+        .send()
+        .await
+        .unwrap();
+    assert!(listing.contents().is_empty());
+
+    let result = server
+        .client
+        .complete_multipart_upload()
+        .bucket("docs")
+        .key("big.pdf")
+        .upload_id(&upload_id)
+        .multipart_upload(
+            CompletedMultipartUpload::builder()
+                .set_parts(Some(completed))
+                .build(),
+        )
+        .send()
+        .await
+        .unwrap();
+
+    let object = server
+        .client
+        .get_object()
+        .bucket("docs")
+        .key("big.pdf")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(object.e_tag(), result.e_tag());
+    assert_eq!(object.body.collect().await.unwrap().to_vec(), source);
+    assert_eq!(keys(&server).await, ["shared/docs/big.pdf"]);
+}
+
+#[tokio::test]
+async fn it_checks_completed_parts() {
+    let server = serve();
+    let (upload_id, mut completed) =
+        upload_parts(&server, "notes.txt", &[b"Hello ", b"World!"]).await;
+
+    let complete = |parts: Vec<CompletedPart>| {
+        server
+            .client
+            .complete_multipart_upload()
+            .bucket("docs")
+            .key("notes.txt")
+            .upload_id(&upload_id)
+            .multipart_upload(
+                CompletedMultipartUpload::builder()
+                    .set_parts(Some(parts))
+                    .build(),
+            )
+            .send()
+    };
+
+    let reversed = completed.iter().rev().cloned().collect();
+    let err = complete(reversed).await.unwrap_err();
+    assert_eq!(
+        err.into_service_error().meta().code(),
+        Some("InvalidPartOrder")
+    );
+
+    let wrong_etag = CompletedPart::builder()
+        .part_number(2)
+        .e_tag("\"0123\"")
+        .build();
+    let err = complete(vec![completed[0].clone(), wrong_etag])
+        .await
+        .unwrap_err();
+    assert_eq!(err.into_service_error().meta().code(), Some("InvalidPart"));
+
+    // Parts may be left out.
+    completed.truncate(1);
+    complete(completed).await.unwrap();
+    let object = server
+        .client
+        .get_object()
+        .bucket("docs")
+        .key("notes.txt")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(object.body.collect().await.unwrap().to_vec(), b"Hello ");
+    assert_eq!(keys(&server).await, ["shared/docs/notes.txt"]);
+}
+
+#[tokio::test]
+async fn it_aborts_multipart_uploads() {
+    let server = serve();
+    let (upload_id, _) = upload_parts(&server, "draft.txt", &[b"Draft"]).await;
+    assert_eq!(keys(&server).await.len(), 1);
+
+    server
+        .client
+        .abort_multipart_upload()
+        .bucket("docs")
+        .key("draft.txt")
+        .upload_id(&upload_id)
+        .send()
+        .await
+        .unwrap();
+    assert!(keys(&server).await.is_empty());
+
+    let err = server
+        .client
+        .upload_part()
+        .bucket("docs")
+        .key("draft.txt")
+        .upload_id(&upload_id)
+        .part_number(2)
+        .body(ByteStream::from_static(b"More"))
+        .send()
+        .await
+        .unwrap_err();
+    assert!(err.into_service_error().meta().code() == Some("NoSuchUpload"));
+}
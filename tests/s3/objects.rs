@@ -0,0 +1,333 @@
+use std::{fs, net::TcpListener, sync::Arc};
+
+use aws_sdk_s3::primitives::ByteStream;
+use hyper::{body::to_bytes, Body, Method, Request, StatusCode};
+use tokio::sync::RwLock;
+
+use cdcfs::{
+    chunks::{ChunkServer, ChunkStore},
+    meta::MetaStore,
+    s3::{self, Buckets},
+    BuildWyHasher, MemoryChunkStore, MemoryMetaStore, RemoteChunkStore, System,
+};
+
+use super::serve;
+use crate::utils::Logs;
+
+async fn put(server: &super::Server, bucket: &str, key: &str, body: impl Into<Vec<u8>>) {
+    server
+        .client
+        .put_object()
+        .bucket(bucket)
+        .key(key)
+        .body(ByteStream::from(body.into()))
+        .send()
+        .await
+        .unwrap();
+}
+
+async fn get(server: &super::Server, bucket: &str, key: &str) -> Vec<u8> {
+    let object = server
+        .client
+        .get_object()
+        .bucket(bucket)
+        .key(key)
+        .send()
+        .await
+        .unwrap();
+    object.body.collect().await.unwrap().to_vec()
+}
+
+#[tokio::test]
+async fn it_serves_objects() {
+    let server = serve();
+    let source = fs::read("tests/fixtures/file-example_PDF_1MB.pdf").unwrap();
+
+    let put = server
+        .client
+        .put_object()
+        .bucket("docs")
+        .key("reports/2023 summary.pdf")
+        .body(ByteStream::from(source.clone()))
+        .send()
+        .await
+        .unwrap();
+    let etag = put.e_tag().unwrap().to_owned();
+
+    // Buckets map to key prefixes.
+    let system = server.system.read().await;
+    let path = "shared/docs/reports/2023 summary.pdf".to_owned();
+    assert_eq!(system.read(&path).await.unwrap(), source);
+    drop(system);
+
+    assert_eq!(
+        get(&server, "docs", "reports/2023 summary.pdf").await,
+        source
+    );
+
+    let head = server
+        .client
+        .head_object()
+        .bucket("docs")
+        .key("reports/2023 summary.pdf")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(head.content_length(), Some(source.len() as i64));
+    assert_eq!(head.e_tag(), Some(etag.as_str()));
+
+    let range = server
+        .client
+        .get_object()
+        .bucket("docs")
+        .key("reports/2023 summary.pdf")
+        .range("bytes=100000-100099")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(range.content_range(), Some("bytes 100000-100099/1042157"));
+    let range = range.body.collect().await.unwrap().to_vec();
+    assert_eq!(range, source[100_000..100_100]);
+
+    server
+        .client
+        .delete_object()
+        .bucket("docs")
+        .key("reports/2023 summary.pdf")
+        .send()
+        .await
+        .unwrap();
+    let err = server
+        .client
+        .get_object()
+        .bucket("docs")
+        .key("reports/2023 summary.pdf")
+        .send()
+        .await
+        .unwrap_err();
+    assert!(err.into_service_error().is_no_such_key());
+    let err = server
+        .client
+        .head_object()
+        .bucket("docs")
+        .key("reports/2023 summary.pdf")
+        .send()
+        .await
+        .unwrap_err();
+    assert!(err.into_service_error().is_not_found());
+}
+
+#[tokio::test]
+async fn it_accepts_streamed_uploads() {
+    let server = serve();
+    let source = fs::read("tests/fixtures/file_example_JPG_2500kB.jpg").unwrap();
+
+    // Bodies read from files are sent with the aws-chunked encoding.
+    let body = ByteStream::from_path("tests/fixtures/file_example_JPG_2500kB.jpg")
+        .await
+        .unwrap();
+    server
+        .client
+        .put_object()
+        .bucket("photos")
+        .key("beach.jpg")
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(get(&server, "photos", "beach.jpg").await, source);
+}
+
+#[tokio::test]
+async fn it_copies_objects_without_copying_chunks() {
+    let server = serve();
+    let source = fs::read("tests/fixtures/file-sample_1MB.docx").unwrap();
+    put(&server, "docs", "draft.docx", source.clone()).await;
+    let chunks = server.system.read().await.chunk_store().hashes().unwrap();
+
+    let copy = server
+        .client
+        .copy_object()
+        .copy_source("docs/draft.docx")
+        .bucket("photos")
+        .key("backup/draft.docx")
+        .send()
+        .await
+        .unwrap();
+    let etag = copy.copy_object_result().unwrap().e_tag().unwrap();
+
+    assert_eq!(get(&server, "photos", "backup/draft.docx").await, source);
+    assert_eq!(get(&server, "docs", "draft.docx").await, source);
+    let system = server.system.read().await;
+    assert_eq!(system.chunk_store().hashes().unwrap().len(), chunks.len());
+
+    let head = server
+        .client
+        .head_object()
+        .bucket("photos")
+        .key("backup/draft.docx")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(head.e_tag(), Some(etag));
+}
+
+#[tokio::test]
+async fn it_lists_objects() {
+    let server = serve();
+    for key in [
+        "2023/01/a.jpg",
+        "2023/01/b.jpg",
+        "2023/02/c.jpg",
+        "2024/d.jpg",
+        "e.jpg",
+        "f g.jpg",
+    ] {
+        put(&server, "photos", key, key).await;
+    }
+    // Files outside the bucket's prefix are not part of it.
+    put(&server, "docs", "2023/notes.txt", "notes").await;
+
+    let listing = server
+        .client
+        .list_objects_v2()
+        .bucket("photos")
+        .send()
+        .await
+        .unwrap();
+    let keys: Vec<_> = listing.contents().iter().filter_map(|o| o.key()).collect();
+    assert_eq!(
+        keys,
+        [
+            "2023/01/a.jpg",
+            "2023/01/b.jpg",
+            "2023/02/c.jpg",
+            "2024/d.jpg",
+            "e.jpg",
+            "f g.jpg"
+        ]
+    );
+    assert_eq!(listing.contents()[0].size(), Some(13));
+    assert_eq!(listing.key_count(), Some(6));
+
+    let listing = server
+        .client
+        .list_objects_v2()
+        .bucket("photos")
+        .prefix("2023/")
+        .delimiter("/")
+        .send()
+        .await
+        .unwrap();
+    let prefixes: Vec<_> = listing
+        .common_prefixes()
+        .iter()
+        .filter_map(|p| p.prefix())
+        .collect();
+    assert_eq!(prefixes, ["2023/01/", "2023/02/"]);
+    assert!(listing.contents().is_empty());
+
+    // Pages through keys and common prefixes alike.
+    let mut entries = vec![];
+    let mut token = None;
+    loop {
+        let page = server
+            .client
+            .list_objects_v2()
+            .bucket("photos")
+            .delimiter("/")
+            .max_keys(2)
+            .set_continuation_token(token)
+            .send()
+            .await
+            .unwrap();
+        assert!(page.key_count().unwrap() <= 2);
+        entries.extend(
+            page.common_prefixes()
+                .iter()
+                .filter_map(|p| p.prefix())
+                .map(str::to_owned),
+        );
+        entries.extend(
+            page.contents()
+                .iter()
+                .filter_map(|o| o.key())
+                .map(str::to_owned),
+        );
+        token = page.next_continuation_token().map(str::to_owned);
+        if !page.is_truncated().unwrap() {
+            break;
+        }
+    }
+    entries.sort();
+    assert_eq!(entries, ["2023/", "2024/", "e.jpg", "f g.jpg"]);
+
+    let listing = server
+        .client
+        .list_objects_v2()
+        .bucket("photos")
+        .start_after("2024/d.jpg")
+        .send()
+        .await
+        .unwrap();
+    let keys: Vec<_> = listing.contents().iter().filter_map(|o| o.key()).collect();
+    assert_eq!(keys, ["e.jpg", "f g.jpg"]);
+}
+
+#[tokio::test]
+async fn it_rejects_unknown_buckets() {
+    let server = serve();
+    let err = server
+        .client
+        .put_object()
+        .bucket("music")
+        .key("song.ogg")
+        .body(ByteStream::from_static(b"la la la"))
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(err.into_service_error().meta().code(), Some("NoSuchBucket"));
+
+    let files = server
+        .system
+        .read()
+        .await
+        .meta_store()
+        .list()
+        .await
+        .unwrap();
+    assert!(files.is_empty());
+}
+
+#[tokio::test]
+async fn it_logs_internal_errors_instead_of_returning_them() {
+    let (logs, _guard) = Logs::capture();
+    let chunk_server = ChunkServer::bind("127.0.0.1:0", MemoryChunkStore::new()).unwrap();
+    let chunks = RemoteChunkStore::new(chunk_server.local_addr()).unwrap();
+    let system = Arc::new(RwLock::new(System::new(
+        chunks,
+        MemoryMetaStore::<String>::new(),
+        BuildWyHasher::default(),
+    )));
+    drop(chunk_server);
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/photos/a.jpg", listener.local_addr().unwrap());
+    let buckets = Buckets::new().with_bucket("photos", "photos/");
+    let task = tokio::spawn(async move { s3::serve(listener, system, buckets).await.unwrap() });
+
+    let request = Request::builder()
+        .method(Method::PUT)
+        .uri(url)
+        .body(Body::from("Hello World!"))
+        .unwrap();
+    let response = hyper::Client::new().request(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    let body = String::from_utf8(to_bytes(response).await.unwrap().to_vec()).unwrap();
+    assert!(body.contains("<Code>InternalError</Code>"), "{body}");
+    assert!(!body.contains("Network error"), "{body}");
+    let logs = logs.contents();
+    assert!(logs.contains("Network error"), "{logs}");
+    task.abort();
+}
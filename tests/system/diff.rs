@@ -0,0 +1,134 @@
+use std::{fs, ops::Range};
+
+use cdcfs::{
+    meta::{self, Meta, MetaStore},
+    system::{DiffSide, Error},
+    System,
+};
+
+use super::system;
+
+fn bounds(ranges: &[Range<usize>]) -> Vec<(usize, usize)> {
+    ranges
+        .iter()
+        .map(|range| (range.start, range.end))
+        .collect()
+}
+
+/// Asserts that the ranges of `side` cover the file without gaps or overlap.
+fn assert_covers(side: &DiffSide) {
+    let mut ranges: Vec<Range<usize>> =
+        side.shared.iter().chain(&side.differing).cloned().collect();
+    ranges.sort_by_key(|range| range.start);
+    let mut offset = 0;
+    for range in ranges {
+        assert_eq!(range.start, offset);
+        assert!(range.end > range.start);
+        offset = range.end;
+    }
+    assert_eq!(offset, side.size);
+}
+
+#[tokio::test]
+async fn it_finds_edited_ranges() {
+    let mut fs = system();
+    let original = fs::read("tests/fixtures/file-example_PDF_1MB.pdf").unwrap();
+    let mut edited = original.clone();
+    edited.splice(500_000..500_100, b"Some edit".repeat(1000));
+    fs.write(&1, &original).await.unwrap();
+    fs.write(&2, &edited).await.unwrap();
+
+    let diff = fs.diff(&1, &2).await.unwrap();
+    assert_eq!(diff.a.size, original.len());
+    assert_eq!(diff.b.size, edited.len());
+    assert_covers(&diff.a);
+    assert_covers(&diff.b);
+
+    // Only the chunks around the edit differ.
+    assert_eq!(diff.a.differing.len(), 1);
+    assert_eq!(diff.b.differing.len(), 1);
+    let (a, b) = (&diff.a.differing[0], &diff.b.differing[0]);
+    assert!(a.start <= 500_000 && a.end >= 500_100, "{a:?}");
+    assert!(b.start <= 500_000 && b.end >= 509_000, "{b:?}");
+    assert_eq!(original[..a.start], edited[..b.start]);
+    assert_eq!(original[a.end..], edited[b.end..]);
+    assert!(diff.similarity() > 0.9);
+}
+
+#[tokio::test]
+async fn it_compares_identical_and_unrelated_files() {
+    let mut fs = system();
+    let pdf = fs::read("tests/fixtures/file-example_PDF_1MB.pdf").unwrap();
+    let jpg = fs::read("tests/fixtures/file_example_JPG_2500kB.jpg").unwrap();
+    fs.write(&1, &pdf).await.unwrap();
+    fs.copy(&1, &2).await.unwrap();
+    fs.write(&3, &jpg).await.unwrap();
+    fs.write(&4, b"").await.unwrap();
+
+    let same = fs.diff(&1, &2).await.unwrap();
+    assert_eq!(bounds(&same.a.shared), [(0, pdf.len())]);
+    assert!(same.a.differing.is_empty());
+    assert_eq!(same.a, same.b);
+    assert_eq!(same.similarity(), 1.0);
+
+    let unrelated = fs.diff(&1, &3).await.unwrap();
+    assert_eq!(bounds(&unrelated.a.differing), [(0, pdf.len())]);
+    assert_eq!(bounds(&unrelated.b.differing), [(0, jpg.len())]);
+    assert_eq!(unrelated.similarity(), 0.0);
+
+    let empty = fs.diff(&4, &4).await.unwrap();
+    assert_eq!(empty.a.size, 0);
+    assert_eq!(empty.similarity(), 1.0);
+
+    assert!(matches!(
+        fs.diff(&1, &5).await,
+        Err(Error::MetaStore(meta::Error::NotFound))
+    ));
+}
+
+#[tokio::test]
+async fn it_counts_moved_content_as_shared() {
+    let mut fs = system();
+    let pdf = fs::read("tests/fixtures/file-example_PDF_1MB.pdf").unwrap();
+    let docx = fs::read("tests/fixtures/file-sample_1MB.docx").unwrap();
+    fs.write(&1, [&pdf[..], &docx[..]].concat()).await.unwrap();
+    fs.write(&2, [&docx[..], &pdf[..]].concat()).await.unwrap();
+
+    let diff = fs.diff(&1, &2).await.unwrap();
+    assert_covers(&diff.a);
+    assert_covers(&diff.b);
+    // Only the chunks around where the files meet differ.
+    assert!(diff.a.differing_bytes() < 200_000);
+    assert!(diff.similarity() > 0.8);
+}
+
+#[tokio::test]
+async fn it_reads_chunk_sizes_of_older_files() {
+    let mut fs = system();
+    let original = fs::read("tests/fixtures/file-sample_1MB.docx").unwrap();
+    let mut edited = original.clone();
+    edited.truncate(700_000);
+    fs.write(&1, &original).await.unwrap();
+    fs.write(&2, &edited).await.unwrap();
+    let expected = fs.diff(&1, &2).await.unwrap();
+
+    let (chunk_store, mut meta_store, hasher) = fs.into_parts();
+    let meta = meta_store.get(&1).await.unwrap();
+    let legacy = Meta {
+        chunk_sizes: None,
+        ..meta.clone()
+    };
+    meta_store.upsert(&1, legacy).await.unwrap();
+    let fs = System::new(chunk_store, meta_store, hasher);
+    assert_eq!(fs.diff(&1, &2).await.unwrap(), expected);
+
+    // Sizes of only some chunks.
+    let (chunk_store, mut meta_store, hasher) = fs.into_parts();
+    let truncated = Meta {
+        chunk_sizes: meta.chunk_sizes.map(|sizes| sizes[..1].to_vec()),
+        ..meta
+    };
+    meta_store.upsert(&1, truncated).await.unwrap();
+    let fs = System::new(chunk_store, meta_store, hasher);
+    assert_eq!(fs.diff(&1, &2).await.unwrap(), expected);
+}
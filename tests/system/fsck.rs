@@ -0,0 +1,149 @@
+use cdcfs::{
+    chunks::ChunkStore,
+    meta::{Meta, MetaStore},
+    system::{ConvergentEncryption, FileStatus},
+    BuildWyHasher, MemoryChunkStore, MemoryMetaStore, System,
+};
+
+async fn populated_system() -> System<MemoryChunkStore, MemoryMetaStore<i32>, BuildWyHasher> {
+    let mut fs = System::new(
+        MemoryChunkStore::new(),
+        MemoryMetaStore::new(),
+        BuildWyHasher::default(),
+    );
+    fs.write(&1, b"Hello World!".repeat(10_000)).await.unwrap();
+    fs.write(&2, b"Goodbye World!".repeat(10_000))
+        .await
+        .unwrap();
+    fs
+}
+
+#[tokio::test]
+async fn it_reports_clean_stores() {
+    let fs = populated_system().await;
+
+    let report = fs.fsck().await.unwrap();
+    assert!(report.is_clean());
+    assert_eq!(report.files.len(), 2);
+    assert!(report.orphan_chunks.is_empty());
+    for file in &report.files {
+        assert_eq!(file.status, FileStatus::Ok);
+        assert_eq!(file.actual_size, file.expected_size);
+    }
+}
+
+#[tokio::test]
+async fn it_finds_missing_and_corrupt_chunks() {
+    let fs = populated_system().await;
+    let hashes = fs.meta_store().get(&1).await.unwrap().hashes;
+
+    let (mut chunk_store, meta_store, hasher) = fs.into_parts();
+    chunk_store.remove(&hashes[0]).unwrap();
+    let mut chunk = chunk_store.get(&hashes[1]).unwrap();
+    chunk[0] ^= 1;
+    chunk_store.upsert(hashes[1], chunk).unwrap();
+    let fs = System::new(chunk_store, meta_store, hasher);
+
+    let report = fs.fsck().await.unwrap();
+    assert!(!report.is_clean());
+
+    let damaged: Vec<_> = report.damaged().collect();
+    assert_eq!(damaged.len(), 1);
+    assert_eq!(damaged[0].key, 1);
+    assert_eq!(damaged[0].status, FileStatus::Damaged);
+    assert_eq!(damaged[0].missing_chunks, vec![hashes[0]]);
+    assert_eq!(damaged[0].corrupt_chunks, vec![hashes[1]]);
+}
+
+#[tokio::test]
+async fn it_reports_repeated_chunks_once() {
+    let mut fs = populated_system().await;
+    fs.concat(&3, &[1, 1, 1]).await.unwrap();
+    let hashes = fs.meta_store().get(&1).await.unwrap().hashes;
+
+    let (mut chunk_store, meta_store, hasher) = fs.into_parts();
+    chunk_store.remove(&hashes[0]).unwrap();
+    let fs = System::new(chunk_store, meta_store, hasher);
+
+    let report = fs.fsck().await.unwrap();
+    let file = report.files.iter().find(|file| file.key == 3).unwrap();
+    assert_eq!(file.status, FileStatus::Damaged);
+    assert_eq!(file.missing_chunks, vec![hashes[0]]);
+}
+
+#[tokio::test]
+async fn it_finds_size_mismatches() {
+    let fs = populated_system().await;
+    let meta = fs.meta_store().get(&1).await.unwrap();
+
+    let (chunk_store, mut meta_store, hasher) = fs.into_parts();
+    meta_store
+        .upsert(
+            &1,
+            Meta {
+                size: meta.size + 1,
+                ..meta
+            },
+        )
+        .await
+        .unwrap();
+    let fs = System::new(chunk_store, meta_store, hasher);
+
+    let report = fs.fsck().await.unwrap();
+    let damaged: Vec<_> = report.damaged().collect();
+    assert_eq!(damaged.len(), 1);
+    assert_eq!(damaged[0].expected_size, damaged[0].actual_size + 1);
+    assert!(damaged[0].missing_chunks.is_empty());
+    assert!(damaged[0].corrupt_chunks.is_empty());
+}
+
+#[tokio::test]
+async fn it_finds_orphan_chunks() {
+    let mut fs = populated_system().await;
+    let hashes = fs.meta_store().get(&2).await.unwrap().hashes;
+    fs.delete(&2).await.unwrap();
+
+    let report = fs.fsck().await.unwrap();
+    assert!(report.is_clean());
+
+    let mut expected = hashes;
+    expected.sort_unstable();
+    expected.dedup();
+    assert_eq!(report.orphan_chunks, expected);
+}
+
+#[tokio::test]
+async fn it_checks_encrypted_files() {
+    let secret = b"shared convergence secret 123456";
+    let mut fs = System::new(
+        MemoryChunkStore::new(),
+        MemoryMetaStore::new(),
+        BuildWyHasher::default(),
+    )
+    .with_convergent_encryption(ConvergentEncryption::new(secret, &[1; 32]));
+    fs.write(&1, b"Hello World!".repeat(10_000)).await.unwrap();
+    assert!(fs.fsck().await.unwrap().is_clean());
+
+    let (chunk_store, meta_store, hasher) = fs.into_parts();
+    let fs = System::new(chunk_store, meta_store, hasher);
+    let report = fs.fsck().await.unwrap();
+    assert_eq!(report.files[0].status, FileStatus::KeysUnavailable);
+    assert!(report.files[0].missing_chunks.is_empty());
+}
+
+#[tokio::test]
+async fn it_serializes_reports() {
+    let fs = populated_system().await;
+    let hashes = fs.meta_store().get(&1).await.unwrap().hashes;
+
+    let (mut chunk_store, mut meta_store, hasher) = fs.into_parts();
+    chunk_store.remove(&hashes[0]).unwrap();
+    meta_store.remove(&2).await.unwrap();
+    let fs = System::new(chunk_store, meta_store, hasher);
+
+    let report = serde_json::to_value(fs.fsck().await.unwrap()).unwrap();
+    assert_eq!(report["files"][0]["key"], 1);
+    assert_eq!(report["files"][0]["status"], "damaged");
+    assert_eq!(report["files"][0]["missing_chunks"][0], hashes[0]);
+    assert!(!report["orphan_chunks"].as_array().unwrap().is_empty());
+}
@@ -0,0 +1,93 @@
+use std::{collections::HashSet, time::Duration};
+
+use cdcfs::{
+    chunks::ChunkStore, meta::MetaStore, system::GcOptions, BuildWyHasher, FileChunkStore,
+    FileMetaStore, MemoryChunkStore, MemoryMetaStore, System,
+};
+
+#[tokio::test]
+async fn it_removes_unreferenced_chunks() {
+    let mut fs = System::new(
+        MemoryChunkStore::new(),
+        MemoryMetaStore::new(),
+        BuildWyHasher::default(),
+    );
+    let kept = b"Hello World!".repeat(10_000);
+    fs.write(&1, &kept).await.unwrap();
+    fs.write(&2, b"Goodbye World!".repeat(10_000))
+        .await
+        .unwrap();
+    let kept_hashes: HashSet<u64> = fs
+        .meta_store()
+        .get(&1)
+        .await
+        .unwrap()
+        .hashes
+        .into_iter()
+        .collect();
+    let deleted: HashSet<u64> = fs
+        .chunk_store()
+        .hashes()
+        .unwrap()
+        .into_iter()
+        .filter(|hash| !kept_hashes.contains(hash))
+        .collect();
+    assert!(!deleted.is_empty());
+
+    fs.delete(&2).await.unwrap();
+    let orphans = fs.orphan_chunks().await.unwrap();
+    assert_eq!(orphans.iter().copied().collect::<HashSet<_>>(), deleted);
+    assert!(orphans.windows(2).all(|pair| pair[0] < pair[1]));
+
+    let options = GcOptions::new().with_count_bytes(true);
+    let report = fs.gc_with(&options).await.unwrap();
+    assert_eq!(report.removed, orphans);
+    assert!(report.bytes_freed.unwrap() > 0);
+    assert_eq!(
+        fs.chunk_store()
+            .hashes()
+            .unwrap()
+            .into_iter()
+            .collect::<HashSet<_>>(),
+        kept_hashes
+    );
+    assert_eq!(fs.read(&1).await.unwrap(), kept);
+    assert!(fs.fsck().await.unwrap().is_clean());
+
+    let report = fs.gc().await.unwrap();
+    assert!(report.removed.is_empty());
+    assert_eq!(report.bytes_freed, None);
+}
+
+#[tokio::test]
+async fn it_spares_chunks_referenced_within_the_grace_period() {
+    let dir = tempfile::tempdir().unwrap();
+    let open = || {
+        System::new(
+            FileChunkStore::new(dir.path().join("chunks")).unwrap(),
+            FileMetaStore::<String>::new(dir.path().join("meta")).unwrap(),
+            BuildWyHasher::default(),
+        )
+    };
+    let mut collector = open();
+    let mut writer = open();
+
+    let source = b"Hello World!".repeat(10_000);
+    writer.write(&"a".to_owned(), &source).await.unwrap();
+    writer.delete(&"a".to_owned()).await.unwrap();
+    writer
+        .write(&"b".to_owned(), b"Goodbye World!".repeat(10_000))
+        .await
+        .unwrap();
+    writer.delete(&"b".to_owned()).await.unwrap();
+
+    // The chunks of `a` are referenced again while the collector waits.
+    let options = GcOptions::new().with_grace(Duration::from_millis(500));
+    let (report, _) = tokio::join!(collector.gc_with(&options), async {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        writer.write(&"a".to_owned(), &source).await.unwrap();
+    });
+    assert!(!report.unwrap().removed.is_empty());
+    assert_eq!(collector.read(&"a".to_owned()).await.unwrap(), source);
+    assert!(collector.orphan_chunks().await.unwrap().is_empty());
+}
@@ -1 +1,21 @@
+mod diff;
+mod fsck;
+mod gc;
+mod repair;
+mod similarity;
+mod stats;
+mod sync;
 mod test;
+
+use cdcfs::{BuildWyHasher, MemoryChunkStore, MemoryMetaStore, System};
+
+type TestSystem = System<MemoryChunkStore, MemoryMetaStore<i32>, BuildWyHasher>;
+
+/// System on empty memory stores, keyed by `i32`.
+fn system() -> TestSystem {
+    System::new(
+        MemoryChunkStore::new(),
+        MemoryMetaStore::new(),
+        BuildWyHasher::default(),
+    )
+}
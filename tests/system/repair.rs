@@ -0,0 +1,128 @@
+use cdcfs::{
+    chunks::{ChunkStore, Error as ChunkError},
+    meta::MetaStore,
+    system::{Error, FileStatus},
+    BuildWyHasher, MemoryChunkStore, MemoryMetaStore, System,
+};
+
+type MemorySystem = System<MemoryChunkStore, MemoryMetaStore<i32>, BuildWyHasher>;
+
+/// Writes two files and returns the system together with a copy of its chunks
+/// and the hashes of the first file.
+async fn system_with_replica() -> (MemorySystem, MemoryChunkStore, Vec<u64>) {
+    let mut fs = System::new(
+        MemoryChunkStore::new(),
+        MemoryMetaStore::new(),
+        BuildWyHasher::default(),
+    );
+    fs.write(&1, b"Hello World!".repeat(10_000)).await.unwrap();
+    fs.write(&2, b"Goodbye World!".repeat(10_000))
+        .await
+        .unwrap();
+
+    let mut replica = MemoryChunkStore::new();
+    for hash in fs.chunk_store().hashes().unwrap() {
+        replica
+            .upsert(hash, fs.chunk_store().get(&hash).unwrap())
+            .unwrap();
+    }
+
+    let hashes = fs.meta_store().get(&1).await.unwrap().hashes;
+    (fs, replica, hashes)
+}
+
+fn damage(fs: MemorySystem, hashes: &[u64]) -> MemorySystem {
+    let (mut chunk_store, meta_store, hasher) = fs.into_parts();
+    chunk_store.remove(&hashes[0]).unwrap();
+    let mut chunk = chunk_store.get(&hashes[1]).unwrap();
+    chunk[0] ^= 1;
+    chunk_store.upsert(hashes[1], chunk).unwrap();
+    System::new(chunk_store, meta_store, hasher)
+}
+
+#[tokio::test]
+async fn it_repairs_from_a_replica() {
+    let (fs, replica, hashes) = system_with_replica().await;
+    let mut fs = damage(fs, &hashes);
+
+    let report = fs.fsck().await.unwrap();
+    assert!(!report.is_clean());
+
+    let repair = fs.repair(&report, &replica).unwrap();
+    assert!(repair.is_complete());
+    let mut expected = vec![hashes[0], hashes[1]];
+    expected.sort_unstable();
+    assert_eq!(repair.repaired, expected);
+
+    assert!(fs.fsck().await.unwrap().is_clean());
+    assert_eq!(fs.read(&1).await.unwrap(), b"Hello World!".repeat(10_000));
+}
+
+#[tokio::test]
+async fn it_reports_unrecoverable_chunks() {
+    let (fs, mut replica, hashes) = system_with_replica().await;
+    let mut fs = damage(fs, &hashes);
+    replica.remove(&hashes[0]).unwrap();
+    let mut chunk = replica.get(&hashes[1]).unwrap();
+    chunk[1] ^= 1;
+    replica.upsert(hashes[1], chunk).unwrap();
+
+    let report = fs.fsck().await.unwrap();
+    let repair = fs.repair(&report, &replica).unwrap();
+    assert!(!repair.is_complete());
+    assert!(repair.repaired.is_empty());
+
+    let report = fs.fsck().await.unwrap();
+    let damaged: Vec<_> = report.damaged().collect();
+    assert_eq!(damaged.len(), 1);
+    assert_eq!(damaged[0].status, FileStatus::Damaged);
+}
+
+#[tokio::test]
+async fn it_repairs_on_read() {
+    let (fs, replica, hashes) = system_with_replica().await;
+    let fs = damage(fs, &hashes).with_read_repair(replica);
+
+    assert_eq!(fs.read(&1).await.unwrap(), b"Hello World!".repeat(10_000));
+    assert!(fs.fsck().await.unwrap().is_clean());
+
+    let (chunk_store, _, _) = fs.into_parts();
+    let (primary, replica) = chunk_store.into_inner();
+    for hash in &hashes[..2] {
+        assert_eq!(primary.get(hash).unwrap(), replica.get(hash).unwrap());
+    }
+}
+
+#[tokio::test]
+async fn it_checks_chunks_without_repairing() {
+    let (fs, replica, hashes) = system_with_replica().await;
+    let fs = damage(fs, &hashes).with_read_repair(replica);
+
+    let found = fs.chunk_store().contains_many(&[hashes[0], 42]).unwrap();
+    assert_eq!(found, [true, false]);
+    let (chunk_store, _, _) = fs.into_parts();
+    let (primary, _) = chunk_store.into_inner();
+    assert!(matches!(primary.get(&hashes[0]), Err(ChunkError::NotFound)));
+}
+
+#[tokio::test]
+async fn it_repairs_streamed_reads() {
+    let (fs, replica, hashes) = system_with_replica().await;
+    let fs = damage(fs, &hashes).with_read_repair(replica);
+
+    let mut buf = vec![];
+    std::io::copy(&mut fs.read_stream(&1).await.unwrap(), &mut buf).unwrap();
+    assert_eq!(buf, b"Hello World!".repeat(10_000));
+}
+
+#[tokio::test]
+async fn it_fails_reads_the_replica_cannot_repair() {
+    let (fs, _, hashes) = system_with_replica().await;
+    let fs = damage(fs, &hashes).with_read_repair(MemoryChunkStore::new());
+
+    assert!(matches!(
+        fs.read(&1).await,
+        Err(Error::ChunkStore(ChunkError::NotFound))
+    ));
+    assert_eq!(fs.read(&2).await.unwrap(), b"Goodbye World!".repeat(10_000));
+}
@@ -0,0 +1,109 @@
+use std::fs;
+
+use cdcfs::{
+    meta::{self, MetaStore},
+    system::{Error, SimilarFile, SimilarityIndex},
+};
+
+use super::system;
+
+fn keys<K: Clone>(found: &[SimilarFile<K>]) -> Vec<K> {
+    found.iter().map(|file| file.key.clone()).collect()
+}
+
+#[tokio::test]
+async fn it_finds_near_duplicates() {
+    let mut fs = system();
+    let pdf = fs::read("tests/fixtures/file-example_PDF_1MB.pdf").unwrap();
+    let docx = fs::read("tests/fixtures/file-sample_1MB.docx").unwrap();
+    let jpg = fs::read("tests/fixtures/file_example_JPG_2500kB.jpg").unwrap();
+    let mut edited = pdf.clone();
+    edited.splice(500_000..500_100, b"Some edit".repeat(1000));
+    fs.write(&1, &pdf).await.unwrap();
+    fs.write(&2, &edited).await.unwrap();
+    fs.write(&3, [&pdf[..], &docx[..]].concat()).await.unwrap();
+    fs.write(&4, &jpg).await.unwrap();
+    fs.write(&5, b"").await.unwrap();
+
+    let index = fs.similarity_index().await.unwrap();
+    // The empty file is similar to none.
+    assert_eq!(index.len(), 4);
+
+    let found = fs.similar(&index, &1, 0.8).await.unwrap();
+    assert_eq!(keys(&found), [2]);
+    assert!(found[0].similarity > 0.9, "{found:?}");
+    let diff = fs.diff(&1, &2).await.unwrap();
+    assert!(diff.similarity() > 0.9);
+
+    // Half of the concatenation's chunks come from the PDF.
+    let found = fs.similar(&index, &1, 0.3).await.unwrap();
+    assert_eq!(keys(&found), [2, 3]);
+    assert!(
+        found[1].similarity > 0.3 && found[1].similarity < 0.7,
+        "{found:?}"
+    );
+
+    assert!(fs.similar(&index, &4, 0.1).await.unwrap().is_empty());
+    assert!(fs.similar(&index, &5, 0.0).await.unwrap().is_empty());
+    assert!(matches!(
+        fs.similar(&index, &6, 0.5).await,
+        Err(Error::MetaStore(meta::Error::NotFound))
+    ));
+}
+
+#[tokio::test]
+async fn it_estimates_similarity_of_unstored_files() {
+    let mut fs = system();
+    let pdf = fs::read("tests/fixtures/file-example_PDF_1MB.pdf").unwrap();
+    let docx = fs::read("tests/fixtures/file-sample_1MB.docx").unwrap();
+    fs.write(&1, &pdf).await.unwrap();
+    fs.write(&2, &docx).await.unwrap();
+    fs.write(&3, [&pdf[..], &docx[..]].concat()).await.unwrap();
+    let index = fs.similarity_index().await.unwrap();
+
+    // Queries with the hashes of a file about to be uploaded.
+    fs.write(&4, &pdf[..pdf.len() * 9 / 10]).await.unwrap();
+    let hashes = fs.meta_store().get(&4).await.unwrap().hashes;
+    let estimated = index.query(&hashes, 0.7);
+    assert_eq!(keys(&estimated), [1]);
+
+    let exact = fs.similar(&index, &4, 0.7).await.unwrap();
+    assert_eq!(keys(&exact), [1]);
+    assert!((estimated[0].similarity - exact[0].similarity).abs() < 0.15);
+}
+
+#[tokio::test]
+async fn it_follows_changes() {
+    let mut fs = system();
+    let pdf = fs::read("tests/fixtures/file-example_PDF_1MB.pdf").unwrap();
+    let jpg = fs::read("tests/fixtures/file_example_JPG_2500kB.jpg").unwrap();
+    fs.write(&1, &pdf).await.unwrap();
+    let mut index = fs.similarity_index().await.unwrap();
+    let mut changes = fs.subscribe().await.unwrap();
+
+    fs.copy(&1, &2).await.unwrap();
+    fs.write(&3, &jpg).await.unwrap();
+    for _ in 0..2 {
+        index.apply(&changes.recv().await.unwrap());
+    }
+    let found = fs.similar(&index, &1, 0.9).await.unwrap();
+    assert_eq!(keys(&found), [2]);
+    assert_eq!(found[0].similarity, 1.0);
+
+    fs.write(&2, &jpg).await.unwrap();
+    fs.delete(&3).await.unwrap();
+    for _ in 0..2 {
+        index.apply(&changes.recv().await.unwrap());
+    }
+    assert_eq!(index.len(), 2);
+    assert!(fs.similar(&index, &1, 0.5).await.unwrap().is_empty());
+
+    // Files deleted after indexing are skipped.
+    fs.delete(&2).await.unwrap();
+    assert!(fs.similar(&index, &1, 0.0).await.unwrap().is_empty());
+
+    let mut index = SimilarityIndex::new();
+    index.insert(1, &[1, 2, 3]);
+    index.remove(&1);
+    assert!(index.is_empty());
+}
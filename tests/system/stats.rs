@@ -0,0 +1,154 @@
+use std::fs;
+
+use cdcfs::{
+    chunks::{ChunkStore, CompressedChunkStore, Compression},
+    meta::{Meta, MetaStore},
+    system::ConvergentEncryption,
+    BuildWyHasher, MemoryChunkStore, MemoryMetaStore, System,
+};
+
+use super::system;
+
+#[tokio::test]
+async fn it_reports_deduplication() {
+    let mut fs = system();
+    let pdf = fs::read("tests/fixtures/file-example_PDF_1MB.pdf").unwrap();
+    let jpg = fs::read("tests/fixtures/file_example_JPG_2500kB.jpg").unwrap();
+    fs.write(&1, &pdf).await.unwrap();
+    fs.copy(&1, &2).await.unwrap();
+    fs.write(&3, &jpg).await.unwrap();
+    // Shares the first half of the PDF.
+    let mut edited = pdf[..pdf.len() / 2].to_vec();
+    edited.extend(b"Edited".repeat(10_000));
+    fs.write(&4, &edited).await.unwrap();
+
+    let stats = fs.stats().await.unwrap();
+    let stored = fs.chunk_store().hashes().unwrap();
+    assert_eq!(stats.files, 4);
+    assert_eq!(
+        stats.logical_bytes,
+        2 * pdf.len() + jpg.len() + edited.len()
+    );
+    assert_eq!(stats.unique_chunks, stored.len());
+    let physical: usize = stored
+        .iter()
+        .map(|hash| fs.chunk_store().get(hash).unwrap().len())
+        .sum();
+    assert_eq!(stats.physical_bytes, physical);
+    assert!(stats.physical_bytes < pdf.len() + jpg.len() + edited.len());
+    assert_eq!(stats.bytes_saved(), stats.logical_bytes - physical);
+    assert_eq!(
+        stats.dedup_ratio,
+        stats.logical_bytes as f64 / physical as f64
+    );
+
+    assert_eq!(stats.chunk_sizes.count(), stored.len());
+    assert_eq!(stats.ref_counts.count(), stored.len());
+    let refs: usize = stats
+        .ref_counts
+        .buckets
+        .iter()
+        .map(|bucket| bucket.count * bucket.min as usize)
+        .sum();
+    assert!(refs >= stored.len());
+
+    let mut per_file = stats.per_file.clone();
+    per_file.sort_by_key(|file| file.key);
+    let shared: Vec<_> = per_file
+        .iter()
+        .map(|file| (file.key, file.size, file.shared_bytes))
+        .collect();
+    assert_eq!(shared[0], (1, pdf.len(), pdf.len()));
+    assert_eq!(shared[1], (2, pdf.len(), pdf.len()));
+    assert_eq!(shared[2], (3, jpg.len(), 0));
+    assert_eq!(per_file[2].shared_ratio(), 0.0);
+    // Chunks up to the edit are shared, apart from the one cut at the edit.
+    let (key, size, shared_bytes) = shared[3];
+    assert_eq!((key, size), (4, edited.len()));
+    assert!(shared_bytes <= pdf.len() / 2);
+    assert!(shared_bytes > pdf.len() / 4, "{shared_bytes}");
+}
+
+#[tokio::test]
+async fn it_reports_empty_stores() {
+    let stats = system().stats().await.unwrap();
+    assert_eq!(stats.files, 0);
+    assert_eq!(stats.physical_bytes, 0);
+    assert_eq!(stats.dedup_ratio, 1.0);
+    assert!(stats.chunk_sizes.buckets.is_empty());
+    assert!(stats.per_file.is_empty());
+}
+
+#[tokio::test]
+async fn it_reads_chunks_of_unrecorded_sizes() {
+    let mut fs = system();
+    let pdf = fs::read("tests/fixtures/file-example_PDF_1MB.pdf").unwrap();
+    fs.write(&1, &pdf).await.unwrap();
+    let expected = fs.stats().await.unwrap();
+
+    // Files written before chunk sizes were recorded.
+    let (chunk_store, mut meta_store, hasher) = fs.into_parts();
+    let meta = meta_store.get(&1).await.unwrap();
+    let legacy = Meta {
+        chunk_sizes: None,
+        ..meta.clone()
+    };
+    meta_store.upsert(&1, legacy).await.unwrap();
+    let fs = System::new(chunk_store, meta_store, hasher);
+    assert_eq!(fs.stats().await.unwrap(), expected);
+
+    // Sizes of only some chunks.
+    let (chunk_store, mut meta_store, hasher) = fs.into_parts();
+    let truncated = Meta {
+        chunk_sizes: meta.chunk_sizes.map(|sizes| sizes[..1].to_vec()),
+        ..meta
+    };
+    meta_store.upsert(&1, truncated).await.unwrap();
+    let fs = System::new(chunk_store, meta_store, hasher);
+    assert_eq!(fs.stats().await.unwrap(), expected);
+}
+
+#[tokio::test]
+async fn it_counts_stored_bytes_of_encrypted_files() {
+    let secret = b"shared convergence secret 123456";
+    let mut fs = system().with_convergent_encryption(ConvergentEncryption::new(secret, &[1; 32]));
+    let contents = b"Hello World!".repeat(10_000);
+    fs.write(&1, &contents).await.unwrap();
+    fs.write(&2, &contents).await.unwrap();
+
+    let stats = fs.stats().await.unwrap();
+    let physical: usize = fs
+        .chunk_store()
+        .hashes()
+        .unwrap()
+        .iter()
+        .map(|hash| fs.chunk_store().get(hash).unwrap().len())
+        .sum();
+    assert_eq!(stats.physical_bytes, physical);
+    // Shared bytes are plaintext bytes.
+    assert_eq!(stats.per_file[0].shared_bytes, contents.len());
+    assert_eq!(stats.per_file[0].shared_ratio(), 1.0);
+}
+
+#[tokio::test]
+async fn it_counts_compressed_bytes() {
+    let mut fs = System::new(
+        CompressedChunkStore::new(MemoryChunkStore::new(), Compression::default()),
+        MemoryMetaStore::new(),
+        BuildWyHasher::default(),
+    );
+    let contents = b"Hello World!".repeat(10_000);
+    fs.write(&1, &contents).await.unwrap();
+
+    let stats = fs.stats().await.unwrap();
+    let inner = fs.chunk_store().inner();
+    let physical: usize = inner
+        .hashes()
+        .unwrap()
+        .iter()
+        .map(|hash| inner.get(hash).unwrap().len())
+        .sum();
+    assert_eq!(stats.physical_bytes, physical);
+    assert!(stats.physical_bytes < contents.len());
+    assert!(stats.dedup_ratio > 1.0);
+}
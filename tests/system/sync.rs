@@ -0,0 +1,213 @@
+use std::{fmt::Debug, hash::Hash};
+
+use cdcfs::{
+    chunks::{self, ChunkStore},
+    meta::MetaStore,
+    system::{Error, SyncReport},
+    BuildHighwayHasher, BuildKeyedBlake3Hasher, BuildWyHasher, MemoryChunkStore, MemoryMetaStore,
+    System,
+};
+
+type MemorySystem<K> = System<MemoryChunkStore, MemoryMetaStore<K>, BuildWyHasher>;
+
+fn system<K: Debug + Clone + Eq + Hash + Send + Sync>() -> MemorySystem<K> {
+    System::new(
+        MemoryChunkStore::new(),
+        MemoryMetaStore::new(),
+        BuildWyHasher::default(),
+    )
+}
+
+/// Incompressible bytes that chunk into many different chunks.
+fn noise(seed: u64, len: usize) -> Vec<u8> {
+    let mut state = seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+/// Memory store refusing writes once its budget is used up.
+#[derive(Debug, Default)]
+struct FlakyChunkStore {
+    inner: MemoryChunkStore,
+    writes_left: Option<usize>,
+}
+
+impl ChunkStore for FlakyChunkStore {
+    fn get(&self, hash: &u64) -> chunks::Result<Vec<u8>> {
+        self.inner.get(hash)
+    }
+
+    fn upsert(&mut self, hash: u64, chunk: Vec<u8>) -> chunks::Result<()> {
+        match &mut self.writes_left {
+            Some(0) => Err(anyhow::anyhow!("Connection reset").into()),
+            Some(left) => {
+                *left -= 1;
+                self.inner.upsert(hash, chunk)
+            }
+            None => self.inner.upsert(hash, chunk),
+        }
+    }
+
+    fn remove(&mut self, hash: &u64) -> chunks::Result<()> {
+        self.inner.remove(hash)
+    }
+
+    fn hashes(&self) -> chunks::Result<Vec<u64>> {
+        self.inner.hashes()
+    }
+
+    fn hasher_fingerprint(&self) -> chunks::Result<Option<[u8; 32]>> {
+        self.inner.hasher_fingerprint()
+    }
+
+    fn set_hasher_fingerprint(&mut self, fingerprint: [u8; 32]) -> chunks::Result<()> {
+        self.inner.set_hasher_fingerprint(fingerprint)
+    }
+}
+
+#[tokio::test]
+async fn it_syncs_files_to_an_empty_system() {
+    let mut source = system();
+    source.write(&1, noise(1, 1 << 20)).await.unwrap();
+    source.write(&2, noise(2, 1 << 20)).await.unwrap();
+    let mut destination = system();
+
+    let report = source.sync_to(&mut destination, vec![1, 2]).await.unwrap();
+    assert_eq!(report.files_synced, 2);
+    assert_eq!(
+        report.chunks_sent,
+        source.chunk_store().hashes().unwrap().len()
+    );
+    assert_eq!(report.bytes_sent, 2 << 20);
+    assert_eq!(report.bytes_saved, 0);
+
+    assert_eq!(destination.read(&1).await.unwrap(), noise(1, 1 << 20));
+    assert_eq!(destination.read(&2).await.unwrap(), noise(2, 1 << 20));
+}
+
+#[tokio::test]
+async fn it_only_sends_chunks_the_destination_lacks() {
+    let mut source = system();
+    let original = noise(1, 1 << 20);
+    let mut edited = original.clone();
+    edited.extend(noise(2, 1 << 16));
+    source.write(&1, &original).await.unwrap();
+    source.write(&2, &edited).await.unwrap();
+
+    let mut destination = system();
+    source.sync_to(&mut destination, vec![1]).await.unwrap();
+
+    let report = source.sync_to(&mut destination, vec![1, 2]).await.unwrap();
+    assert_eq!(report.files_synced, 1);
+    assert_eq!(report.files_unchanged, 1);
+    assert!(report.bytes_sent < 1 << 18, "{report:?}");
+    assert_eq!(
+        report.bytes_sent + report.bytes_saved,
+        original.len() + edited.len()
+    );
+    assert_eq!(destination.read(&2).await.unwrap(), edited);
+}
+
+#[tokio::test]
+async fn it_sends_nothing_once_in_sync() {
+    let mut source = system();
+    source.write(&1, noise(1, 1 << 18)).await.unwrap();
+    let mut destination = system();
+    source.sync_to(&mut destination, vec![1]).await.unwrap();
+
+    let report = source.sync_to(&mut destination, vec![1]).await.unwrap();
+    assert_eq!(
+        report,
+        SyncReport {
+            files_unchanged: 1,
+            bytes_saved: 1 << 18,
+            ..SyncReport::default()
+        }
+    );
+}
+
+#[tokio::test]
+async fn it_syncs_files_by_prefix() {
+    let mut source = system();
+    source.write(&"photos/a".to_owned(), b"a").await.unwrap();
+    source.write(&"photos/b".to_owned(), b"b").await.unwrap();
+    source.write(&"videos/c".to_owned(), b"c").await.unwrap();
+    let mut destination = system();
+
+    let report = source.sync_to(&mut destination, "photos/").await.unwrap();
+    assert_eq!(report.files_synced, 2);
+
+    let mut keys: Vec<String> = destination
+        .meta_store()
+        .list()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|(key, _)| key)
+        .collect();
+    keys.sort();
+    assert_eq!(keys, ["photos/a", "photos/b"]);
+}
+
+#[tokio::test]
+async fn it_resumes_an_interrupted_sync() {
+    let mut source = system();
+    source.write(&1, noise(1, 1 << 20)).await.unwrap();
+    let chunks = source.chunk_store().hashes().unwrap().len();
+
+    let mut destination = System::new(
+        FlakyChunkStore {
+            writes_left: Some(10),
+            ..FlakyChunkStore::default()
+        },
+        MemoryMetaStore::new(),
+        BuildWyHasher::default(),
+    );
+    assert!(source.sync_to(&mut destination, vec![1]).await.is_err());
+    assert!(destination.read(&1).await.is_err());
+
+    let (mut chunk_store, meta_store, hasher) = destination.into_parts();
+    chunk_store.writes_left = None;
+    let mut destination = System::new(chunk_store, meta_store, hasher);
+
+    let report = source.sync_to(&mut destination, vec![1]).await.unwrap();
+    assert_eq!(report.files_synced, 1);
+    assert_eq!(report.chunks_sent, chunks - 10);
+    assert_eq!(destination.read(&1).await.unwrap(), noise(1, 1 << 20));
+}
+
+#[tokio::test]
+async fn it_refuses_to_sync_between_different_hashers() {
+    let mut source = System::new_keyed(
+        MemoryChunkStore::new(),
+        MemoryMetaStore::new(),
+        BuildKeyedBlake3Hasher::new(&[7; 32]),
+    )
+    .unwrap();
+    source.write(&1, b"Hello World!").await.unwrap();
+    let mut destination = system();
+
+    assert!(matches!(
+        source.sync_to(&mut destination, vec![1]).await,
+        Err(Error::HasherMismatch)
+    ));
+
+    // Neither store has a fingerprint to compare.
+    let mut source = System::new(
+        MemoryChunkStore::new(),
+        MemoryMetaStore::new(),
+        BuildHighwayHasher::default(),
+    );
+    source.write(&1, b"Hello World!").await.unwrap();
+    assert!(matches!(
+        source.sync_to(&mut destination, vec![1]).await,
+        Err(Error::HasherMismatch)
+    ));
+    assert!(destination.read(&1).await.is_err());
+}
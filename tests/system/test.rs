@@ -3,7 +3,11 @@ use std::{fs, io::Read};
 use with_postgres_ready::with_postgres_ready;
 
 use cdcfs::{
-    BuildWyHasher, MemoryChunkStore, MemoryMetaStore, PostgresMetaStore, RedisChunkStore, System,
+    chunks::{ChunkStore, CompressedChunkStore, Compression},
+    meta::{ChangeKind, MetaStore},
+    system::{ConvergentEncryption, Upload},
+    BuildHighwayHasher, BuildKeyedBlake3Hasher, BuildKeyedHighwayHasher, BuildWyHasher,
+    FileChunkStore, MemoryChunkStore, MemoryMetaStore, PostgresMetaStore, RedisChunkStore, System,
 };
 
 use crate::utils::with_redis_ready;
@@ -348,3 +352,532 @@ async fn can_have_the_same_entity_multiple_times() {
     fs.read_into(&2, &mut buf).await.unwrap();
     assert_eq!(buf, file);
 }
+
+#[tokio::test]
+async fn it_can_read_and_write_compressed() {
+    let source = b"Hello World!".repeat(10_000);
+    let mut fs = System::new(
+        CompressedChunkStore::new(MemoryChunkStore::new(), Compression::default()),
+        MemoryMetaStore::new(),
+        BuildWyHasher::default(),
+    );
+    fs.write(&42, &source).await.unwrap();
+    assert_eq!(fs.read(&42).await.unwrap(), source);
+}
+
+#[tokio::test]
+async fn it_can_read_and_write_with_convergent_encryption() {
+    let source = b"Hello World!".repeat(10_000);
+    let mut fs = System::new(
+        MemoryChunkStore::new(),
+        MemoryMetaStore::new(),
+        BuildWyHasher::default(),
+    )
+    .with_convergent_encryption(ConvergentEncryption::new(SECRET, OWNER_A));
+    fs.write(&42, &source).await.unwrap();
+
+    assert_eq!(fs.read(&42).await.unwrap(), source);
+
+    let mut buf = vec![];
+    fs.read_into(&42, &mut buf).await.unwrap();
+    assert_eq!(buf, source);
+
+    let mut buf = vec![];
+    fs.read_stream(&42)
+        .await
+        .unwrap()
+        .read_to_end(&mut buf)
+        .unwrap();
+    assert_eq!(buf, source);
+
+    let meta = fs.meta_store().get(&42).await.unwrap();
+    for hash in &meta.hashes {
+        let chunk = fs.chunk_store().get(hash).unwrap();
+        assert!(!source.windows(chunk.len()).any(|window| window == chunk));
+    }
+}
+
+#[tokio::test]
+async fn convergent_encryption_dedups_across_owners() {
+    let source = b"Hello World!".repeat(10_000);
+
+    let mut fs_a = System::new(
+        MemoryChunkStore::new(),
+        MemoryMetaStore::new(),
+        BuildWyHasher::default(),
+    )
+    .with_convergent_encryption(ConvergentEncryption::new(SECRET, OWNER_A));
+    fs_a.write(&42, &source).await.unwrap();
+
+    let mut fs_b = System::new(
+        MemoryChunkStore::new(),
+        MemoryMetaStore::new(),
+        BuildWyHasher::default(),
+    )
+    .with_convergent_encryption(ConvergentEncryption::new(SECRET, OWNER_B));
+    fs_b.write(&42, &source).await.unwrap();
+
+    let meta_a = fs_a.meta_store().get(&42).await.unwrap();
+    let meta_b = fs_b.meta_store().get(&42).await.unwrap();
+    assert_eq!(meta_a.hashes, meta_b.hashes);
+    assert_ne!(meta_a.sealed_keys, meta_b.sealed_keys);
+}
+
+#[tokio::test]
+async fn convergent_encryption_requires_owner_key() {
+    let source = b"Hello World!".repeat(10_000);
+    let mut fs = System::new(
+        MemoryChunkStore::new(),
+        MemoryMetaStore::new(),
+        BuildWyHasher::default(),
+    )
+    .with_convergent_encryption(ConvergentEncryption::new(SECRET, OWNER_A));
+    fs.write(&42, &source).await.unwrap();
+
+    let (chunk_store, meta_store, hasher) = fs.into_parts();
+    let fs = System::new(chunk_store, meta_store, hasher)
+        .with_convergent_encryption(ConvergentEncryption::new(SECRET, OWNER_B));
+    assert!(matches!(
+        fs.read(&42).await,
+        Err(cdcfs::system::Error::KeysUnavailable)
+    ));
+
+    let (chunk_store, meta_store, hasher) = fs.into_parts();
+    let fs = System::new(chunk_store, meta_store, hasher);
+    assert!(matches!(
+        fs.read(&42).await,
+        Err(cdcfs::system::Error::KeysUnavailable)
+    ));
+}
+
+const SECRET: &[u8; 32] = b"shared convergence secret 123456";
+const OWNER_A: &[u8; 32] = b"owner key of the first customer.";
+const OWNER_B: &[u8; 32] = b"owner key of the second customer";
+
+#[tokio::test]
+async fn it_can_read_and_write_with_keyed_hashers() {
+    let source = b"Hello World!".repeat(10_000);
+
+    let mut fs = System::new_keyed(
+        MemoryChunkStore::new(),
+        MemoryMetaStore::new(),
+        BuildKeyedHighwayHasher::new(HASH_KEY_A),
+    )
+    .unwrap();
+    fs.write(&42, &source).await.unwrap();
+    assert_eq!(fs.read(&42).await.unwrap(), source);
+
+    let mut fs = System::new_keyed(
+        MemoryChunkStore::new(),
+        MemoryMetaStore::new(),
+        BuildKeyedBlake3Hasher::new(HASH_KEY_A),
+    )
+    .unwrap();
+    fs.write(&42, &source).await.unwrap();
+    assert_eq!(fs.read(&42).await.unwrap(), source);
+}
+
+#[tokio::test]
+async fn keyed_hashers_hide_chunk_ids() {
+    let source = b"Hello World!".repeat(10_000);
+
+    let mut plain = System::new(
+        MemoryChunkStore::new(),
+        MemoryMetaStore::new(),
+        BuildHighwayHasher::default(),
+    );
+    plain.write(&42, &source).await.unwrap();
+
+    let mut keyed_a = System::new_keyed(
+        MemoryChunkStore::new(),
+        MemoryMetaStore::new(),
+        BuildKeyedHighwayHasher::new(HASH_KEY_A),
+    )
+    .unwrap();
+    keyed_a.write(&42, &source).await.unwrap();
+
+    let mut keyed_b = System::new_keyed(
+        MemoryChunkStore::new(),
+        MemoryMetaStore::new(),
+        BuildKeyedHighwayHasher::new(HASH_KEY_B),
+    )
+    .unwrap();
+    keyed_b.write(&42, &source).await.unwrap();
+
+    let plain = plain.meta_store().get(&42).await.unwrap().hashes;
+    let keyed_a = keyed_a.meta_store().get(&42).await.unwrap().hashes;
+    let keyed_b = keyed_b.meta_store().get(&42).await.unwrap().hashes;
+    assert_ne!(plain, keyed_a);
+    assert_ne!(keyed_a, keyed_b);
+}
+
+#[tokio::test]
+async fn it_rejects_mixing_hasher_keys() {
+    let fs = System::new_keyed(
+        MemoryChunkStore::new(),
+        MemoryMetaStore::<i32>::new(),
+        BuildKeyedHighwayHasher::new(HASH_KEY_A),
+    )
+    .unwrap();
+
+    let (chunk_store, meta_store, _) = fs.into_parts();
+    let fs = System::new_keyed(
+        chunk_store,
+        meta_store,
+        BuildKeyedHighwayHasher::new(HASH_KEY_A),
+    )
+    .unwrap();
+
+    let (chunk_store, meta_store, _) = fs.into_parts();
+    assert!(matches!(
+        System::new_keyed(
+            chunk_store,
+            meta_store,
+            BuildKeyedHighwayHasher::new(HASH_KEY_B),
+        ),
+        Err(cdcfs::system::Error::HasherMismatch)
+    ));
+}
+
+#[tokio::test]
+async fn it_rejects_switching_keyed_algorithms() {
+    let fs = System::new_keyed(
+        MemoryChunkStore::new(),
+        MemoryMetaStore::<i32>::new(),
+        BuildKeyedHighwayHasher::new(HASH_KEY_A),
+    )
+    .unwrap();
+
+    let (chunk_store, meta_store, _) = fs.into_parts();
+    assert!(matches!(
+        System::new_keyed(
+            chunk_store,
+            meta_store,
+            BuildKeyedBlake3Hasher::new(HASH_KEY_A),
+        ),
+        Err(cdcfs::system::Error::HasherMismatch)
+    ));
+}
+
+#[tokio::test]
+async fn it_rejects_unkeyed_writes_to_keyed_stores() {
+    let fs = System::new_keyed(
+        MemoryChunkStore::new(),
+        MemoryMetaStore::<i32>::new(),
+        BuildKeyedBlake3Hasher::new(HASH_KEY_A),
+    )
+    .unwrap();
+
+    let (chunk_store, meta_store, _) = fs.into_parts();
+    let mut fs = System::new(chunk_store, meta_store, BuildWyHasher::default());
+    assert!(matches!(
+        fs.write(&1, b"Hello World!").await,
+        Err(cdcfs::system::Error::HasherMismatch)
+    ));
+    assert!(fs.chunk_store().hashes().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn it_rejects_keyed_hashers_on_unkeyed_stores() {
+    let mut fs = System::new(
+        MemoryChunkStore::new(),
+        MemoryMetaStore::<i32>::new(),
+        BuildWyHasher::default(),
+    );
+    fs.write(&1, b"Hello World!").await.unwrap();
+
+    let (chunk_store, meta_store, _) = fs.into_parts();
+    assert!(matches!(
+        System::new_keyed(
+            chunk_store,
+            meta_store,
+            BuildKeyedBlake3Hasher::new(HASH_KEY_A),
+        ),
+        Err(cdcfs::system::Error::HasherMismatch)
+    ));
+}
+
+/// Chunk store leaving every provided method to the trait.
+#[derive(Debug, Default)]
+struct BareChunkStore(MemoryChunkStore);
+
+impl ChunkStore for BareChunkStore {
+    fn get(&self, hash: &u64) -> cdcfs::chunks::Result<Vec<u8>> {
+        self.0.get(hash)
+    }
+
+    fn upsert(&mut self, hash: u64, chunk: Vec<u8>) -> cdcfs::chunks::Result<()> {
+        self.0.upsert(hash, chunk)
+    }
+
+    fn remove(&mut self, hash: &u64) -> cdcfs::chunks::Result<()> {
+        self.0.remove(hash)
+    }
+
+    fn hashes(&self) -> cdcfs::chunks::Result<Vec<u64>> {
+        self.0.hashes()
+    }
+}
+
+#[tokio::test]
+async fn it_needs_fingerprints_only_for_keyed_hashers() {
+    let mut fs = System::new(
+        BareChunkStore::default(),
+        MemoryMetaStore::new(),
+        BuildWyHasher::default(),
+    );
+    fs.write(&1, b"Hello World!").await.unwrap();
+    assert_eq!(fs.read(&1).await.unwrap(), b"Hello World!");
+
+    let (chunk_store, meta_store, _) = fs.into_parts();
+    assert!(matches!(
+        System::new_keyed(
+            chunk_store,
+            meta_store,
+            BuildKeyedBlake3Hasher::new(HASH_KEY_A),
+        ),
+        Err(cdcfs::system::Error::HasherMismatch)
+    ));
+    assert!(matches!(
+        System::new_keyed(
+            BareChunkStore::default(),
+            MemoryMetaStore::<i32>::new(),
+            BuildKeyedBlake3Hasher::new(HASH_KEY_A),
+        ),
+        Err(cdcfs::system::Error::ChunkStore(_))
+    ));
+}
+
+const HASH_KEY_A: &[u8; 32] = b"first secret chunk id hasher key";
+const HASH_KEY_B: &[u8; 32] = b"other secret chunk id hasher key";
+
+#[tokio::test]
+async fn verify_on_read_detects_corrupt_chunks() {
+    let source = b"Hello World!".repeat(10_000);
+    let mut fs = System::new(
+        MemoryChunkStore::new(),
+        MemoryMetaStore::new(),
+        BuildWyHasher::default(),
+    )
+    .with_verify_on_read(true);
+    fs.write(&42, &source).await.unwrap();
+    assert_eq!(fs.read(&42).await.unwrap(), source);
+
+    let hash = fs.meta_store().get(&42).await.unwrap().hashes[0];
+    let (mut chunk_store, meta_store, hasher) = fs.into_parts();
+    let mut chunk = chunk_store.get(&hash).unwrap();
+    chunk[0] ^= 1;
+    chunk_store.upsert(hash, chunk).unwrap();
+    let fs = System::new(chunk_store, meta_store, hasher).with_verify_on_read(true);
+
+    assert!(matches!(
+        fs.read(&42).await,
+        Err(cdcfs::system::Error::Corrupt { hash: h }) if h == hash
+    ));
+
+    let mut buf = vec![];
+    assert!(matches!(
+        fs.read_into(&42, &mut buf).await,
+        Err(cdcfs::system::Error::Corrupt { hash: h }) if h == hash
+    ));
+
+    let mut buf = vec![];
+    let err = fs
+        .read_stream(&42)
+        .await
+        .unwrap()
+        .read_to_end(&mut buf)
+        .unwrap_err();
+    assert!(matches!(
+        err.into_inner().unwrap().downcast_ref(),
+        Some(cdcfs::system::Error::Corrupt { hash: h }) if *h == hash
+    ));
+
+    let (chunk_store, meta_store, hasher) = fs.into_parts();
+    let fs = System::new(chunk_store, meta_store, hasher);
+    assert_ne!(fs.read(&42).await.unwrap(), source);
+}
+
+#[tokio::test]
+async fn verify_on_read_checks_encrypted_chunks() {
+    let source = b"Hello World!".repeat(10_000);
+    let mut fs = System::new(
+        MemoryChunkStore::new(),
+        MemoryMetaStore::new(),
+        BuildWyHasher::default(),
+    )
+    .with_convergent_encryption(ConvergentEncryption::new(SECRET, OWNER_A))
+    .with_verify_on_read(true);
+    fs.write(&42, &source).await.unwrap();
+    assert_eq!(fs.read(&42).await.unwrap(), source);
+
+    let mut buf = vec![];
+    fs.read_stream(&42)
+        .await
+        .unwrap()
+        .read_to_end(&mut buf)
+        .unwrap();
+    assert_eq!(buf, source);
+}
+
+#[tokio::test]
+async fn it_announces_file_changes() {
+    let mut fs = System::new(
+        MemoryChunkStore::new(),
+        MemoryMetaStore::new(),
+        BuildWyHasher::default(),
+    );
+    let mut changes = fs.subscribe().await.unwrap();
+
+    fs.write(&1, b"Hello World!").await.unwrap();
+    fs.copy(&1, &2).await.unwrap();
+    fs.delete(&1).await.unwrap();
+
+    let created = changes.recv().await.unwrap();
+    assert_eq!((created.key, created.kind), (1, ChangeKind::Created));
+    assert_eq!(created.new.unwrap().size, 12);
+
+    let copied = changes.recv().await.unwrap();
+    assert_eq!(
+        (copied.key, copied.kind),
+        (2, ChangeKind::Copied { from: 1 })
+    );
+
+    let deleted = changes.recv().await.unwrap();
+    assert_eq!((deleted.key, deleted.kind), (1, ChangeKind::Deleted));
+}
+
+#[tokio::test]
+async fn uploads_chunk_like_whole_writes() {
+    let source = fs::read("tests/fixtures/file-example_PDF_1MB.pdf").unwrap();
+    let mut fs = System::new(
+        MemoryChunkStore::new(),
+        MemoryMetaStore::new(),
+        BuildWyHasher::default(),
+    );
+    fs.write(&1, &source).await.unwrap();
+
+    let mut upload = Upload::new();
+    for piece in source.chunks(7_919) {
+        fs.upload(&mut upload, piece).unwrap();
+    }
+    assert_eq!(upload.size(), source.len());
+    fs.finish_upload(&2, upload).await.unwrap();
+
+    let written = fs.meta_store().get(&1).await.unwrap();
+    let uploaded = fs.meta_store().get(&2).await.unwrap();
+    assert_eq!(uploaded, written);
+    assert_eq!(
+        uploaded
+            .chunk_sizes
+            .unwrap()
+            .iter()
+            .map(|size| *size as usize)
+            .sum::<usize>(),
+        source.len()
+    );
+    assert_eq!(fs.read(&2).await.unwrap(), source);
+}
+
+#[tokio::test]
+async fn shared_uploads_chunk_like_whole_writes() {
+    let source = fs::read("tests/fixtures/file-example_PDF_1MB.pdf").unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let mut fs = System::new(
+        FileChunkStore::new(dir.path()).unwrap(),
+        MemoryMetaStore::new(),
+        BuildWyHasher::default(),
+    );
+    assert!(fs.uploads_shared());
+    fs.write(&1, &source).await.unwrap();
+
+    let mut upload = Upload::new();
+    for piece in source.chunks(7_919) {
+        fs.upload_shared(&mut upload, piece).unwrap();
+    }
+    fs.flush_upload_shared(&mut upload).unwrap();
+    fs.finish_upload(&2, upload).await.unwrap();
+
+    let written = fs.meta_store().get(&1).await.unwrap();
+    assert_eq!(fs.meta_store().get(&2).await.unwrap(), written);
+    assert_eq!(fs.read(&2).await.unwrap(), source);
+}
+
+#[tokio::test]
+async fn memory_stores_cannot_upload_shared() {
+    let fs = System::new(
+        MemoryChunkStore::new(),
+        MemoryMetaStore::<i32>::new(),
+        BuildWyHasher::default(),
+    );
+    assert!(!fs.uploads_shared());
+    let mut upload = Upload::new();
+    fs.upload_shared(&mut upload, b"Hello World!").unwrap();
+    assert!(fs.flush_upload_shared(&mut upload).is_err());
+}
+
+#[tokio::test]
+async fn it_can_read_ranges() {
+    let source = fs::read("tests/fixtures/file-sample_1MB.docx").unwrap();
+    let mut fs = System::new(
+        MemoryChunkStore::new(),
+        MemoryMetaStore::new(),
+        BuildWyHasher::default(),
+    )
+    .with_convergent_encryption(ConvergentEncryption::new(&[1; 32], &[2; 32]));
+    fs.write(&1, &source).await.unwrap();
+
+    // Files written before chunk sizes were recorded are read from the start.
+    let mut legacy = fs.meta_store().get(&1).await.unwrap();
+    legacy.chunk_sizes = None;
+
+    let ranges = [
+        0..0,
+        0..10,
+        1_000..70_000,
+        500_000..source.len(),
+        0..usize::MAX,
+    ];
+    for range in ranges {
+        let expected = &source[range.start..range.end.min(source.len())];
+        assert_eq!(fs.read_range(&1, range.clone()).await.unwrap(), expected);
+        assert_eq!(fs.read_range_of(&legacy, range).unwrap(), expected);
+    }
+    assert!(fs
+        .read_range(&1, source.len() + 1..source.len() + 5)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn it_can_concat_files() {
+    let source = fs::read("tests/fixtures/file-sample_1MB.docx").unwrap();
+    let (head, tail) = source.split_at(300_000);
+    let mut fs = System::new(
+        MemoryChunkStore::new(),
+        MemoryMetaStore::new(),
+        BuildWyHasher::default(),
+    )
+    .with_convergent_encryption(ConvergentEncryption::new(&[1; 32], &[2; 32]));
+    fs.write(&1, head).await.unwrap();
+    fs.write(&2, tail).await.unwrap();
+    let chunks = fs.chunk_store().hashes().unwrap().len();
+
+    fs.concat(&3, &[1, 2]).await.unwrap();
+    assert_eq!(fs.read(&3).await.unwrap(), source);
+    assert_eq!(
+        fs.read_range(&3, 299_990..300_010).await.unwrap(),
+        source[299_990..300_010]
+    );
+    assert_eq!(fs.chunk_store().hashes().unwrap().len(), chunks);
+
+    fs.concat(&4, &[]).await.unwrap();
+    assert!(fs.read(&4).await.unwrap().is_empty());
+    assert!(matches!(
+        fs.concat(&5, &[1, 6]).await,
+        Err(cdcfs::system::Error::MetaStore(
+            cdcfs::meta::Error::NotFound
+        ))
+    ));
+}
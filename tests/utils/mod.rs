@@ -1,3 +1,5 @@
+mod logs;
 mod redis;
 
-pub use self::redis::with_redis_ready;
+pub use self::logs::Logs;
+pub use self::redis::{with_redis_cluster_ready, with_redis_ready, with_redis_sentinel_ready};
@@ -4,7 +4,7 @@ use std::{
 };
 
 use dockertest::{waitfor::RunningWait, Composition, DockerTest, Image};
-use redis::Client;
+use redis::{Client, Connection};
 use tokio::time::sleep;
 
 pub fn with_redis_ready<T, Fut>(f: T)
@@ -40,10 +40,178 @@ where
 }
 
 async fn wait_for_connection(url: &str) {
-    let Ok( client) = Client::open(url) else {
+    let Ok(client) = Client::open(url) else {
         panic!("Invalid redis url: {}", url);
     };
     while client.get_connection().is_err() {
         sleep(Duration::from_millis(10)).await;
     }
 }
+
+/// Starts `nodes` cluster-enabled Redis containers, assigns each an equal
+/// share of the hash slots and joins them into one cluster.
+pub fn with_redis_cluster_ready<T, Fut>(nodes: usize, f: T)
+where
+    T: FnOnce(Vec<String>) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let timeout = Duration::from_secs(30);
+    let start = Instant::now();
+
+    let mut test = DockerTest::new();
+
+    let names: Vec<String> = (0..nodes).map(|i| format!("redis-node-{i}")).collect();
+    for name in &names {
+        let image = Image::with_repository("redis").tag("6.0.19-alpine3.18");
+        let composition = Composition::with_image(image)
+            .with_container_name(name)
+            .with_cmd(vec![
+                "redis-server".to_string(),
+                "--cluster-enabled".to_string(),
+                "yes".to_string(),
+            ])
+            .with_wait_for(Box::new(RunningWait {
+                check_interval: 1,
+                max_checks: 10,
+            }));
+        test.add_composition(composition);
+    }
+
+    test.run(|ops| {
+        let ips: Vec<String> = names
+            .iter()
+            .map(|name| ops.handle(name).ip().to_string())
+            .collect();
+        let urls: Vec<String> = ips.iter().map(|ip| format!("redis://{ip}")).collect();
+
+        let fut = f(urls.clone());
+        async move {
+            tokio::select! {
+                _ = form_cluster(&ips, &urls) => (),
+                _ = sleep(timeout - start.elapsed()) => panic!("Cluster timeout after {:?}", start.elapsed()),
+            }
+
+            fut.await;
+        }
+    });
+}
+
+/// Starts a Redis master and a sentinel monitoring it under `service_name`.
+/// The closure receives the sentinel urls.
+pub fn with_redis_sentinel_ready<T, Fut>(service_name: &str, f: T)
+where
+    T: FnOnce(Vec<String>) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let timeout = Duration::from_secs(30);
+    let start = Instant::now();
+
+    let mut test = DockerTest::new();
+
+    let master = Composition::with_image(Image::with_repository("redis").tag("6.0.19-alpine3.18"))
+        .with_container_name("redis-master")
+        .with_wait_for(Box::new(RunningWait {
+            check_interval: 1,
+            max_checks: 10,
+        }));
+    test.add_composition(master);
+
+    let sentinel =
+        Composition::with_image(Image::with_repository("redis").tag("6.0.19-alpine3.18"))
+            .with_container_name("redis-sentinel")
+            .with_cmd(vec![
+        "sh".to_string(),
+        "-c".to_string(),
+        "echo 'port 26379' > /tmp/sentinel.conf && redis-server /tmp/sentinel.conf --sentinel"
+            .to_string(),
+    ])
+            .with_wait_for(Box::new(RunningWait {
+                check_interval: 1,
+                max_checks: 10,
+            }));
+    test.add_composition(sentinel);
+
+    let service_name = service_name.to_string();
+    test.run(|ops| {
+        let master_ip = ops.handle("redis-master").ip().to_string();
+        let master_url = format!("redis://{master_ip}");
+        let sentinel_url = format!("redis://{}:26379", ops.handle("redis-sentinel").ip());
+
+        let fut = f(vec![sentinel_url.clone()]);
+        async move {
+            tokio::select! {
+                _ = async {
+                    wait_for_connection(&master_url).await;
+                    wait_for_connection(&sentinel_url).await;
+                    let mut conn = Client::open(sentinel_url.as_str())
+                        .unwrap()
+                        .get_connection()
+                        .unwrap();
+                    redis::cmd("SENTINEL")
+                        .arg("MONITOR")
+                        .arg(&service_name)
+                        .arg(&master_ip)
+                        .arg(6379)
+                        .arg(1)
+                        .query::<()>(&mut conn)
+                        .unwrap();
+                } => (),
+                _ = sleep(timeout - start.elapsed()) => panic!("Sentinel timeout after {:?}", start.elapsed()),
+            }
+
+            fut.await;
+        }
+    });
+}
+
+async fn form_cluster(ips: &[String], urls: &[String]) {
+    for url in urls {
+        wait_for_connection(url).await;
+    }
+
+    let mut conns: Vec<Connection> = urls
+        .iter()
+        .map(|url| {
+            Client::open(url.as_str())
+                .unwrap()
+                .get_connection()
+                .unwrap()
+        })
+        .collect();
+
+    let slots_per_node = CLUSTER_SLOTS / conns.len() as u16;
+    for (idx, conn) in conns.iter_mut().enumerate() {
+        let first = idx as u16 * slots_per_node;
+        let last = if idx + 1 == urls.len() {
+            CLUSTER_SLOTS
+        } else {
+            first + slots_per_node
+        };
+        redis::cmd("CLUSTER")
+            .arg("ADDSLOTS")
+            .arg((first..last).collect::<Vec<u16>>())
+            .query::<()>(conn)
+            .unwrap();
+    }
+
+    for ip in &ips[1..] {
+        redis::cmd("CLUSTER")
+            .arg("MEET")
+            .arg(ip)
+            .arg(6379)
+            .query::<()>(&mut conns[0])
+            .unwrap();
+    }
+
+    for conn in conns.iter_mut() {
+        loop {
+            let info: String = redis::cmd("CLUSTER").arg("INFO").query(conn).unwrap();
+            if info.contains("cluster_state:ok") {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+    }
+}
+
+const CLUSTER_SLOTS: u16 = 16384;